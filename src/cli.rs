@@ -0,0 +1,500 @@
+use crate::get_db::get_db;
+use crate::scraper_v2::common::{parse_date, DateRange, MemoryChunkStore, SurrealChunkStore};
+use crate::scraper_v2::graph::{
+    Direction, EdgeKind, GraphPath, KnowledgeGraph, MemoryGraphStore, Neighborhood, Traversal,
+};
+use crate::scraper_v2::search::{
+    Embedder, EmbeddingStore, HashingEmbedder, HnswIndex, MemoryEmbeddingStore, SearchFilter,
+    SearchHit, SurrealEmbeddingStore, TextIndex, TextQuery, TextResults,
+};
+use crate::scraper_v2::Result;
+
+use clap::{Parser, Subcommand};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug, Parser)]
+#[command(about = "Scrape BBC News and Wikipedia, and search what was scraped")]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Build (or bring up to date) a vector index from the embeddings saved by a `MemoryEmbeddingStore` or in the database.
+    /// Chunks in the index that are no longer in the store are taken out of it.
+    Index {
+        /// The JSON file of embeddings.
+        #[arg(long, required_unless_present = "db", conflicts_with = "db")]
+        embeddings: Option<PathBuf>,
+        /// Read the embeddings from this SurrealDB database (a `SurrealEmbeddingStore`) instead of a file.
+        #[arg(long)]
+        db: Option<String>,
+        /// The index file, created if it doesn't exist.
+        #[arg(long)]
+        index: PathBuf,
+        /// The model whose embeddings are indexed. Defaults to the model of the existing index, or the one most embeddings are from.
+        #[arg(long)]
+        model: Option<String>,
+        /// Build the index from scratch rather than updating it, to drop the nodes that replaced and removed chunks leave in the graph.
+        #[arg(long)]
+        rebuild: bool,
+    },
+    /// Find the chunks closest in meaning to a query.
+    Search {
+        query: String,
+        /// The index file built with `index`.
+        #[arg(long)]
+        index: PathBuf,
+        /// How many chunks to return.
+        #[arg(short, default_value_t = 10)]
+        k: usize,
+        /// Only pages on this site, eg bbc.co.uk or en.wikipedia.org.
+        #[arg(long)]
+        site: Option<String>,
+        /// Only pages published on or after this date, eg 2024-05-01.
+        #[arg(long)]
+        from: Option<String>,
+        /// Only pages published on or before this date (up to the end of the day unless a time is given).
+        #[arg(long)]
+        to: Option<String>,
+        /// Only pages in this Wikipedia category.
+        #[arg(long)]
+        category: Option<String>,
+        /// The directory of the model the index was built with. Not needed for hashing embeddings.
+        #[arg(long)]
+        model_dir: Option<PathBuf>,
+        /// Print the hits as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Rebuild the full text index from the chunks saved by a `MemoryChunkStore` or in the database.
+    TextIndex {
+        /// The JSON file of chunks.
+        #[arg(long, required_unless_present = "db", conflicts_with = "db")]
+        chunks: Option<PathBuf>,
+        /// Read the chunks from this SurrealDB database (a `SurrealChunkStore`) instead of a file.
+        #[arg(long)]
+        db: Option<String>,
+        /// The index directory, created if it doesn't exist. Anything already in it is replaced.
+        #[arg(long)]
+        index_dir: PathBuf,
+    },
+    /// Find the chunks that match a keyword query, eg `"climate change" flooding -sport`.
+    TextSearch {
+        query: String,
+        /// The index directory built with `text-index` or by a crawl.
+        #[arg(long)]
+        index_dir: PathBuf,
+        /// How many chunks to return.
+        #[arg(short, default_value_t = 10)]
+        k: usize,
+        /// Only pages on this site, eg bbc.co.uk or en.wikipedia.org.
+        #[arg(long)]
+        site: Option<String>,
+        /// Only pages with this topic, eg "Climate change".
+        #[arg(long)]
+        topic: Option<String>,
+        /// Only pages in this Wikipedia category.
+        #[arg(long)]
+        category: Option<String>,
+        /// Only pages published on or after this date, eg 2024-05-01.
+        #[arg(long)]
+        from: Option<String>,
+        /// Only pages published on or before this date (up to the end of the day unless a time is given).
+        #[arg(long)]
+        to: Option<String>,
+        /// Print the results as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show the nodes within a few edges of a node of the knowledge graph saved by a `MemoryGraphStore`, eg a page url or "topic:Climate change".
+    GraphNeighbors {
+        node: String,
+        /// The JSON file of the graph.
+        #[arg(long)]
+        graph: PathBuf,
+        /// How many edges away to go.
+        #[arg(long, default_value_t = 1)]
+        depth: usize,
+        /// Only follow these kinds of edges: links-to, in-category, related-topic, cites or mentions-entity. Can be given more than once.
+        #[arg(long)]
+        kind: Vec<EdgeKind>,
+        /// Follow the edges going out of nodes (out), coming into them (in) or both.
+        #[arg(long, default_value = "both")]
+        direction: Direction,
+        /// Print the neighbourhood as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Find the shortest chain of edges between two nodes of the knowledge graph.
+    GraphPath {
+        from: String,
+        to: String,
+        /// The JSON file of the graph.
+        #[arg(long)]
+        graph: PathBuf,
+        /// The longest chain to look for.
+        #[arg(long, default_value_t = 6)]
+        max_hops: usize,
+        /// Only follow these kinds of edges. Can be given more than once.
+        #[arg(long)]
+        kind: Vec<EdgeKind>,
+        /// Follow the edges going out of nodes (out), coming into them (in) or both.
+        #[arg(long, default_value = "both")]
+        direction: Direction,
+        /// Print the path as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+pub async fn run() -> Result<()> {
+    let Some(command) = Cli::parse().command else {
+        return Ok(());
+    };
+    match command {
+        Command::Index {
+            embeddings,
+            db,
+            index,
+            model,
+            rebuild,
+        } => {
+            let (store, source): (Box<dyn EmbeddingStore>, String) = match (db, embeddings) {
+                (Some(db), _) => (
+                    Box::new(SurrealEmbeddingStore::new(&open_db(&db).await?)),
+                    format!("the {db} database"),
+                ),
+                (None, Some(embeddings)) => (
+                    Box::new(MemoryEmbeddingStore::load(&embeddings).await?),
+                    embeddings.display().to_string(),
+                ),
+                (None, None) => return Err(crate::Error::custom("Pass --embeddings or --db")),
+            };
+            build_index(store.as_ref(), &source, &index, model, rebuild).await
+        }
+        Command::Search {
+            query,
+            index,
+            k,
+            site,
+            from,
+            to,
+            category,
+            model_dir,
+            json,
+        } => {
+            let mut filter = SearchFilter::new();
+            if let Some(site) = site {
+                filter = filter.with_site(site);
+            }
+            if from.is_some() || to.is_some() {
+                filter = filter.with_date_range(DateRange::new(
+                    from.as_deref().map(date_arg).transpose()?,
+                    to.as_deref().map(end_date_arg).transpose()?,
+                ));
+            }
+            if let Some(category) = category {
+                filter = filter.with_category(category);
+            }
+
+            let index = HnswIndex::load(&index).await?;
+            let Some(model) = index.model() else {
+                println!("The index is empty.");
+                return Ok(());
+            };
+            let embedder = embedder_for(model, model_dir.as_deref())?;
+            let hits = index.query(embedder.as_ref(), &query, k, &filter)?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&hits).map_err(crate::Error::custom)?
+                );
+            } else {
+                print_hits(&hits);
+            }
+            Ok(())
+        }
+        Command::TextIndex {
+            chunks,
+            db,
+            index_dir,
+        } => {
+            let chunks = match (db, chunks) {
+                (Some(db), _) => {
+                    SurrealChunkStore::new(&open_db(&db).await?)
+                        .all_chunks()
+                        .await?
+                }
+                (None, Some(chunks)) => MemoryChunkStore::load(&chunks).await?.all_chunks().await,
+                (None, None) => return Err(crate::Error::custom("Pass --chunks or --db")),
+            };
+            let index = TextIndex::open(&index_dir)?;
+            let count = index.rebuild(chunks)?;
+            println!("Indexed {count} chunks in {}.", index_dir.display());
+            Ok(())
+        }
+        Command::TextSearch {
+            query,
+            index_dir,
+            k,
+            site,
+            topic,
+            category,
+            from,
+            to,
+            json,
+        } => {
+            let mut query = TextQuery::new(query).with_limit(k);
+            if let Some(site) = site {
+                query = query.with_site(site);
+            }
+            if let Some(topic) = topic {
+                query = query.with_topic(topic);
+            }
+            if let Some(category) = category {
+                query = query.with_category(category);
+            }
+            if from.is_some() || to.is_some() {
+                query = query.with_date_range(DateRange::new(
+                    from.as_deref().map(date_arg).transpose()?,
+                    to.as_deref().map(end_date_arg).transpose()?,
+                ));
+            }
+
+            let results = TextIndex::open_read_only(&index_dir)?.search(&query)?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&results).map_err(crate::Error::custom)?
+                );
+            } else {
+                print_text_results(&results);
+            }
+            Ok(())
+        }
+        Command::GraphNeighbors {
+            node,
+            graph,
+            depth,
+            kind,
+            direction,
+            json,
+        } => {
+            let graph = open_graph(&graph).await?;
+            let traversal = Traversal::new().with_kinds(kind).with_direction(direction);
+            let neighborhood = graph.neighborhood(&node, depth, &traversal).await?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&neighborhood).map_err(crate::Error::custom)?
+                );
+            } else {
+                print_neighborhood(&neighborhood);
+            }
+            Ok(())
+        }
+        Command::GraphPath {
+            from,
+            to,
+            graph,
+            max_hops,
+            kind,
+            direction,
+            json,
+        } => {
+            let graph = open_graph(&graph).await?;
+            let traversal = Traversal::new().with_kinds(kind).with_direction(direction);
+            let path = graph
+                .shortest_path(&from, &to, max_hops, &traversal)
+                .await?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&path).map_err(crate::Error::custom)?
+                );
+            } else {
+                match path {
+                    Some(path) => print_path(&path),
+                    None => println!("No path of up to {max_hops} edges."),
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn open_graph(path: &Path) -> Result<KnowledgeGraph> {
+    KnowledgeGraph::open(Arc::new(MemoryGraphStore::load(path).await?)).await
+}
+
+async fn open_db(name: &str) -> Result<surrealdb::Surreal<surrealdb::engine::remote::ws::Client>> {
+    get_db(name).await.map_err(crate::Error::custom)
+}
+
+async fn build_index(
+    store: &dyn EmbeddingStore,
+    source: &str,
+    index_path: &Path,
+    model: Option<String>,
+    rebuild: bool,
+) -> Result<()> {
+    let mut index = if rebuild {
+        HnswIndex::new()
+    } else {
+        HnswIndex::load(index_path).await?
+    };
+    let model = match model.or_else(|| index.model().map(str::to_string)) {
+        Some(model) => model,
+        None => match store.models().await?.into_iter().next() {
+            Some(model) => model,
+            None => {
+                println!("There are no embeddings in {source}.");
+                return Ok(());
+            }
+        },
+    };
+    let embeddings = store.embeddings(&model).await?;
+    //? Chunks that were dropped from the store since the index was built (eg when their page changed) are dropped from the index too.
+    let current = embeddings
+        .iter()
+        .map(|embedding| embedding.chunk.id.clone())
+        .collect::<HashSet<String>>();
+    let removed = index.retain(|chunk_id| current.contains(chunk_id));
+    index.extend(embeddings)?;
+    index.save(index_path).await?;
+    println!(
+        "Indexed {} chunks embedded by {model} in {}, removed {removed}.",
+        index.len(),
+        index_path.display()
+    );
+    if index.removed_count() > index.len() {
+        println!("Most of the index is replaced or removed chunks, pass --rebuild to drop them.");
+    }
+    Ok(())
+}
+
+/// The embedder that made the vectors of an index, so queries are embedded the same way.
+fn embedder_for(model: &str, model_dir: Option<&Path>) -> Result<Box<dyn Embedder>> {
+    if let Some(dimensions) = model
+        .strip_prefix("hashing-")
+        .and_then(|dimensions| dimensions.parse().ok())
+    {
+        return Ok(Box::new(HashingEmbedder::new(dimensions)));
+    }
+    let Some(model_dir) = model_dir else {
+        return Err(crate::Error::custom(format!(
+            "The index was built with {model}, pass its directory with --model-dir"
+        )));
+    };
+    local_embedder(model_dir)
+}
+
+#[cfg(feature = "local-embeddings")]
+fn local_embedder(model_dir: &Path) -> Result<Box<dyn Embedder>> {
+    Ok(Box::new(crate::scraper_v2::search::BertEmbedder::load(
+        model_dir,
+    )?))
+}
+
+#[cfg(not(feature = "local-embeddings"))]
+fn local_embedder(_model_dir: &Path) -> Result<Box<dyn Embedder>> {
+    Err(crate::Error::custom(
+        "Local models need the local-embeddings feature",
+    ))
+}
+
+fn date_arg(date: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    parse_date(date).ok_or_else(|| crate::Error::custom(format!("Not a date: {date}")))
+}
+
+/// The end of a date range, which is inclusive: a day without a time means up to the end of that day.
+fn end_date_arg(date: &str) -> Result<chrono::DateTime<chrono::Utc>> {
+    match chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+        Ok(day) => day
+            .and_hms_nano_opt(23, 59, 59, 999_999_999)
+            .map(|end| end.and_utc())
+            .ok_or_else(|| crate::Error::custom(format!("Not a date: {date}"))),
+        Err(_) => date_arg(date),
+    }
+}
+
+fn print_hits(hits: &[SearchHit]) {
+    for (rank, hit) in hits.iter().enumerate() {
+        println!(
+            "{}. {:.3}  {}\n   {}",
+            rank + 1,
+            hit.score,
+            hit.chunk.breadcrumb(),
+            hit.chunk.url
+        );
+        for line in hit.chunk.text.lines() {
+            println!("   | {line}");
+        }
+        println!();
+    }
+}
+
+fn print_text_results(results: &TextResults) {
+    println!("{} chunks matched.", results.total);
+    for (name, counts) in [
+        ("Sites", &results.sites),
+        ("Topics", &results.topics),
+        ("Categories", &results.categories),
+    ] {
+        if !counts.is_empty() {
+            let counts = counts
+                .iter()
+                .map(|(value, count)| format!("{value} ({count})"))
+                .collect::<Vec<_>>();
+            println!("{name}: {}", counts.join(", "));
+        }
+    }
+    println!();
+    for (rank, hit) in results.hits.iter().enumerate() {
+        let heading = match (&hit.title, hit.section.is_empty()) {
+            (Some(title), false) => format!("{title} > {}", hit.section),
+            (Some(title), true) => title.clone(),
+            (None, _) => hit.section.clone(),
+        };
+        println!(
+            "{}. {:.3}  {heading}\n   {}\n   {}\n",
+            rank + 1,
+            hit.score,
+            hit.url,
+            hit.snippet
+        );
+    }
+}
+
+fn print_neighborhood(neighborhood: &Neighborhood) {
+    for (node, distance) in &neighborhood.nodes {
+        println!(
+            "{}{} [{:?}]  {}",
+            "  ".repeat(*distance),
+            node.name(),
+            node.kind,
+            node.id
+        );
+    }
+    println!();
+    for edge in &neighborhood.edges {
+        println!("{} -{}-> {}", edge.from, edge.kind, edge.to);
+    }
+    if neighborhood.truncated {
+        println!("(Stopped early, there are more nodes in range.)");
+    }
+}
+
+fn print_path(path: &GraphPath) {
+    for (index, node) in path.nodes.iter().enumerate() {
+        if let Some(edge) = index.checked_sub(1).map(|previous| &path.edges[previous]) {
+            let arrow = if edge.to == node.id { "->" } else { "<-" };
+            println!("   {arrow} {}", edge.kind);
+        }
+        println!("{}  {}", node.name(), node.id);
+    }
+}
@@ -1,3 +1,4 @@
+mod cli;
 pub mod get_db;
 // pub mod scraper;
 pub mod scraper_v2;
@@ -5,5 +6,5 @@ pub mod scraper_v2;
 use scraper_v2::*;
 #[tokio::main]
 async fn main() -> Result<()> {
-    Ok(())
+    cli::run().await
 }
@@ -0,0 +1,311 @@
+use super::CommonError;
+use crate::Result;
+
+use percent_encoding::percent_decode_str;
+use url::Url;
+
+/// Query parameters that are removed outright when canonicalizing a url.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "ocid", "xtor", "mc_cid", "mc_eid", "cmpid", "ref_src",
+];
+
+/// Query parameter prefixes that are used for tracking (eg `utm_source`, `at_medium`).
+const TRACKING_PARAM_PREFIXES: &[&str] = &["utm_", "at_", "ns_"];
+
+/// Canonicalizes urls for a site so that the same page always maps to the same string.
+///
+/// The canonical form is an absolute `https` url on the site's host with:
+/// - relative and protocol-relative urls resolved against a page url (or the site's base url),
+/// - host aliases (eg `bbc.co.uk` for `www.bbc.co.uk`) mapped to the base url host,
+/// - the fragment removed,
+/// - tracking query parameters removed and the remaining ones sorted,
+/// - percent-encoding normalized (unreserved characters decoded, hex digits upper-cased),
+/// - duplicate and trailing slashes removed from the path.
+#[derive(Debug, Clone, Copy)]
+pub struct Canonicalizer<'a> {
+    base_url: &'a str,
+    host_aliases: &'a [&'a str],
+}
+
+impl<'a> Canonicalizer<'a> {
+    pub fn new(base_url: &'a str, host_aliases: &'a [&'a str]) -> Self {
+        Self {
+            base_url,
+            host_aliases,
+        }
+    }
+
+    /// Canonicalize a url, resolving it against the base url if it is relative.
+    pub fn canonicalize(&self, url: &str) -> Result<String> {
+        self.canonicalize_relative_to(url, self.base_url)
+    }
+
+    /// Canonicalize a url, resolving it against `page_url` if it is relative.
+    pub fn canonicalize_relative_to(&self, url: &str, page_url: &str) -> Result<String> {
+        let invalid = |reason: &str| CommonError::InvalidUrl {
+            url: url.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let trimmed = url.trim();
+        if trimmed.is_empty() {
+            return Err(invalid("Empty url").into());
+        }
+
+        let base = Url::parse(page_url)
+            .or_else(|_| Url::parse(self.base_url))
+            .map_err(|_| invalid("Invalid base url"))?;
+        let mut parsed = base
+            .join(trimmed)
+            .map_err(|e| invalid(&format!("Unable to resolve url: {e}")))?;
+
+        match parsed.scheme() {
+            "https" => {}
+            "http" => {
+                parsed
+                    .set_scheme("https")
+                    .map_err(|_| invalid("Unable to set scheme"))?;
+            }
+            scheme => return Err(invalid(&format!("Unsupported scheme: {scheme}")).into()),
+        }
+        if matches!(parsed.port(), Some(80) | Some(443)) {
+            let _ = parsed.set_port(None);
+        }
+
+        self.normalize_host(&mut parsed)
+            .map_err(|_| invalid("Unable to set host"))?;
+
+        parsed.set_fragment(None);
+        Self::normalize_query(&mut parsed);
+        Self::normalize_path(&mut parsed);
+
+        Ok(parsed.to_string())
+    }
+
+    /// The host of the base url, which all aliases are mapped onto.
+    fn canonical_host(&self) -> Option<String> {
+        Url::parse(self.base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+    }
+
+    fn normalize_host(&self, url: &mut Url) -> std::result::Result<(), url::ParseError> {
+        let Some(host) = url.host_str() else {
+            return Ok(());
+        };
+        // Hosts are already lower-cased by the parser, only the FQDN trailing dot is left.
+        let host = host.trim_end_matches('.').to_string();
+        match self.canonical_host() {
+            Some(canonical) if canonical == host || self.host_aliases.contains(&host.as_str()) => {
+                url.set_host(Some(&canonical))
+            }
+            _ if url.host_str() != Some(host.as_str()) => url.set_host(Some(&host)),
+            _ => Ok(()),
+        }
+    }
+
+    //? The params are kept as they were written rather than decoded and re-encoded, as form encoding would turn `%20` into `+` (and `+` means a space only to some servers).
+    fn normalize_query(url: &mut Url) {
+        let mut params = url
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| !param.is_empty())
+            .filter(|param| {
+                let key = param.split('=').next().unwrap_or_default();
+                !is_tracking_param(&percent_decode_str(key).decode_utf8_lossy())
+            })
+            .map(normalize_percent_encoding)
+            .collect::<Vec<String>>();
+
+        if params.is_empty() {
+            url.set_query(None);
+            return;
+        }
+        params.sort();
+        url.set_query(Some(&params.join("&")));
+    }
+
+    fn normalize_path(url: &mut Url) {
+        if url.cannot_be_a_base() {
+            return;
+        }
+        let mut path = normalize_percent_encoding(url.path());
+        while path.contains("//") {
+            path = path.replace("//", "/");
+        }
+        if path.len() > 1 {
+            path = path.trim_end_matches('/').to_string();
+        }
+        url.set_path(&path);
+    }
+}
+
+fn is_tracking_param(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    TRACKING_PARAMS.contains(&key.as_str())
+        || TRACKING_PARAM_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+}
+
+/// Decode percent-encoded unreserved characters (RFC 3986 section 2.3) and upper-case the hex digits of every other escape, so `%7e`, `%7E` and `~` all compare equal.
+pub fn normalize_percent_encoding(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut output = String::with_capacity(input.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let is_escape = bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit();
+        if is_escape {
+            let hex = &input[i + 1..i + 3];
+            let byte = u8::from_str_radix(hex, 16).unwrap_or_default();
+            if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+                output.push(byte as char);
+            } else {
+                output.push('%');
+                output.push_str(&hex.to_ascii_uppercase());
+            }
+            i += 3;
+            continue;
+        }
+        let c = input[i..].chars().next().unwrap_or_default();
+        output.push(c);
+        i += c.len_utf8();
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const BASE_URL: &str = "https://www.bbc.co.uk";
+    const ALIASES: &[&str] = &["bbc.co.uk", "m.bbc.co.uk"];
+
+    fn canonicalize(url: &str) -> String {
+        Canonicalizer::new(BASE_URL, ALIASES)
+            .canonicalize(url)
+            .unwrap()
+    }
+
+    fn host() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec![
+            "www.bbc.co.uk",
+            "bbc.co.uk",
+            "m.bbc.co.uk",
+            "example.com",
+        ])
+    }
+
+    /// Path segments and query values, with escapes of both reserved and unreserved characters in either case.
+    fn text() -> impl Strategy<Value = String> {
+        prop::collection::vec(
+            prop_oneof![
+                "[a-zA-Z0-9._~-]{1,6}",
+                Just("%20".to_string()),
+                Just("%7e".to_string()),
+                Just("%7E".to_string()),
+                Just("%41".to_string()),
+                Just("%2f".to_string()),
+                Just("%26".to_string()),
+                Just("+".to_string()),
+                Just("é".to_string()),
+            ],
+            0..4,
+        )
+        .prop_map(|parts| parts.concat())
+    }
+
+    fn param() -> impl Strategy<Value = (String, String)> {
+        ("[a-z]{1,5}", text())
+    }
+
+    fn tracking_param() -> impl Strategy<Value = (String, String)> {
+        (
+            prop_oneof![
+                "(utm_|at_|ns_)[a-z]{1,6}",
+                prop::sample::select(TRACKING_PARAMS.to_vec()).prop_map(str::to_string),
+                Just("UTM_Source".to_string()),
+            ],
+            text(),
+        )
+    }
+
+    fn query(params: &[(String, String)]) -> String {
+        params
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    prop_compose! {
+        fn url()(
+            scheme in prop::sample::select(vec!["http", "https"]),
+            host in host(),
+            segments in prop::collection::vec(text(), 0..4),
+            trailing_slash in any::<bool>(),
+            params in prop::collection::vec(param(), 0..4),
+        ) -> String {
+            let mut url = format!("{scheme}://{host}/{}", segments.join("/"));
+            if trailing_slash {
+                url.push('/');
+            }
+            if !params.is_empty() {
+                url.push('?');
+                url.push_str(&query(&params));
+            }
+            url
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn canonicalizing_is_idempotent(url in url()) {
+            let canonical = canonicalize(&url);
+            prop_assert_eq!(canonicalize(&canonical), canonical);
+        }
+
+        #[test]
+        fn fragments_are_removed(url in url(), fragment in text()) {
+            let canonical = canonicalize(&format!("{url}#{fragment}"));
+            prop_assert!(!canonical.contains('#'));
+            prop_assert_eq!(canonical, canonicalize(&url));
+        }
+
+        #[test]
+        fn tracking_params_are_stripped(
+            url in url(),
+            tracking in prop::collection::vec(tracking_param(), 1..4),
+        ) {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            let canonical = canonicalize(&format!("{url}{separator}{}", query(&tracking)));
+            prop_assert_eq!(&canonical, &canonicalize(&url));
+            let parsed = Url::parse(&canonical).unwrap();
+            for (key, _) in parsed.query_pairs() {
+                prop_assert!(!is_tracking_param(&key));
+            }
+        }
+
+        #[test]
+        fn percent_encoding_is_stable(url in url()) {
+            let canonical = canonicalize(&url);
+            prop_assert_eq!(normalize_percent_encoding(&canonical), canonical.clone());
+            // Escapes of unreserved characters are decoded and the rest are upper-cased, so the case of the hex digits doesn't matter.
+            prop_assert_eq!(canonicalize(&url.replace("%7E", "%7e")), canonical.clone());
+            prop_assert!(!canonical.contains("%7e") && !canonical.contains("%7E"));
+            prop_assert!(!canonical.contains("%41"));
+            prop_assert!(!canonical.contains("%2f"));
+        }
+
+        #[test]
+        fn encoded_spaces_stay_encoded(key in "[a-z]{1,5}", value in "[a-z]{1,5}") {
+            let canonical = canonicalize(&format!("https://www.bbc.co.uk/news?{key}=a%20{value}"));
+            prop_assert!(canonical.ends_with(&format!("?{key}=a%20{value}")), "{}", canonical);
+        }
+    }
+}
@@ -0,0 +1,656 @@
+use super::{content_hash, ScrapableContent, UrlTrait};
+use crate::Result;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use lazy_regex::{regex, regex_captures, regex_replace_all};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+use tokio::sync::Mutex;
+
+/// The SurrealDB table chunks are stored in, one record per chunk id.
+const CHUNK_TABLE: &str = "chunk";
+
+/// The fields of a chunk record, with the chunk's id read out of the record id.
+//? `SELECT *` would return the record id (a `Thing`), which doesn't deserialize into the chunk's `String` id.
+const CHUNK_FIELDS: &str = "meta::id(id) AS id, url, title, section_path, index, text, token_count, content_hash, published, categories, topics";
+
+/// A piece of a page's content small enough to be embedded or indexed on its own.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Chunk {
+    /// Derived from the page's url, the section the chunk is in and the chunk's text, so the same text in the same place gets the same id on every crawl.
+    pub id: String,
+    pub url: String,
+    /// The title of the page, its first top level heading.
+    pub title: Option<String>,
+    /// The headings the chunk is under, outermost first. Empty for text before the first heading.
+    pub section_path: Vec<String>,
+    /// The position of the chunk in the page.
+    pub index: usize,
+    /// The chunk's Markdown, without the headings it is under.
+    pub text: String,
+    pub token_count: usize,
+    /// A hash of the text, to tell whether a chunk needs to be embedded or indexed again.
+    pub content_hash: String,
+    /// When the page says it was published.
+    #[serde(default)]
+    pub published: Option<DateTime<Utc>>,
+    /// The categories the page is in (`ScrapableContent::categories`).
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// The topics the page is tagged with (`ScrapableContent::topics`).
+    #[serde(default)]
+    pub topics: Vec<String>,
+}
+
+impl Chunk {
+    /// The title and the section path, eg "London > History > Roman London".
+    pub fn breadcrumb(&self) -> String {
+        self.title.iter().chain(&self.section_path).join(" > ")
+    }
+
+    /// The host of the page's url, eg "www.bbc.co.uk".
+    pub fn site(&self) -> Option<String> {
+        url::Url::parse(&self.url)
+            .ok()?
+            .host_str()
+            .map(str::to_string)
+    }
+}
+
+/// Splits content into overlapping chunks of at most `max_tokens` tokens, along the structure of its Markdown.
+///
+/// The Markdown (`ScrapableContent::to_markdown`) is split into sections at its headings (Wikipedia's sections, the crossheads of BBC articles),
+/// and each section's paragraphs, lists, tables and code blocks are packed into chunks without splitting them.
+/// Blocks that are too long on their own are split into lines, then sentences, then words.
+/// Each chunk after the first of a section starts with the last sentences of the one before it, up to `overlap` tokens, so text isn't cut off from its context.
+///
+/// ```ignore
+/// let chunks = Chunker::new().with_max_tokens(200).chunk(&url, page.content());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Chunker {
+    max_tokens: usize,
+    overlap: usize,
+    count_tokens: fn(&str) -> usize,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chunker {
+    /// Chunks of up to 256 tokens overlapping by up to 32, counted with `estimate_tokens`.
+    pub fn new() -> Self {
+        Self {
+            max_tokens: 256,
+            overlap: 32,
+            count_tokens: estimate_tokens,
+        }
+    }
+
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Self {
+        self.max_tokens = max_tokens.max(1);
+        self
+    }
+
+    /// How many tokens of the previous chunk to repeat at the start of the next one. At most half of `max_tokens` is used.
+    pub fn with_overlap(mut self, overlap: usize) -> Self {
+        self.overlap = overlap;
+        self
+    }
+
+    /// Count tokens with the tokenizer of the model the chunks are for, instead of estimating them.
+    pub fn with_token_counter(mut self, count_tokens: fn(&str) -> usize) -> Self {
+        self.count_tokens = count_tokens;
+        self
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub fn overlap(&self) -> usize {
+        self.overlap
+    }
+
+    /// Chunk a page's content, rendered to Markdown. The chunks carry the page's publication date, categories and topics, to filter searches by.
+    pub fn chunk<C: ScrapableContent>(&self, url: &C::Url, content: &C) -> Vec<Chunk> {
+        let published = content.published();
+        let categories = content.categories();
+        let topics = content.topics();
+        let mut chunks = self.chunk_markdown(&url.full_url(), &content.to_markdown());
+        for chunk in &mut chunks {
+            chunk.published = published;
+            chunk.categories.clone_from(&categories);
+            chunk.topics.clone_from(&topics);
+        }
+        chunks
+    }
+
+    /// Chunk the Markdown of the page at `url`. The chunks have no publication date, categories or topics.
+    pub fn chunk_markdown(&self, url: &str, markdown: &str) -> Vec<Chunk> {
+        let (title, sections) = sections(markdown);
+        //? Identical text under the same heading (eg a repeated caption) would get the same id, so later copies are numbered.
+        let mut occurrences = HashMap::<String, usize>::new();
+
+        sections
+            .into_iter()
+            .flat_map(|section| {
+                let units = section
+                    .blocks
+                    .iter()
+                    .flat_map(|block| self.split(block))
+                    .collect_vec();
+                self.pack(units)
+                    .into_iter()
+                    .map(move |text| (section.path.clone(), text))
+            })
+            .enumerate()
+            .map(|(index, (section_path, text))| {
+                let hash = content_hash(&text);
+                let key = format!("{url}\n{}\n{hash}", section_path.join("\n"));
+                let occurrence = occurrences.entry(key.clone()).or_default();
+                let id = content_hash(&format!("{key}\n{occurrence}"));
+                *occurrence += 1;
+                Chunk {
+                    id,
+                    url: url.to_string(),
+                    title: title.clone(),
+                    section_path,
+                    index,
+                    token_count: self.count(&text),
+                    text,
+                    content_hash: hash,
+                    published: None,
+                    categories: Vec::new(),
+                    topics: Vec::new(),
+                }
+            })
+            .collect()
+    }
+
+    fn count(&self, text: &str) -> usize {
+        (self.count_tokens)(text)
+    }
+
+    /// The most tokens repeated from one chunk at the start of the next.
+    fn overlap_budget(&self) -> usize {
+        self.overlap.min(self.max_tokens / 2)
+    }
+
+    /// Split a block that is too long into lines, sentences or words, and put the pieces back together into parts that fit.
+    //? Parts leave room for the overlap, so the chunks a long block is split into still overlap.
+    fn split(&self, text: &str) -> Vec<String> {
+        let max_tokens = self.max_tokens - self.overlap_budget();
+        if self.count(text) <= max_tokens {
+            return vec![text.to_string()];
+        }
+        let (pieces, separator) = if text.contains('\n') {
+            (text.lines().map(str::to_string).collect_vec(), "\n")
+        } else {
+            let sentences = sentences(text);
+            if sentences.len() > 1 {
+                (sentences, " ")
+            } else {
+                (
+                    text.split_whitespace().map(str::to_string).collect_vec(),
+                    " ",
+                )
+            }
+        };
+        //? A single word longer than a chunk is kept whole.
+        if pieces.len() <= 1 {
+            return vec![text.to_string()];
+        }
+
+        let mut parts = Vec::new();
+        let mut current = String::new();
+        for piece in pieces.iter().flat_map(|piece| self.split(piece)) {
+            if !current.is_empty() {
+                let joined = format!("{current}{separator}{piece}");
+                if self.count(&joined) <= max_tokens {
+                    current = joined;
+                    continue;
+                }
+                parts.push(std::mem::take(&mut current));
+            }
+            current = piece;
+        }
+        if !current.is_empty() {
+            parts.push(current);
+        }
+        parts
+    }
+
+    /// Pack the blocks of a section into chunks, repeating the end of each chunk at the start of the next.
+    fn pack(&self, units: Vec<String>) -> Vec<String> {
+        let mut chunks = Vec::new();
+        let mut current = Vec::new();
+        let mut tokens = 0;
+        for unit in units {
+            let unit_tokens = self.count(&unit);
+            if !current.is_empty() && tokens + unit_tokens > self.max_tokens {
+                let text = current.join("\n\n");
+                let overlap = self.overlap_of(&text);
+                chunks.push(text);
+                current.clear();
+                tokens = 0;
+                if let Some(overlap) = overlap {
+                    let overlap_tokens = self.count(&overlap);
+                    if overlap_tokens + unit_tokens <= self.max_tokens {
+                        tokens = overlap_tokens;
+                        current.push(overlap);
+                    }
+                }
+            }
+            tokens += unit_tokens;
+            current.push(unit);
+        }
+        if !current.is_empty() {
+            chunks.push(current.join("\n\n"));
+        }
+        chunks
+    }
+
+    /// The end of a chunk to repeat at the start of the next one: its last sentences that fit in the overlap, or its last words if not even one sentence fits.
+    fn overlap_of(&self, text: &str) -> Option<String> {
+        let budget = self.overlap_budget();
+        if budget == 0 {
+            return None;
+        }
+        let last_block = text.rsplit("\n\n").next()?;
+
+        let mut tail = Vec::new();
+        let mut tokens = 0;
+        for sentence in sentences(last_block).into_iter().rev() {
+            let sentence_tokens = self.count(&sentence);
+            if tokens + sentence_tokens > budget {
+                break;
+            }
+            tokens += sentence_tokens;
+            tail.push(sentence);
+        }
+        if !tail.is_empty() {
+            tail.reverse();
+            return Some(tail.join(" "));
+        }
+
+        let words = last_block.split_whitespace().collect_vec();
+        let mut start = words.len();
+        while start > 0 && self.count(&words[start - 1..].join(" ")) <= budget {
+            start -= 1;
+        }
+        (start < words.len()).then(|| words[start..].join(" "))
+    }
+}
+
+/// A rough count of the tokens a subword tokenizer would split text into: a token for every five characters of each word, rounding up.
+/// It errs on the high side for English, so chunks stay within a model's limit.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.split_whitespace()
+        .map(|word| word.chars().count().div_ceil(5))
+        .sum()
+}
+
+/// The blocks of Markdown under one heading.
+struct Section {
+    path: Vec<String>,
+    blocks: Vec<String>,
+}
+
+/// Split Markdown into its title (the first `#` heading) and the blocks under each heading.
+/// Blocks are separated by blank lines, except in fenced code which is always one block.
+fn sections(markdown: &str) -> (Option<String>, Vec<Section>) {
+    let mut title = None;
+    let mut headings: Vec<(usize, String)> = Vec::new();
+    let mut sections = vec![Section {
+        path: Vec::new(),
+        blocks: Vec::new(),
+    }];
+    let mut block = Vec::new();
+    let mut fence = None;
+
+    for line in markdown.lines() {
+        if let Some(marker) = fence {
+            block.push(line);
+            if line.trim_start().starts_with(marker) {
+                fence = None;
+            }
+            continue;
+        }
+        if let Some((_, marker)) = regex_captures!(r"^\s*(```+|~~~+)", line) {
+            fence = Some(marker);
+            block.push(line);
+            continue;
+        }
+        if let Some((_, hashes, heading)) =
+            regex_captures!(r"^(#{1,6})\s+(.+?)(?:\s+#+)?\s*$", line)
+        {
+            push_block(&mut block, &mut sections);
+            let level = hashes.len();
+            //? Headings are kept as plain text for breadcrumbs, so the escapes of Markdown characters are dropped.
+            let heading = regex_replace_all!(
+                r"\\([\\`*_{}\[\]()#+\-.!|~<>])",
+                heading,
+                |_, escaped: &str| { escaped.to_string() }
+            );
+            if level == 1 && title.is_none() {
+                title = Some(heading.to_string());
+                headings.clear();
+            } else {
+                headings.retain(|(outer, _)| *outer < level);
+                headings.push((level, heading.to_string()));
+            }
+            sections.push(Section {
+                path: headings
+                    .iter()
+                    .map(|(_, heading)| heading.clone())
+                    .collect(),
+                blocks: Vec::new(),
+            });
+            continue;
+        }
+        if line.trim().is_empty() {
+            push_block(&mut block, &mut sections);
+        } else {
+            block.push(line);
+        }
+    }
+    push_block(&mut block, &mut sections);
+    sections.retain(|section| !section.blocks.is_empty());
+    (title, sections)
+}
+
+fn push_block(block: &mut Vec<&str>, sections: &mut [Section]) {
+    if block.is_empty() {
+        return;
+    }
+    if let Some(section) = sections.last_mut() {
+        section.blocks.push(block.join("\n"));
+    }
+    block.clear();
+}
+
+/// Split text after each full stop, question mark or exclamation mark (and any closing quotes or brackets) that is followed by whitespace.
+fn sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    for end in regex!(r#"[.!?…]["'”’)\]]*\s+"#).find_iter(text) {
+        sentences.push(text[start..end.end()].trim().to_string());
+        start = end.end();
+    }
+    let rest = text[start..].trim();
+    if !rest.is_empty() {
+        sentences.push(rest.to_string());
+    }
+    sentences
+}
+
+/// Storage for the chunks of each page, kept alongside its `PageRecord`.
+#[async_trait]
+pub trait ChunkStore<U: UrlTrait>: Debug + Send + Sync {
+    async fn get_chunks(&self, url: &U) -> Result<Vec<Chunk>>;
+    /// Replace the chunks of a page.
+    async fn put_chunks(&self, url: &U, chunks: Vec<Chunk>) -> Result<()>;
+}
+
+/// A ChunkStore held in memory that can be saved to and loaded from a JSON file between runs.
+#[derive(Debug, Default)]
+pub struct MemoryChunkStore {
+    chunks: Mutex<HashMap<String, Vec<Chunk>>>,
+}
+
+impl MemoryChunkStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the chunks from a JSON file. A missing file gives an empty store.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let chunks = match tokio::fs::read_to_string(path).await {
+            Ok(json) => serde_json::from_str(&json).map_err(crate::Error::custom)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            chunks: Mutex::new(chunks),
+        })
+    }
+
+    /// Save the chunks to a JSON file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = {
+            let chunks = self.chunks.lock().await;
+            serde_json::to_string_pretty(&*chunks).map_err(crate::Error::custom)?
+        };
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+
+    /// The chunks of every page in the store.
+    pub async fn all_chunks(&self) -> Vec<Chunk> {
+        self.chunks
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl<U: UrlTrait + Sync> ChunkStore<U> for MemoryChunkStore {
+    async fn get_chunks(&self, url: &U) -> Result<Vec<Chunk>> {
+        Ok(self
+            .chunks
+            .lock()
+            .await
+            .get(&url.full_url())
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn put_chunks(&self, url: &U, chunks: Vec<Chunk>) -> Result<()> {
+        self.chunks.lock().await.insert(url.full_url(), chunks);
+        Ok(())
+    }
+}
+
+/// Stores chunks in SurrealDB, in the `chunk` table under their ids.
+#[derive(Debug, Clone)]
+pub struct SurrealChunkStore {
+    db: Surreal<Client>,
+}
+
+impl SurrealChunkStore {
+    pub fn new(db: &Surreal<Client>) -> Self {
+        Self { db: db.clone() }
+    }
+
+    /// The chunks of every page in the store, eg to rebuild a search index from.
+    pub async fn all_chunks(&self) -> Result<Vec<Chunk>> {
+        let mut response = self
+            .db
+            .query(format!(
+                "SELECT {CHUNK_FIELDS} FROM type::table($table) ORDER BY url, index"
+            ))
+            .bind(("table", CHUNK_TABLE))
+            .await
+            .map_err(crate::Error::custom)?;
+        response.take(0).map_err(crate::Error::custom)
+    }
+}
+
+#[async_trait]
+impl<U: UrlTrait + Sync> ChunkStore<U> for SurrealChunkStore {
+    async fn get_chunks(&self, url: &U) -> Result<Vec<Chunk>> {
+        let mut response = self
+            .db
+            .query(format!(
+                "SELECT {CHUNK_FIELDS} FROM type::table($table) WHERE url = $url ORDER BY index"
+            ))
+            .bind(("table", CHUNK_TABLE))
+            .bind(("url", url.full_url()))
+            .await
+            .map_err(crate::Error::custom)?;
+        response.take(0).map_err(crate::Error::custom)
+    }
+
+    async fn put_chunks(&self, url: &U, chunks: Vec<Chunk>) -> Result<()> {
+        self.db
+            .query("DELETE type::table($table) WHERE url = $url")
+            .bind(("table", CHUNK_TABLE))
+            .bind(("url", url.full_url()))
+            .await
+            .map_err(crate::Error::custom)?;
+        for chunk in chunks {
+            //? The id is the record's id, so it is left out of the content (a content id that isn't the record's is an error).
+            let id = chunk.id.clone();
+            let mut record = serde_json::to_value(chunk).map_err(crate::Error::custom)?;
+            if let Some(fields) = record.as_object_mut() {
+                fields.remove("id");
+            }
+            self.db
+                .query("UPDATE type::thing($table, $id) CONTENT $record")
+                .bind(("table", CHUNK_TABLE))
+                .bind(("id", id))
+                .bind(("record", record))
+                .await
+                .map_err(crate::Error::custom)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://www.example.com/rivers";
+
+    /// A page with a title, nested sections and paragraphs of short sentences.
+    fn markdown() -> String {
+        let paragraph = |topic: &str| {
+            format!("The {topic} rose again today. Boats were moored upstream. Walkers kept away from the banks.")
+        };
+        format!(
+            "# Rivers\n\nRivers carry water to the sea.\n\n## Flooding\n\n{}\n\n{}\n\n### Defences\n\n{}\n\n{}\n\n## Wildlife\n\n{}",
+            paragraph("Thames"),
+            paragraph("Severn"),
+            paragraph("Trent"),
+            paragraph("Wye"),
+            paragraph("Tweed"),
+        )
+    }
+
+    #[test]
+    fn the_same_markdown_gets_the_same_ids() {
+        let chunker = Chunker::new().with_max_tokens(20).with_overlap(6);
+        let ids = |markdown: &str| {
+            chunker
+                .chunk_markdown(URL, markdown)
+                .into_iter()
+                .map(|chunk| chunk.id)
+                .collect_vec()
+        };
+
+        let first = ids(&markdown());
+        assert!(first.len() > 1);
+        assert_eq!(first, ids(&markdown()));
+        assert_eq!(first.iter().unique().count(), first.len());
+    }
+
+    #[test]
+    fn chunks_fit_in_max_tokens_unless_they_are_one_word() {
+        let long_word = "Pneumonoultramicroscopicsilicovolcanoconiosis";
+        let markdown = format!("{}\n\nA {long_word} case was reported.", markdown());
+        let chunker = Chunker::new().with_max_tokens(8).with_overlap(2);
+        let chunks = chunker.chunk_markdown(URL, &markdown);
+
+        for chunk in &chunks {
+            assert_eq!(chunk.token_count, estimate_tokens(&chunk.text));
+            if chunk.text != long_word {
+                assert!(chunk.token_count <= 8, "{chunk:?}");
+            }
+        }
+        assert!(chunks.iter().any(|chunk| chunk.text == long_word));
+    }
+
+    #[test]
+    fn each_chunk_starts_with_the_end_of_the_one_before() {
+        let chunker = Chunker::new().with_max_tokens(20).with_overlap(6);
+        let chunks = chunker.chunk_markdown(URL, &markdown());
+
+        let flooding = chunks
+            .iter()
+            .filter(|chunk| chunk.section_path == ["Flooding"])
+            .collect_vec();
+        assert!(flooding.len() > 1);
+        for (previous, chunk) in flooding.iter().tuple_windows() {
+            let overlap = chunk.text.split("\n\n").next().unwrap();
+            assert!(sentences(overlap).len() == 1, "{overlap}");
+            assert!(previous.text.ends_with(overlap));
+            assert_ne!(chunk.text, overlap);
+        }
+    }
+
+    #[test]
+    fn chunks_are_under_the_headings_they_follow() {
+        let chunks = Chunker::new().chunk_markdown(URL, &markdown());
+
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.title.as_deref() == Some("Rivers")));
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.section_path.clone())
+                .collect_vec(),
+            [
+                vec![],
+                vec!["Flooding".to_string()],
+                vec!["Flooding".to_string(), "Defences".to_string()],
+                vec!["Wildlife".to_string()],
+            ]
+        );
+        assert_eq!(chunks[2].breadcrumb(), "Rivers > Flooding > Defences");
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.index).collect_vec(),
+            [0, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn fenced_code_is_one_block() {
+        let markdown = "# Setup\n\n```toml\n[package]\n\n# a comment, not a heading\nname = \"rivers\"\n```\n\nThen build it.";
+        let chunks = Chunker::new().chunk_markdown(URL, markdown);
+
+        assert_eq!(chunks.len(), 1);
+        assert!(chunks[0].section_path.is_empty());
+        assert!(chunks[0]
+            .text
+            .starts_with("```toml\n[package]\n\n# a comment, not a heading\n"));
+    }
+
+    #[test]
+    fn repeated_text_under_a_heading_gets_distinct_ids() {
+        let caption = "Photograph: Environment Agency.";
+        let markdown = format!("# Rivers\n\n## Flooding\n\n{caption}\n\n{caption}");
+        let chunker = Chunker::new().with_max_tokens(8).with_overlap(0);
+        let chunks = chunker.chunk_markdown(URL, &markdown);
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, chunks[1].text);
+        assert_eq!(chunks[0].content_hash, chunks[1].content_hash);
+        assert_ne!(chunks[0].id, chunks[1].id);
+    }
+}
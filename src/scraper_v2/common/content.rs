@@ -1,8 +1,8 @@
 use crate::Result;
 
-use super::{LinkTo, Page, UrlTrait};
+use super::{escape_markdown, FetchedDocument, LinkTo, Page, UrlTrait};
 
-use scraper::Html;
+use chrono::{DateTime, Utc};
 use std::collections::HashSet;
 use std::fmt::Debug;
 
@@ -11,9 +11,74 @@ pub trait ScrapableContent: Debug + Eq + Send {
     /// The type of the Url.
     type Url: UrlTrait;
     /// This is a helper method that takes a url and a document and returns a Result of the type.
-    fn from_scraped_page(url: &Self::Url, document: &Html) -> Result<Self>
+    /// The document derefs to `Html` and also carries the final url the page was served from.
+    fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self>
     where
         Self: Sized;
 
     fn get_related_pages(&self) -> HashSet<Page<LinkTo, Self::Url>>;
+
+    /// Versions of the same page in other languages or editions. These are only followed when the handler is told which ones to follow (`PageHandler::with_alternates`).
+    fn get_alternate_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
+        HashSet::new()
+    }
+
+    /// The url the content says it belongs at, when the page itself knows better than the request (eg the article a Wikipedia redirect landed on).
+    /// Pages whose canonical url differs from the requested one are stored under the canonical url and the requested one is recorded as an alias.
+    fn canonical_url(&self) -> Option<Self::Url> {
+        None
+    }
+
+    /// The page this content only points at, eg a redirect page that wasn't followed by the server.
+    /// The handler records the page as an alias of the target and queues the target instead of keeping the content.
+    fn redirect_target(&self) -> Option<Self::Url> {
+        None
+    }
+
+    /// The main body text of the content. Used to fingerprint pages so near-duplicates served under different urls are only stored once.
+    fn body_text(&self) -> String {
+        String::new()
+    }
+
+    /// The content as Markdown, to read or to split up for search. Content that keeps the markup of the page renders it with `Renderer`, so links, emphasis, lists and headings are kept.
+    /// By default this is the body text, escaped.
+    fn to_markdown(&self) -> String {
+        escape_markdown(&self.body_text())
+    }
+
+    /// The title of the page, eg the headline of an article.
+    fn page_title(&self) -> Option<String> {
+        None
+    }
+
+    /// The categories the content is filed under, eg a Wikipedia article's categories. They are kept on the content's chunks so searches can be limited to a category.
+    fn categories(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The topics the content is tagged with, eg the related topics of a BBC article. They are kept on the content's chunks for keyword search facets.
+    fn topics(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The urls of the sources the content cites, eg the references of a Wikipedia article.
+    fn citations(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// The names of the entity the content is the page for, eg the title of a Wikipedia article and the redirects it was reached through.
+    /// Pages on other sites that name the entity are linked to this one in the knowledge graph.
+    fn entity_names(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// When the content says it was published, used to decide when a page is worth revisiting and to filter crawls by date.
+    fn published(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// When the content says it was last updated, if it has been.
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        None
+    }
 }
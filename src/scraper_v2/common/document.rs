@@ -0,0 +1,101 @@
+use super::{StructuredData, Validators};
+
+use scraper::{Html, Selector};
+use std::fmt::Debug;
+use std::ops::Deref;
+
+/// A fetched HTML document along with the url it was finally served from (after any redirects).
+///
+/// Derefs to `Html` so it can be used anywhere a parsed document is expected.
+pub struct FetchedDocument {
+    html: Html,
+    final_url: String,
+    validators: Validators,
+}
+
+impl FetchedDocument {
+    pub fn new(html: Html, final_url: impl Into<String>) -> Self {
+        Self {
+            html,
+            final_url: final_url.into(),
+            validators: Validators::default(),
+        }
+    }
+
+    /// Set the ETag / Last-Modified validators the document was served with.
+    pub fn with_validators(mut self, validators: Validators) -> Self {
+        self.validators = validators;
+        self
+    }
+
+    pub fn validators(&self) -> &Validators {
+        &self.validators
+    }
+
+    /// Parse a raw HTML string that was served from `final_url`.
+    pub fn parse(html: &str, final_url: impl Into<String>) -> Self {
+        Self::new(Html::parse_document(html), final_url)
+    }
+
+    /// The url the document was served from, after following redirects.
+    pub fn final_url(&self) -> &str {
+        &self.final_url
+    }
+
+    /// The url relative links should be resolved against. This is the `<base href>` if the document has one (itself resolved against the final url), otherwise the final url.
+    pub fn base_url(&self) -> String {
+        let base_selector = Selector::parse("base[href]").unwrap();
+        self.html
+            .select(&base_selector)
+            .next()
+            .and_then(|base| base.value().attr("href"))
+            .and_then(|href| {
+                url::Url::parse(&self.final_url)
+                    .and_then(|final_url| final_url.join(href.trim()))
+                    .ok()
+            })
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| self.final_url.clone())
+    }
+
+    /// The `<link rel="canonical">` url of the document, resolved against the final url.
+    pub fn canonical_link(&self) -> Option<String> {
+        let canonical_selector = Selector::parse("link[rel~='canonical'][href]").unwrap();
+        let href = self
+            .html
+            .select(&canonical_selector)
+            .next()?
+            .value()
+            .attr("href")?;
+        url::Url::parse(&self.final_url)
+            .and_then(|final_url| final_url.join(href.trim()))
+            .ok()
+            .map(|url| url.to_string())
+    }
+
+    /// The JSON-LD, Open Graph, Twitter card and microdata metadata embedded in the document.
+    pub fn structured_data(&self) -> StructuredData {
+        StructuredData::from_document(&self.html)
+    }
+
+    pub fn html(&self) -> &Html {
+        &self.html
+    }
+}
+
+impl Deref for FetchedDocument {
+    type Target = Html;
+
+    fn deref(&self) -> &Self::Target {
+        &self.html
+    }
+}
+
+impl Debug for FetchedDocument {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FetchedDocument")
+            .field("final_url", &self.final_url)
+            .field("validators", &self.validators)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,21 @@
+#[derive(Debug, derive_more::From)]
+pub enum CommonError {
+    #[from]
+    Custom(String),
+    InvalidUrl {
+        url: String,
+        reason: String,
+    },
+    InvalidXml {
+        reason: String,
+    },
+}
+
+// Error boilerplate.
+impl core::fmt::Display for CommonError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for CommonError {}
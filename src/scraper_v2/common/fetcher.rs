@@ -0,0 +1,30 @@
+use super::{make_conditional_request, FetchedDocument, UrlTrait, Validators};
+use crate::Result;
+
+use async_trait::async_trait;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Where a crawl gets the documents it scrapes from. The default is the site itself (`WebFetcher`), a site can also provide another source (eg an API) that gives the same document for a url.
+#[async_trait]
+pub trait Fetcher<U: UrlTrait>: Debug + Send + Sync {
+    /// Fetch the document for a url, sending the validators from a previous fetch if there are any.
+    /// Returns None if the source says the page has not been modified.
+    async fn fetch(&self, url: &U, validators: &Validators) -> Result<Option<FetchedDocument>>;
+
+    /// Called with every url of a crawl iteration before they are fetched one by one, for a source that can fetch several pages in one request (eg an API taking many titles at once).
+    async fn prefetch(&self, _urls: &[Arc<U>]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Fetches the page from the site with a (conditional) GET request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebFetcher;
+
+#[async_trait]
+impl<U: UrlTrait + Sync> Fetcher<U> for WebFetcher {
+    async fn fetch(&self, url: &U, validators: &Validators) -> Result<Option<FetchedDocument>> {
+        make_conditional_request(url, validators).await
+    }
+}
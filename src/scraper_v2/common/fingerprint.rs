@@ -0,0 +1,173 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Number of words in each shingle that goes into the SimHash.
+const SHINGLE_SIZE: usize = 3;
+
+/// A 64 bit SimHash of a page's body text. Near-duplicate texts produce fingerprints that differ in only a few bits.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct SimHash(u64);
+
+impl SimHash {
+    /// Fingerprint some text. Returns None if the text has no words to hash.
+    pub fn from_text(text: &str) -> Option<Self> {
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<String>>();
+        if words.is_empty() {
+            return None;
+        }
+
+        let mut weights = [0i64; 64];
+        let shingle_size = SHINGLE_SIZE.min(words.len());
+        for shingle in words.windows(shingle_size) {
+            let hash = fnv1a(shingle.join(" ").as_bytes());
+            for (bit, weight) in weights.iter_mut().enumerate() {
+                if hash & (1 << bit) != 0 {
+                    *weight += 1;
+                } else {
+                    *weight -= 1;
+                }
+            }
+        }
+
+        let fingerprint = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0)
+            .fold(0u64, |acc, (bit, _)| acc | (1 << bit));
+        Some(SimHash(fingerprint))
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    /// The number of bits that differ between two fingerprints.
+    pub fn distance(&self, other: &SimHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// Whether two fingerprints are within `max_distance` bits of each other.
+    pub fn is_near_duplicate(&self, other: &SimHash, max_distance: u32) -> bool {
+        self.distance(other) <= max_distance
+    }
+}
+
+/// Fingerprints with a value each (eg the page they are of), to find one near a fingerprint without comparing against all of them.
+///
+/// Fingerprints within `max_distance` bits of each other agree on at least one of `max_distance + 1` bands of their bits, so only those sharing a band are compared.
+#[derive(Debug, Clone)]
+pub struct SimHashIndex<T> {
+    max_distance: u32,
+    entries: Vec<(SimHash, T)>,
+    /// For each band, the entries with each value of its bits.
+    bands: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+impl<T> SimHashIndex<T> {
+    /// An index finding fingerprints at most `max_distance` bits away (at most 63).
+    pub fn new(max_distance: u32) -> Self {
+        let max_distance = max_distance.min(63);
+        Self {
+            max_distance,
+            entries: Vec::new(),
+            bands: vec![HashMap::new(); max_distance as usize + 1],
+        }
+    }
+
+    pub fn insert(&mut self, fingerprint: SimHash, value: T) {
+        let index = self.entries.len();
+        for (band, key) in self.band_keys(&fingerprint).enumerate() {
+            self.bands[band].entry(key).or_default().push(index);
+        }
+        self.entries.push((fingerprint, value));
+    }
+
+    /// The value of the first fingerprint added that is within `max_distance` bits of this one.
+    pub fn find(&self, fingerprint: &SimHash) -> Option<&T> {
+        self.band_keys(fingerprint)
+            .enumerate()
+            .filter_map(|(band, key)| self.bands[band].get(&key))
+            .flatten()
+            .filter(|index| {
+                self.entries[**index]
+                    .0
+                    .is_near_duplicate(fingerprint, self.max_distance)
+            })
+            .min()
+            .map(|index| &self.entries[*index].1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The bits of each band, the last band taking the bits left over.
+    fn band_keys(&self, fingerprint: &SimHash) -> impl Iterator<Item = u64> {
+        let bands = self.bands.len() as u32;
+        let width = 64 / bands;
+        let value = fingerprint.value();
+        (0..bands).map(move |band| {
+            let bits = if band == bands - 1 {
+                64 - width * band
+            } else {
+                width
+            };
+            let mask = if bits == 64 {
+                u64::MAX
+            } else {
+                (1 << bits) - 1
+            };
+            (value >> (width * band)) & mask
+        })
+    }
+}
+
+/// A stable hash of some text, used to tell whether a page's content changed between crawls.
+pub fn content_hash(text: &str) -> String {
+    format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+/// 64 bit FNV-1a. Used instead of `DefaultHasher` so fingerprints are stable across builds and can be stored.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn the_index_finds_what_a_scan_finds(
+            fingerprints in proptest::collection::vec(any::<u64>(), 0..50),
+            flips in proptest::collection::vec(0u32..64, 0..6),
+            pick in any::<prop::sample::Index>(),
+        ) {
+            let mut index = SimHashIndex::new(3);
+            for (position, fingerprint) in fingerprints.iter().enumerate() {
+                index.insert(SimHash(*fingerprint), position);
+            }
+            //? Look for a fingerprint a few bits away from one that was added, or any fingerprint when none were.
+            let mut wanted = fingerprints.get(pick.index(fingerprints.len().max(1))).copied().unwrap_or_default();
+            for bit in flips {
+                wanted ^= 1 << bit;
+            }
+            let wanted = SimHash(wanted);
+            let scanned = fingerprints
+                .iter()
+                .position(|fingerprint| SimHash(*fingerprint).is_near_duplicate(&wanted, 3));
+            prop_assert_eq!(index.find(&wanted).copied(), scanned);
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use super::{FetchedDocument, LinkTo, Page, UrlTrait};
+
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A `rel` attribute value on a link.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LinkRel {
+    NoFollow,
+    Canonical,
+    Alternate,
+    /// The next page of a paginated listing.
+    Next,
+    Other(String),
+}
+
+impl LinkRel {
+    /// Parse a space separated `rel` attribute into its values.
+    pub fn parse_list(rel: &str) -> Vec<LinkRel> {
+        rel.split_ascii_whitespace()
+            .map(|value| match value.to_ascii_lowercase().as_str() {
+                "nofollow" => LinkRel::NoFollow,
+                "canonical" => LinkRel::Canonical,
+                "alternate" => LinkRel::Alternate,
+                "next" => LinkRel::Next,
+                other => LinkRel::Other(other.to_string()),
+            })
+            .collect()
+    }
+}
+
+/// A link found on a page with its href resolved to an absolute url.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractedLink {
+    pub href: String,
+    pub text: String,
+    pub rel: Vec<LinkRel>,
+}
+
+impl ExtractedLink {
+    pub fn is_nofollow(&self) -> bool {
+        self.rel.contains(&LinkRel::NoFollow)
+    }
+
+    /// Turn the link into a LinkTo page if its href is a valid url of type U.
+    pub fn to_page<U: UrlTrait>(&self) -> Option<Page<LinkTo, U>> {
+        let url = U::try_from(self.href.clone()).ok()?;
+        Some(Page::<LinkTo, U>::new_with_rel(
+            url,
+            self.text.trim(),
+            self.rel.clone(),
+        ))
+    }
+}
+
+/// Extract every `<a href>` within `scope`, resolving each href against the document's `<base>` element or final url.
+///
+/// Hrefs that can't be resolved (eg `javascript:` or malformed urls) are skipped.
+pub fn extract_links(scope: &ElementRef, document: &FetchedDocument) -> Vec<ExtractedLink> {
+    let link_selector = Selector::parse("a[href]").unwrap();
+    let base = url::Url::parse(&document.base_url()).ok();
+
+    scope
+        .select(&link_selector)
+        .filter_map(|element| {
+            let href = element.value().attr("href")?.trim();
+            let resolved = match &base {
+                Some(base) => base.join(href).ok()?,
+                None => url::Url::parse(href).ok()?,
+            };
+            if !matches!(resolved.scheme(), "http" | "https") {
+                return None;
+            }
+            Some(ExtractedLink {
+                href: resolved.to_string(),
+                text: element.text().collect::<String>(),
+                rel: element
+                    .value()
+                    .attr("rel")
+                    .map(LinkRel::parse_list)
+                    .unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// Extract the links within `scope` that are valid urls of type U as LinkTo pages.
+pub fn extract_page_links<U: UrlTrait>(
+    scope: &ElementRef,
+    document: &FetchedDocument,
+) -> HashSet<Page<LinkTo, U>> {
+    extract_links(scope, document)
+        .iter()
+        .filter_map(ExtractedLink::to_page)
+        .collect()
+}
@@ -1,11 +1,10 @@
 use crate::Result;
 
-use super::UrlTrait;
+use super::{CommonError, FetchedDocument, UrlTrait, Validators};
 
 use lazy_static::lazy_static;
 use rand::seq::SliceRandom;
 use reqwest::{Client, IntoUrl, Response};
-use scraper::Html;
 use std::sync::Arc;
 use tokio::sync::Semaphore;
 use tokio::task;
@@ -53,19 +52,81 @@ lazy_static! {
         .unwrap();
 }
 
-/// Make a request to a given URL and return the scraper HTML content
+/// Make a request to a given URL and return the parsed HTML along with the final (post-redirect) url.
 #[instrument]
-pub async fn make_request(url: &impl UrlTrait) -> Result<Html> {
+pub async fn make_request(url: &impl UrlTrait) -> Result<FetchedDocument> {
+    make_conditional_request(url, &Validators::default())
+        .await?
+        .ok_or_else(|| {
+            CommonError::Custom(format!(
+                "Unexpected 304 Not Modified from: {}",
+                url.full_url()
+            ))
+            .into()
+        })
+}
+
+/// Make a request to a given URL sending the validators (If-None-Match / If-Modified-Since) from a previous fetch.
+/// Returns None if the server says the page has not been modified.
+#[instrument]
+pub async fn make_conditional_request(
+    url: &impl UrlTrait,
+    validators: &Validators,
+) -> Result<Option<FetchedDocument>> {
     debug!("Making request to: {:?}", url.full_url());
     sleep(Duration::from_millis(50)).await; //? something pretty arbitrary but find it helps when sending off lots of requests at once
     let _permit = SEMAPHORE.acquire().await;
     let user_agent = USER_AGENTS.choose(&mut rand::thread_rng()).unwrap();
-    let response = CLIENT
-        .get(url.full_url())
-        .header("User-Agent", *user_agent)
-        .send()
-        .await?;
+    let mut request = CLIENT.get(url.full_url()).header("User-Agent", *user_agent);
+    if let Some(etag) = &validators.etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = &validators.last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
+    let response = request.send().await?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        info!("---> Not modified: {:?}", url.full_url());
+        return Ok(None);
+    }
+
+    let header = |name: reqwest::header::HeaderName| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    let validators = Validators {
+        etag: header(reqwest::header::ETAG),
+        last_modified: header(reqwest::header::LAST_MODIFIED),
+    };
+    let final_url = response.url().to_string();
     let html_content = response.text().await?;
     info!("---> Finished request to: {:?}", url.full_url());
-    Ok(Html::parse_document(&html_content))
+    Ok(Some(
+        FetchedDocument::parse(&html_content, final_url).with_validators(validators),
+    ))
+}
+
+/// Make a request to any URL and return the raw response body. Used for non-HTML resources such as sitemaps and feeds.
+pub async fn fetch_bytes(url: &str) -> Result<Vec<u8>> {
+    let user_agent = USER_AGENTS.choose(&mut rand::thread_rng()).unwrap();
+    fetch_bytes_as(url, user_agent).await
+}
+
+/// Make a request to any URL with the given user agent and return the raw response body. Used for APIs that ask clients to identify themselves (eg the Wikimedia APIs).
+#[instrument]
+pub async fn fetch_bytes_as(url: &str, user_agent: &str) -> Result<Vec<u8>> {
+    debug!("Fetching: {:?}", url);
+    let _permit = SEMAPHORE.acquire().await;
+    let response = CLIENT
+        .get(url)
+        .header("User-Agent", user_agent)
+        .send()
+        .await?
+        .error_for_status()?;
+    let bytes = response.bytes().await?;
+    info!("---> Finished fetching: {:?}", url);
+    Ok(bytes.to_vec())
 }
@@ -1,13 +1,47 @@
+mod canonical;
+mod chunk;
 mod content;
+mod document;
+mod error;
+mod fetcher;
+mod fingerprint;
+mod links;
 mod make_request;
 mod page;
+mod recrawl;
+mod render;
 mod scraper;
+mod seed;
+mod structured;
+mod timestamp;
 mod url;
 
-pub use make_request::make_request;
-pub use page::{LinkTo, Page, PageState, Scrapable, Scraped, ToScrape, WasScraped};
+pub use canonical::{normalize_percent_encoding, Canonicalizer};
+pub use chunk::{estimate_tokens, Chunk, ChunkStore, Chunker, MemoryChunkStore, SurrealChunkStore};
+pub use document::FetchedDocument;
+pub use error::CommonError;
+pub use fetcher::{Fetcher, WebFetcher};
+pub(crate) use fingerprint::fnv1a;
+pub use fingerprint::{content_hash, SimHash, SimHashIndex};
+pub use links::{extract_links, extract_page_links, ExtractedLink, LinkRel};
+pub use make_request::{fetch_bytes, fetch_bytes_as, make_conditional_request, make_request};
+pub use page::{
+    AliasReason, AliasRecord, LinkTo, Page, PageState, Scrapable, Scraped, ToScrape, WasScraped,
+};
 pub use url::UrlTrait;
 
-pub use scraper::{PageHandler, PageScraper};
+pub use recrawl::{
+    MemoryPageStore, PageRecord, PageStore, RecrawlDecision, RevisitPolicy, Validators,
+};
+pub use render::{
+    escape_markdown, normalize_text, strip_markdown, to_markdown, to_plain_text, RenderFormat,
+    Renderer,
+};
+pub use scraper::{PageHandler, PageScraper, UrlFilter};
+pub use seed::{
+    decode_body, parse_date, parse_feed, parse_sitemap, SeedEntry, SeedSource, Sitemap,
+};
+pub use structured::{ArticleMetadata, MicrodataItem, StructuredData};
+pub use timestamp::{DateRange, Timestamp};
 
 pub use content::ScrapableContent;
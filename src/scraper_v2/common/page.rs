@@ -1,8 +1,11 @@
 use crate::Result;
 
-use super::{make_request, ScrapableContent, UrlTrait};
+use super::{
+    content_hash, make_request, FetchedDocument, Fetcher, LinkRel, PageRecord, ScrapableContent,
+    SimHash, UrlTrait, Validators, WebFetcher,
+};
 
-use scraper::Html;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fmt::Debug;
@@ -24,6 +27,9 @@ pub struct ToScrape;
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub struct LinkTo {
     title: String,
+    rel: Vec<LinkRel>,
+    /// When the linked page was published, if the link source said (eg a sitemap or feed).
+    published: Option<DateTime<Utc>>,
 }
 
 /// A struct representing a page that has been scraped. The content field is the scraped content of the page.
@@ -31,6 +37,26 @@ pub struct LinkTo {
 pub struct WasScraped<C: ScrapableContent> {
     content: C,
     link_title: Option<String>,
+    /// The url the page was finally served from, after any redirects.
+    final_url: String,
+    /// The `<link rel="canonical">` url of the page, if it had one.
+    canonical_link: Option<String>,
+    /// The ETag / Last-Modified the page was served with.
+    validators: Validators,
+    fetched_at: DateTime<Utc>,
+}
+
+impl<C: ScrapableContent> WasScraped<C> {
+    fn new(content: C, link_title: Option<String>, document: &FetchedDocument) -> Self {
+        Self {
+            content,
+            link_title,
+            final_url: document.final_url().to_string(),
+            canonical_link: document.canonical_link(),
+            validators: document.validators().clone(),
+            fetched_at: Utc::now(),
+        }
+    }
 }
 
 macro_rules! impl_page_state_and_as_ref {
@@ -60,7 +86,7 @@ impl<C: ScrapableContent> PageState for WasScraped<C> {
 /// The reason we need to use ?Sized is because in the Scraper we want a list of Scrapable pages (ToScrape and LinkTo). We can't have a list of Scrapable because Scrapable is a trait and doesn't have a known size at compile time. So we need to use ?Sized to relax the Sized trait bound.
 ///
 /// The following code would now not work:
-/// ```rust
+/// ```ignore
 /// let page: Page<dyn PageState, Url> = Page {
 ///     url: Arc::new(Url::parse("https://example.com").unwrap()),
 ///     state: ToScrape,
@@ -69,7 +95,7 @@ impl<C: ScrapableContent> PageState for WasScraped<C> {
 /// This is because dyn PageState is a trait object and does not have a known size at compile time. The Page struct requires its state field to be Sized, so this code will not compile.
 ///
 /// However, you can still create a Page instance if S is Sized. For example:
-/// ```rust
+/// ```ignore
 ///
 /// let page: Page<ToScrape, Url> = Page {
 ///     url: Arc::new(Url::parse("https://example.com").unwrap()),
@@ -97,6 +123,7 @@ impl<S: PageState + ?Sized, U: UrlTrait> Page<S, U> {
         Arc::clone(&self.url)
     }
     /// Transition to a new state while keeping the page in place or in a box.
+    #[allow(clippy::boxed_local)]
     fn transition_in_place<N: PageState>(self: Box<Self>, next: N) -> Box<Page<N, U>> {
         Box::new(Page {
             url: Arc::clone(&self.url),
@@ -115,7 +142,7 @@ impl<S: PageState, U: UrlTrait> Hash for Page<S, U> {
 impl<S: PageState, U: UrlTrait> AsRef<U> for Page<S, U> {
     /// Get a reference to the URL of the page.
     fn as_ref(&self) -> &U {
-        &*self.url
+        &self.url
     }
 }
 
@@ -136,16 +163,37 @@ impl<U: UrlTrait> Page<ToScrape, U> {
 impl<U: UrlTrait> Page<LinkTo, U> {
     /// Create a new LinkTo page with a URL and a title.
     pub fn new(url: U, title: impl Into<String>) -> Self {
+        Self::new_with_rel(url, title, Vec::new())
+    }
+    /// Create a new LinkTo page with a URL and a title.
+    pub fn new_link_to(url: U, title: impl Into<String>) -> Self {
+        Self::new(url, title)
+    }
+    /// Create a new LinkTo page with a URL, a title and the `rel` values of the link.
+    pub fn new_with_rel(url: U, title: impl Into<String>, rel: Vec<LinkRel>) -> Self {
         Page {
             url: Arc::new(url),
             state: LinkTo {
                 title: title.into(),
+                rel,
+                published: None,
             },
         }
     }
-    /// Create a new LinkTo page with a URL and a title.
-    pub fn new_link_to(url: U, title: impl Into<String>) -> Self {
-        Self::new(url, title)
+    /// Set the publication date of the linked page.
+    pub fn with_published(mut self, published: Option<DateTime<Utc>>) -> Self {
+        self.state.published = published;
+        self
+    }
+    pub fn published(&self) -> Option<DateTime<Utc>> {
+        self.state.published
+    }
+    /// The `rel` values of the link (eg nofollow, canonical, alternate).
+    pub fn rel(&self) -> &[LinkRel] {
+        &self.state.rel
+    }
+    pub fn is_nofollow(&self) -> bool {
+        self.state.rel.contains(&LinkRel::NoFollow)
     }
 }
 
@@ -165,7 +213,7 @@ impl Scrapable for LinkTo {
 
 impl<U: UrlTrait, S: Scrapable> Page<S, U> {
     /// Scrape the page. This will make a request to the page and scrape the content. The content is then converted into a Scraped type.
-    pub async fn scrape<C: ScrapableContent<Url = U>>(self) -> Result<Page<WasScraped<C>, U>>
+    pub async fn scrape<C>(self) -> Result<Page<WasScraped<C>, U>>
     where
         C: ScrapableContent<Url = U>,
     {
@@ -176,22 +224,27 @@ impl<U: UrlTrait, S: Scrapable> Page<S, U> {
             url.to_string(),
             title.as_ref().unwrap_or(&"[No title]".to_string())
         );
-        let html = make_request(url).await?;
-        let page = C::from_scraped_page(&url, &html)?;
+        let document = make_request(url).await?;
+        let page = C::from_scraped_page(url, &document)?;
+
+        Ok(self.transition(WasScraped::new(page, title, &document)))
+    }
 
-        Ok(self.transition(WasScraped {
-            content: page,
-            link_title: title,
-        }))
+    /// Scrape the page from a document that was got some other way than fetching it, eg read from a dump.
+    pub fn scrape_document<C>(self, document: &FetchedDocument) -> Result<Page<WasScraped<C>, U>>
+    where
+        C: ScrapableContent<Url = U>,
+    {
+        let title = self.state.get_title();
+        let page = C::from_scraped_page(self.url.as_ref(), document)?;
+        Ok(self.transition(WasScraped::new(page, title, document)))
     }
 }
 impl<U: UrlTrait, S: Scrapable + ?Sized> Page<S, U> {
     /// Scrape the page. This will make a request to the page and scrape the content. The content is then converted into a Scraped type.
     /// Would prefer if this was consuming self but it's not possible because of the transition method.
     #[instrument]
-    pub async fn scrape_in_place<C: ScrapableContent<Url = U>>(
-        self: Box<Self>,
-    ) -> Result<Page<WasScraped<C>, U>>
+    pub async fn scrape_in_place<C>(self: Box<Self>) -> Result<Page<WasScraped<C>, U>>
     where
         C: ScrapableContent<Url = U>,
     {
@@ -202,13 +255,51 @@ impl<U: UrlTrait, S: Scrapable + ?Sized> Page<S, U> {
             url.to_string(),
             title.as_ref().unwrap_or(&"[No title]".to_string())
         );
-        let html = make_request(url).await?;
-        let page = C::from_scraped_page(&url, &html)?;
+        let document = make_request(url).await?;
+        let page = C::from_scraped_page(url, &document)?;
         // Because we are going from a unsized type to a sized type, we can take the data out of the box and put it back on the stack.
-        Ok(*self.transition_in_place(WasScraped {
-            content: page,
-            link_title: title,
-        }))
+        Ok(*self.transition_in_place(WasScraped::new(page, title, &document)))
+    }
+
+    /// Scrape the page with a conditional request using the validators from a previous fetch.
+    /// Returns None if the server says the page has not been modified.
+    pub async fn scrape_in_place_conditional<C>(
+        self: Box<Self>,
+        validators: &Validators,
+    ) -> Result<Option<Page<WasScraped<C>, U>>>
+    where
+        C: ScrapableContent<Url = U>,
+        U: Sync,
+    {
+        self.scrape_in_place_with::<C>(&WebFetcher, validators)
+            .await
+    }
+
+    /// Scrape the page with the document from `fetcher`, sending the validators from a previous fetch if there are any.
+    /// Returns None if the fetcher says the page has not been modified.
+    #[instrument]
+    pub async fn scrape_in_place_with<C>(
+        self: Box<Self>,
+        fetcher: &dyn Fetcher<U>,
+        validators: &Validators,
+    ) -> Result<Option<Page<WasScraped<C>, U>>>
+    where
+        C: ScrapableContent<Url = U>,
+    {
+        let title = self.state.get_title();
+        let url = self.url.as_ref();
+        info!(
+            "Conditionally scraping: {} - {}",
+            url.to_string(),
+            title.as_ref().unwrap_or(&"[No title]".to_string())
+        );
+        let Some(document) = fetcher.fetch(url, validators).await? else {
+            return Ok(None);
+        };
+        let page = C::from_scraped_page(url, &document)?;
+        Ok(Some(*self.transition_in_place(WasScraped::new(
+            page, title, &document,
+        ))))
     }
 }
 
@@ -223,4 +314,90 @@ where
     pub fn get_all_page_links(&self) -> HashSet<Page<LinkTo, U>> {
         self.state.content.get_related_pages()
     }
+
+    /// Links to versions of the page in other languages or editions.
+    pub fn get_alternate_page_links(&self) -> HashSet<Page<LinkTo, U>> {
+        self.state.content.get_alternate_pages()
+    }
+
+    pub fn content(&self) -> &C {
+        &self.state.content
+    }
+
+    /// The canonical url of the page if it differs from the url that was requested, along with why it is considered canonical.
+    /// A `<link rel="canonical">` takes precedence over the url the content declares, which takes precedence over the redirect target.
+    pub fn canonical_url(&self) -> Option<(U, AliasReason)> {
+        let candidates = [
+            (
+                self.state
+                    .canonical_link
+                    .clone()
+                    .and_then(|link| U::try_from(link).ok()),
+                AliasReason::CanonicalLink,
+            ),
+            (self.state.content.canonical_url(), AliasReason::Redirect),
+            (
+                U::try_from(self.state.final_url.clone()).ok(),
+                AliasReason::Redirect,
+            ),
+        ];
+        candidates.into_iter().find_map(|(candidate, reason)| {
+            let url = candidate?;
+            (url != *self.url).then_some((url, reason))
+        })
+    }
+
+    /// The page this one only points at (see `ScrapableContent::redirect_target`), if it isn't the page itself.
+    pub fn redirect_target(&self) -> Option<U> {
+        self.state
+            .content
+            .redirect_target()
+            .filter(|target| *target != *self.url)
+    }
+
+    /// The record to store for this page so the next crawl can tell if it changed.
+    pub fn record(&self) -> PageRecord {
+        let body_text = self.state.content.body_text();
+        PageRecord {
+            fetched_at: self.state.fetched_at,
+            validators: self.state.validators.clone(),
+            content_hash: (!body_text.is_empty()).then(|| content_hash(&body_text)),
+            published: self.state.content.published(),
+        }
+    }
+
+    /// A SimHash of the page's body text, None if the content has no body text.
+    pub fn fingerprint(&self) -> Option<SimHash> {
+        SimHash::from_text(&self.state.content.body_text())
+    }
+}
+
+/// Why a url is considered an alias of another page.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum AliasReason {
+    /// The page declared another url with `<link rel="canonical">`.
+    CanonicalLink,
+    /// The request was redirected to another url.
+    Redirect,
+    /// The page's body text is a near duplicate of an already scraped page.
+    DuplicateContent,
+}
+
+/// A record that `alias` is the same page as `canonical`.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct AliasRecord<U: UrlTrait> {
+    pub alias: Arc<U>,
+    pub canonical: Arc<U>,
+    pub reason: AliasReason,
+}
+
+// Manual impl so U doesn't need to be Clone, only the Arcs are cloned.
+impl<U: UrlTrait> Clone for AliasRecord<U> {
+    fn clone(&self) -> Self {
+        Self {
+            alias: Arc::clone(&self.alias),
+            canonical: Arc::clone(&self.canonical),
+            reason: self.reason,
+        }
+    }
 }
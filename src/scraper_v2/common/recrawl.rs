@@ -0,0 +1,268 @@
+use super::{AliasRecord, UrlTrait};
+use crate::Result;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::Path;
+use tokio::sync::Mutex;
+
+/// HTTP validators from a previous fetch, sent back to the server to make a conditional request.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// What we know about a page from the last time it was scraped.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PageRecord {
+    pub fetched_at: DateTime<Utc>,
+    pub validators: Validators,
+    /// A hash of the page's body text, used to tell if a refetched page actually changed.
+    pub content_hash: Option<String>,
+    /// When the article says it was published (or last updated).
+    pub published: Option<DateTime<Utc>>,
+}
+
+/// What to do with a url that is in the frontier of a recrawl.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecrawlDecision {
+    /// The stored copy is fresh enough, don't request the page.
+    Skip,
+    /// Request the page with the stored validators, the server may answer 304 Not Modified.
+    Conditional(Validators),
+    /// Request and scrape the page again.
+    Fetch,
+}
+
+/// How often the pages of a site should be revisited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevisitPolicy {
+    /// Pages fetched more recently than this are skipped.
+    pub min_revisit_interval: Duration,
+    /// Articles published longer ago than this are assumed to no longer change, and are skipped once they have been fetched after that point.
+    pub settled_after: Option<Duration>,
+    /// Pages fetched longer ago than this are always refetched in full, even if they have validators.
+    pub max_age: Duration,
+}
+
+impl Default for RevisitPolicy {
+    fn default() -> Self {
+        Self {
+            min_revisit_interval: Duration::hours(24),
+            settled_after: None,
+            max_age: Duration::days(30),
+        }
+    }
+}
+
+impl RevisitPolicy {
+    /// Decide what to do with a url given its stored record (if any).
+    pub fn decide(&self, record: Option<&PageRecord>, now: DateTime<Utc>) -> RecrawlDecision {
+        let Some(record) = record else {
+            return RecrawlDecision::Fetch;
+        };
+        let since_fetch = now - record.fetched_at;
+
+        if since_fetch < self.min_revisit_interval {
+            return RecrawlDecision::Skip;
+        }
+        if let (Some(settled_after), Some(published)) = (self.settled_after, record.published) {
+            if record.fetched_at - published > settled_after {
+                return RecrawlDecision::Skip;
+            }
+        }
+        if since_fetch > self.max_age || record.validators.is_empty() {
+            return RecrawlDecision::Fetch;
+        }
+        RecrawlDecision::Conditional(record.validators.clone())
+    }
+}
+
+/// Storage for page records so a crawl can tell what has changed since the last run.
+#[async_trait]
+pub trait PageStore<U: UrlTrait>: Debug + Send + Sync {
+    async fn get_record(&self, url: &U) -> Result<Option<PageRecord>>;
+    async fn put_record(&self, url: &U, record: PageRecord) -> Result<()>;
+    /// Keep the urls found to be another page, so they are still known once the handler lets go of them.
+    async fn put_aliases(&self, aliases: &[AliasRecord<U>]) -> Result<()>;
+    /// The page a url is an alias of, None if it isn't a known alias.
+    async fn get_canonical(&self, url: &U) -> Result<Option<U>>;
+}
+
+/// A PageStore held in memory that can be saved to and loaded from a JSON file between runs.
+#[derive(Debug, Default)]
+pub struct MemoryPageStore {
+    records: Mutex<HashMap<String, PageRecord>>,
+    /// The canonical url of each alias.
+    aliases: Mutex<HashMap<String, String>>,
+}
+
+/// What a `MemoryPageStore` is saved as.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SavedPageStore {
+    records: HashMap<String, PageRecord>,
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+/// Files saved before aliases were kept are only the records.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SavedPages {
+    Store(SavedPageStore),
+    Records(HashMap<String, PageRecord>),
+}
+
+impl MemoryPageStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the records and aliases from a JSON file. A missing file gives an empty store.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let saved = match tokio::fs::read_to_string(path).await {
+            Ok(json) => match serde_json::from_str(&json).map_err(crate::Error::custom)? {
+                SavedPages::Store(saved) => saved,
+                SavedPages::Records(records) => SavedPageStore {
+                    records,
+                    aliases: HashMap::new(),
+                },
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SavedPageStore::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            records: Mutex::new(saved.records),
+            aliases: Mutex::new(saved.aliases),
+        })
+    }
+
+    /// Save the records and aliases to a JSON file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = {
+            let saved = SavedPageStore {
+                records: self.records.lock().await.clone(),
+                aliases: self.aliases.lock().await.clone(),
+            };
+            serde_json::to_string_pretty(&saved).map_err(crate::Error::custom)?
+        };
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<U: UrlTrait + Send + Sync> PageStore<U> for MemoryPageStore {
+    async fn get_record(&self, url: &U) -> Result<Option<PageRecord>> {
+        Ok(self.records.lock().await.get(&url.full_url()).cloned())
+    }
+
+    async fn put_record(&self, url: &U, record: PageRecord) -> Result<()> {
+        self.records.lock().await.insert(url.full_url(), record);
+        Ok(())
+    }
+
+    async fn put_aliases(&self, aliases: &[AliasRecord<U>]) -> Result<()> {
+        self.aliases.lock().await.extend(
+            aliases
+                .iter()
+                .map(|record| (record.alias.full_url(), record.canonical.full_url())),
+        );
+        Ok(())
+    }
+
+    async fn get_canonical(&self, url: &U) -> Result<Option<U>> {
+        let canonical = self.aliases.lock().await.get(&url.full_url()).cloned();
+        canonical.map(U::try_from).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        "2024-06-01T12:00:00Z".parse().unwrap()
+    }
+
+    fn record(fetched_ago: Duration, validators: Validators) -> PageRecord {
+        PageRecord {
+            fetched_at: now() - fetched_ago,
+            validators,
+            content_hash: None,
+            published: None,
+        }
+    }
+
+    fn etag() -> Validators {
+        Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+        }
+    }
+
+    #[test]
+    fn a_page_without_a_record_is_fetched() {
+        let policy = RevisitPolicy::default();
+        assert_eq!(policy.decide(None, now()), RecrawlDecision::Fetch);
+    }
+
+    #[test]
+    fn a_page_fetched_within_the_revisit_interval_is_skipped() {
+        let policy = RevisitPolicy::default();
+        let recent = record(Duration::hours(2), Validators::default());
+        assert_eq!(policy.decide(Some(&recent), now()), RecrawlDecision::Skip);
+    }
+
+    #[test]
+    fn a_settled_page_is_skipped() {
+        let policy = RevisitPolicy {
+            settled_after: Some(Duration::days(7)),
+            ..RevisitPolicy::default()
+        };
+        let mut settled = record(Duration::days(3), etag());
+        settled.published = Some(now() - Duration::days(60));
+        assert_eq!(policy.decide(Some(&settled), now()), RecrawlDecision::Skip);
+
+        //? Fetched before it settled, so it may have changed since.
+        let mut fresh = record(Duration::days(3), etag());
+        fresh.published = Some(now() - Duration::days(5));
+        assert_eq!(
+            policy.decide(Some(&fresh), now()),
+            RecrawlDecision::Conditional(etag())
+        );
+    }
+
+    #[test]
+    fn a_page_past_the_max_age_is_fetched_in_full() {
+        let policy = RevisitPolicy::default();
+        let old = record(Duration::days(45), etag());
+        assert_eq!(policy.decide(Some(&old), now()), RecrawlDecision::Fetch);
+    }
+
+    #[test]
+    fn validators_make_the_request_conditional() {
+        let policy = RevisitPolicy::default();
+        let with_validators = record(Duration::days(3), etag());
+        assert_eq!(
+            policy.decide(Some(&with_validators), now()),
+            RecrawlDecision::Conditional(etag())
+        );
+
+        let without_validators = record(Duration::days(3), Validators::default());
+        assert_eq!(
+            policy.decide(Some(&without_validators), now()),
+            RecrawlDecision::Fetch
+        );
+    }
+}
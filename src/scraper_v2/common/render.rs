@@ -0,0 +1,697 @@
+use itertools::Itertools;
+use lazy_regex::regex_is_match;
+use scraper::{ElementRef, Node, Selector};
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+
+/// Elements whose contents are never rendered.
+const SKIPPED_TAGS: &[&str] = &[
+    "head", "script", "style", "noscript", "template", "svg", "canvas", "iframe", "object",
+    "button", "select", "input", "textarea",
+];
+
+/// Elements that start a new block. Everything else is rendered inline, as part of the block it is in.
+const BLOCK_TAGS: &[&str] = &[
+    "html",
+    "body",
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "caption",
+    "dd",
+    "details",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+/// Characters that don't show but get in the way of matching and tokenizing text: zero width spaces, word joiners, byte order marks and soft hyphens.
+const INVISIBLE_CHARS: &[char] = &['\u{200B}', '\u{2060}', '\u{FEFF}', '\u{00AD}'];
+
+/// Stands in for a `<br>` while the whitespace of a block is collapsed.
+//? A private use character, so it can't clash with the page text.
+const LINE_BREAK: char = '\u{E001}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderFormat {
+    Markdown,
+    /// Text without markup. Headings, paragraphs and list items are still kept on their own lines.
+    PlainText,
+}
+
+/// Renders an element and everything in it to Markdown or to clean plain text.
+///
+/// Headings, paragraphs, lists (nested ones indented), quotes, code and tables are kept as blocks separated by a blank line,
+/// whitespace within a block is collapsed and the text is unicode normalized (NFC) with invisible characters removed.
+/// Links are resolved against the base url, and either written inline or numbered and listed at the end (`with_link_footnotes`).
+///
+/// ```ignore
+/// let markdown = Renderer::markdown()
+///     .with_base_url(document.final_url())
+///     .with_skip_selector(".advert, .share-buttons")
+///     .render(&article);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    format: RenderFormat,
+    link_footnotes: bool,
+    base_url: Option<Url>,
+    skip_selector: Option<Selector>,
+    skip: Option<fn(&ElementRef) -> bool>,
+}
+
+impl Renderer {
+    pub fn new(format: RenderFormat) -> Self {
+        Self {
+            format,
+            link_footnotes: false,
+            base_url: None,
+            skip_selector: None,
+            skip: None,
+        }
+    }
+
+    pub fn markdown() -> Self {
+        Self::new(RenderFormat::Markdown)
+    }
+
+    pub fn plain_text() -> Self {
+        Self::new(RenderFormat::PlainText)
+    }
+
+    /// Number the links and list their urls at the end, eg "the council said [1]" and "[1] https://...".
+    /// Without this Markdown links are written inline and plain text only keeps the link text.
+    pub fn with_link_footnotes(mut self) -> Self {
+        self.link_footnotes = true;
+        self
+    }
+
+    /// The url relative links and images are resolved against, usually `FetchedDocument::base_url`. Without one relative links are left out.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = Url::parse(base_url).ok();
+        self
+    }
+
+    /// Leave out the elements matching a CSS selector, eg `.advert, .share-buttons`.
+    pub fn with_skip_selector(mut self, selector: &str) -> Self {
+        self.skip_selector = Selector::parse(selector).ok();
+        self
+    }
+
+    /// Leave out the elements a function picks, for when a selector isn't enough.
+    pub fn with_skip(mut self, skip: fn(&ElementRef) -> bool) -> Self {
+        self.skip = Some(skip);
+        self
+    }
+
+    pub fn format(&self) -> RenderFormat {
+        self.format
+    }
+
+    pub fn render(&self, element: &ElementRef) -> String {
+        self.render_all([*element])
+    }
+
+    /// Render several elements as one document, eg the blocks of an article. Link footnotes are numbered across all of them.
+    pub fn render_all<'a>(&self, elements: impl IntoIterator<Item = ElementRef<'a>>) -> String {
+        let mut footnotes = Vec::new();
+        let mut blocks = Vec::new();
+        for element in elements {
+            self.push_element(&element, &mut footnotes, &mut blocks);
+        }
+        let mut rendered = blocks.join("\n\n");
+        if self.link_footnotes && !footnotes.is_empty() {
+            let list = footnotes
+                .iter()
+                .enumerate()
+                .map(|(index, url)| match self.format {
+                    RenderFormat::Markdown => format!("[{}]: {url}", index + 1),
+                    RenderFormat::PlainText => format!("[{}] {url}", index + 1),
+                })
+                .join("\n");
+            rendered.push_str("\n\n");
+            rendered.push_str(&list);
+        }
+        rendered.nfc().collect()
+    }
+
+    fn is_markdown(&self) -> bool {
+        self.format == RenderFormat::Markdown
+    }
+
+    fn is_skipped(&self, element: &ElementRef) -> bool {
+        let value = element.value();
+        SKIPPED_TAGS.contains(&value.name())
+            || value.attr("hidden").is_some()
+            || value.attr("aria-hidden") == Some("true")
+            || value
+                .attr("style")
+                .is_some_and(|style| regex_is_match!(r"display:\s*none", style))
+            || self
+                .skip_selector
+                .as_ref()
+                .is_some_and(|selector| selector.matches(element))
+            || self.skip.is_some_and(|skip| skip(element))
+    }
+
+    /// Render an element that starts a block.
+    fn push_element(
+        &self,
+        element: &ElementRef,
+        footnotes: &mut Vec<String>,
+        blocks: &mut Vec<String>,
+    ) {
+        if self.is_skipped(element) {
+            return;
+        }
+        let name = element.value().name();
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = self.inline_text(element, footnotes, " ");
+                if text.is_empty() {
+                    return;
+                }
+                blocks.push(match self.format {
+                    RenderFormat::Markdown => {
+                        format!("{} {text}", "#".repeat(name[1..].parse().unwrap_or(2)))
+                    }
+                    RenderFormat::PlainText => text,
+                });
+            }
+            "ul" | "ol" => {
+                let lines = self.list_lines(element, "", footnotes);
+                if !lines.is_empty() {
+                    blocks.push(lines.join("\n"));
+                }
+            }
+            "blockquote" => {
+                let mut quoted = Vec::new();
+                self.push_children(element, footnotes, &mut quoted);
+                if quoted.is_empty() {
+                    return;
+                }
+                let text = quoted.join("\n\n");
+                blocks.push(match self.format {
+                    RenderFormat::Markdown => text
+                        .lines()
+                        .map(|line| {
+                            if line.is_empty() {
+                                ">".to_string()
+                            } else {
+                                format!("> {line}")
+                            }
+                        })
+                        .join("\n"),
+                    RenderFormat::PlainText => text,
+                });
+            }
+            "pre" => blocks.extend(self.code_block(element)),
+            "table" => self.push_table(element, footnotes, blocks),
+            "hr" if self.is_markdown() => blocks.push("---".to_string()),
+            "hr" | "br" => {}
+            _ => self.push_children(element, footnotes, blocks),
+        }
+    }
+
+    /// Render the children of a container. Runs of text and inline elements between the blocks are paragraphs.
+    fn push_children(
+        &self,
+        element: &ElementRef,
+        footnotes: &mut Vec<String>,
+        blocks: &mut Vec<String>,
+    ) {
+        let mut inline = String::new();
+        for child in element.children() {
+            match ElementRef::wrap(child) {
+                Some(child) if BLOCK_TAGS.contains(&child.value().name()) => {
+                    self.push_paragraph(&inline, blocks);
+                    inline.clear();
+                    self.push_element(&child, footnotes, blocks);
+                }
+                Some(child) => self.push_inline(&child, footnotes, &mut inline),
+                None => self.push_text(child.value(), &mut inline),
+            }
+        }
+        self.push_paragraph(&inline, blocks);
+    }
+
+    fn push_paragraph(&self, inline: &str, blocks: &mut Vec<String>) {
+        let line_break = match self.format {
+            RenderFormat::Markdown => "  \n",
+            RenderFormat::PlainText => "\n",
+        };
+        let text = finish_inline(inline, line_break);
+        if text.is_empty() {
+            return;
+        }
+        blocks.push(match self.format {
+            RenderFormat::Markdown => escape_block_start(&text),
+            RenderFormat::PlainText => text,
+        });
+    }
+
+    /// The inline rendering of the contents of an element on a single line.
+    fn inline_text(
+        &self,
+        element: &ElementRef,
+        footnotes: &mut Vec<String>,
+        line_break: &str,
+    ) -> String {
+        let mut inline = String::new();
+        self.push_inline_children(element, footnotes, &mut inline);
+        finish_inline(&inline, line_break)
+    }
+
+    /// Render the children of an element inline.
+    fn push_inline_children(
+        &self,
+        element: &ElementRef,
+        footnotes: &mut Vec<String>,
+        out: &mut String,
+    ) {
+        for child in element.children() {
+            match ElementRef::wrap(child) {
+                Some(child) => self.push_inline(&child, footnotes, out),
+                None => self.push_text(child.value(), out),
+            }
+        }
+    }
+
+    fn push_text(&self, node: &Node, out: &mut String) {
+        if let Node::Text(text) = node {
+            let text = text.replace(INVISIBLE_CHARS, "");
+            out.push_str(&match self.format {
+                RenderFormat::Markdown => escape_markdown_inline(&text),
+                RenderFormat::PlainText => text,
+            });
+        }
+    }
+
+    fn push_inline(&self, element: &ElementRef, footnotes: &mut Vec<String>, out: &mut String) {
+        if self.is_skipped(element) {
+            return;
+        }
+        let markdown = self.is_markdown();
+        match element.value().name() {
+            "br" => out.push(LINE_BREAK),
+            "img" if markdown => {
+                let value = element.value();
+                if let Some(src) = [value.attr("src"), value.attr("data-src")]
+                    .into_iter()
+                    .flatten()
+                    .filter(|src| !src.trim().starts_with("data:"))
+                    .find_map(|src| self.resolve(src))
+                {
+                    let alt = normalize_text(value.attr("alt").unwrap_or_default());
+                    out.push_str(&format!(
+                        "![{}]({})",
+                        escape_markdown_inline(&alt),
+                        markdown_url(&src)
+                    ));
+                }
+            }
+            "strong" | "b" if markdown => self.push_wrapped(element, "**", footnotes, out),
+            "em" | "i" | "cite" if markdown => self.push_wrapped(element, "*", footnotes, out),
+            "del" | "s" | "strike" if markdown => self.push_wrapped(element, "~~", footnotes, out),
+            "code" | "kbd" | "samp" if markdown => {
+                let code = element
+                    .text()
+                    .collect::<String>()
+                    .split_whitespace()
+                    .join(" ");
+                if !code.is_empty() {
+                    let fence = if code.contains('`') { "``" } else { "`" };
+                    out.push_str(&format!("{fence}{code}{fence}"));
+                }
+            }
+            "a" => self.push_link(element, footnotes, out),
+            name => {
+                //? Blocks inside inline elements (eg a div in a link) are at least kept apart from the text around them.
+                let is_block = BLOCK_TAGS.contains(&name);
+                if is_block {
+                    out.push(' ');
+                }
+                self.push_inline_children(element, footnotes, out);
+                if is_block {
+                    out.push(' ');
+                }
+            }
+        }
+    }
+
+    /// Wrap the contents of an element in a Markdown marker, eg `**` for bold. The marker has to hug the text, so surrounding spaces are kept outside it.
+    fn push_wrapped(
+        &self,
+        element: &ElementRef,
+        marker: &str,
+        footnotes: &mut Vec<String>,
+        out: &mut String,
+    ) {
+        let mut inner = String::new();
+        self.push_inline_children(element, footnotes, &mut inner);
+        out.push_str(&wrap_trimmed(&inner, marker, marker));
+    }
+
+    fn push_link(&self, link: &ElementRef, footnotes: &mut Vec<String>, out: &mut String) {
+        let mut inner = String::new();
+        self.push_inline_children(link, footnotes, &mut inner);
+        let Some(url) = link
+            .value()
+            .attr("href")
+            .filter(|href| !href.trim().starts_with('#'))
+            .and_then(|href| self.resolve(href))
+        else {
+            out.push_str(&inner);
+            return;
+        };
+        if inner.trim().is_empty() {
+            return;
+        }
+        if !self.link_footnotes {
+            match self.format {
+                RenderFormat::Markdown => out.push_str(&wrap_trimmed(
+                    &inner,
+                    "[",
+                    &format!("]({})", markdown_url(&url)),
+                )),
+                RenderFormat::PlainText => out.push_str(&inner),
+            }
+            return;
+        }
+        let number = match footnotes.iter().position(|footnote| *footnote == url) {
+            Some(index) => index + 1,
+            None => {
+                footnotes.push(url);
+                footnotes.len()
+            }
+        };
+        match self.format {
+            RenderFormat::Markdown => {
+                out.push_str(&wrap_trimmed(&inner, "[", &format!("][{number}]")))
+            }
+            RenderFormat::PlainText => {
+                out.push_str(&wrap_trimmed(&inner, "", &format!(" [{number}]")))
+            }
+        }
+    }
+
+    /// An absolute http(s) or mailto url for an href or src, resolved against the base url.
+    fn resolve(&self, href: &str) -> Option<String> {
+        let href = href.trim();
+        let url = match &self.base_url {
+            Some(base) => base.join(href).ok()?,
+            None => Url::parse(href).ok()?,
+        };
+        matches!(url.scheme(), "http" | "https" | "mailto").then(|| url.to_string())
+    }
+
+    /// The lines of a list, with nested lists indented under their item.
+    fn list_lines(
+        &self,
+        list: &ElementRef,
+        indent: &str,
+        footnotes: &mut Vec<String>,
+    ) -> Vec<String> {
+        let ordered = list.value().name() == "ol";
+        let start = list
+            .value()
+            .attr("start")
+            .and_then(|start| start.trim().parse::<usize>().ok())
+            .unwrap_or(1);
+        let mut lines = Vec::new();
+        for (index, item) in list
+            .child_elements()
+            .filter(|child| child.value().name() == "li" && !self.is_skipped(child))
+            .enumerate()
+        {
+            let marker = if ordered {
+                format!("{}. ", start + index)
+            } else {
+                "- ".to_string()
+            };
+
+            let mut inline = String::new();
+            let mut nested = Vec::new();
+            for child in item.children() {
+                match ElementRef::wrap(child) {
+                    Some(child) if matches!(child.value().name(), "ul" | "ol") => {
+                        if !self.is_skipped(&child) {
+                            let nested_indent = format!("{indent}{}", " ".repeat(marker.len()));
+                            nested.extend(self.list_lines(&child, &nested_indent, footnotes));
+                        }
+                    }
+                    Some(child) => self.push_inline(&child, footnotes, &mut inline),
+                    None => self.push_text(child.value(), &mut inline),
+                }
+            }
+            let text = finish_inline(&inline, " ");
+            if text.is_empty() && nested.is_empty() {
+                continue;
+            }
+            lines.push(format!("{indent}{marker}{text}").trim_end().to_string());
+            lines.extend(nested);
+        }
+        lines
+    }
+
+    fn code_block(&self, pre: &ElementRef) -> Option<String> {
+        let code = pre.text().collect::<String>().replace(INVISIBLE_CHARS, "");
+        let code = code.trim_matches('\n').trim_end();
+        if code.trim().is_empty() {
+            return None;
+        }
+        if !self.is_markdown() {
+            return Some(code.to_string());
+        }
+        //? Syntax highlighters put the language in a class on the pre or the code element, eg `language-rust`.
+        let code_selector = Selector::parse("code").unwrap();
+        let language = std::iter::once(*pre)
+            .chain(pre.select(&code_selector))
+            .filter_map(|element| element.value().attr("class"))
+            .flat_map(str::split_ascii_whitespace)
+            .find_map(|class| {
+                class
+                    .strip_prefix("language-")
+                    .or_else(|| class.strip_prefix("lang-"))
+            })
+            .unwrap_or_default();
+        let fence = if code.contains("```") { "~~~" } else { "```" };
+        Some(format!("{fence}{language}\n{code}\n{fence}"))
+    }
+
+    /// A table as a Markdown table, or one line per row in plain text. Spanning cells are only written once.
+    fn push_table(
+        &self,
+        table: &ElementRef,
+        footnotes: &mut Vec<String>,
+        blocks: &mut Vec<String>,
+    ) {
+        let caption = table
+            .child_elements()
+            .find(|child| child.value().name() == "caption")
+            .map(|caption| self.inline_text(&caption, footnotes, " "))
+            .filter(|caption| !caption.is_empty());
+
+        let row_selector = Selector::parse("tr").unwrap();
+        let rows = table
+            .select(&row_selector)
+            .filter(|row| {
+                //? Rows of nested tables belong to those tables.
+                row.ancestors()
+                    .filter_map(ElementRef::wrap)
+                    .find(|ancestor| ancestor.value().name() == "table")
+                    .is_some_and(|closest| closest.id() == table.id())
+            })
+            .filter(|row| !self.is_skipped(row))
+            .map(|row| {
+                row.child_elements()
+                    .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+                    .filter(|cell| !self.is_skipped(cell))
+                    .map(|cell| {
+                        let text = self.inline_text(&cell, footnotes, " ");
+                        match self.format {
+                            RenderFormat::Markdown => text.replace('|', "\\|"),
+                            RenderFormat::PlainText => text,
+                        }
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .filter(|cells| cells.iter().any(|cell| !cell.is_empty()))
+            .collect::<Vec<Vec<String>>>();
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+
+        blocks.extend(caption);
+        let table = match self.format {
+            RenderFormat::Markdown => {
+                let line = |cells: &[String]| {
+                    let padding = vec![String::new(); width - cells.len()];
+                    format!("| {} |", cells.iter().chain(&padding).join(" | "))
+                };
+                std::iter::once(line(&rows[0]))
+                    .chain(std::iter::once(format!("|{}", " --- |".repeat(width))))
+                    .chain(rows[1..].iter().map(|cells| line(cells)))
+                    .join("\n")
+            }
+            RenderFormat::PlainText => rows
+                .iter()
+                .map(|cells| cells.iter().filter(|cell| !cell.is_empty()).join(" | "))
+                .join("\n"),
+        };
+        blocks.push(table);
+    }
+}
+
+/// Render an element to Markdown with the default settings, see `Renderer`.
+pub fn to_markdown(element: &ElementRef) -> String {
+    Renderer::markdown().render(element)
+}
+
+/// Render an element to plain text with the default settings, see `Renderer`.
+pub fn to_plain_text(element: &ElementRef) -> String {
+    Renderer::plain_text().render(element)
+}
+
+/// Collapse the whitespace of text to single spaces, take out invisible characters and unicode normalize it (NFC), so the same text always compares equal.
+pub fn normalize_text(text: &str) -> String {
+    text.replace(INVISIBLE_CHARS, "")
+        .split_whitespace()
+        .join(" ")
+        .nfc()
+        .collect()
+}
+
+/// The text of Markdown without its markup, for indexing and snippets: links and images keep their text, emphasis, code marks,
+/// heading and quote markers, table pipes and escapes are taken out, and each block stays on its own lines.
+pub fn strip_markdown(markdown: &str) -> String {
+    markdown
+        .lines()
+        .filter(|line| {
+            !lazy_regex::regex_is_match!(r"^\s*(?:```|~~~|\|?\s*:?-{3,}|[-*_]{3,}\s*$)", line)
+        })
+        .map(|line| {
+            let line = lazy_regex::regex_replace_all!(
+                r"^\s*(?:#{1,6}\s+|(?:>\s?)+|[-*+]\s+|\d+[.)]\s+)",
+                line,
+                |_| ""
+            );
+            let line = lazy_regex::regex_replace_all!(
+                r"!?\[([^\]]*)\](?:\([^)]*\)|\[\d+\])",
+                &line,
+                |_, text: &str| text.to_string()
+            );
+            let line = lazy_regex::regex_replace_all!(
+                r"\\(.)|\*\*|~~|[*`|]",
+                &line,
+                |_, escaped: &str| {
+                    if escaped.is_empty() {
+                        " ".to_string()
+                    } else {
+                        escaped.to_string()
+                    }
+                }
+            );
+            line.split_whitespace().join(" ")
+        })
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Escape text so it is shown as it is in Markdown, rather than read as emphasis, links, headings or lists.
+pub fn escape_markdown(text: &str) -> String {
+    text.split('\n')
+        .map(|line| escape_block_start(&escape_markdown_inline(line)))
+        .join("\n")
+}
+
+fn escape_markdown_inline(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape the start of a line that would otherwise be read as a heading, quote, list item or rule.
+fn escape_block_start(text: &str) -> String {
+    if regex_is_match!(r"^(?:[#>+=]|-(?:\s|-|$))", text) {
+        return format!("\\{text}");
+    }
+    match lazy_regex::regex_captures!(r"^(\d+)([.)])(\s.*)?$", text) {
+        Some((_, number, marker, rest)) => format!("{number}\\{marker}{rest}"),
+        None => text.to_string(),
+    }
+}
+
+/// Collapse the whitespace of rendered inline text and put the line breaks back.
+fn finish_inline(inline: &str, line_break: &str) -> String {
+    inline
+        .split(LINE_BREAK)
+        .map(|line| line.split_whitespace().join(" "))
+        .filter(|line| !line.is_empty())
+        .join(line_break)
+}
+
+/// Put `open` and `close` around the text with its leading and trailing whitespace kept outside.
+fn wrap_trimmed(text: &str, open: &str, close: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_string();
+    }
+    let leading = if text.starts_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    let trailing = if text.ends_with(char::is_whitespace) {
+        " "
+    } else {
+        ""
+    };
+    format!("{leading}{open}{trimmed}{close}{trailing}")
+}
+
+/// Urls with spaces or parentheses are put in angle brackets so they don't end the link early.
+fn markdown_url(url: &str) -> String {
+    if url.contains([' ', '(', ')']) {
+        format!("<{url}>")
+    } else {
+        url.to_string()
+    }
+}
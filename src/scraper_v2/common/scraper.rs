@@ -1,11 +1,19 @@
-use crate::common::{Page, PageState, Scrapable, ScrapableContent, UrlTrait, WasScraped};
+use crate::common::{
+    AliasReason, AliasRecord, ChunkStore, Chunker, DateRange, Fetcher, LinkTo, Page, PageRecord,
+    PageState, PageStore, RecrawlDecision, Scrapable, ScrapableContent, SeedSource, SimHash,
+    SimHashIndex, UrlTrait, Validators, WasScraped, WebFetcher,
+};
+use crate::graph::KnowledgeGraph;
+use crate::search::{EmbeddingPipeline, TextIndex};
 use crate::Result;
 
 use async_trait::async_trait;
+use chrono::Utc;
 use futures::stream::futures_unordered::IntoIter;
 use futures::stream::{self, StreamExt};
 use std::cmp::Eq;
 use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -41,11 +49,156 @@ pub trait PageScraper<U: UrlTrait> {
     async fn scrape_pages_recursive<C: ScrapableContent<Url = U>>(&mut self, mut max_depth: u32);
 }
 
+/// Pages whose body text SimHashes differ by at most this many bits are treated as duplicates.
+const NEAR_DUPLICATE_DISTANCE: u32 = 3;
+
+/// A queue of pages waiting to be scraped, shared between the concurrent scraping tasks.
+type PageQueue<U> = Arc<Mutex<VecDeque<Box<Page<dyn Scrapable, U>>>>>;
+
+/// Content fingerprints of the scraped pages, each with the canonical url of the page.
+type Fingerprints<U> = SimHashIndex<Arc<U>>;
+
+/// The alias records found this run, keyed by the alias.
+type Aliases<U> = HashMap<Arc<U>, AliasRecord<U>>;
+
+/// A predicate on urls, used to pick which alternate pages (eg other languages) a crawl follows.
+pub struct UrlFilter<U>(Arc<dyn Fn(&U) -> bool + Send + Sync>);
+
+impl<U> UrlFilter<U> {
+    pub fn new(filter: impl Fn(&U) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(filter))
+    }
+
+    pub fn allows(&self, url: &U) -> bool {
+        (self.0)(url)
+    }
+}
+
+// Manual impls as the closure is neither Clone nor Debug, only the Arc is cloned.
+impl<U> Clone for UrlFilter<U> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<U> std::fmt::Debug for UrlFilter<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("UrlFilter")
+    }
+}
+
 #[derive(Debug)]
 pub struct PageHandler<U: UrlTrait> {
     //scraper: Box<dyn Scraper<U, C>>,
     visited: Arc<Mutex<HashSet<Arc<U>>>>,
-    pages_queue: Arc<Mutex<VecDeque<Box<Page<dyn Scrapable, U>>>>>,
+    pages_queue: PageQueue<U>,
+    /// Urls that turned out to be another page (canonical link, redirect or duplicate content), until they are flushed to the store.
+    aliases: Arc<Mutex<Aliases<U>>>,
+    fingerprints: Arc<Mutex<Fingerprints<U>>>,
+    /// Records of previous crawls. When set the handler only scrapes pages that are new or due a revisit.
+    store: Option<Arc<dyn PageStore<U>>>,
+    /// Pages scraped this run that were new or whose content changed.
+    fresh: Arc<Mutex<Vec<Arc<U>>>>,
+    /// Only keep articles published in this range. Pages outside it are not counted as fresh and their links are not followed.
+    date_range: Option<DateRange>,
+    /// Which alternate pages (eg the same article in other languages) to follow. None follows none of them.
+    alternates: Option<UrlFilter<U>>,
+    /// Where pages are fetched from. None fetches them from the site.
+    fetcher: Option<Arc<dyn Fetcher<U>>>,
+    /// What is done with each fresh page.
+    pipeline: PagePipeline<U>,
+}
+
+/// The steps each fresh page goes through after it is stored, shared by crawled and ingested pages.
+#[derive(Debug)]
+struct PagePipeline<U: UrlTrait> {
+    /// How fresh pages are split into chunks and where the chunks are put. None doesn't chunk pages.
+    chunking: Option<(Chunker, Arc<dyn ChunkStore<U>>)>,
+    /// Embeds the chunks of fresh pages. Only used when pages are chunked.
+    embeddings: Option<EmbeddingPipeline>,
+    /// Indexes the chunks of fresh pages for keyword search. Only used when pages are chunked.
+    text_index: Option<Arc<TextIndex>>,
+    /// Adds fresh pages to a knowledge graph. None doesn't build one.
+    graph: Option<Arc<KnowledgeGraph>>,
+}
+
+// Manual impls as U doesn't need to be Clone or Default, only the Arcs are cloned.
+impl<U: UrlTrait> Clone for PagePipeline<U> {
+    fn clone(&self) -> Self {
+        Self {
+            chunking: self.chunking.clone(),
+            embeddings: self.embeddings.clone(),
+            text_index: self.text_index.clone(),
+            graph: self.graph.clone(),
+        }
+    }
+}
+
+impl<U: UrlTrait> Default for PagePipeline<U> {
+    fn default() -> Self {
+        Self {
+            chunking: None,
+            embeddings: None,
+            text_index: None,
+            graph: None,
+        }
+    }
+}
+
+impl<U: UrlTrait + Sync> PagePipeline<U> {
+    /// Chunk, embed and index a fresh page stored under `url`, and add it to the graph.
+    /// A step that fails is logged and the others still run, so one bad page or a store that is briefly down doesn't stop a crawl.
+    //? Everything is read from the content before the returned future runs, so the future doesn't hold on to it (content isn't Sync, and the future has to be Send).
+    fn process<'a, C: ScrapableContent<Url = U>>(
+        &'a self,
+        url: &'a U,
+        content: &C,
+    ) -> impl Future<Output = ()> + Send + 'a {
+        let chunks = self
+            .chunking
+            .as_ref()
+            .map(|(chunker, chunk_store)| (chunker.chunk(url, content), chunk_store));
+        let add_to_graph = self
+            .graph
+            .as_ref()
+            .map(|graph| graph.add_page(url, content));
+        async move {
+            if let Some((chunks, chunk_store)) = chunks {
+                if let Some(embeddings) = &self.embeddings {
+                    if let Err(e) = embeddings
+                        .embed_chunks(&url.full_url(), chunks.clone())
+                        .await
+                    {
+                        tracing::warn!("Unable to embed the chunks of {}: {e:?}", url.full_url());
+                    }
+                }
+                if let Some(text_index) = &self.text_index {
+                    if let Err(e) = text_index.put_chunks(&url.full_url(), &chunks) {
+                        tracing::warn!("Unable to index the chunks of {}: {e:?}", url.full_url());
+                    }
+                }
+                if let Err(e) = chunk_store.put_chunks(url, chunks).await {
+                    tracing::warn!("Unable to store the chunks of {}: {e:?}", url.full_url());
+                }
+            }
+            if let Some(add_to_graph) = add_to_graph {
+                if let Err(e) = add_to_graph.await {
+                    tracing::warn!("Unable to add {} to the graph: {e:?}", url.full_url());
+                }
+            }
+        }
+    }
+
+    /// Tell the graph (if there is one) that a url is an alias of another page, so links to it are links to that page.
+    async fn record_alias(&self, record: &AliasRecord<U>) {
+        let Some(graph) = &self.graph else {
+            return;
+        };
+        let (alias, canonical) = (record.alias.full_url(), record.canonical.full_url());
+        if let Err(e) = graph.add_alias(&alias, &canonical).await {
+            tracing::warn!("Unable to add the alias {alias} of {canonical} to the graph: {e:?}");
+        }
+    }
 }
 
 #[async_trait]
@@ -75,9 +228,15 @@ where
     }
 }
 
+impl<U: UrlTrait + Sync> Default for PageHandler<U> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<U: UrlTrait> PageHandler<U>
 where
-    U: UrlTrait + Eq,
+    U: UrlTrait + Eq + Sync,
     //S: PageState + Scrapable + Eq,
     //C: ScrapableContent<Url = U>,
 {
@@ -86,7 +245,354 @@ where
             //scraper,
             visited: Arc::new(Mutex::new(HashSet::new())),
             pages_queue: Arc::new(Mutex::new(VecDeque::new())),
+            aliases: Arc::new(Mutex::new(HashMap::new())),
+            fingerprints: Arc::new(Mutex::new(SimHashIndex::new(NEAR_DUPLICATE_DISTANCE))),
+            store: None,
+            fresh: Arc::new(Mutex::new(Vec::new())),
+            date_range: None,
+            alternates: None,
+            fetcher: None,
+            pipeline: PagePipeline::default(),
+        }
+    }
+
+    /// Turn on recrawl mode. Each frontier url is checked against the store and the site's revisit policy to decide if it is skipped, conditionally fetched or refetched.
+    pub fn with_store(mut self, store: Arc<dyn PageStore<U>>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Limit the crawl to articles published within `date_range`, eg `DateRange::last(Duration::hours(48))`.
+    /// Pages that don't say when they were published are kept.
+    pub fn with_date_range(mut self, date_range: DateRange) -> Self {
+        self.date_range = Some(date_range);
+        self
+    }
+
+    /// Follow the alternate pages (`ScrapableContent::get_alternate_pages`) that pass the filter, eg only the French and German versions of Wikipedia articles.
+    pub fn with_alternates(mut self, filter: impl Fn(&U) -> bool + Send + Sync + 'static) -> Self {
+        self.alternates = Some(UrlFilter::new(filter));
+        self
+    }
+
+    /// Fetch pages from another source than the site itself, eg `wikipedia::WikipediaApiFetcher` to use the MediaWiki API.
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn Fetcher<U>>) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// Split the content of each fresh page into chunks with `chunker` and put them in `store`, under the page's canonical url.
+    /// Pages whose content hasn't changed since the last run keep the chunks they already have.
+    pub fn with_chunks(mut self, chunker: Chunker, store: Arc<dyn ChunkStore<U>>) -> Self {
+        self.pipeline.chunking = Some((chunker, store));
+        self
+    }
+
+    /// Embed the chunks of each fresh page as they are stored (see `with_chunks`), reusing the vectors of chunks that were embedded before.
+    pub fn with_embeddings(mut self, pipeline: EmbeddingPipeline) -> Self {
+        self.pipeline.embeddings = Some(pipeline);
+        self
+    }
+
+    /// Add the chunks of each fresh page to a full text index (see `with_chunks`). They are committed after each iteration of a crawl, or with `commit_text_index`.
+    pub fn with_text_index(mut self, index: Arc<TextIndex>) -> Self {
+        self.pipeline.text_index = Some(index);
+        self
+    }
+
+    /// Add each fresh page to a knowledge graph, with edges to the pages it links to, its categories, topics and sources, and the entities it names.
+    /// Pages whose content hasn't changed since the last run keep the edges they already have.
+    pub fn with_graph(mut self, graph: Arc<KnowledgeGraph>) -> Self {
+        self.pipeline.graph = Some(graph);
+        self
+    }
+
+    /// Make the chunks added to the text index since the last commit searchable.
+    pub fn commit_text_index(&self) -> Result<()> {
+        match &self.pipeline.text_index {
+            Some(index) => index.commit(),
+            None => Ok(()),
+        }
+    }
+
+    /// The pages scraped so far that were new or whose content changed since they were last stored.
+    pub async fn fresh_pages(&self) -> Vec<Arc<U>> {
+        self.fresh.lock().await.clone()
+    }
+
+    /// Scrape a page, consulting the store (if there is one) first.
+    /// Returns None if the page was skipped, was not modified or failed to scrape, along with the previous record of the page.
+    /// The record is looked up under the url, and under its canonical url if it is a known alias.
+    /// Skipped and unmodified pages are marked as visited so they aren't checked again this run.
+    async fn fetch_page<C: ScrapableContent<Url = U>>(
+        visited: &Mutex<HashSet<Arc<U>>>,
+        aliases: &Mutex<Aliases<U>>,
+        store: Option<&dyn PageStore<U>>,
+        fetcher: Option<&dyn Fetcher<U>>,
+        scrapable_page: Box<Page<dyn Scrapable, U>>,
+    ) -> (Option<Page<WasScraped<C>, U>>, Option<PageRecord>) {
+        let fetcher = fetcher.unwrap_or(&WebFetcher);
+        let scrape = |scrapable_page: Box<Page<dyn Scrapable, U>>, validators: Validators| async move {
+            scrapable_page
+                .scrape_in_place_with::<C>(fetcher, &validators)
+                .await
+        };
+        let Some(store) = store else {
+            let scraped = scrape(scrapable_page, Validators::default()).await;
+            return (scraped.ok().flatten(), None);
+        };
+        let url = scrapable_page.get_url_arc();
+        let mut previous = store.get_record(&url).await.ok().flatten();
+        if previous.is_none() {
+            if let Some(canonical) = Self::canonical_in(aliases, Some(store), &url).await {
+                previous = store.get_record(&canonical).await.ok().flatten();
+            }
+        }
+
+        let scraped = match U::revisit_policy().decide(previous.as_ref(), Utc::now()) {
+            RecrawlDecision::Skip => {
+                visited.lock().await.insert(url);
+                return (None, previous);
+            }
+            RecrawlDecision::Conditional(validators) => scrape(scrapable_page, validators).await,
+            RecrawlDecision::Fetch => scrape(scrapable_page, Validators::default()).await,
+        };
+
+        if let (Ok(None), Some(record)) = (&scraped, &previous) {
+            //? Not modified, just note that we checked.
+            let record = PageRecord {
+                fetched_at: Utc::now(),
+                ..record.clone()
+            };
+            let _ = store.put_record(&url, record).await;
+            visited.lock().await.insert(url);
         }
+        (scraped.ok().flatten(), previous)
+    }
+
+    /// Fetch a sitemap or feed and queue every page it lists that is a valid url of type U. Returns the number of pages queued.
+    /// Entries dated outside the date range (if one is set) are not queued.
+    pub async fn add_seeds(&mut self, source: &SeedSource) -> Result<usize> {
+        let pages = source.fetch_pages::<U>().await?;
+        Ok(self.add_seed_pages(pages).await)
+    }
+
+    /// Queue pages discovered outside of a crawl (eg the promos on a front page). Returns the number of pages queued.
+    /// Pages dated outside the date range (if one is set) are not queued.
+    pub async fn add_seed_pages(&mut self, mut pages: Vec<Page<LinkTo, U>>) -> usize {
+        if let Some(date_range) = &self.date_range {
+            pages.retain(|page| date_range.allows(page.published()));
+        }
+        let count = pages.len();
+        let mut pages_queue = self.pages_queue.lock().await;
+        pages_queue.extend(
+            pages
+                .into_iter()
+                .map(|page| Box::new(page) as Box<Page<dyn Scrapable, U>>),
+        );
+        count
+    }
+
+    /// The alias records found so far that haven't been flushed to the store.
+    pub async fn aliases(&self) -> Vec<AliasRecord<U>> {
+        self.aliases.lock().await.values().cloned().collect()
+    }
+
+    /// The canonical page for a url, if the url was found to be an alias (this run, or by a run that flushed it to the store).
+    pub async fn canonical_of(&self, url: &U) -> Option<Arc<U>> {
+        Self::canonical_in(&self.aliases, self.store.as_deref(), url).await
+    }
+
+    async fn canonical_in(
+        aliases: &Mutex<Aliases<U>>,
+        store: Option<&dyn PageStore<U>>,
+        url: &U,
+    ) -> Option<Arc<U>> {
+        if let Some(record) = aliases.lock().await.get(url) {
+            return Some(Arc::clone(&record.canonical));
+        }
+        let canonical = store?.get_canonical(url).await.ok().flatten()?;
+        Some(Arc::new(canonical))
+    }
+
+    /// Put the alias records found so far in the store, and let go of them and of the urls visited so far.
+    /// This keeps a long ingest (eg a whole dump, see `ingest_page`) from holding every url in memory.
+    /// Pages seen before the flush aren't recognised as visited after it, so a crawl would check them against the store again.
+    /// Does nothing if the handler has no store, as the aliases would be lost.
+    pub async fn flush_aliases(&self) -> Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        let aliases = std::mem::take(&mut *self.aliases.lock().await);
+        let records = aliases.into_values().collect::<Vec<AliasRecord<U>>>();
+        store.put_aliases(&records).await?;
+        self.visited.lock().await.clear();
+        Ok(())
+    }
+
+    /// Put the record of a fetch under the url that was requested and under the url the page is stored as, so a recrawl finds it from either.
+    async fn put_records(store: &dyn PageStore<U>, url: &U, canonical: &U, record: PageRecord) {
+        if *url != *canonical {
+            let _ = store.put_record(url, record.clone()).await;
+        }
+        let _ = store.put_record(canonical, record).await;
+    }
+
+    /// Add an alias record, passing it on to the graph.
+    async fn push_alias(
+        aliases: &Mutex<Aliases<U>>,
+        pipeline: &PagePipeline<U>,
+        record: AliasRecord<U>,
+    ) {
+        pipeline.record_alias(&record).await;
+        aliases
+            .lock()
+            .await
+            .insert(Arc::clone(&record.alias), record);
+    }
+
+    /// Work out which page a freshly scraped page should be stored as, recording any aliases.
+    /// Returns None if the page is an alias of a page that has already been scraped.
+    //? Takes the parts of the page it needs rather than the page itself, so the content doesn't need to be Sync to be held across the awaits.
+    async fn resolve_canonical(
+        visited: &Mutex<HashSet<Arc<U>>>,
+        aliases: &Mutex<Aliases<U>>,
+        pipeline: &PagePipeline<U>,
+        fingerprints: &Mutex<Fingerprints<U>>,
+        url: Arc<U>,
+        canonical_url: Option<(U, AliasReason)>,
+        fingerprint: Option<SimHash>,
+    ) -> Option<Arc<U>> {
+        let mut canonical = Arc::clone(&url);
+
+        if let Some((canonical_url, reason)) = canonical_url {
+            canonical = Arc::new(canonical_url);
+            let record = AliasRecord {
+                alias: Arc::clone(&url),
+                canonical: Arc::clone(&canonical),
+                reason,
+            };
+            Self::push_alias(aliases, pipeline, record).await;
+            let mut visited_urls = visited.lock().await;
+            visited_urls.insert(Arc::clone(&url));
+            //? The canonical page was already scraped under its own url, so there is nothing new here.
+            if !visited_urls.insert(Arc::clone(&canonical)) {
+                return None;
+            }
+        }
+
+        if let Some(fingerprint) = fingerprint {
+            let mut fingerprints = fingerprints.lock().await;
+            let original = fingerprints.find(&fingerprint).map(Arc::clone);
+            if let Some(original) = original {
+                drop(fingerprints);
+                //? The page being stored as itself already, eg an alias of it and the page were scraped together.
+                if original == canonical {
+                    visited.lock().await.insert(url);
+                    return None;
+                }
+                let record = AliasRecord {
+                    alias: Arc::clone(&canonical),
+                    canonical: original,
+                    reason: AliasReason::DuplicateContent,
+                };
+                Self::push_alias(aliases, pipeline, record).await;
+                visited.lock().await.insert(url);
+                return None;
+            }
+            fingerprints.insert(fingerprint, Arc::clone(&canonical));
+        }
+
+        Some(canonical)
+    }
+
+    /// Record a page that only points at another (see `ScrapableContent::redirect_target`) as an alias of it.
+    /// Returns the target page if it hasn't been scraped, for the crawl to queue.
+    async fn record_redirect(
+        visited: &Mutex<HashSet<Arc<U>>>,
+        aliases: &Mutex<Aliases<U>>,
+        pipeline: &PagePipeline<U>,
+        url: Arc<U>,
+        target: U,
+    ) -> Option<Page<LinkTo, U>> {
+        let title = target.to_string();
+        //? U isn't Clone, so the target page is built first and the alias record shares its Arc.
+        let target_page = Page::<LinkTo, U>::new(target, title);
+        let target = target_page.get_url_arc();
+        let record = AliasRecord {
+            alias: Arc::clone(&url),
+            canonical: Arc::clone(&target),
+            reason: AliasReason::Redirect,
+        };
+        Self::push_alias(aliases, pipeline, record).await;
+        let mut visited_urls = visited.lock().await;
+        visited_urls.insert(url);
+        (!visited_urls.contains(&target)).then_some(target_page)
+    }
+
+    /// Feed a page that was scraped outside of a crawl (eg read from a dump with `Page::scrape_document`) through the same steps as a crawled page:
+    /// redirects and canonical urls are recorded as aliases, the record is put in the store and the page is marked as visited.
+    /// Returns the url the page is stored under, None if it is an alias of another page or is outside the date range.
+    /// The page's links aren't queued, they are its edges in the link graph (or can be passed to `add_seed_pages` to crawl on from there).
+    //? Pages aren't fingerprinted: a dump has one copy of each page, and comparing against every page so far is far too slow for a whole dump.
+    pub async fn ingest_page<C: ScrapableContent<Url = U>>(
+        &self,
+        page: &Page<WasScraped<C>, U>,
+    ) -> Option<Arc<U>> {
+        if let Some(target) = page.redirect_target() {
+            Self::record_redirect(
+                &self.visited,
+                &self.aliases,
+                &self.pipeline,
+                page.get_url_arc(),
+                target,
+            )
+            .await;
+            return None;
+        }
+        let url = page.get_url_arc();
+        let record = page.record();
+        let canonical = Self::resolve_canonical(
+            &self.visited,
+            &self.aliases,
+            &self.pipeline,
+            &self.fingerprints,
+            Arc::clone(&url),
+            page.canonical_url(),
+            None,
+        )
+        .await;
+        let Some(canonical) = canonical else {
+            if let Some(store) = &self.store {
+                let _ = store.put_record(&url, record).await;
+            }
+            return None;
+        };
+
+        let in_range = self
+            .date_range
+            .is_none_or(|date_range| date_range.allows(page.content().published()));
+        let mut changed = true;
+        if let Some(store) = &self.store {
+            let previous = store.get_record(&canonical).await.ok().flatten();
+            changed = previous.is_none_or(|previous| {
+                previous.content_hash.is_none() || previous.content_hash != record.content_hash
+            });
+            Self::put_records(store.as_ref(), &url, &canonical, record).await;
+        }
+        {
+            let mut visited_urls = self.visited.lock().await;
+            visited_urls.insert(page.get_url_arc());
+            visited_urls.insert(Arc::clone(&canonical));
+        }
+        if !in_range {
+            return None;
+        }
+        if changed {
+            self.pipeline.process(&*canonical, page.content()).await;
+            self.fresh.lock().await.push(Arc::clone(&canonical));
+        }
+        Some(canonical)
     }
 
     /// Drain all pages from the queue.
@@ -102,11 +608,10 @@ where
     ) -> Vec<Box<Page<dyn Scrapable, U>>> {
         let mut seen = HashSet::new();
 
-        let unique_pages_to_scrape = pages_to_scrape
+        pages_to_scrape
             .into_iter()
             .filter(|page| seen.insert(page.get_url_arc()))
-            .collect::<Vec<Box<Page<dyn Scrapable, U>>>>();
-        unique_pages_to_scrape
+            .collect::<Vec<Box<Page<dyn Scrapable, U>>>>()
     }
 
     /// Remove pages that have already been visited from the list of pages to scrape.
@@ -154,11 +659,30 @@ where
             unique_pages_to_scrape
         );
 
-        stream::iter(unique_pages_to_scrape.into_iter())
+        if let Some(fetcher) = &self.fetcher {
+            let urls = unique_pages_to_scrape
+                .iter()
+                .map(|page| page.get_url_arc())
+                .collect::<Vec<Arc<U>>>();
+            //? A failed prefetch isn't fatal, each page is then fetched on its own.
+            if let Err(e) = fetcher.prefetch(&urls).await {
+                tracing::warn!("Unable to prefetch pages: {e:?}");
+            }
+        }
+
+        stream::iter(unique_pages_to_scrape)
             .for_each_concurrent(None, |scrapable_page| {
                 //? Each of the tasks need to have access to scraped_pages, but cant directly pass scraped_pages to them because it would mean multiple owners. What we are doing here is creating a new reference (Arc) to the data (.clone()). This new arc can then be moved into the concurrent task, giving it access to the shared data.
                 let visited_mutex = Arc::clone(&self.visited);
                 let pages_mutex = Arc::clone(&self.pages_queue);
+                let aliases_mutex = Arc::clone(&self.aliases);
+                let fingerprints_mutex = Arc::clone(&self.fingerprints);
+                let fresh_mutex = Arc::clone(&self.fresh);
+                let store = self.store.clone();
+                let date_range = self.date_range;
+                let alternates = self.alternates.clone();
+                let fetcher = self.fetcher.clone();
+                let pipeline = self.pipeline.clone();
 
                 //? here the async means creating an async block of code that can be awaited.
                 //? The move means the closure takes ownership of the values it uses inside the closure (url, scraped_pages).
@@ -170,10 +694,89 @@ where
                         }
                     }
 
-                    if let Some(page) = scrapable_page.scrape_in_place::<C>().await.ok() {
+                    let (page, previous) = Self::fetch_page::<C>(
+                        &visited_mutex,
+                        &aliases_mutex,
+                        store.as_deref(),
+                        fetcher.as_deref(),
+                        scrapable_page,
+                    )
+                    .await;
+
+                    if let Some(page) = page {
+                        if let Some(target) = page.redirect_target() {
+                            let target_page = Self::record_redirect(
+                                &visited_mutex,
+                                &aliases_mutex,
+                                &pipeline,
+                                page.get_url_arc(),
+                                target,
+                            )
+                            .await;
+                            if let Some(target_page) = target_page {
+                                pages_mutex.lock().await.push_back(Box::new(target_page));
+                            }
+                            return;
+                        }
+                        let record = page.record();
+                        let canonical = Self::resolve_canonical(
+                            &visited_mutex,
+                            &aliases_mutex,
+                            &pipeline,
+                            &fingerprints_mutex,
+                            page.get_url_arc(),
+                            page.canonical_url(),
+                            page.fingerprint(),
+                        )
+                        .await;
+                        let Some(canonical) = canonical else {
+                            //? The page is stored as another one, but the fetch is still recorded under its own url so a recrawl can skip it.
+                            if let Some(store) = &store {
+                                let _ = store.put_record(&page.get_url_arc(), record).await;
+                            }
+                            return;
+                        };
+
+                        //? Out of range articles are still recorded so a recrawl knows about them, but they aren't part of this crawl.
+                        let in_range = date_range
+                            .is_none_or(|date_range| date_range.allows(page.content().published()));
+
+                        let changed = previous.is_none_or(|previous| {
+                            previous.content_hash.is_none()
+                                || previous.content_hash != record.content_hash
+                        });
+                        if let Some(store) = &store {
+                            Self::put_records(
+                                store.as_ref(),
+                                &page.get_url_arc(),
+                                &canonical,
+                                record,
+                            )
+                            .await;
+                        }
+                        if !in_range {
+                            let mut visited_urls = visited_mutex.lock().await;
+                            visited_urls.insert(page.get_url_arc());
+                            visited_urls.insert(canonical);
+                            return;
+                        }
+                        if changed {
+                            pipeline.process(&*canonical, page.content()).await;
+                            fresh_mutex.lock().await.push(Arc::clone(&canonical));
+                        }
+
+                        let alternate_pages = alternates
+                            .map(|filter| {
+                                page.get_alternate_page_links()
+                                    .into_iter()
+                                    .filter(|alternate| filter.allows(alternate.as_ref()))
+                                    .collect::<Vec<Page<LinkTo, U>>>()
+                            })
+                            .unwrap_or_default();
                         let linked_pages = page
                             .get_all_page_links()
                             .into_iter()
+                            .chain(alternate_pages)
                             .map(|page| Box::new(page) as Box<Page<dyn Scrapable, U>>)
                             .collect::<Vec<Box<Page<dyn Scrapable, U>>>>();
 
@@ -185,6 +788,7 @@ where
                           //? Didn't need to do the same thing here as the guard is dropped at the end of the block
                         let mut visited_urls = visited_mutex.lock().await;
                         visited_urls.insert(page.get_url_arc());
+                        visited_urls.insert(canonical);
                         // TODO: Insert data to db here?
                     }
                 }
@@ -192,6 +796,9 @@ where
             .await;
 
         println!("Pages visited: {:#?}", self.visited.lock().await.len());
+        if let Err(e) = self.commit_text_index() {
+            tracing::warn!("Unable to commit the text index: {e:?}");
+        }
 
         Box::pin(self.get_pages_recursive_internal::<C>(max_depth, current_depth + 1)).await;
     }
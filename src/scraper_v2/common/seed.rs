@@ -0,0 +1,387 @@
+use super::{fetch_bytes, CommonError, LinkTo, Page, UrlTrait};
+use crate::Result;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use std::io::Read;
+use tracing::{debug, warn};
+
+/// How deep to follow nested sitemap indexes.
+const MAX_SITEMAP_DEPTH: u32 = 3;
+
+/// The namespace of the `<news:news>` elements of Google news sitemaps.
+const NEWS_NAMESPACE: &str = "http://www.google.com/schemas/sitemap-news/0.9";
+
+/// Where to get the initial pages of a crawl from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedSource {
+    /// An XML sitemap, sitemap index or news sitemap (optionally gzipped).
+    Sitemap(String),
+    /// An RSS or Atom feed.
+    Feed(String),
+}
+
+/// A url found in a sitemap or feed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeedEntry {
+    pub url: String,
+    pub title: Option<String>,
+    pub published: Option<DateTime<Utc>>,
+    /// When the page last changed, eg a sitemap's `<lastmod>`. Not a publication date, so not used to filter by date.
+    pub updated: Option<DateTime<Utc>>,
+}
+
+impl SeedEntry {
+    /// Turn the entry into a LinkTo page if the url is a valid url of type U.
+    pub fn to_page<U: UrlTrait>(&self) -> Option<Page<LinkTo, U>> {
+        let url = U::try_from(self.url.clone()).ok()?;
+        let title = self.title.clone().unwrap_or_default();
+        Some(Page::<LinkTo, U>::new(url, title).with_published(self.published))
+    }
+}
+
+/// A parsed sitemap document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sitemap {
+    /// A `<urlset>` listing pages.
+    UrlSet(Vec<SeedEntry>),
+    /// A `<sitemapindex>` listing other sitemaps.
+    Index(Vec<String>),
+}
+
+impl SeedSource {
+    /// Fetch the source and return every entry it lists. Sitemap indexes are followed.
+    pub async fn fetch_entries(&self) -> Result<Vec<SeedEntry>> {
+        match self {
+            SeedSource::Sitemap(url) => fetch_sitemap_entries(url.clone(), 0).await,
+            SeedSource::Feed(url) => {
+                let xml = decode_body(fetch_bytes(url).await?)?;
+                parse_feed(&xml)
+            }
+        }
+    }
+
+    /// Fetch the source and return the entries that are valid urls of type U as LinkTo pages.
+    pub async fn fetch_pages<U: UrlTrait>(&self) -> Result<Vec<Page<LinkTo, U>>> {
+        Ok(self
+            .fetch_entries()
+            .await?
+            .iter()
+            .filter_map(SeedEntry::to_page)
+            .collect())
+    }
+}
+
+//? Boxed because the function is recursive (sitemap indexes point at more sitemaps).
+fn fetch_sitemap_entries(url: String, depth: u32) -> BoxFuture<'static, Result<Vec<SeedEntry>>> {
+    async move {
+        let xml = decode_body(fetch_bytes(&url).await?)?;
+        match parse_sitemap(&xml)? {
+            Sitemap::UrlSet(entries) => Ok(entries),
+            Sitemap::Index(sitemaps) if depth < MAX_SITEMAP_DEPTH => {
+                let mut entries = Vec::new();
+                for sitemap in sitemaps {
+                    match fetch_sitemap_entries(sitemap.clone(), depth + 1).await {
+                        Ok(found) => entries.extend(found),
+                        Err(e) => warn!("Unable to fetch sitemap {}: {}", sitemap, e),
+                    }
+                }
+                Ok(entries)
+            }
+            Sitemap::Index(_) => {
+                debug!("Max sitemap depth reached at: {}", url);
+                Ok(Vec::new())
+            }
+        }
+    }
+    .boxed()
+}
+
+/// Gunzip the body if it is gzipped and decode it as UTF-8.
+pub fn decode_body(bytes: Vec<u8>) -> Result<String> {
+    let bytes = if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut decoded = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut decoded)?;
+        decoded
+    } else {
+        bytes
+    };
+    String::from_utf8(bytes).map_err(|e| {
+        CommonError::InvalidXml {
+            reason: e.to_string(),
+        }
+        .into()
+    })
+}
+
+fn parse_xml(xml: &str) -> Result<roxmltree::Document<'_>> {
+    roxmltree::Document::parse(xml).map_err(|e| {
+        CommonError::InvalidXml {
+            reason: e.to_string(),
+        }
+        .into()
+    })
+}
+
+/// The trimmed text of the first child of `node` with the local name `name`.
+//? Only children, so the `<image:title>` of an `<image:image>` isn't taken for the title of a sitemap entry.
+fn child_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// The trimmed text of the element `name` of a news sitemap entry's `<news:news>`.
+fn news_text(node: roxmltree::Node, name: &str) -> Option<String> {
+    node.children()
+        .find(|child| child.has_tag_name((NEWS_NAMESPACE, "news")))?
+        .children()
+        .find(|child| child.has_tag_name((NEWS_NAMESPACE, name)))
+        .and_then(|child| child.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// Parse a sitemap or sitemap index, including Google news sitemaps (`<news:news>`).
+pub fn parse_sitemap(xml: &str) -> Result<Sitemap> {
+    let document = parse_xml(xml)?;
+    let root = document.root_element();
+    match root.tag_name().name() {
+        "sitemapindex" => Ok(Sitemap::Index(
+            root.children()
+                .filter(|node| node.tag_name().name() == "sitemap")
+                .filter_map(|node| child_text(node, "loc"))
+                .collect(),
+        )),
+        "urlset" => Ok(Sitemap::UrlSet(
+            root.children()
+                .filter(|node| node.tag_name().name() == "url")
+                .filter_map(|node| {
+                    //? `<lastmod>` is when the page last changed, which can be long after it was published.
+                    Some(SeedEntry {
+                        url: child_text(node, "loc")?,
+                        title: news_text(node, "title"),
+                        published: news_text(node, "publication_date")
+                            .and_then(|date| parse_date(&date)),
+                        updated: child_text(node, "lastmod").and_then(|date| parse_date(&date)),
+                    })
+                })
+                .collect(),
+        )),
+        other => Err(CommonError::InvalidXml {
+            reason: format!("Unknown sitemap root element: {other}"),
+        }
+        .into()),
+    }
+}
+
+/// Parse an RSS 2.0 or Atom feed into its entries.
+pub fn parse_feed(xml: &str) -> Result<Vec<SeedEntry>> {
+    let document = parse_xml(xml)?;
+    let root = document.root_element();
+    match root.tag_name().name() {
+        "rss" | "RDF" => Ok(root
+            .descendants()
+            .filter(|node| node.tag_name().name() == "item")
+            .filter_map(|item| {
+                Some(SeedEntry {
+                    url: child_text(item, "link")?,
+                    title: child_text(item, "title"),
+                    published: child_text(item, "pubDate")
+                        .or_else(|| child_text(item, "date"))
+                        .and_then(|date| parse_date(&date)),
+                    updated: None,
+                })
+            })
+            .collect()),
+        "feed" => Ok(root
+            .children()
+            .filter(|node| node.tag_name().name() == "entry")
+            .filter_map(|entry| {
+                // Prefer the rel="alternate" link, which is the default when rel is missing.
+                let url = entry
+                    .children()
+                    .filter(|node| node.tag_name().name() == "link")
+                    .find(|link| link.attribute("rel").unwrap_or("alternate") == "alternate")
+                    .and_then(|link| link.attribute("href"))?
+                    .to_string();
+                Some(SeedEntry {
+                    url,
+                    title: child_text(entry, "title"),
+                    published: child_text(entry, "published")
+                        .or_else(|| child_text(entry, "updated"))
+                        .and_then(|date| parse_date(&date)),
+                    updated: child_text(entry, "updated").and_then(|date| parse_date(&date)),
+                })
+            })
+            .collect()),
+        other => Err(CommonError::InvalidXml {
+            reason: format!("Unknown feed root element: {other}"),
+        }
+        .into()),
+    }
+}
+
+/// Parse the date formats used by sitemaps (W3C datetime) and feeds (RFC 2822 / RFC 3339).
+pub fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(date))
+        .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M%:z"))
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|date| date.and_utc())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sitemap_dates_and_titles_come_from_the_right_elements() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:news="http://www.google.com/schemas/sitemap-news/0.9"
+        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1">
+  <url>
+    <loc>https://www.example.com/news/comet</loc>
+    <lastmod>2024-06-01T12:00:00Z</lastmod>
+    <image:image>
+      <image:loc>https://www.example.com/comet.jpg</image:loc>
+      <image:title>The comet over the cliffs</image:title>
+    </image:image>
+    <news:news>
+      <news:publication_date>2024-05-01T08:30:00Z</news:publication_date>
+      <news:title>Comet seen over the coast</news:title>
+    </news:news>
+  </url>
+  <url>
+    <loc>https://www.example.com/about</loc>
+    <lastmod>2024-06-02</lastmod>
+    <image:image>
+      <image:title>Our office</image:title>
+    </image:image>
+  </url>
+</urlset>"#;
+        let Sitemap::UrlSet(entries) = parse_sitemap(xml).unwrap() else {
+            panic!("not a url set");
+        };
+
+        assert_eq!(
+            entries[0].title.as_deref(),
+            Some("Comet seen over the coast")
+        );
+        assert_eq!(entries[0].published, parse_date("2024-05-01T08:30:00Z"));
+        assert_eq!(entries[0].updated, parse_date("2024-06-01T12:00:00Z"));
+        assert_eq!(entries[1].title, None);
+        assert_eq!(entries[1].published, None);
+        assert_eq!(entries[1].updated, parse_date("2024-06-02"));
+    }
+
+    #[test]
+    fn sitemap_indexes_list_their_sitemaps() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap>
+    <loc>https://www.example.com/sitemaps/news.xml</loc>
+    <lastmod>2024-06-01T12:00:00Z</lastmod>
+  </sitemap>
+  <sitemap>
+    <loc> https://www.example.com/sitemaps/archive.xml.gz </loc>
+  </sitemap>
+</sitemapindex>"#;
+
+        assert_eq!(
+            parse_sitemap(xml).unwrap(),
+            Sitemap::Index(vec![
+                "https://www.example.com/sitemaps/news.xml".to_string(),
+                "https://www.example.com/sitemaps/archive.xml.gz".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn gzipped_bodies_are_decoded() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let xml = "<urlset><url><loc>https://www.example.com/a</loc></url></urlset>";
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+
+        assert_eq!(decode_body(encoder.finish().unwrap()).unwrap(), xml);
+        assert_eq!(decode_body(xml.as_bytes().to_vec()).unwrap(), xml);
+    }
+
+    #[test]
+    fn rss_items_are_dated_by_their_pub_date() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel>
+    <title>Example News</title>
+    <link>https://www.example.com/</link>
+    <item>
+      <title>Comet seen over the coast</title>
+      <link>https://www.example.com/news/comet</link>
+      <pubDate>Wed, 01 May 2024 08:30:00 GMT</pubDate>
+    </item>
+    <item>
+      <title>Council approves budget</title>
+      <link>https://www.example.com/news/budget</link>
+      <dc:date>2024-05-02T10:00:00Z</dc:date>
+    </item>
+  </channel>
+</rss>"#;
+        let entries = parse_feed(xml).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "https://www.example.com/news/comet");
+        assert_eq!(
+            entries[0].title.as_deref(),
+            Some("Comet seen over the coast")
+        );
+        assert_eq!(entries[0].published, parse_date("2024-05-01T08:30:00Z"));
+        assert_eq!(entries[1].published, parse_date("2024-05-02T10:00:00Z"));
+        assert_eq!(entries[1].updated, None);
+    }
+
+    #[test]
+    fn atom_entries_are_published_or_else_updated() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example News</title>
+  <updated>2024-06-01T12:00:00Z</updated>
+  <entry>
+    <title>Comet seen over the coast</title>
+    <link rel="enclosure" href="https://www.example.com/comet.jpg"/>
+    <link href="https://www.example.com/news/comet"/>
+    <published>2024-05-01T08:30:00Z</published>
+    <updated>2024-06-01T12:00:00Z</updated>
+  </entry>
+  <entry>
+    <title>Council approves budget</title>
+    <link rel="alternate" href="https://www.example.com/news/budget"/>
+    <updated>2024-05-02T10:00:00+01:00</updated>
+  </entry>
+</feed>"#;
+        let entries = parse_feed(xml).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "https://www.example.com/news/comet");
+        assert_eq!(entries[0].published, parse_date("2024-05-01T08:30:00Z"));
+        assert_eq!(entries[0].updated, parse_date("2024-06-01T12:00:00Z"));
+        assert_eq!(entries[1].url, "https://www.example.com/news/budget");
+        assert_eq!(entries[1].published, parse_date("2024-05-02T09:00:00Z"));
+        assert_eq!(entries[1].updated, entries[1].published);
+    }
+}
@@ -0,0 +1,308 @@
+use super::Timestamp;
+
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// schema.org types that describe an article, the most specific first.
+//? `WebPage` isn't one: pages often describe themselves alongside the article, and the page's name and dates aren't the article's.
+const ARTICLE_TYPES: &[&str] = &[
+    "ReportageNewsArticle",
+    "AnalysisNewsArticle",
+    "NewsArticle",
+    "LiveBlogPosting",
+    "BlogPosting",
+    "Article",
+];
+
+/// A microdata item (`itemscope`) with its properties.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct MicrodataItem {
+    pub item_type: Option<String>,
+    pub properties: BTreeMap<String, Vec<String>>,
+}
+
+/// The machine readable metadata embedded in a page.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct StructuredData {
+    /// Every JSON-LD object on the page, with `@graph` containers and arrays flattened out.
+    pub json_ld: Vec<Value>,
+    /// `og:*` and `article:*` meta properties.
+    pub open_graph: BTreeMap<String, String>,
+    /// `twitter:*` meta names.
+    pub twitter: BTreeMap<String, String>,
+    pub microdata: Vec<MicrodataItem>,
+}
+
+/// The article fields we care about, merged from whichever structured data source has them.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ArticleMetadata {
+    pub headline: Option<String>,
+    pub description: Option<String>,
+    pub date_published: Timestamp,
+    pub date_modified: Timestamp,
+    pub authors: Vec<String>,
+    pub thumbnail: Option<String>,
+    pub section: Option<String>,
+}
+
+impl StructuredData {
+    pub fn from_document(document: &Html) -> Self {
+        Self {
+            json_ld: Self::extract_json_ld(document),
+            open_graph: Self::extract_meta(document, "property", &["og:", "article:"]),
+            twitter: Self::extract_meta(document, "name", &["twitter:"]),
+            microdata: Self::extract_microdata(document),
+        }
+    }
+
+    fn extract_json_ld(document: &Html) -> Vec<Value> {
+        let script_selector = Selector::parse("script[type='application/ld+json']").unwrap();
+        let mut values = Vec::new();
+        for script in document.select(&script_selector) {
+            let json = script.text().collect::<String>();
+            if let Ok(value) = serde_json::from_str::<Value>(json.trim()) {
+                flatten_json_ld(value, &mut values);
+            }
+        }
+        values
+    }
+
+    fn extract_meta(
+        document: &Html,
+        attribute: &str,
+        prefixes: &[&str],
+    ) -> BTreeMap<String, String> {
+        let meta_selector = Selector::parse("meta[content]").unwrap();
+        document
+            .select(&meta_selector)
+            .filter_map(|meta| {
+                // Some sites use name= for og: tags and property= for twitter: tags, so accept both.
+                let key = meta
+                    .value()
+                    .attr(attribute)
+                    .or_else(|| meta.value().attr("name"))
+                    .or_else(|| meta.value().attr("property"))?;
+                prefixes
+                    .iter()
+                    .any(|prefix| key.starts_with(prefix))
+                    .then(|| {
+                        (
+                            key.to_string(),
+                            meta.value()
+                                .attr("content")
+                                .unwrap_or_default()
+                                .trim()
+                                .to_string(),
+                        )
+                    })
+            })
+            .filter(|(_, content)| !content.is_empty())
+            .collect()
+    }
+
+    fn extract_microdata(document: &Html) -> Vec<MicrodataItem> {
+        let scope_selector = Selector::parse("[itemscope]").unwrap();
+        let property_selector = Selector::parse("[itemprop]").unwrap();
+        document
+            .select(&scope_selector)
+            .map(|scope| {
+                let mut properties = BTreeMap::<String, Vec<String>>::new();
+                for property in scope.select(&property_selector) {
+                    // Only properties that belong to this item, not to a nested item.
+                    if nearest_scope(&property).map(|owner| owner.id()) != Some(scope.id()) {
+                        continue;
+                    }
+                    let value = microdata_value(&property);
+                    if value.is_empty() {
+                        continue;
+                    }
+                    for name in property
+                        .value()
+                        .attr("itemprop")
+                        .unwrap_or_default()
+                        .split_whitespace()
+                    {
+                        properties
+                            .entry(name.to_string())
+                            .or_default()
+                            .push(value.clone());
+                    }
+                }
+                MicrodataItem {
+                    item_type: scope.value().attr("itemtype").map(str::to_string),
+                    properties,
+                }
+            })
+            .collect()
+    }
+
+    /// The first JSON-LD object of the first of `types` the page has an object of, so types are given in order of preference.
+    pub fn find_json_ld(&self, types: &[&str]) -> Option<&Value> {
+        types.iter().find_map(|wanted| {
+            self.json_ld.iter().find(|value| match value.get("@type") {
+                Some(Value::String(kind)) => kind == wanted,
+                Some(Value::Array(kinds)) => kinds.iter().any(|kind| kind == wanted),
+                _ => false,
+            })
+        })
+    }
+
+    /// Article metadata taken from JSON-LD first, then Open Graph, Twitter cards and microdata.
+    pub fn article(&self) -> ArticleMetadata {
+        let json_ld = self.find_json_ld(ARTICLE_TYPES);
+        let json_ld_str = |key: &str| json_ld.and_then(|value| value_text(value.get(key)?));
+        let og = |key: &str| self.open_graph.get(key).cloned();
+        let twitter = |key: &str| self.twitter.get(key).cloned();
+        let microdata = self.microdata.iter().find(|item| {
+            item.item_type
+                .as_deref()
+                .is_some_and(|kind| kind.ends_with("Article") || kind.ends_with("BlogPosting"))
+        });
+        let micro =
+            |key: &str| microdata.and_then(|item| item.properties.get(key)?.first().cloned());
+
+        let authors = json_ld
+            .and_then(|value| value.get("author"))
+            .map(value_names)
+            .filter(|authors| !authors.is_empty())
+            .or_else(|| og("article:author").map(|author| vec![author]))
+            .or_else(|| microdata.and_then(|item| item.properties.get("author").cloned()))
+            .unwrap_or_default();
+
+        let thumbnail = json_ld
+            .and_then(|value| {
+                ["thumbnailUrl", "image"]
+                    .iter()
+                    .find_map(|key| value_url(value.get(*key)?))
+            })
+            .or_else(|| og("og:image"))
+            .or_else(|| twitter("twitter:image"))
+            .or_else(|| micro("image"));
+
+        ArticleMetadata {
+            headline: json_ld_str("headline")
+                .or_else(|| og("og:title"))
+                .or_else(|| twitter("twitter:title"))
+                .or_else(|| micro("headline")),
+            description: json_ld_str("description")
+                .or_else(|| og("og:description"))
+                .or_else(|| twitter("twitter:description"))
+                .or_else(|| micro("description")),
+            date_published: Timestamp::parse(
+                json_ld_str("datePublished")
+                    .or_else(|| og("article:published_time"))
+                    .or_else(|| micro("datePublished"))
+                    .as_deref(),
+            ),
+            date_modified: Timestamp::parse(
+                json_ld_str("dateModified")
+                    .or_else(|| og("article:modified_time"))
+                    .or_else(|| micro("dateModified"))
+                    .as_deref(),
+            ),
+            authors,
+            thumbnail,
+            section: json_ld_str("articleSection")
+                .or_else(|| og("article:section"))
+                .or_else(|| micro("articleSection")),
+        }
+    }
+}
+
+fn flatten_json_ld(value: Value, values: &mut Vec<Value>) {
+    match value {
+        Value::Array(items) => items
+            .into_iter()
+            .for_each(|item| flatten_json_ld(item, values)),
+        Value::Object(mut object) => {
+            if let Some(graph) = object.remove("@graph") {
+                flatten_json_ld(graph, values);
+            }
+            if object.contains_key("@type") {
+                values.push(Value::Object(object));
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A JSON-LD value as text, taking the first item of arrays.
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.trim().to_string()).filter(|text| !text.is_empty()),
+        Value::Array(items) => items.iter().find_map(value_text),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
+
+/// A JSON-LD url, which may be a plain string or an ImageObject.
+fn value_url(value: &Value) -> Option<String> {
+    match value {
+        Value::Object(object) => object.get("url").and_then(value_text),
+        Value::Array(items) => items.iter().find_map(value_url),
+        other => value_text(other),
+    }
+}
+
+/// The names of JSON-LD people or organisations, which may be strings, objects or arrays of either.
+fn value_names(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(items) => items.iter().flat_map(value_names).collect(),
+        Value::Object(object) => object
+            .get("name")
+            .and_then(value_text)
+            .into_iter()
+            .collect(),
+        other => value_text(other).into_iter().collect(),
+    }
+}
+
+/// The closest `itemscope` ancestor of a property element.
+fn nearest_scope<'a>(element: &ElementRef<'a>) -> Option<ElementRef<'a>> {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|ancestor| ancestor.value().attr("itemscope").is_some())
+}
+
+/// The value of a microdata property, which depends on the element it is on.
+fn microdata_value(element: &ElementRef) -> String {
+    let value = element.value();
+    let attribute = match value.name() {
+        "meta" => value.attr("content"),
+        "a" | "link" | "area" => value.attr("href"),
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" => value.attr("src"),
+        "time" => value.attr("datetime"),
+        "data" | "meter" => value.attr("value"),
+        _ => None,
+    };
+    attribute
+        .map(str::to_string)
+        .unwrap_or_else(|| element.text().collect::<String>())
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_most_specific_article_type_is_used() {
+        let html = r#"<html><head>
+<script type="application/ld+json">{"@context":"https://schema.org","@type":"Article","headline":"Hub page"}</script>
+<script type="application/ld+json">{"@context":"https://schema.org","@type":"ReportageNewsArticle","headline":"Floods close the high street","datePublished":"2024-03-01T09:00:00Z"}</script>
+</head><body></body></html>"#;
+        let structured = StructuredData::from_document(&Html::parse_document(html));
+        let article = structured.article();
+
+        assert_eq!(
+            article.headline.as_deref(),
+            Some("Floods close the high street")
+        );
+        assert!(article.date_published.datetime().is_some());
+    }
+}
@@ -0,0 +1,93 @@
+use super::parse_date;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A date scraped from a page. Keeps the difference between a page that doesn't give a date and one whose date we couldn't read.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum Timestamp {
+    /// The page doesn't give a date.
+    #[default]
+    Missing,
+    /// The page gives a date in a format we don't understand, kept as it appeared.
+    Unparseable(String),
+    Parsed(DateTime<Utc>),
+}
+
+impl Timestamp {
+    /// Parse a raw date (eg a `datetime` attribute or a JSON-LD value).
+    pub fn parse(raw: Option<&str>) -> Self {
+        match raw.map(str::trim).filter(|raw| !raw.is_empty()) {
+            None => Timestamp::Missing,
+            Some(raw) => parse_date(raw)
+                .map(Timestamp::Parsed)
+                .unwrap_or_else(|| Timestamp::Unparseable(raw.to_string())),
+        }
+    }
+
+    pub fn datetime(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Timestamp::Parsed(datetime) => Some(*datetime),
+            _ => None,
+        }
+    }
+
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Timestamp::Missing)
+    }
+
+    pub fn is_unparseable(&self) -> bool {
+        matches!(self, Timestamp::Unparseable(_))
+    }
+
+    /// Use the fallback if this timestamp wasn't parsed. If neither was parsed an unparseable date is kept over a missing one.
+    pub fn or_else(self, fallback: impl FnOnce() -> Timestamp) -> Timestamp {
+        if let Timestamp::Parsed(_) = self {
+            return self;
+        }
+        match fallback() {
+            fallback @ Timestamp::Parsed(_) => fallback,
+            fallback if self.is_missing() => fallback,
+            _ => self,
+        }
+    }
+}
+
+impl From<Option<DateTime<Utc>>> for Timestamp {
+    fn from(datetime: Option<DateTime<Utc>>) -> Self {
+        datetime.map_or(Timestamp::Missing, Timestamp::Parsed)
+    }
+}
+
+/// A window of publication dates, used to limit a crawl to articles from a period (eg the last 48 hours).
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl DateRange {
+    pub fn new(start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        Self { start, end }
+    }
+
+    /// Everything published from `start` onwards.
+    pub fn since(start: DateTime<Utc>) -> Self {
+        Self::new(Some(start), None)
+    }
+
+    /// Everything published within `duration` of now.
+    pub fn last(duration: Duration) -> Self {
+        Self::since(Utc::now() - duration)
+    }
+
+    pub fn contains(&self, datetime: DateTime<Utc>) -> bool {
+        self.start.is_none_or(|start| datetime >= start)
+            && self.end.is_none_or(|end| datetime <= end)
+    }
+
+    /// Whether something with the given date should be kept. Undated things are kept, as there is no way to tell.
+    pub fn allows(&self, datetime: Option<DateTime<Utc>>) -> bool {
+        datetime.is_none_or(|datetime| self.contains(datetime))
+    }
+}
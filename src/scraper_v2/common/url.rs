@@ -1,3 +1,4 @@
+use super::{Canonicalizer, RevisitPolicy};
 use crate::{Error, Result};
 
 use serde::{Deserialize, Serialize};
@@ -5,7 +6,7 @@ use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 
 /// This is a trait that is used to represent a url.
-pub trait UrlTrait: Hash + Debug + TryFrom<String> + AsRef<String> + Eq {
+pub trait UrlTrait: Hash + Debug + TryFrom<String, Error = Error> + AsRef<String> + Eq {
     /// To create a new Url type from a string. Can also be used on the type itself.
     fn parse(url: impl Into<String>) -> std::result::Result<Self, Self::Error>
     where
@@ -19,6 +20,34 @@ pub trait UrlTrait: Hash + Debug + TryFrom<String> + AsRef<String> + Eq {
 
     /// The base url for the site.
     fn base_url() -> &'static str;
+    /// Other hosts that serve the same site (eg `bbc.co.uk` for `www.bbc.co.uk`). These are rewritten to the base url host when canonicalizing.
+    fn host_aliases() -> &'static [&'static str] {
+        &[]
+    }
+    /// How often pages of this site should be revisited when recrawling.
+    fn revisit_policy() -> RevisitPolicy {
+        RevisitPolicy::default()
+    }
+    /// The canonicalizer for this site.
+    fn canonicalizer() -> Canonicalizer<'static> {
+        Canonicalizer::new(Self::base_url(), Self::host_aliases())
+    }
+    /// Canonicalize a url for this site, this is applied to every url before `parse_url`.
+    fn canonicalize(url: &str) -> Result<String> {
+        Self::canonicalizer().canonicalize(url)
+    }
+    /// Canonicalize and then parse a url. This is what `TryFrom<String>` should call.
+    fn parse_canonical(url: &str) -> Result<String> {
+        Self::parse_url(&Self::canonicalize(url)?)
+    }
+    /// Resolve a (possibly relative) url against the page it was found on and parse it as Self.
+    fn resolve(page_url: &str, url: &str) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        let canonical = Self::canonicalizer().canonicalize_relative_to(url, page_url)?;
+        Self::try_from(canonical)
+    }
     /// Returns the url as a string.
     fn to_string(&self) -> String;
     /// Returns the full url.
@@ -47,7 +76,7 @@ pub trait UrlTrait: Hash + Debug + TryFrom<String> + AsRef<String> + Eq {
 ///
 /// Example of how to use the macro to generate the above boilerplate code
 /// TODO: MACRO TO GENERATE ABOVE BOILERPLATE CODE (./sites/bbc/url.rs)
-/// ```rust
+/// ```ignore
 /// use crate::create_url_type;
 /// use crate::common::ScrapableContent;
 ///
@@ -70,11 +99,11 @@ pub trait UrlTrait: Hash + Debug + TryFrom<String> + AsRef<String> + Eq {
 ///
 /// impl ScrapableContent for ExampleContent {
 ///     type Url = ExampleURL;
-///     fn from_scraped_page(url: &Self::Url, document: &Html) -> Result<Self> {
+///     fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self> {
 ///         todo!("Ensure this function is implemented")
 ///     }
 /// }
-
+/// ```
 #[macro_export]
 macro_rules! create_url_type {
     ($type_name:ident, $content_type:ty, $base_url:expr, $parse_function:expr) => {
@@ -91,7 +120,7 @@ macro_rules! create_url_type {
             type Error = Error;
 
             fn try_from(url: String) -> Result<Self> {
-                match $parse_function(&url) {
+                match Self::parse_canonical(&url) {
                     Ok(parsed_url) => Ok($type_name(parsed_url)),
                     Err(e) => Err(e),
                 }
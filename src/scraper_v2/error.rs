@@ -1,6 +1,9 @@
 pub type Result<T> = std::result::Result<T, Error>;
 //pub type Error = Box<dyn std::error::Error>;
 
+use super::common::CommonError;
+use super::graph::error::GraphError;
+use super::search::error::SearchError;
 use super::sites::error::*;
 
 #[derive(Debug, derive_more::From)]
@@ -12,13 +15,20 @@ pub enum Error {
         too_low: u32,
     },
     // -- Module
-    //Common(super:common::Error),
+    #[from]
+    Common(CommonError),
     #[from]
     ScraperError(ScraperError),
     #[from]
     WikipediaError(WikipediaError),
     #[from]
     BBCError(BBCError),
+    #[from]
+    GenericError(GenericError),
+    #[from]
+    SearchError(SearchError),
+    #[from]
+    GraphError(GraphError),
 
     // -- Externals
     #[from]
@@ -0,0 +1,143 @@
+use std::collections::{HashMap, HashSet};
+
+/// Names longer than this many words aren't looked for, to keep matching cheap.
+const MAX_NAME_WORDS: usize = 8;
+
+#[derive(Debug, Clone)]
+struct Target {
+    /// None when the name was given for more than one entity, so it can't be told which one is meant.
+    url: Option<String>,
+    /// Whether the name was given as it is, rather than by dropping the qualifier of a title like "Mercury (planet)".
+    exact: bool,
+}
+
+/// Finds the names of known entities in text, eg the titles of the Wikipedia articles a BBC article names.
+///
+/// Names are matched on whole words, case sensitive apart from the first letter (as Wikipedia titles are), so "Climate change" matches "climate change" but "The Who" doesn't match "the who".
+/// A name of a single word only matches a capitalised word that doesn't start a sentence, so common words that have an article (eg "Water") aren't linked everywhere.
+/// A title with a qualifier also matches without it, eg "Mercury (planet)" matches "Mercury", unless another title shares the name.
+#[derive(Debug, Clone, Default)]
+pub struct EntityLinker {
+    names: HashMap<String, Target>,
+    /// The most words in a name, so longer runs of words aren't tried.
+    max_words: usize,
+}
+
+impl EntityLinker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a name of the entity with the page at `url`, eg the title of a Wikipedia article or a redirect to it.
+    pub fn add(&mut self, name: &str, url: &str) {
+        self.insert(name, url, true);
+        if let Some(base) = without_qualifier(name) {
+            self.insert(base, url, false);
+        }
+    }
+
+    fn insert(&mut self, name: &str, url: &str, exact: bool) {
+        let words = words(name)
+            .into_iter()
+            .map(|(word, _)| word)
+            .collect::<Vec<_>>();
+        if words.is_empty() || words.len() > MAX_NAME_WORDS {
+            return;
+        }
+        self.max_words = self.max_words.max(words.len());
+        let target = Target {
+            url: Some(url.to_string()),
+            exact,
+        };
+        let existing = self.names.entry(words.join(" ")).or_insert(target.clone());
+        if existing.url.as_deref() == Some(url) {
+            existing.exact |= exact;
+        } else if exact && !existing.exact {
+            *existing = target;
+        } else if !exact && !existing.exact {
+            existing.url = None;
+        }
+    }
+
+    /// The number of names that can be found.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// The urls of the entities named in the text, in the order they are first named.
+    //? The longest name starting at each word wins, so "New York City" isn't also a mention of "New York".
+    pub fn find(&self, text: &str) -> Vec<String> {
+        let words = words(text);
+        let mut found = Vec::new();
+        let mut seen = HashSet::new();
+        let mut start = 0;
+        while start < words.len() {
+            let longest = (1..=self.max_words.min(words.len() - start))
+                .rev()
+                .find_map(|length| {
+                    let run = &words[start..start + length];
+                    if length == 1 {
+                        let (word, sentence_start) = run[0];
+                        if sentence_start || !word.starts_with(char::is_uppercase) {
+                            return None;
+                        }
+                    }
+                    let name = run.iter().map(|(word, _)| *word).collect::<Vec<_>>();
+                    let url = self.lookup(&name.join(" "))?;
+                    Some((length, url))
+                });
+            match longest {
+                Some((length, url)) => {
+                    if seen.insert(url) {
+                        found.push(url.to_string());
+                    }
+                    start += length;
+                }
+                None => start += 1,
+            }
+        }
+        found
+    }
+
+    fn lookup(&self, name: &str) -> Option<&str> {
+        let target = self.names.get(name).or_else(|| {
+            let mut chars = name.chars();
+            let first = chars.next()?;
+            self.names
+                .get(&format!("{}{}", first.to_uppercase(), chars.as_str()))
+        })?;
+        target.url.as_deref()
+    }
+}
+
+/// The name without the qualifier of a title like "Mercury (planet)", None if it has none.
+pub(super) fn without_qualifier(name: &str) -> Option<&str> {
+    name.strip_suffix(')')
+        .and_then(|name| name.rsplit_once(" ("))
+        .map(|(base, _)| base)
+}
+
+/// The words of the text, and whether each one starts a sentence.
+pub(super) fn words(text: &str) -> Vec<(&str, bool)> {
+    let mut words = Vec::new();
+    let mut word_start = None;
+    let mut sentence_start = true;
+    for (index, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        if c.is_alphanumeric() {
+            word_start.get_or_insert(index);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            words.push((&text[start..index], sentence_start));
+            sentence_start = false;
+        }
+        if matches!(c, '.' | '!' | '?' | ':' | '\n' | '"' | '\u{201C}') {
+            sentence_start = true;
+        }
+    }
+    words
+}
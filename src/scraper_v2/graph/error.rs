@@ -0,0 +1,18 @@
+#[derive(Debug, derive_more::From)]
+pub enum GraphError {
+    #[from]
+    Custom(String),
+    /// Not one of links-to, in-category, related-topic, cites or mentions-entity.
+    UnknownEdgeKind { kind: String },
+    /// Not one of out, in or both.
+    UnknownDirection { direction: String },
+}
+
+// Error boilerplate.
+impl core::fmt::Display for GraphError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for GraphError {}
@@ -0,0 +1,450 @@
+use super::entity::{without_qualifier, words};
+use super::{Direction, Edge, EdgeKind, EntityLinker, GraphStore, Node};
+use crate::common::{ScrapableContent, UrlTrait};
+use crate::Result;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+
+/// Which edges a neighbourhood or path may follow, and how far a search may spread.
+#[derive(Debug, Clone)]
+pub struct Traversal {
+    /// Empty follows every kind of edge.
+    kinds: HashSet<EdgeKind>,
+    direction: Direction,
+    max_nodes: usize,
+}
+
+impl Default for Traversal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Traversal {
+    /// Follows every edge either way, and stops after reaching 1000 nodes.
+    pub fn new() -> Self {
+        Self {
+            kinds: HashSet::new(),
+            direction: Direction::Both,
+            max_nodes: 1000,
+        }
+    }
+
+    /// Only follow these kinds of edges, eg only `EdgeKind::LinksTo` for the link graph.
+    pub fn with_kinds(mut self, kinds: impl IntoIterator<Item = EdgeKind>) -> Self {
+        self.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Stop spreading out once this many nodes are reached. A popular page can be a couple of edges away from most of the graph.
+    pub fn with_max_nodes(mut self, max_nodes: usize) -> Self {
+        self.max_nodes = max_nodes.max(1);
+        self
+    }
+
+    fn follows(&self, edge: &Edge) -> bool {
+        self.kinds.is_empty() || self.kinds.contains(&edge.kind)
+    }
+}
+
+/// The nodes within a number of edges of a node, and the edges between them.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Neighborhood {
+    /// Each node with how many edges away it is, nearest first. The node itself comes first, 0 edges away.
+    pub nodes: Vec<(Node, usize)>,
+    pub edges: Vec<Edge>,
+    /// Whether the search stopped at `Traversal::with_max_nodes` before reaching every node in range.
+    pub truncated: bool,
+}
+
+/// A chain of edges from one node to another. `edges[i]` joins `nodes[i]` and `nodes[i + 1]`, though it can point either way when both directions are followed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct GraphPath {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+/// A knowledge graph of scraped pages, kept in a `GraphStore`.
+///
+/// Each page added is a node with edges to the pages it links to, the categories it is in, the topics it is tagged with, the sources it cites, and the entities it names.
+/// Pages that are the page of an entity (`ScrapableContent::entity_names`, eg Wikipedia articles) make their names known, so pages from other sites that name them get a mentions-entity edge, whether they were added before or after the entity.
+/// Links are followed through the aliases the crawl found (`add_alias`), so a redirect and the page it redirects to are one node.
+///
+/// ```ignore
+/// let graph = Arc::new(KnowledgeGraph::open(Arc::new(MemoryGraphStore::load("graph.json").await?)).await?);
+/// let handler = PageHandler::new().with_graph(Arc::clone(&graph));
+/// // ... crawl ...
+/// let around = graph.neighborhood("https://en.wikipedia.org/wiki/London", 1, &Traversal::new()).await?;
+/// ```
+#[derive(Debug)]
+pub struct KnowledgeGraph {
+    store: Arc<dyn GraphStore>,
+    entities: RwLock<EntityLinker>,
+    /// The pages whose text has each word (lowercased), to find the pages that may name an entity that becomes known.
+    pages_by_word: RwLock<HashMap<String, HashSet<String>>>,
+    /// The page each alias url is another url of.
+    aliases: RwLock<HashMap<String, String>>,
+}
+
+impl KnowledgeGraph {
+    /// A graph kept in `store`, knowing the entities, page texts and aliases already in it.
+    pub async fn open(store: Arc<dyn GraphStore>) -> Result<Self> {
+        let mut entities = EntityLinker::new();
+        for node in store.entities().await? {
+            for name in &node.names {
+                entities.add(name, &node.id);
+            }
+        }
+        let mut pages_by_word = HashMap::new();
+        for (id, text) in store.texts().await? {
+            index_words(&mut pages_by_word, &id, &text);
+        }
+        let aliases = store.aliases().await?.into_iter().collect();
+        Ok(Self {
+            store,
+            entities: RwLock::new(entities),
+            pages_by_word: RwLock::new(pages_by_word),
+            aliases: RwLock::new(aliases),
+        })
+    }
+
+    pub fn store(&self) -> &Arc<dyn GraphStore> {
+        &self.store
+    }
+
+    /// Make an entity known without scraping its page, eg the title of every article in a Wikipedia dump, so pages naming it are linked to it.
+    /// Pages already in the graph that name it are linked to it too.
+    pub async fn add_entity(&self, name: &str, url: &str) -> Result<()> {
+        self.entities
+            .write()
+            .map_err(crate::Error::custom)?
+            .add(name, url);
+        let mut node = match self.store.get_node(url).await? {
+            Some(node) => node,
+            None => Node::page(url, Some(name.to_string())),
+        };
+        if !node.names.iter().any(|known| known == name) {
+            node.names.push(name.to_string());
+            self.store.put_node(node).await?;
+        }
+        self.link_mentions(url, &[name.to_string()]).await
+    }
+
+    /// Record that `alias` (eg a redirect or a duplicate) is another url of the page `canonical`.
+    /// Links to the alias, from pages added before and after, become links to the page.
+    pub async fn add_alias(&self, alias: &str, canonical: &str) -> Result<()> {
+        if alias == canonical {
+            return Ok(());
+        }
+        self.aliases
+            .write()
+            .map_err(crate::Error::custom)?
+            .insert(alias.to_string(), canonical.to_string());
+        self.store.put_alias(alias, canonical).await?;
+
+        let linking = self
+            .store
+            .edges_to(alias)
+            .await?
+            .into_iter()
+            .filter(|edge| edge.kind == EdgeKind::LinksTo)
+            .map(|edge| edge.from)
+            .collect::<HashSet<_>>();
+        for from in linking {
+            let mut seen = HashSet::new();
+            let edges = self
+                .store
+                .edges_from(&from)
+                .await?
+                .into_iter()
+                .map(|mut edge| {
+                    if edge.kind == EdgeKind::LinksTo && edge.to == alias {
+                        edge.to = canonical.to_string();
+                    }
+                    edge
+                })
+                .filter(|edge| edge.to != edge.from && seen.insert((edge.to.clone(), edge.kind)))
+                .collect();
+            self.store.put_edges(&from, edges).await?;
+        }
+        Ok(())
+    }
+
+    /// Add a scraped page, replacing the edges it had if it was added before.
+    //? Everything is read from the content before the returned future runs, so the future doesn't hold on to it (content isn't Sync, and the future has to be Send).
+    pub fn add_page<C: ScrapableContent>(
+        &self,
+        url: &C::Url,
+        content: &C,
+    ) -> impl Future<Output = Result<()>> + Send + '_ {
+        let page = self.read_page(url, content);
+        async move {
+            let (node, related, edges, text) = page?;
+            let id = node.id.clone();
+            let names = node.names.clone();
+            self.store.put_node(node).await?;
+            for node in related {
+                self.store.add_node(node).await?;
+            }
+            self.store.put_edges(&id, edges).await?;
+            self.store.put_text(&id, text).await?;
+            if names.is_empty() {
+                return Ok(());
+            }
+            self.link_mentions(&id, &names).await
+        }
+    }
+
+    /// The node of a page, the category, topic and source nodes it refers to, its edges, and the text entities are found in.
+    fn read_page<C: ScrapableContent>(
+        &self,
+        url: &C::Url,
+        content: &C,
+    ) -> Result<(Node, Vec<Node>, Vec<Edge>, String)> {
+        let id = url.full_url();
+        let title = content.page_title();
+        let names = content.entity_names();
+        {
+            let mut entities = self.entities.write().map_err(crate::Error::custom)?;
+            for name in &names {
+                entities.add(name, &id);
+            }
+        }
+        let mut node = Node::page(&id, title.clone());
+        node.names = names;
+
+        let mut edges = Vec::new();
+        let aliases = self.aliases.read().map_err(crate::Error::custom)?;
+        for link in content.get_related_pages() {
+            let to = link.as_ref().full_url();
+            let to = aliases.get(&to).cloned().unwrap_or(to);
+            if to != id {
+                edges.push(Edge::new(&id, to, EdgeKind::LinksTo));
+            }
+        }
+        let mut related = Vec::new();
+        for category in content.categories() {
+            related.push((Node::category(&category), EdgeKind::InCategory));
+        }
+        for topic in content.topics() {
+            related.push((Node::topic(&topic), EdgeKind::RelatedTopic));
+        }
+        for source in content.citations() {
+            related.push((Node::source(source), EdgeKind::Cites));
+        }
+        drop(aliases);
+        for (related_node, kind) in &related {
+            edges.push(Edge::new(&id, &related_node.id, *kind));
+        }
+
+        //? Only entities on other sites are linked by name: a page on the same site as an entity links to it if it means it.
+        let text = format!("{}\n{}", title.unwrap_or_default(), content.body_text());
+        let mentioned = self
+            .entities
+            .read()
+            .map_err(crate::Error::custom)?
+            .find(&text);
+        let site = host(&id);
+        for entity in mentioned {
+            if entity != id && host(&entity) != site {
+                edges.push(Edge::new(&id, entity, EdgeKind::MentionsEntity));
+            }
+        }
+        let mut pages_by_word = self.pages_by_word.write().map_err(crate::Error::custom)?;
+        index_words(&mut pages_by_word, &id, &text);
+        drop(pages_by_word);
+
+        let mut seen = HashSet::new();
+        edges.retain(|edge| seen.insert((edge.to.clone(), edge.kind)));
+        let related = related.into_iter().map(|(node, _)| node).collect();
+        Ok((node, related, edges, text))
+    }
+
+    /// Add a mentions-entity edge to `entity` from each page in the graph that names it by one of `names`, as pages added before the entity was known weren't linked to it.
+    //? Only pages with every word of a name are read, found through the word index starting from the rarest word.
+    async fn link_mentions(&self, entity: &str, names: &[String]) -> Result<()> {
+        let candidates = {
+            let pages_by_word = self.pages_by_word.read().map_err(crate::Error::custom)?;
+            let mut candidates = HashSet::new();
+            for name in names {
+                let name = without_qualifier(name).unwrap_or(name);
+                let mut pages = words(name)
+                    .into_iter()
+                    .map(|(word, _)| pages_by_word.get(&word.to_lowercase()))
+                    .collect::<Option<Vec<_>>>()
+                    .unwrap_or_default();
+                pages.sort_by_key(|pages| pages.len());
+                if let Some((rarest, rest)) = pages.split_first() {
+                    candidates.extend(
+                        rarest
+                            .iter()
+                            .filter(|page| rest.iter().all(|pages| pages.contains(*page)))
+                            .cloned(),
+                    );
+                }
+            }
+            candidates
+        };
+
+        let site = host(entity);
+        for page in candidates {
+            if page == entity || host(&page) == site {
+                continue;
+            }
+            let Some(text) = self.store.get_text(&page).await? else {
+                continue;
+            };
+            //? The whole linker reads the text, so a longer name that covers this one (eg "New York City" for "New York") still wins.
+            let named = self
+                .entities
+                .read()
+                .map_err(crate::Error::custom)?
+                .find(&text)
+                .contains(&entity.to_string());
+            if named {
+                self.store
+                    .add_edge(Edge::new(&page, entity, EdgeKind::MentionsEntity))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The node, or a page node with only its url when the node is only linked to.
+    pub async fn node(&self, id: &str) -> Result<Node> {
+        Ok(self
+            .store
+            .get_node(id)
+            .await?
+            .unwrap_or_else(|| Node::page(id, None)))
+    }
+
+    /// The nodes within `depth` edges of the node with the id.
+    pub async fn neighborhood(
+        &self,
+        id: &str,
+        depth: usize,
+        traversal: &Traversal,
+    ) -> Result<Neighborhood> {
+        let mut distances = HashMap::from([(id.to_string(), 0)]);
+        let mut order = vec![id.to_string()];
+        let mut edges = Vec::new();
+        let mut seen_edges = HashSet::new();
+        let mut truncated = false;
+        let mut frontier = vec![id.to_string()];
+        for distance in 1..=depth {
+            let mut next = Vec::new();
+            for current in &frontier {
+                for edge in self.edges_of(current, traversal).await? {
+                    let other = edge.other(current).to_string();
+                    if !distances.contains_key(&other) {
+                        if distances.len() >= traversal.max_nodes {
+                            truncated = true;
+                            continue;
+                        }
+                        distances.insert(other.clone(), distance);
+                        order.push(other.clone());
+                        next.push(other);
+                    }
+                    if seen_edges.insert(edge.clone()) {
+                        edges.push(edge);
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        let mut nodes = Vec::with_capacity(order.len());
+        for node_id in order {
+            let distance = distances[&node_id];
+            nodes.push((self.node(&node_id).await?, distance));
+        }
+        Ok(Neighborhood {
+            nodes,
+            edges,
+            truncated,
+        })
+    }
+
+    /// The shortest chain of at most `max_hops` edges from one node to another, None if there is none.
+    pub async fn shortest_path(
+        &self,
+        from: &str,
+        to: &str,
+        max_hops: usize,
+        traversal: &Traversal,
+    ) -> Result<Option<GraphPath>> {
+        //? A breadth first search, remembering the edge each node was first reached by to walk back along.
+        let mut reached_by: HashMap<String, Option<Edge>> =
+            HashMap::from([(from.to_string(), None)]);
+        let mut queue = VecDeque::from([(from.to_string(), 0)]);
+        while let Some((current, hops)) = queue.pop_front() {
+            if current == to {
+                break;
+            }
+            if hops == max_hops || reached_by.len() >= traversal.max_nodes {
+                continue;
+            }
+            for edge in self.edges_of(&current, traversal).await? {
+                let other = edge.other(&current).to_string();
+                if !reached_by.contains_key(&other) {
+                    reached_by.insert(other.clone(), Some(edge));
+                    queue.push_back((other, hops + 1));
+                }
+            }
+        }
+        if !reached_by.contains_key(to) {
+            return Ok(None);
+        }
+
+        let mut current = to.to_string();
+        let mut ids = vec![current.clone()];
+        let mut edges = Vec::new();
+        while let Some(Some(edge)) = reached_by.get(&current) {
+            current = edge.other(&current).to_string();
+            edges.push(edge.clone());
+            ids.push(current.clone());
+        }
+        ids.reverse();
+        edges.reverse();
+        let mut nodes = Vec::with_capacity(ids.len());
+        for id in ids {
+            nodes.push(self.node(&id).await?);
+        }
+        Ok(Some(GraphPath { nodes, edges }))
+    }
+
+    async fn edges_of(&self, id: &str, traversal: &Traversal) -> Result<Vec<Edge>> {
+        let mut edges = Vec::new();
+        if traversal.direction != Direction::Incoming {
+            edges.extend(self.store.edges_from(id).await?);
+        }
+        if traversal.direction != Direction::Outgoing {
+            edges.extend(self.store.edges_to(id).await?);
+        }
+        edges.retain(|edge| traversal.follows(edge));
+        Ok(edges)
+    }
+}
+
+/// Note that the page with the id has each word of the text.
+fn index_words(pages_by_word: &mut HashMap<String, HashSet<String>>, id: &str, text: &str) {
+    for (word, _) in words(text) {
+        pages_by_word
+            .entry(word.to_lowercase())
+            .or_default()
+            .insert(id.to_string());
+    }
+}
+
+fn host(url: &str) -> Option<String> {
+    url::Url::parse(url).ok()?.host_str().map(str::to_string)
+}
@@ -0,0 +1,10 @@
+mod entity;
+pub mod error;
+mod knowledge;
+mod model;
+mod store;
+
+pub use entity::EntityLinker;
+pub use knowledge::{GraphPath, KnowledgeGraph, Neighborhood, Traversal};
+pub use model::{Direction, Edge, EdgeKind, Node, NodeKind};
+pub use store::{GraphStore, MemoryGraphStore, SurrealGraphStore};
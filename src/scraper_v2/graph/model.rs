@@ -0,0 +1,194 @@
+use super::error::GraphError;
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// What a node of the graph stands for.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeKind {
+    /// A page that was scraped or that a scraped page links to. Its id is its url.
+    Page,
+    /// A category pages are filed under, eg a Wikipedia category. Its id is `category:<name>`.
+    Category,
+    /// A topic pages are tagged with, eg a BBC related topic. Its id is `topic:<name>`.
+    Topic,
+    /// A document a page cites as a source, eg a Wikipedia reference. Its id is its url.
+    Source,
+}
+
+/// A node of the knowledge graph.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Node {
+    pub id: String,
+    pub kind: NodeKind,
+    /// The title of the page or the name of the category or topic.
+    pub label: Option<String>,
+    /// The names of the entity the page is about, when it is the page for one (`ScrapableContent::entity_names`).
+    #[serde(default)]
+    pub names: Vec<String>,
+}
+
+impl Node {
+    pub fn page(url: impl Into<String>, title: Option<String>) -> Self {
+        Self {
+            id: url.into(),
+            kind: NodeKind::Page,
+            label: title,
+            names: Vec::new(),
+        }
+    }
+
+    pub fn category(name: &str) -> Self {
+        let name = name.replace('_', " ").trim().to_string();
+        Self {
+            id: Self::category_id(&name),
+            kind: NodeKind::Category,
+            label: Some(name),
+            names: Vec::new(),
+        }
+    }
+
+    pub fn topic(name: &str) -> Self {
+        let name = name.trim().to_string();
+        Self {
+            id: Self::topic_id(&name),
+            kind: NodeKind::Topic,
+            label: Some(name),
+            names: Vec::new(),
+        }
+    }
+
+    pub fn source(url: impl Into<String>) -> Self {
+        Self {
+            id: url.into(),
+            kind: NodeKind::Source,
+            label: None,
+            names: Vec::new(),
+        }
+    }
+
+    /// The id of the node for a category, eg "category:Cities in England".
+    pub fn category_id(name: &str) -> String {
+        format!("category:{}", name.replace('_', " ").trim())
+    }
+
+    /// The id of the node for a topic, eg "topic:Climate change".
+    pub fn topic_id(name: &str) -> String {
+        format!("topic:{}", name.trim())
+    }
+
+    /// The label, or the id for nodes without one.
+    pub fn name(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.id)
+    }
+}
+
+/// How two nodes are related.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum EdgeKind {
+    /// A page links to another page.
+    LinksTo,
+    /// A page is filed under a category.
+    InCategory,
+    /// A page is tagged with a topic.
+    RelatedTopic,
+    /// A page cites a source.
+    Cites,
+    /// A page names an entity that has its own page, eg a BBC article naming a person with a Wikipedia article.
+    MentionsEntity,
+}
+
+impl EdgeKind {
+    pub const ALL: [EdgeKind; 5] = [
+        EdgeKind::LinksTo,
+        EdgeKind::InCategory,
+        EdgeKind::RelatedTopic,
+        EdgeKind::Cites,
+        EdgeKind::MentionsEntity,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::LinksTo => "links-to",
+            EdgeKind::InCategory => "in-category",
+            EdgeKind::RelatedTopic => "related-topic",
+            EdgeKind::Cites => "cites",
+            EdgeKind::MentionsEntity => "mentions-entity",
+        }
+    }
+}
+
+impl std::fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EdgeKind {
+    type Err = GraphError;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        EdgeKind::ALL
+            .into_iter()
+            .find(|edge_kind| edge_kind.as_str() == kind.trim().to_lowercase().replace('_', "-"))
+            .ok_or_else(|| GraphError::UnknownEdgeKind {
+                kind: kind.to_string(),
+            })
+    }
+}
+
+/// A typed edge from one node to another.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: EdgeKind,
+}
+
+impl Edge {
+    pub fn new(from: impl Into<String>, to: impl Into<String>, kind: EdgeKind) -> Self {
+        Self {
+            from: from.into(),
+            to: to.into(),
+            kind,
+        }
+    }
+
+    /// The node at the other end of the edge from `id`.
+    pub fn other(&self, id: &str) -> &str {
+        if self.from == id {
+            &self.to
+        } else {
+            &self.from
+        }
+    }
+}
+
+/// Which edges of a node to follow.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Direction {
+    /// The edges going out of the node, eg the pages it links to.
+    Outgoing,
+    /// The edges coming into the node, eg the pages that link to it.
+    Incoming,
+    #[default]
+    Both,
+}
+
+impl FromStr for Direction {
+    type Err = GraphError;
+
+    fn from_str(direction: &str) -> Result<Self, Self::Err> {
+        match direction.trim().to_lowercase().as_str() {
+            "out" | "outgoing" => Ok(Direction::Outgoing),
+            "in" | "incoming" => Ok(Direction::Incoming),
+            "both" => Ok(Direction::Both),
+            _ => Err(GraphError::UnknownDirection {
+                direction: direction.to_string(),
+            }),
+        }
+    }
+}
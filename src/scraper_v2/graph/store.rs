@@ -0,0 +1,475 @@
+use super::{Edge, EdgeKind, Node, NodeKind};
+use crate::Result;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::path::Path;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+use tokio::sync::Mutex;
+
+/// The SurrealDB table nodes are stored in, one record per node id.
+const NODE_TABLE: &str = "graph_node";
+/// The SurrealDB relation table edges are stored in, so they can also be walked with SurrealQL (`->graph_edge->graph_node`).
+const EDGE_TABLE: &str = "graph_edge";
+/// The SurrealDB table the texts of pages are kept in, one record per node id.
+const TEXT_TABLE: &str = "graph_text";
+/// The SurrealDB table of alias urls, one record per alias.
+const ALIAS_TABLE: &str = "graph_alias";
+
+/// Storage for the knowledge graph: nodes, and the typed edges between them looked up from either end.
+#[async_trait]
+pub trait GraphStore: Debug + Send + Sync {
+    async fn get_node(&self, id: &str) -> Result<Option<Node>>;
+    /// Add the node, replacing any with the same id.
+    async fn put_node(&self, node: Node) -> Result<()>;
+    /// Add the node unless there is one with the same id already, eg a category every page in it refers to.
+    async fn add_node(&self, node: Node) -> Result<()>;
+    /// Replace the edges going out of a node, eg the links of a page that was scraped again.
+    async fn put_edges(&self, from: &str, edges: Vec<Edge>) -> Result<()>;
+    async fn edges_from(&self, id: &str) -> Result<Vec<Edge>>;
+    async fn edges_to(&self, id: &str) -> Result<Vec<Edge>>;
+    /// Every node with entity names (`Node::names`), to find mentions of them in other pages.
+    async fn entities(&self) -> Result<Vec<Node>>;
+    /// Add an edge to those going out of its node, unless it is there already, eg a mention of an entity that became known after the page was added.
+    async fn add_edge(&self, edge: Edge) -> Result<()>;
+    /// Keep the text of a page, replacing any it had, so it can be searched for entities that become known later.
+    async fn put_text(&self, id: &str, text: String) -> Result<()>;
+    async fn get_text(&self, id: &str) -> Result<Option<String>>;
+    /// The ids of the pages with a text kept, along with it.
+    async fn texts(&self) -> Result<Vec<(String, String)>>;
+    /// Record that `alias` (eg a redirect) is another url of the page `canonical`.
+    async fn put_alias(&self, alias: &str, canonical: &str) -> Result<()>;
+    /// Every alias, with the page it is an alias of.
+    async fn aliases(&self) -> Result<Vec<(String, String)>>;
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Graph {
+    nodes: HashMap<String, Node>,
+    /// The edges going out of each node.
+    edges: HashMap<String, Vec<Edge>>,
+    /// The nodes with edges into each node. Rebuilt when the graph is loaded.
+    #[serde(skip)]
+    incoming: HashMap<String, HashSet<String>>,
+    /// The text of each page, to find entities in.
+    #[serde(default)]
+    texts: HashMap<String, String>,
+    /// The page each alias url is another url of.
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+impl Graph {
+    fn index_incoming(&mut self) {
+        self.incoming.clear();
+        for (from, edges) in &self.edges {
+            for edge in edges {
+                self.incoming
+                    .entry(edge.to.clone())
+                    .or_default()
+                    .insert(from.clone());
+            }
+        }
+    }
+}
+
+/// A GraphStore held in memory that can be saved to and loaded from a JSON file between runs.
+#[derive(Debug, Default)]
+pub struct MemoryGraphStore {
+    graph: Mutex<Graph>,
+}
+
+impl MemoryGraphStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the graph from a JSON file. A missing file gives an empty graph.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut graph: Graph = match tokio::fs::read_to_string(path).await {
+            Ok(json) => serde_json::from_str(&json).map_err(crate::Error::custom)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Graph::default(),
+            Err(e) => return Err(e.into()),
+        };
+        graph.index_incoming();
+        Ok(Self {
+            graph: Mutex::new(graph),
+        })
+    }
+
+    /// Save the graph to a JSON file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = {
+            let graph = self.graph.lock().await;
+            serde_json::to_string_pretty(&*graph).map_err(crate::Error::custom)?
+        };
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+
+    /// The number of nodes that were put or added, not counting the pages that are only linked to.
+    pub async fn node_count(&self) -> usize {
+        self.graph.lock().await.nodes.len()
+    }
+
+    pub async fn edge_count(&self) -> usize {
+        self.graph.lock().await.edges.values().map(Vec::len).sum()
+    }
+}
+
+#[async_trait]
+impl GraphStore for MemoryGraphStore {
+    async fn get_node(&self, id: &str) -> Result<Option<Node>> {
+        Ok(self.graph.lock().await.nodes.get(id).cloned())
+    }
+
+    async fn put_node(&self, node: Node) -> Result<()> {
+        self.graph.lock().await.nodes.insert(node.id.clone(), node);
+        Ok(())
+    }
+
+    async fn add_node(&self, node: Node) -> Result<()> {
+        self.graph
+            .lock()
+            .await
+            .nodes
+            .entry(node.id.clone())
+            .or_insert(node);
+        Ok(())
+    }
+
+    async fn put_edges(&self, from: &str, edges: Vec<Edge>) -> Result<()> {
+        let mut graph = self.graph.lock().await;
+        let previous = graph.edges.remove(from).unwrap_or_default();
+        for edge in previous {
+            if let Some(sources) = graph.incoming.get_mut(&edge.to) {
+                sources.remove(from);
+            }
+        }
+        for edge in &edges {
+            graph
+                .incoming
+                .entry(edge.to.clone())
+                .or_default()
+                .insert(from.to_string());
+        }
+        if !edges.is_empty() {
+            graph.edges.insert(from.to_string(), edges);
+        }
+        Ok(())
+    }
+
+    async fn edges_from(&self, id: &str) -> Result<Vec<Edge>> {
+        Ok(self
+            .graph
+            .lock()
+            .await
+            .edges
+            .get(id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn edges_to(&self, id: &str) -> Result<Vec<Edge>> {
+        let graph = self.graph.lock().await;
+        let Some(sources) = graph.incoming.get(id) else {
+            return Ok(Vec::new());
+        };
+        Ok(sources
+            .iter()
+            .filter_map(|source| graph.edges.get(source))
+            .flatten()
+            .filter(|edge| edge.to == id)
+            .cloned()
+            .collect())
+    }
+
+    async fn entities(&self) -> Result<Vec<Node>> {
+        Ok(self
+            .graph
+            .lock()
+            .await
+            .nodes
+            .values()
+            .filter(|node| !node.names.is_empty())
+            .cloned()
+            .collect())
+    }
+
+    async fn add_edge(&self, edge: Edge) -> Result<()> {
+        let mut graph = self.graph.lock().await;
+        let edges = graph.edges.entry(edge.from.clone()).or_default();
+        if edges.contains(&edge) {
+            return Ok(());
+        }
+        edges.push(edge.clone());
+        graph.incoming.entry(edge.to).or_default().insert(edge.from);
+        Ok(())
+    }
+
+    async fn put_text(&self, id: &str, text: String) -> Result<()> {
+        self.graph.lock().await.texts.insert(id.to_string(), text);
+        Ok(())
+    }
+
+    async fn get_text(&self, id: &str) -> Result<Option<String>> {
+        Ok(self.graph.lock().await.texts.get(id).cloned())
+    }
+
+    async fn texts(&self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .graph
+            .lock()
+            .await
+            .texts
+            .iter()
+            .map(|(id, text)| (id.clone(), text.clone()))
+            .collect())
+    }
+
+    async fn put_alias(&self, alias: &str, canonical: &str) -> Result<()> {
+        self.graph
+            .lock()
+            .await
+            .aliases
+            .insert(alias.to_string(), canonical.to_string());
+        Ok(())
+    }
+
+    async fn aliases(&self) -> Result<Vec<(String, String)>> {
+        Ok(self
+            .graph
+            .lock()
+            .await
+            .aliases
+            .iter()
+            .map(|(alias, canonical)| (alias.clone(), canonical.clone()))
+            .collect())
+    }
+}
+
+/// The fields of a node record. The node's id is the record's id.
+#[derive(Debug, Serialize)]
+struct NodeRecord {
+    kind: NodeKind,
+    label: Option<String>,
+    names: Vec<String>,
+}
+
+//? `from` is a keyword in SurrealQL, so the ends of an edge are read under other names.
+#[derive(Debug, Deserialize)]
+struct EdgeRow {
+    source: String,
+    target: String,
+    kind: EdgeKind,
+}
+
+impl From<EdgeRow> for Edge {
+    fn from(row: EdgeRow) -> Self {
+        Edge::new(row.source, row.target, row.kind)
+    }
+}
+
+/// Stores the graph in SurrealDB: nodes in the `graph_node` table under their ids, and edges as `graph_edge` relations between them.
+#[derive(Debug, Clone)]
+pub struct SurrealGraphStore {
+    db: Surreal<Client>,
+}
+
+impl SurrealGraphStore {
+    pub fn new(db: &Surreal<Client>) -> Self {
+        Self { db: db.clone() }
+    }
+
+    async fn edges_where(&self, end: &str, id: &str) -> Result<Vec<Edge>> {
+        let mut response = self
+            .db
+            .query(format!(
+                "SELECT meta::id(in) AS source, meta::id(out) AS target, kind FROM {EDGE_TABLE} WHERE {end} = type::thing($nodes, $id)"
+            ))
+            .bind(("nodes", NODE_TABLE))
+            .bind(("id", id.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        let rows: Vec<EdgeRow> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(rows.into_iter().map(Edge::from).collect())
+    }
+}
+
+#[async_trait]
+impl GraphStore for SurrealGraphStore {
+    async fn get_node(&self, id: &str) -> Result<Option<Node>> {
+        let mut response = self
+            .db
+            .query("SELECT meta::id(id) AS id, kind, label, names FROM type::thing($nodes, $id)")
+            .bind(("nodes", NODE_TABLE))
+            .bind(("id", id.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        let nodes: Vec<Node> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(nodes.into_iter().next())
+    }
+
+    async fn put_node(&self, node: Node) -> Result<()> {
+        self.db
+            .query("UPDATE type::thing($nodes, $id) CONTENT $record")
+            .bind(("nodes", NODE_TABLE))
+            .bind(("id", node.id))
+            .bind((
+                "record",
+                NodeRecord {
+                    kind: node.kind,
+                    label: node.label,
+                    names: node.names,
+                },
+            ))
+            .await
+            .map_err(crate::Error::custom)?;
+        Ok(())
+    }
+
+    async fn add_node(&self, node: Node) -> Result<()> {
+        //? Updating a record that doesn't exist creates it, and `??` keeps the fields of one that does.
+        self.db
+            .query("UPDATE type::thing($nodes, $id) SET kind = kind ?? $kind, label = label ?? $label, names = names ?? $names")
+            .bind(("nodes", NODE_TABLE))
+            .bind(("id", node.id))
+            .bind(("kind", node.kind))
+            .bind(("label", node.label))
+            .bind(("names", node.names))
+            .await
+            .map_err(crate::Error::custom)?;
+        Ok(())
+    }
+
+    async fn put_edges(&self, from: &str, edges: Vec<Edge>) -> Result<()> {
+        self.db
+            .query(format!(
+                "DELETE {EDGE_TABLE} WHERE in = type::thing($nodes, $from)"
+            ))
+            .bind(("nodes", NODE_TABLE))
+            .bind(("from", from.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        for edge in edges {
+            self.db
+                .query(format!(
+                    "LET $source = type::thing($nodes, $from); LET $target = type::thing($nodes, $to); RELATE $source->{EDGE_TABLE}->$target SET kind = $kind"
+                ))
+                .bind(("nodes", NODE_TABLE))
+                .bind(("from", from.to_string()))
+                .bind(("to", edge.to))
+                .bind(("kind", edge.kind))
+                .await
+                .map_err(crate::Error::custom)?;
+        }
+        Ok(())
+    }
+
+    async fn edges_from(&self, id: &str) -> Result<Vec<Edge>> {
+        self.edges_where("in", id).await
+    }
+
+    async fn edges_to(&self, id: &str) -> Result<Vec<Edge>> {
+        self.edges_where("out", id).await
+    }
+
+    async fn entities(&self) -> Result<Vec<Node>> {
+        let mut response = self
+            .db
+            .query("SELECT meta::id(id) AS id, kind, label, names FROM type::table($nodes) WHERE array::len(names) > 0")
+            .bind(("nodes", NODE_TABLE))
+            .await
+            .map_err(crate::Error::custom)?;
+        response.take(0).map_err(crate::Error::custom)
+    }
+
+    async fn add_edge(&self, edge: Edge) -> Result<()> {
+        //? Deleting the same edge first keeps a single relation of each kind between two nodes.
+        self.db
+            .query(format!(
+                "LET $source = type::thing($nodes, $from); LET $target = type::thing($nodes, $to); \
+                DELETE {EDGE_TABLE} WHERE in = $source AND out = $target AND kind = $kind; \
+                RELATE $source->{EDGE_TABLE}->$target SET kind = $kind"
+            ))
+            .bind(("nodes", NODE_TABLE))
+            .bind(("from", edge.from))
+            .bind(("to", edge.to))
+            .bind(("kind", edge.kind))
+            .await
+            .map_err(crate::Error::custom)?;
+        Ok(())
+    }
+
+    async fn put_text(&self, id: &str, text: String) -> Result<()> {
+        self.db
+            .query("UPDATE type::thing($texts, $id) SET text = $text")
+            .bind(("texts", TEXT_TABLE))
+            .bind(("id", id.to_string()))
+            .bind(("text", text))
+            .await
+            .map_err(crate::Error::custom)?;
+        Ok(())
+    }
+
+    async fn get_text(&self, id: &str) -> Result<Option<String>> {
+        let mut response = self
+            .db
+            .query("SELECT VALUE text FROM type::thing($texts, $id)")
+            .bind(("texts", TEXT_TABLE))
+            .bind(("id", id.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        let texts: Vec<String> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(texts.into_iter().next())
+    }
+
+    async fn texts(&self) -> Result<Vec<(String, String)>> {
+        #[derive(Debug, Deserialize)]
+        struct TextRow {
+            id: String,
+            text: String,
+        }
+        let mut response = self
+            .db
+            .query("SELECT meta::id(id) AS id, text FROM type::table($texts)")
+            .bind(("texts", TEXT_TABLE))
+            .await
+            .map_err(crate::Error::custom)?;
+        let rows: Vec<TextRow> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(rows.into_iter().map(|row| (row.id, row.text)).collect())
+    }
+
+    async fn put_alias(&self, alias: &str, canonical: &str) -> Result<()> {
+        self.db
+            .query("UPDATE type::thing($aliases, $alias) SET canonical = $canonical")
+            .bind(("aliases", ALIAS_TABLE))
+            .bind(("alias", alias.to_string()))
+            .bind(("canonical", canonical.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        Ok(())
+    }
+
+    async fn aliases(&self) -> Result<Vec<(String, String)>> {
+        #[derive(Debug, Deserialize)]
+        struct AliasRow {
+            alias: String,
+            canonical: String,
+        }
+        let mut response = self
+            .db
+            .query("SELECT meta::id(id) AS alias, canonical FROM type::table($aliases)")
+            .bind(("aliases", ALIAS_TABLE))
+            .await
+            .map_err(crate::Error::custom)?;
+        let rows: Vec<AliasRow> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.alias, row.canonical))
+            .collect())
+    }
+}
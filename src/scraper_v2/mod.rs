@@ -1,5 +1,7 @@
 pub mod common;
 mod error;
+pub mod graph;
+pub mod search;
 pub mod sites;
 
 pub use self::error::{Error, Result};
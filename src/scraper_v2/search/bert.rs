@@ -0,0 +1,131 @@
+use super::embed::normalize;
+use super::error::SearchError;
+use super::Embedder;
+use crate::Result;
+
+use candle_core::{Device, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+use std::fmt::Debug;
+use std::path::Path;
+use tokenizers::{PaddingParams, PaddingStrategy, Tokenizer, TruncationParams};
+
+/// A sentence embedding model with a BERT architecture (eg all-MiniLM-L6-v2 or bge-small-en) run on the CPU with candle.
+///
+/// The model is loaded from a local directory holding the `config.json`, `tokenizer.json` and `model.safetensors` files of the model's repository,
+/// nothing is downloaded. A text's vector is the mean of its token embeddings, normalized to unit length, as sentence-transformers does it.
+/// Texts longer than the model's context are truncated, so chunks should be made no longer than that (`Chunker::with_max_tokens`).
+pub struct BertEmbedder {
+    model: BertModel,
+    tokenizer: Tokenizer,
+    model_id: String,
+    dimensions: usize,
+}
+
+impl Debug for BertEmbedder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BertEmbedder")
+            .field("model_id", &self.model_id)
+            .field("dimensions", &self.dimensions)
+            .finish()
+    }
+}
+
+impl BertEmbedder {
+    /// Load the model in `dir`. Its model id is the name of the directory.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let config = std::fs::read_to_string(dir.join("config.json"))?;
+        let config: Config = serde_json::from_str(&config).map_err(model_error)?;
+
+        let mut tokenizer =
+            Tokenizer::from_file(dir.join("tokenizer.json")).map_err(model_error)?;
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: PaddingStrategy::BatchLongest,
+            ..Default::default()
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.max_position_embeddings,
+                ..Default::default()
+            }))
+            .map_err(model_error)?;
+
+        //? The weights are read into memory rather than memory mapped, which would need unsafe code.
+        let weights = std::fs::read(dir.join("model.safetensors"))?;
+        let var_builder = VarBuilder::from_buffered_safetensors(weights, DTYPE, &Device::Cpu)
+            .map_err(model_error)?;
+        let model = BertModel::load(var_builder, &config).map_err(model_error)?;
+
+        let model_id = dir
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| dir.display().to_string());
+        Ok(Self {
+            model,
+            tokenizer,
+            model_id,
+            dimensions: config.hidden_size,
+        })
+    }
+
+    /// Name the model something other than its directory, eg to tell two versions of it apart in the cache.
+    pub fn with_model_id(mut self, model_id: impl Into<String>) -> Self {
+        self.model_id = model_id.into();
+        self
+    }
+
+    fn embed_batch(&self, texts: &[String]) -> candle_core::Result<Vec<Vec<f32>>> {
+        let encodings = self
+            .tokenizer
+            .encode_batch(texts.to_vec(), true)
+            .map_err(candle_core::Error::msg)?;
+        let device = &self.model.device;
+        let input_ids = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_ids(), device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let attention_mask = encodings
+            .iter()
+            .map(|encoding| Tensor::new(encoding.get_attention_mask(), device))
+            .collect::<candle_core::Result<Vec<_>>>()?;
+        let input_ids = Tensor::stack(&input_ids, 0)?;
+        let attention_mask = Tensor::stack(&attention_mask, 0)?;
+        let token_type_ids = input_ids.zeros_like()?;
+
+        let output = self
+            .model
+            .forward(&input_ids, &token_type_ids, Some(&attention_mask))?;
+        //? Mean pooling over the tokens that aren't padding.
+        let mask = attention_mask.to_dtype(DTYPE)?.unsqueeze(2)?;
+        let summed = output.broadcast_mul(&mask)?.sum(1)?;
+        let counts = mask.sum(1)?;
+        summed.broadcast_div(&counts)?.to_vec2::<f32>()
+    }
+}
+
+impl Embedder for BertEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut vectors = self.embed_batch(texts).map_err(model_error)?;
+        vectors.iter_mut().for_each(|vector| normalize(vector));
+        Ok(vectors)
+    }
+}
+
+fn model_error(error: impl std::fmt::Display) -> crate::Error {
+    SearchError::Model {
+        reason: error.to_string(),
+    }
+    .into()
+}
@@ -0,0 +1,569 @@
+use super::error::SearchError;
+use crate::common::{fnv1a, Chunk};
+use crate::Result;
+
+use async_trait::async_trait;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::path::Path;
+use std::sync::Arc;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+use tokio::sync::Mutex;
+
+/// The SurrealDB table embeddings are stored in, one record per model and chunk id.
+const EMBEDDING_TABLE: &str = "chunk_embedding";
+
+/// Turns text into vectors that are close together when the texts are close in meaning.
+//? Embedding is CPU bound, so the trait is sync and the pipeline runs it with `spawn_blocking`.
+pub trait Embedder: Debug + Send + Sync {
+    /// Names the model and its settings, so vectors made by different models are never mixed up in the cache.
+    fn model_id(&self) -> &str;
+    /// The length of the vectors.
+    fn dimensions(&self) -> usize;
+    /// Embed a batch of texts, one vector per text in the same order.
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+}
+
+/// An embedder that hashes the words and word pairs of the text into a fixed number of buckets, with no model at all.
+/// The same text always gets the same vector, on any machine, so it is meant for tests and for trying out a pipeline.
+/// Texts sharing words are close, but it knows nothing about meaning.
+#[derive(Debug, Clone)]
+pub struct HashingEmbedder {
+    dimensions: usize,
+    model_id: String,
+}
+
+impl Default for HashingEmbedder {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl HashingEmbedder {
+    pub fn new(dimensions: usize) -> Self {
+        let dimensions = dimensions.max(1);
+        Self {
+            dimensions,
+            model_id: format!("hashing-{dimensions}"),
+        }
+    }
+
+    fn embed_text(&self, text: &str) -> Vec<f32> {
+        let words = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect_vec();
+        let pairs = words
+            .iter()
+            .tuple_windows()
+            .map(|(first, second)| format!("{first} {second}"));
+
+        let mut vector = vec![0.0; self.dimensions];
+        for feature in words.iter().cloned().chain(pairs) {
+            let hash = fnv1a(feature.as_bytes());
+            //? The top bit picks the sign so collisions tend to cancel out rather than pile up.
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            vector[(hash % self.dimensions as u64) as usize] += sign;
+        }
+        normalize(&mut vector);
+        vector
+    }
+}
+
+impl Embedder for HashingEmbedder {
+    fn model_id(&self) -> &str {
+        &self.model_id
+    }
+
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_text(text)).collect())
+    }
+}
+
+/// Scale a vector to unit length, so the dot product of two vectors is their cosine similarity. A zero vector is left as it is.
+pub(crate) fn normalize(vector: &mut [f32]) {
+    let norm = vector.iter().map(|value| value * value).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|value| *value /= norm);
+    }
+}
+
+/// A chunk along with the vector a model made for its text.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Embedding {
+    pub chunk: Chunk,
+    pub model: String,
+    pub vector: Vec<f32>,
+}
+
+/// Storage for embeddings. It doubles as the cache: a chunk whose text the model has already embedded (under any url) reuses that vector.
+#[async_trait]
+pub trait EmbeddingStore: Debug + Send + Sync {
+    /// A vector the model already made for text with this content hash (`Chunk::content_hash`).
+    async fn cached_vector(&self, model: &str, content_hash: &str) -> Result<Option<Vec<f32>>>;
+    /// Replace the embeddings the model made for the chunks of the page at `url`, so the chunks the page no longer has are dropped. Embeddings made by other models are kept.
+    async fn put_page_embeddings(
+        &self,
+        url: &str,
+        model: &str,
+        embeddings: Vec<Embedding>,
+    ) -> Result<()>;
+    /// Every stored embedding made by the model, eg to build a search index from.
+    async fn embeddings(&self, model: &str) -> Result<Vec<Embedding>>;
+    /// The models of the stored embeddings, the one with the most embeddings first.
+    async fn models(&self) -> Result<Vec<String>>;
+}
+
+/// Order models by how many embeddings they made, most first.
+fn by_count(counts: impl IntoIterator<Item = (String, usize)>) -> Vec<String> {
+    counts
+        .into_iter()
+        .sorted_by(|(model_a, count_a), (model_b, count_b)| {
+            count_b.cmp(count_a).then(model_a.cmp(model_b))
+        })
+        .map(|(model, _)| model)
+        .collect()
+}
+
+/// The model and chunk id an embedding is stored under.
+type EmbeddingKey = (String, String);
+
+#[derive(Debug, Default)]
+struct Embeddings {
+    by_chunk: HashMap<EmbeddingKey, Embedding>,
+    /// The key of an embedding of each model and content hash.
+    by_hash: HashMap<(String, String), EmbeddingKey>,
+}
+
+impl Embeddings {
+    fn insert(&mut self, embedding: Embedding) {
+        let key = (embedding.model.clone(), embedding.chunk.id.clone());
+        self.by_hash.insert(
+            (
+                embedding.model.clone(),
+                embedding.chunk.content_hash.clone(),
+            ),
+            key.clone(),
+        );
+        self.by_chunk.insert(key, embedding);
+    }
+
+    /// Remove the embeddings the model made for the chunks of a page.
+    fn remove_page(&mut self, url: &str, model: &str) {
+        self.by_chunk.retain(|(embedding_model, _), embedding| {
+            embedding_model != model || embedding.chunk.url != url
+        });
+        let by_chunk = &self.by_chunk;
+        self.by_hash.retain(|_, key| by_chunk.contains_key(key));
+    }
+}
+
+/// An EmbeddingStore held in memory that can be saved to and loaded from a JSON file between runs.
+#[derive(Debug, Default)]
+pub struct MemoryEmbeddingStore {
+    embeddings: Mutex<Embeddings>,
+}
+
+impl MemoryEmbeddingStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the embeddings from a JSON file. A missing file gives an empty store.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let stored: Vec<Embedding> = match tokio::fs::read_to_string(path).await {
+            Ok(json) => serde_json::from_str(&json).map_err(crate::Error::custom)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut embeddings = Embeddings::default();
+        stored
+            .into_iter()
+            .for_each(|embedding| embeddings.insert(embedding));
+        Ok(Self {
+            embeddings: Mutex::new(embeddings),
+        })
+    }
+
+    /// Save the embeddings to a JSON file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = {
+            let embeddings = self.embeddings.lock().await;
+            serde_json::to_string(&embeddings.by_chunk.values().collect_vec())
+                .map_err(crate::Error::custom)?
+        };
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EmbeddingStore for MemoryEmbeddingStore {
+    async fn cached_vector(&self, model: &str, content_hash: &str) -> Result<Option<Vec<f32>>> {
+        let embeddings = self.embeddings.lock().await;
+        Ok(embeddings
+            .by_hash
+            .get(&(model.to_string(), content_hash.to_string()))
+            .and_then(|key| embeddings.by_chunk.get(key))
+            .map(|embedding| embedding.vector.clone()))
+    }
+
+    async fn put_page_embeddings(
+        &self,
+        url: &str,
+        model: &str,
+        embeddings: Vec<Embedding>,
+    ) -> Result<()> {
+        let mut stored = self.embeddings.lock().await;
+        stored.remove_page(url, model);
+        embeddings
+            .into_iter()
+            .for_each(|embedding| stored.insert(embedding));
+        Ok(())
+    }
+
+    async fn embeddings(&self, model: &str) -> Result<Vec<Embedding>> {
+        Ok(self
+            .embeddings
+            .lock()
+            .await
+            .by_chunk
+            .values()
+            .filter(|embedding| embedding.model == model)
+            .cloned()
+            .collect())
+    }
+
+    async fn models(&self) -> Result<Vec<String>> {
+        Ok(by_count(
+            self.embeddings
+                .lock()
+                .await
+                .by_chunk
+                .values()
+                .counts_by(|embedding| embedding.model.clone()),
+        ))
+    }
+}
+
+/// Stores embeddings in SurrealDB, in the `chunk_embedding` table under their chunk ids.
+#[derive(Debug, Clone)]
+pub struct SurrealEmbeddingStore {
+    db: Surreal<Client>,
+}
+
+impl SurrealEmbeddingStore {
+    pub fn new(db: &Surreal<Client>) -> Self {
+        Self { db: db.clone() }
+    }
+}
+
+#[async_trait]
+impl EmbeddingStore for SurrealEmbeddingStore {
+    async fn cached_vector(&self, model: &str, content_hash: &str) -> Result<Option<Vec<f32>>> {
+        let mut response = self
+            .db
+            .query("SELECT VALUE vector FROM type::table($table) WHERE model = $model AND chunk.content_hash = $content_hash LIMIT 1")
+            .bind(("table", EMBEDDING_TABLE))
+            .bind(("model", model.to_string()))
+            .bind(("content_hash", content_hash.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        let vectors: Vec<Vec<f32>> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(vectors.into_iter().next())
+    }
+
+    async fn put_page_embeddings(
+        &self,
+        url: &str,
+        model: &str,
+        embeddings: Vec<Embedding>,
+    ) -> Result<()> {
+        self.db
+            .query("DELETE type::table($table) WHERE model = $model AND chunk.url = $url")
+            .bind(("table", EMBEDDING_TABLE))
+            .bind(("model", model.to_string()))
+            .bind(("url", url.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        for embedding in embeddings {
+            //? The record id is the model and the chunk id, so each model keeps its own vector for a chunk.
+            self.db
+                .query("UPDATE type::thing($table, [$model, $id]) CONTENT $record")
+                .bind(("table", EMBEDDING_TABLE))
+                .bind(("model", embedding.model.clone()))
+                .bind(("id", embedding.chunk.id.clone()))
+                .bind(("record", embedding))
+                .await
+                .map_err(crate::Error::custom)?;
+        }
+        Ok(())
+    }
+
+    async fn embeddings(&self, model: &str) -> Result<Vec<Embedding>> {
+        let mut response = self
+            .db
+            .query("SELECT * FROM type::table($table) WHERE model = $model")
+            .bind(("table", EMBEDDING_TABLE))
+            .bind(("model", model.to_string()))
+            .await
+            .map_err(crate::Error::custom)?;
+        response.take(0).map_err(crate::Error::custom)
+    }
+
+    async fn models(&self) -> Result<Vec<String>> {
+        #[derive(Debug, Deserialize)]
+        struct ModelCount {
+            model: String,
+            count: usize,
+        }
+        let mut response = self
+            .db
+            .query("SELECT model, count() AS count FROM type::table($table) GROUP BY model")
+            .bind(("table", EMBEDDING_TABLE))
+            .await
+            .map_err(crate::Error::custom)?;
+        let counts: Vec<ModelCount> = response.take(0).map_err(crate::Error::custom)?;
+        Ok(by_count(
+            counts.into_iter().map(|count| (count.model, count.count)),
+        ))
+    }
+}
+
+/// Embeds chunks in batches and puts the embeddings in a store, reusing the vectors of chunks whose text was embedded before.
+///
+/// ```ignore
+/// let pipeline = EmbeddingPipeline::new(Arc::new(HashingEmbedder::default()), Arc::new(MemoryEmbeddingStore::new()));
+/// let handler = PageHandler::new()
+///     .with_chunks(Chunker::new(), chunk_store)
+///     .with_embeddings(pipeline);
+/// ```
+#[derive(Debug, Clone)]
+pub struct EmbeddingPipeline {
+    embedder: Arc<dyn Embedder>,
+    store: Arc<dyn EmbeddingStore>,
+    batch_size: usize,
+}
+
+impl EmbeddingPipeline {
+    /// Embeds 32 texts at a time.
+    pub fn new(embedder: Arc<dyn Embedder>, store: Arc<dyn EmbeddingStore>) -> Self {
+        Self {
+            embedder,
+            store,
+            batch_size: 32,
+        }
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    pub fn embedder(&self) -> &Arc<dyn Embedder> {
+        &self.embedder
+    }
+
+    pub fn store(&self) -> &Arc<dyn EmbeddingStore> {
+        &self.store
+    }
+
+    /// Embed the chunks of the page at `url` and store the embeddings in place of the ones the page had. Returns how many texts had to be embedded, the rest were in the cache.
+    pub async fn embed_chunks(&self, url: &str, chunks: Vec<Chunk>) -> Result<usize> {
+        let model = self.embedder.model_id().to_string();
+        let mut vectors = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut missing = Vec::new();
+        for chunk in &chunks {
+            if !seen.insert(chunk.content_hash.as_str()) {
+                continue;
+            }
+            match self
+                .store
+                .cached_vector(&model, &chunk.content_hash)
+                .await?
+            {
+                Some(vector) => {
+                    vectors.insert(chunk.content_hash.clone(), vector);
+                }
+                None => missing.push(chunk),
+            }
+        }
+
+        for batch in missing.chunks(self.batch_size) {
+            let texts = batch.iter().map(|chunk| chunk.text.clone()).collect_vec();
+            let embedder = Arc::clone(&self.embedder);
+            let batch_vectors = tokio::task::spawn_blocking(move || embedder.embed(&texts))
+                .await
+                .map_err(crate::Error::custom)??;
+            if batch_vectors.len() != batch.len() {
+                return Err(crate::Error::custom(format!(
+                    "Embedder returned {} vectors for {} texts",
+                    batch_vectors.len(),
+                    batch.len()
+                )));
+            }
+            for (chunk, vector) in batch.iter().zip(batch_vectors) {
+                if vector.len() != self.embedder.dimensions() {
+                    return Err(SearchError::DimensionMismatch {
+                        expected: self.embedder.dimensions(),
+                        found: vector.len(),
+                    }
+                    .into());
+                }
+                vectors.insert(chunk.content_hash.clone(), vector);
+            }
+        }
+        let embedded = missing.len();
+
+        let embeddings = chunks
+            .into_iter()
+            .filter_map(|chunk| {
+                let vector = vectors.get(&chunk.content_hash)?.clone();
+                Some(Embedding {
+                    chunk,
+                    model: model.clone(),
+                    vector,
+                })
+            })
+            .collect();
+        self.store
+            .put_page_embeddings(url, &model, embeddings)
+            .await?;
+        Ok(embedded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::content_hash;
+
+    fn dot(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(a, b)| a * b).sum()
+    }
+
+    fn embed(embedder: &HashingEmbedder, text: &str) -> Vec<f32> {
+        embedder.embed(&[text.to_string()]).unwrap().remove(0)
+    }
+
+    fn chunk(url: &str, index: usize, text: &str) -> Chunk {
+        Chunk {
+            id: format!("{url}#{index}"),
+            url: url.to_string(),
+            title: None,
+            section_path: Vec::new(),
+            index,
+            text: text.to_string(),
+            token_count: text.split_whitespace().count(),
+            content_hash: content_hash(text),
+            published: None,
+            categories: Vec::new(),
+            topics: Vec::new(),
+        }
+    }
+
+    fn chunk_ids(embeddings: &[Embedding]) -> Vec<&str> {
+        embeddings
+            .iter()
+            .map(|embedding| embedding.chunk.id.as_str())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn hashing_embeddings_are_unit_vectors_of_the_given_length() {
+        let embedder = HashingEmbedder::new(64);
+        assert_eq!(embedder.model_id(), "hashing-64");
+        let vector = embed(&embedder, "The river flooded the town");
+        assert_eq!(vector.len(), 64);
+        assert!((dot(&vector, &vector) - 1.0).abs() < 1e-5);
+        assert_eq!(embed(&embedder, ""), vec![0.0; 64]);
+        assert_eq!(HashingEmbedder::new(0).dimensions(), 1);
+    }
+
+    #[test]
+    fn hashing_embeddings_are_the_same_for_the_same_words() {
+        let embedder = HashingEmbedder::default();
+        assert_eq!(
+            embed(&embedder, "Flood defences approved"),
+            embed(&embedder, "flood, DEFENCES approved!")
+        );
+    }
+
+    #[test]
+    fn texts_sharing_words_are_closer() {
+        let embedder = HashingEmbedder::default();
+        let query = embed(&embedder, "flood defences for the town");
+        let near = embed(&embedder, "new flood defences approved for the town");
+        let far = embed(&embedder, "the chancellor set out tax plans");
+        assert!(dot(&query, &near) > dot(&query, &far));
+    }
+
+    #[tokio::test]
+    async fn reembedding_a_page_drops_the_chunks_it_no_longer_has() {
+        let store = Arc::new(MemoryEmbeddingStore::new());
+        let pipeline = EmbeddingPipeline::new(Arc::new(HashingEmbedder::default()), store.clone());
+        let url = "https://www.bbc.co.uk/news/articles/c0flood0001o";
+        let other = "https://www.bbc.co.uk/news/articles/c0budget002o";
+        pipeline
+            .embed_chunks(url, vec![chunk(url, 0, "First"), chunk(url, 1, "Second")])
+            .await
+            .unwrap();
+        pipeline
+            .embed_chunks(other, vec![chunk(other, 0, "Other")])
+            .await
+            .unwrap();
+
+        let embedded = pipeline
+            .embed_chunks(url, vec![chunk(url, 0, "First")])
+            .await
+            .unwrap();
+        assert_eq!(embedded, 0, "the vector of an unchanged chunk is reused");
+        let stored = store.embeddings("hashing-256").await.unwrap();
+        assert_eq!(
+            chunk_ids(&stored),
+            [format!("{other}#0"), format!("{url}#0")]
+        );
+
+        pipeline.embed_chunks(url, Vec::new()).await.unwrap();
+        let stored = store.embeddings("hashing-256").await.unwrap();
+        assert_eq!(chunk_ids(&stored), [format!("{other}#0")]);
+    }
+
+    #[tokio::test]
+    async fn each_model_keeps_its_own_embedding_of_a_chunk() {
+        let store = Arc::new(MemoryEmbeddingStore::new());
+        let url = "https://en.wikipedia.org/wiki/London";
+        let chunks = vec![chunk(url, 0, "London is the capital of England")];
+        for dimensions in [16, 32] {
+            EmbeddingPipeline::new(Arc::new(HashingEmbedder::new(dimensions)), store.clone())
+                .embed_chunks(url, chunks.clone())
+                .await
+                .unwrap();
+        }
+
+        for (model, dimensions) in [("hashing-16", 16), ("hashing-32", 32)] {
+            let stored = store.embeddings(model).await.unwrap();
+            assert_eq!(stored.len(), 1, "{model}");
+            assert_eq!(stored[0].vector.len(), dimensions);
+            assert_eq!(
+                store
+                    .cached_vector(model, &chunks[0].content_hash)
+                    .await
+                    .unwrap()
+                    .map(|vector| vector.len()),
+                Some(dimensions)
+            );
+        }
+        assert_eq!(store.models().await.unwrap(), ["hashing-16", "hashing-32"]);
+    }
+}
@@ -0,0 +1,24 @@
+#[derive(Debug, derive_more::From)]
+pub enum SearchError {
+    #[from]
+    Custom(String),
+    /// The embedding model couldn't be loaded or failed to run.
+    Model { reason: String },
+    /// Vectors of one model were mixed with those of another, eg querying an index with another embedder than it was built with.
+    ModelMismatch { expected: String, found: String },
+    /// The embedder returned vectors of another size than it said it would.
+    DimensionMismatch { expected: usize, found: usize },
+    /// The full text index couldn't be opened, written or searched.
+    Index { reason: String },
+    /// A keyword query that couldn't be parsed, eg an unclosed quote.
+    InvalidQuery { query: String, reason: String },
+}
+
+// Error boilerplate.
+impl core::fmt::Display for SearchError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for SearchError {}
@@ -0,0 +1,702 @@
+use super::embed::normalize;
+use super::error::SearchError;
+use super::{Embedder, Embedding};
+use crate::common::{fnv1a, Chunk, DateRange};
+use crate::Result;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::path::Path;
+
+/// Nodes are never put on more layers than this, however unlucky the draw.
+const MAX_LEVEL: usize = 16;
+
+/// Limits a search to the chunks of some pages. An empty filter lets everything through.
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilter {
+    site: Option<String>,
+    date_range: Option<DateRange>,
+    category: Option<String>,
+}
+
+impl SearchFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only chunks of pages on this host or its subdomains, eg "bbc.co.uk" or "en.wikipedia.org".
+    pub fn with_site(mut self, site: impl Into<String>) -> Self {
+        self.site = Some(site.into().to_lowercase());
+        self
+    }
+
+    /// Only chunks of pages published within the range. Undated pages are kept, as they are in a crawl.
+    pub fn with_date_range(mut self, date_range: DateRange) -> Self {
+        self.date_range = Some(date_range);
+        self
+    }
+
+    /// Only chunks of pages in the category, eg "Cities in England". Case and underscores don't matter.
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category_key(&category.into()));
+        self
+    }
+
+    pub fn allows(&self, chunk: &Chunk) -> bool {
+        let site_allowed = self.site.as_ref().is_none_or(|site| {
+            chunk.site().is_some_and(|host| {
+                let host = host.to_lowercase();
+                host == *site || host.ends_with(&format!(".{site}"))
+            })
+        });
+        let category_allowed = self.category.as_ref().is_none_or(|category| {
+            chunk
+                .categories
+                .iter()
+                .any(|chunk_category| category_key(chunk_category) == *category)
+        });
+        site_allowed
+            && category_allowed
+            && self
+                .date_range
+                .is_none_or(|date_range| date_range.allows(chunk.published))
+    }
+}
+
+pub(super) fn category_key(category: &str) -> String {
+    category.replace('_', " ").trim().to_lowercase()
+}
+
+/// A chunk found by a search, with its cosine similarity to the query (1 is the same direction).
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct SearchHit {
+    pub chunk: Chunk,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Node {
+    embedding: Embedding,
+    /// The node's neighbours on each layer it is on, from the bottom layer up.
+    neighbours: Vec<Vec<usize>>,
+    /// The chunk has been embedded again since. Removed nodes are still walked through, but never returned.
+    removed: bool,
+}
+
+/// A distance and the node it is to, ordered by distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored(f32, usize);
+
+impl Eq for Scored {}
+
+impl PartialOrd for Scored {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scored {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// An in-process approximate nearest neighbour index over chunk embeddings (HNSW), saved to and loaded from a JSON file between runs.
+///
+/// Each embedding is a node in a graph of layers: every node is on the bottom layer, linked to its nearest neighbours,
+/// and exponentially fewer are on each layer above, so a search can start on the sparse top layer and work its way down towards the query.
+/// See "Efficient and robust approximate nearest neighbor search using Hierarchical Navigable Small World graphs" (Malkov & Yashunin).
+///
+/// The index holds the chunks too, so search hits come with their text, url, title and metadata.
+/// Nodes are drawn onto layers by a hash of the chunk id rather than at random, so the same embeddings always build the same index.
+///
+/// ```ignore
+/// let mut index = HnswIndex::new().with_max_neighbours(8).with_ef_search(16);
+/// index.extend(store.embeddings(embedder.model_id()).await?)?;
+/// let hits = index.query(&embedder, "flooding in the Thames valley", 10, &SearchFilter::new().with_site("bbc.co.uk"))?;
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HnswIndex {
+    model: Option<String>,
+    max_neighbours: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    nodes: Vec<Node>,
+    entry_point: Option<usize>,
+    #[serde(skip)]
+    by_chunk: HashMap<String, usize>,
+}
+
+impl Default for HnswIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HnswIndex {
+    /// An empty index linking each node to 16 neighbours (32 on the bottom layer), considering 100 candidates when inserting and 50 when searching.
+    pub fn new() -> Self {
+        Self {
+            model: None,
+            max_neighbours: 16,
+            ef_construction: 100,
+            ef_search: 50,
+            nodes: Vec::new(),
+            entry_point: None,
+            by_chunk: HashMap::new(),
+        }
+    }
+
+    /// How many neighbours each node is linked to (M). More gives better recall for more memory and slower inserts.
+    pub fn with_max_neighbours(mut self, max_neighbours: usize) -> Self {
+        self.max_neighbours = max_neighbours.max(2);
+        self
+    }
+
+    /// How many candidate neighbours are considered when inserting a node.
+    pub fn with_ef_construction(mut self, ef_construction: usize) -> Self {
+        self.ef_construction = ef_construction.max(1);
+        self
+    }
+
+    /// How many candidates are considered when searching, at least as many as the results asked for.
+    pub fn with_ef_search(mut self, ef_search: usize) -> Self {
+        self.ef_search = ef_search.max(1);
+        self
+    }
+
+    /// The model the vectors in the index were made by, None while it is empty.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// The number of chunks in the index.
+    pub fn len(&self) -> usize {
+        self.by_chunk.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_chunk.is_empty()
+    }
+
+    /// Load an index from a JSON file. A missing file gives an empty index.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let mut index: Self = match tokio::fs::read_to_string(path).await {
+            Ok(json) => serde_json::from_str(&json).map_err(crate::Error::custom)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e.into()),
+        };
+        index.by_chunk = index
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| !node.removed)
+            .map(|(position, node)| (node.embedding.chunk.id.clone(), position))
+            .collect();
+        Ok(index)
+    }
+
+    /// Save the index to a JSON file.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let json = serde_json::to_string(self).map_err(crate::Error::custom)?;
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+
+    /// Add embeddings to the index, see `insert`.
+    pub fn extend(&mut self, embeddings: impl IntoIterator<Item = Embedding>) -> Result<()> {
+        embeddings
+            .into_iter()
+            .try_for_each(|embedding| self.insert(embedding))
+    }
+
+    /// Add an embedding to the index. An embedding of a chunk that is already in the index replaces it.
+    /// All the embeddings in an index have to be made by the same model.
+    pub fn insert(&mut self, mut embedding: Embedding) -> Result<()> {
+        match &self.model {
+            Some(model) if *model != embedding.model => {
+                return Err(SearchError::ModelMismatch {
+                    expected: model.clone(),
+                    found: embedding.model,
+                }
+                .into())
+            }
+            Some(_) => {}
+            None => self.model = Some(embedding.model.clone()),
+        }
+        if let Some(node) = self.nodes.first() {
+            let dimensions = node.embedding.vector.len();
+            if embedding.vector.len() != dimensions {
+                return Err(SearchError::DimensionMismatch {
+                    expected: dimensions,
+                    found: embedding.vector.len(),
+                }
+                .into());
+            }
+        }
+        normalize(&mut embedding.vector);
+
+        if let Some(&existing) = self.by_chunk.get(&embedding.chunk.id) {
+            //? Same text, same vector: only the chunk's metadata (eg its position in the page) can have changed.
+            if self.nodes[existing].embedding.vector == embedding.vector {
+                self.nodes[existing].embedding = embedding;
+                return Ok(());
+            }
+            self.nodes[existing].removed = true;
+        }
+
+        let position = self.nodes.len();
+        let level = self.level_of(&embedding.chunk.id);
+        let query = embedding.vector.clone();
+        self.by_chunk.insert(embedding.chunk.id.clone(), position);
+        self.nodes.push(Node {
+            embedding,
+            neighbours: vec![Vec::new(); level + 1],
+            removed: false,
+        });
+
+        let Some(entry_point) = self.entry_point else {
+            self.entry_point = Some(position);
+            return Ok(());
+        };
+        let top_level = self.nodes[entry_point].neighbours.len() - 1;
+
+        let mut entry_points = vec![entry_point];
+        for layer in (level + 1..=top_level).rev() {
+            entry_points = self.closest_on_layer(&query, &entry_points, layer);
+        }
+        for layer in (0..=level.min(top_level)).rev() {
+            let candidates = self.search_layer(&query, &entry_points, self.ef_construction, layer);
+            let neighbours = self.select_neighbours(&candidates, self.max_neighbours_on(layer));
+            for &neighbour in &neighbours {
+                self.nodes[neighbour].neighbours[layer].push(position);
+                if self.nodes[neighbour].neighbours[layer].len() > self.max_neighbours_on(layer) {
+                    self.prune(neighbour, layer);
+                }
+            }
+            self.nodes[position].neighbours[layer] = neighbours;
+            entry_points = candidates.into_iter().map(|Scored(_, node)| node).collect();
+        }
+        if level > top_level {
+            self.entry_point = Some(position);
+        }
+        Ok(())
+    }
+
+    /// Take a chunk out of the index. Returns whether it was in it.
+    //? The node stays in the graph as a stepping stone for searches, it just isn't returned any more.
+    pub fn remove(&mut self, chunk_id: &str) -> bool {
+        match self.by_chunk.remove(chunk_id) {
+            Some(position) => {
+                self.nodes[position].removed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Take every chunk the predicate rejects out of the index, eg the chunks that are no longer in the embedding store. Returns how many were removed.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) -> usize {
+        let removed = self
+            .by_chunk
+            .keys()
+            .filter(|chunk_id| !keep(chunk_id))
+            .cloned()
+            .collect_vec();
+        removed
+            .iter()
+            .filter(|chunk_id| self.remove(chunk_id))
+            .count()
+    }
+
+    /// The number of nodes in the graph that are no longer returned by searches (replaced or removed chunks).
+    pub fn removed_count(&self) -> usize {
+        self.nodes.len() - self.by_chunk.len()
+    }
+
+    /// A copy of the index with only the current embedding of each chunk, built with the same settings.
+    /// Replaced and removed embeddings stay in the graph until the index is rebuilt, so it is worth doing after a lot of pages changed.
+    pub fn rebuilt(&self) -> Result<Self> {
+        let mut index = Self {
+            model: None,
+            nodes: Vec::new(),
+            entry_point: None,
+            by_chunk: HashMap::new(),
+            ..*self
+        };
+        index.extend(
+            self.nodes
+                .iter()
+                .filter(|node| !node.removed)
+                .map(|node| node.embedding.clone()),
+        )?;
+        Ok(index)
+    }
+
+    /// Embed the query with the model the index was built with and search for it.
+    pub fn query(
+        &self,
+        embedder: &dyn Embedder,
+        query: &str,
+        k: usize,
+        filter: &SearchFilter,
+    ) -> Result<Vec<SearchHit>> {
+        if let Some(model) = &self.model {
+            if model != embedder.model_id() {
+                return Err(SearchError::ModelMismatch {
+                    expected: model.clone(),
+                    found: embedder.model_id().to_string(),
+                }
+                .into());
+            }
+        }
+        let vector = embedder
+            .embed(&[query.to_string()])?
+            .pop()
+            .unwrap_or_default();
+        Ok(self.search(&vector, k, filter))
+    }
+
+    /// The `k` chunks closest to the vector that pass the filter, closest first.
+    //? Filtered out nodes still count towards the candidates, so the search widens until it has found `k` that pass or it has seen the whole index.
+    pub fn search(&self, vector: &[f32], k: usize, filter: &SearchFilter) -> Vec<SearchHit> {
+        let Some(entry_point) = self.entry_point else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+        let mut query = vector.to_vec();
+        normalize(&mut query);
+
+        let mut entry_points = vec![entry_point];
+        for layer in (1..self.nodes[entry_point].neighbours.len()).rev() {
+            entry_points = self.closest_on_layer(&query, &entry_points, layer);
+        }
+        let mut ef = self.ef_search.max(k);
+        loop {
+            let hits = self
+                .search_layer(&query, &entry_points, ef, 0)
+                .into_iter()
+                .map(|Scored(distance, node)| (distance, &self.nodes[node]))
+                .filter(|(_, node)| !node.removed && filter.allows(&node.embedding.chunk))
+                .take(k)
+                .map(|(distance, node)| SearchHit {
+                    chunk: node.embedding.chunk.clone(),
+                    score: 1.0 - distance,
+                })
+                .collect_vec();
+            if hits.len() >= k || ef >= self.nodes.len() {
+                return hits;
+            }
+            ef = (ef * 4).min(self.nodes.len());
+        }
+    }
+
+    fn max_neighbours_on(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.max_neighbours * 2
+        } else {
+            self.max_neighbours
+        }
+    }
+
+    /// The layer a node goes up to: 0 for most, with each layer above holding about 1 in `max_neighbours` of the nodes of the one below.
+    fn level_of(&self, chunk_id: &str) -> usize {
+        let uniform = (fnv1a(chunk_id.as_bytes()) >> 11) as f64 / (1u64 << 53) as f64;
+        let level = -(1.0 - uniform).ln() / (self.max_neighbours as f64).ln();
+        (level as usize).min(MAX_LEVEL)
+    }
+
+    fn distance(&self, query: &[f32], node: usize) -> f32 {
+        let vector = &self.nodes[node].embedding.vector;
+        1.0 - query.iter().zip(vector).map(|(a, b)| a * b).sum::<f32>()
+    }
+
+    fn closest_on_layer(&self, query: &[f32], entry_points: &[usize], layer: usize) -> Vec<usize> {
+        self.search_layer(query, entry_points, 1, layer)
+            .into_iter()
+            .map(|Scored(_, node)| node)
+            .collect()
+    }
+
+    /// The `ef` nodes on the layer closest to the query that can be reached from the entry points, closest first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Scored> {
+        let mut visited = entry_points.iter().copied().collect::<HashSet<_>>();
+        let mut candidates = BinaryHeap::new();
+        let mut found = BinaryHeap::new();
+        for &entry_point in entry_points {
+            let scored = Scored(self.distance(query, entry_point), entry_point);
+            candidates.push(Reverse(scored));
+            found.push(scored);
+        }
+        while found.len() > ef {
+            found.pop();
+        }
+
+        while let Some(Reverse(Scored(distance, node))) = candidates.pop() {
+            if found.len() >= ef && found.peek().is_some_and(|furthest| distance > furthest.0) {
+                break;
+            }
+            for &neighbour in self.nodes[node].neighbours.get(layer).into_iter().flatten() {
+                if !visited.insert(neighbour) {
+                    continue;
+                }
+                let scored = Scored(self.distance(query, neighbour), neighbour);
+                if found.len() < ef || found.peek().is_some_and(|furthest| scored < *furthest) {
+                    candidates.push(Reverse(scored));
+                    found.push(scored);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    /// Pick up to `max` neighbours from candidates sorted closest first, skipping a candidate that is closer to a neighbour already picked than to the node itself.
+    //? Only taking the closest would link a node to one tight cluster, and a node far from every cluster could be pruned out of all its neighbours' lists and never be reached.
+    fn select_neighbours(&self, candidates: &[Scored], max: usize) -> Vec<usize> {
+        let mut neighbours = Vec::new();
+        for &Scored(distance, candidate) in candidates {
+            if neighbours.len() >= max {
+                break;
+            }
+            let vector = &self.nodes[candidate].embedding.vector;
+            if neighbours
+                .iter()
+                .all(|&neighbour| self.distance(vector, neighbour) > distance)
+            {
+                neighbours.push(candidate);
+            }
+        }
+        neighbours
+    }
+
+    /// Keep only the neighbours of a node on a layer that `select_neighbours` picks.
+    fn prune(&mut self, node: usize, layer: usize) {
+        let vector = &self.nodes[node].embedding.vector;
+        let candidates = self.nodes[node].neighbours[layer]
+            .iter()
+            .map(|&neighbour| Scored(self.distance(vector, neighbour), neighbour))
+            .sorted()
+            .collect_vec();
+        self.nodes[node].neighbours[layer] =
+            self.select_neighbours(&candidates, self.max_neighbours_on(layer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::content_hash;
+    use crate::scraper_v2::search::HashingEmbedder;
+
+    const WORDS: &[&str] = &[
+        "flood", "river", "council", "budget", "school", "hospital", "railway", "election",
+        "harbour", "museum", "festival", "storm",
+    ];
+
+    fn chunk(url: &str, index: usize, text: &str, categories: &[&str]) -> Chunk {
+        Chunk {
+            id: format!("{url}#{index}"),
+            url: url.to_string(),
+            title: None,
+            section_path: Vec::new(),
+            index,
+            text: text.to_string(),
+            token_count: text.split_whitespace().count(),
+            content_hash: content_hash(text),
+            published: None,
+            categories: categories
+                .iter()
+                .map(|category| category.to_string())
+                .collect(),
+            topics: Vec::new(),
+        }
+    }
+
+    fn embedding(embedder: &HashingEmbedder, chunk: Chunk) -> Embedding {
+        Embedding {
+            vector: embedder
+                .embed(std::slice::from_ref(&chunk.text))
+                .unwrap()
+                .remove(0),
+            model: embedder.model_id().to_string(),
+            chunk,
+        }
+    }
+
+    /// Chunks about pairs of words, every third one on Wikipedia in the "Rivers" category and the rest on the BBC.
+    fn embeddings(embedder: &HashingEmbedder) -> Vec<Embedding> {
+        WORDS
+            .iter()
+            .cartesian_product(WORDS)
+            .filter(|(first, second)| first != second)
+            .enumerate()
+            .map(|(index, (first, second))| {
+                let text = format!("A report on the {first} and the {second}");
+                let chunk = if index % 3 == 0 {
+                    chunk(
+                        "https://en.wikipedia.org/wiki/Rivers",
+                        index,
+                        &text,
+                        &["Rivers"],
+                    )
+                } else {
+                    chunk(
+                        "https://www.bbc.co.uk/news/articles/c0local0001o",
+                        index,
+                        &text,
+                        &[],
+                    )
+                };
+                embedding(embedder, chunk)
+            })
+            .collect()
+    }
+
+    fn index(embeddings: &[Embedding]) -> HnswIndex {
+        let mut index = HnswIndex::new().with_max_neighbours(8).with_ef_search(16);
+        index.extend(embeddings.iter().cloned()).unwrap();
+        index
+    }
+
+    #[test]
+    fn every_chunk_is_found_by_its_own_vector() {
+        let embedder = HashingEmbedder::new(64);
+        let embeddings = embeddings(&embedder);
+        let index = index(&embeddings);
+
+        assert_eq!(index.len(), embeddings.len());
+        assert_eq!(index.model(), Some("hashing-64"));
+        for embedding in &embeddings {
+            let hits = index.search(&embedding.vector, 1, &SearchFilter::new());
+            assert_eq!(hits[0].chunk.id, embedding.chunk.id);
+            assert!((hits[0].score - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn replaced_and_removed_chunks_are_never_returned() {
+        let embedder = HashingEmbedder::new(64);
+        let embeddings = embeddings(&embedder);
+        let mut index = index(&embeddings);
+        let all = embeddings.len();
+
+        let old = embeddings[0].clone();
+        let mut replacement = old.chunk.clone();
+        replacement.text = "The harbour wall was rebuilt after the storm".to_string();
+        index.insert(embedding(&embedder, replacement)).unwrap();
+        assert_eq!(index.len(), all);
+        assert_eq!(index.removed_count(), 1);
+
+        let replacement = embedding(
+            &embedder,
+            chunk(
+                &old.chunk.url,
+                0,
+                "The harbour wall was rebuilt after the storm",
+                &[],
+            ),
+        );
+        let hits = index.search(&replacement.vector, 1, &SearchFilter::new());
+        assert_eq!(hits[0].chunk.text, replacement.chunk.text);
+        let hits = index.search(&old.vector, 20, &SearchFilter::new());
+        assert_eq!(hits.len(), 20);
+        assert!(!hits.iter().any(|hit| hit.chunk.text == old.chunk.text));
+
+        assert!(index.remove(&embeddings[1].chunk.id));
+        assert!(!index.remove(&embeddings[1].chunk.id));
+        let removed = index.retain(|chunk_id| !chunk_id.ends_with("#2"));
+        assert_eq!(removed, 1);
+        assert_eq!(index.len(), all - 2);
+        for gone in &embeddings[1..3] {
+            let hits = index.search(&gone.vector, 20, &SearchFilter::new());
+            assert_eq!(hits.len(), 20);
+            assert!(!hits.iter().any(|hit| hit.chunk.id == gone.chunk.id));
+        }
+
+        let rebuilt = index.rebuilt().unwrap();
+        assert_eq!(rebuilt.len(), all - 2);
+        assert_eq!(rebuilt.removed_count(), 0);
+    }
+
+    #[test]
+    fn filtered_searches_still_return_k_hits() {
+        let embedder = HashingEmbedder::new(64);
+        let embeddings = embeddings(&embedder);
+        let index = index(&embeddings);
+        //? A BBC chunk, so its nearest neighbours are mostly filtered out.
+        let query = &embeddings[1].vector;
+
+        let by_site = index.search(query, 10, &SearchFilter::new().with_site("wikipedia.org"));
+        assert_eq!(by_site.len(), 10);
+        assert!(by_site
+            .iter()
+            .all(|hit| hit.chunk.url.starts_with("https://en.wikipedia.org/")));
+
+        let by_category = index.search(query, 10, &SearchFilter::new().with_category("rivers"));
+        assert_eq!(by_category.len(), 10);
+        assert!(by_category
+            .iter()
+            .all(|hit| hit.chunk.categories == ["Rivers"]));
+
+        let nothing = index.search(query, 10, &SearchFilter::new().with_site("example.com"));
+        assert!(nothing.is_empty());
+    }
+
+    #[test]
+    fn embeddings_of_another_model_or_length_are_rejected() {
+        let embedder = HashingEmbedder::new(64);
+        let mut index = index(&embeddings(&embedder)[..4]);
+        let chunk = chunk("https://www.bbc.co.uk/news", 99, "Storm warning", &[]);
+
+        let mut other_model = embedding(&embedder, chunk.clone());
+        other_model.model = "another-model".to_string();
+        assert!(index.insert(other_model).is_err());
+        assert!(index
+            .insert(embedding(&HashingEmbedder::new(32), chunk))
+            .is_err());
+        assert!(index
+            .query(&HashingEmbedder::new(32), "storm", 1, &SearchFilter::new())
+            .is_err());
+        assert_eq!(index.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn a_saved_index_loads_with_its_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.json");
+        let embedder = HashingEmbedder::new(64);
+        let embeddings = embeddings(&embedder);
+        let mut index = index(&embeddings);
+        index.remove(&embeddings[0].chunk.id);
+        index.save(&path).await.unwrap();
+
+        let loaded = HnswIndex::load(&path).await.unwrap();
+        assert_eq!(loaded.len(), embeddings.len() - 1);
+        assert_eq!(loaded.model(), Some("hashing-64"));
+        for embedding in &embeddings[1..] {
+            let hits = loaded.search(&embedding.vector, 1, &SearchFilter::new());
+            assert_eq!(hits[0].chunk.id, embedding.chunk.id);
+        }
+        //? `by_chunk` isn't saved, so it has to be rebuilt for replacing and removing to work.
+        assert!(!loaded.clone().remove(&embeddings[0].chunk.id));
+        assert!(loaded.clone().remove(&embeddings[1].chunk.id));
+
+        let missing = HnswIndex::load(dir.path().join("missing.json"))
+            .await
+            .unwrap();
+        assert!(missing.is_empty());
+    }
+}
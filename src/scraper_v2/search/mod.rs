@@ -0,0 +1,15 @@
+#[cfg(feature = "local-embeddings")]
+mod bert;
+mod embed;
+pub mod error;
+mod hnsw;
+mod text;
+
+#[cfg(feature = "local-embeddings")]
+pub use bert::BertEmbedder;
+pub use embed::{
+    Embedder, Embedding, EmbeddingPipeline, EmbeddingStore, HashingEmbedder, MemoryEmbeddingStore,
+    SurrealEmbeddingStore,
+};
+pub use hnsw::{HnswIndex, SearchFilter, SearchHit};
+pub use text::{TextHit, TextIndex, TextQuery, TextResults};
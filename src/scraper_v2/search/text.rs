@@ -0,0 +1,570 @@
+use super::error::SearchError;
+use super::hnsw::category_key;
+use crate::common::{strip_markdown, Chunk, DateRange};
+use crate::Result;
+
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Mutex;
+use tantivy::collector::{Count, FacetCollector, TopDocs};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{
+    AllQuery, BooleanQuery, ExistsQuery, Occur, Query, QueryParser, RangeQuery, TermQuery,
+};
+use tantivy::schema::{
+    DateOptions, Facet, FacetOptions, Field, IndexRecordOption, Schema, TextFieldIndexing,
+    TextOptions, Value, FAST, INDEXED, STORED, STRING,
+};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+/// Memory the index writer may use before it flushes what it has to disk.
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// How long snippets are, in characters.
+const SNIPPET_LENGTH: usize = 200;
+
+/// The fields of a chunk document.
+#[derive(Debug, Clone, Copy)]
+struct Fields {
+    chunk_id: Field,
+    url: Field,
+    title: Field,
+    section: Field,
+    body: Field,
+    site: Field,
+    /// The labels of the host in reverse (/uk/co/bbc/www), so a site matches its subdomains.
+    domain: Field,
+    published: Field,
+    topics: Field,
+    /// /host/.., /topic/.. and /category/.. facets, counted for each search.
+    facets: Field,
+    /// The topics and categories as facets again, in lowercase, to filter on.
+    filters: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Fields) {
+        let text = TextOptions::default()
+            .set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer("en_stem")
+                    .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+            )
+            .set_stored();
+        let mut builder = Schema::builder();
+        let fields = Fields {
+            chunk_id: builder.add_text_field("chunk_id", STRING | STORED),
+            url: builder.add_text_field("url", STRING | STORED),
+            title: builder.add_text_field("title", text.clone()),
+            section: builder.add_text_field("section", text.clone()),
+            body: builder.add_text_field("body", text.clone()),
+            site: builder.add_text_field("site", STRING | STORED),
+            domain: builder.add_facet_field("domain", FacetOptions::default()),
+            published: builder
+                .add_date_field("published", DateOptions::from(INDEXED) | STORED | FAST),
+            topics: builder.add_text_field("topics", text),
+            facets: builder.add_facet_field("facets", FacetOptions::default()),
+            filters: builder.add_facet_field("filters", FacetOptions::default()),
+        };
+        (builder.build(), fields)
+    }
+}
+
+/// A keyword search, with optional filters on the site, topic, category and publication date of the pages.
+///
+/// The query uses tantivy's syntax: words (all of them have to match), `"quoted phrases"`, `-excluded` words, `OR`, and fields like `title:flood` or `topics:"climate change"`.
+#[derive(Debug, Clone)]
+pub struct TextQuery {
+    query: String,
+    limit: usize,
+    site: Option<String>,
+    topic: Option<String>,
+    category: Option<String>,
+    date_range: Option<DateRange>,
+}
+
+impl TextQuery {
+    /// Returns the 10 best matches.
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            limit: 10,
+            site: None,
+            topic: None,
+            category: None,
+            date_range: None,
+        }
+    }
+
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Only chunks of pages on this host or its subdomains, eg "bbc.co.uk".
+    pub fn with_site(mut self, site: impl Into<String>) -> Self {
+        self.site = Some(site.into().to_lowercase());
+        self
+    }
+
+    /// Only chunks of pages tagged with the topic, eg "Climate change". Case doesn't matter.
+    pub fn with_topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = Some(topic.into().trim().to_lowercase());
+        self
+    }
+
+    /// Only chunks of pages in the Wikipedia category, eg "Cities in England". Case and underscores don't matter.
+    pub fn with_category(mut self, category: impl Into<String>) -> Self {
+        self.category = Some(category_key(&category.into()));
+        self
+    }
+
+    /// Only chunks of pages published within the range. Undated pages are kept, as they are in a crawl.
+    pub fn with_date_range(mut self, date_range: DateRange) -> Self {
+        self.date_range = Some(date_range);
+        self
+    }
+}
+
+/// A chunk that matched a keyword search.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct TextHit {
+    pub chunk_id: String,
+    pub url: String,
+    pub title: Option<String>,
+    /// The headings the chunk is under, eg "History > Roman London".
+    pub section: String,
+    pub published: Option<DateTime<Utc>>,
+    pub topics: Vec<String>,
+    pub score: f32,
+    /// The best matching part of the chunk's text, with the matched words in `**bold**`.
+    pub snippet: String,
+}
+
+/// The hits of a keyword search, with how many chunks matched in all and how they are spread over sites and topics.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct TextResults {
+    pub total: usize,
+    pub hits: Vec<TextHit>,
+    /// The hosts of the matching chunks and how many matched on each, most first.
+    pub sites: Vec<(String, u64)>,
+    /// The topics of the matching chunks and how many matched with each, most first.
+    pub topics: Vec<(String, u64)>,
+    /// The categories of the matching chunks and how many matched in each, most first.
+    pub categories: Vec<(String, u64)>,
+}
+
+/// A full text index of chunks, built with tantivy, for keyword search alongside the vector index.
+///
+/// Each chunk is a document with its title, section headings, text (without the Markdown), site, publication date, topics and categories.
+/// Searches support phrases, facet counts (sites, topics and categories) and highlighted snippets.
+/// Pages are added with `put_chunks` (`PageHandler::with_text_index` does this as pages are scraped) and are searchable once `commit` is called.
+/// The index can always be rebuilt from the chunks in a `ChunkStore`, eg `index.rebuild(store.all_chunks().await?)`.
+/// Only one writer can have an index open at a time, so searches from another process (eg while a crawl is adding to it) open it with `open_read_only`.
+pub struct TextIndex {
+    index: Index,
+    fields: Fields,
+    /// None when the index was opened read only.
+    writer: Option<Mutex<IndexWriter>>,
+    reader: IndexReader,
+}
+
+impl std::fmt::Debug for TextIndex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TextIndex")
+            .field("documents", &self.reader.searcher().num_docs())
+            .finish()
+    }
+}
+
+impl TextIndex {
+    /// Open the index in a directory, creating it if there isn't one.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let directory = MmapDirectory::open(dir.as_ref()).map_err(index_error)?;
+        let (schema, fields) = Fields::schema();
+        Self::with_index(
+            Index::open_or_create(directory, schema).map_err(index_error)?,
+            fields,
+        )
+    }
+
+    /// Open an existing index in a directory to search it, without taking the lock a writer needs. Commits made since are picked up as they happen.
+    pub fn open_read_only(dir: impl AsRef<Path>) -> Result<Self> {
+        let index = Index::open_in_dir(dir.as_ref()).map_err(index_error)?;
+        let (_, fields) = Fields::schema();
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommitWithDelay)
+            .try_into()
+            .map_err(index_error)?;
+        Ok(Self {
+            index,
+            fields,
+            writer: None,
+            reader,
+        })
+    }
+
+    /// An index held in memory, gone when it is dropped.
+    pub fn in_memory() -> Result<Self> {
+        let (schema, fields) = Fields::schema();
+        Self::with_index(Index::create_in_ram(schema), fields)
+    }
+
+    fn with_index(index: Index, fields: Fields) -> Result<Self> {
+        let writer = index.writer(WRITER_MEMORY).map_err(index_error)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(index_error)?;
+        Ok(Self {
+            index,
+            fields,
+            writer: Some(Mutex::new(writer)),
+            reader,
+        })
+    }
+
+    fn writer(&self) -> Result<std::sync::MutexGuard<'_, IndexWriter>> {
+        self.writer
+            .as_ref()
+            .ok_or_else(|| index_error("The index was opened read only"))?
+            .lock()
+            .map_err(crate::Error::custom)
+    }
+
+    /// Replace the chunks of the page at `url` (its full url) with these ones.
+    pub fn put_chunks(&self, url: &str, chunks: &[Chunk]) -> Result<()> {
+        let writer = self.writer()?;
+        writer.delete_term(Term::from_field_text(self.fields.url, url));
+        for chunk in chunks {
+            writer
+                .add_document(self.document(chunk))
+                .map_err(index_error)?;
+        }
+        Ok(())
+    }
+
+    /// Make the chunks put since the last commit searchable, and save them to disk.
+    pub fn commit(&self) -> Result<()> {
+        self.writer()?.commit().map_err(index_error)?;
+        self.reader.reload().map_err(index_error)
+    }
+
+    /// Replace everything in the index with these chunks, eg all the chunks in the database. Returns how many were indexed.
+    pub fn rebuild(&self, chunks: impl IntoIterator<Item = Chunk>) -> Result<usize> {
+        let mut count = 0;
+        {
+            let writer = self.writer()?;
+            writer.delete_all_documents().map_err(index_error)?;
+            for chunk in chunks {
+                writer
+                    .add_document(self.document(&chunk))
+                    .map_err(index_error)?;
+                count += 1;
+            }
+        }
+        self.commit()?;
+        Ok(count)
+    }
+
+    /// The number of chunks that can be searched.
+    pub fn len(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn search(&self, query: &TextQuery) -> Result<TextResults> {
+        let fields = self.fields;
+        let mut parser = QueryParser::for_index(
+            &self.index,
+            vec![fields.title, fields.section, fields.body, fields.topics],
+        );
+        parser.set_conjunction_by_default();
+        parser.set_field_boost(fields.title, 2.0);
+        parser.set_field_boost(fields.section, 1.5);
+        let text_query =
+            parser
+                .parse_query(&query.query)
+                .map_err(|error| SearchError::InvalidQuery {
+                    query: query.query.clone(),
+                    reason: error.to_string(),
+                })?;
+
+        let mut clauses = vec![(Occur::Must, text_query.box_clone())];
+        if let Some(site) = &query.site {
+            clauses.push((Occur::Must, facet_query(fields.domain, &domain_facet(site))));
+        }
+        if let Some(topic) = &query.topic {
+            clauses.push((
+                Occur::Must,
+                facet_query(fields.filters, &Facet::from_path(["topic", topic.as_str()])),
+            ));
+        }
+        if let Some(category) = &query.category {
+            clauses.push((
+                Occur::Must,
+                facet_query(
+                    fields.filters,
+                    &Facet::from_path(["category", category.as_str()]),
+                ),
+            ));
+        }
+        if let Some(date_range) = query.date_range {
+            clauses.push((Occur::Must, date_query(date_range)));
+        }
+        let filtered = BooleanQuery::new(clauses);
+
+        let searcher = self.reader.searcher();
+        let mut facet_collector = FacetCollector::for_field("facets");
+        facet_collector.add_facet("/host");
+        facet_collector.add_facet("/topic");
+        facet_collector.add_facet("/category");
+        let (top_docs, total, facet_counts) = searcher
+            .search(
+                &filtered,
+                &(
+                    TopDocs::with_limit(query.limit.max(1)),
+                    Count,
+                    facet_collector,
+                ),
+            )
+            .map_err(index_error)?;
+
+        let mut snippets =
+            SnippetGenerator::create(&searcher, &*text_query, fields.body).map_err(index_error)?;
+        snippets.set_max_num_chars(SNIPPET_LENGTH);
+
+        let hits = top_docs
+            .into_iter()
+            .take(query.limit)
+            .map(|(score, address)| {
+                let document: TantivyDocument = searcher.doc(address).map_err(index_error)?;
+                let text = |field| {
+                    document
+                        .get_first(field)
+                        .and_then(|value| value.as_str())
+                        .map(str::to_string)
+                };
+                let snippet = snippets.snippet_from_doc(&document);
+                let snippet = if snippet.highlighted().is_empty() {
+                    //? The query only matched the title, section or topics, so the start of the text stands in.
+                    text(fields.body)
+                        .unwrap_or_default()
+                        .chars()
+                        .take(SNIPPET_LENGTH)
+                        .collect()
+                } else {
+                    highlight(snippet.fragment(), snippet.highlighted())
+                };
+                Ok(TextHit {
+                    chunk_id: text(fields.chunk_id).unwrap_or_default(),
+                    url: text(fields.url).unwrap_or_default(),
+                    title: text(fields.title),
+                    section: text(fields.section).unwrap_or_default(),
+                    published: document
+                        .get_first(fields.published)
+                        .and_then(|value| value.as_datetime())
+                        .and_then(|date| DateTime::from_timestamp(date.into_timestamp_secs(), 0)),
+                    topics: document
+                        .get_all(fields.topics)
+                        .filter_map(|value| value.as_str())
+                        .map(str::to_string)
+                        .collect(),
+                    score,
+                    snippet,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let counts = |facet: &str| {
+            facet_counts
+                .get(facet)
+                .filter_map(|(facet, count)| {
+                    let name = facet.to_path().last()?.to_string();
+                    Some((name, count))
+                })
+                .sorted_by(|(name_a, count_a), (name_b, count_b)| {
+                    count_b.cmp(count_a).then(name_a.cmp(name_b))
+                })
+                .collect_vec()
+        };
+        Ok(TextResults {
+            total,
+            hits,
+            sites: counts("/host"),
+            topics: counts("/topic"),
+            categories: counts("/category"),
+        })
+    }
+
+    fn document(&self, chunk: &Chunk) -> TantivyDocument {
+        let fields = self.fields;
+        let mut document = TantivyDocument::default();
+        document.add_text(fields.chunk_id, &chunk.id);
+        document.add_text(fields.url, &chunk.url);
+        if let Some(title) = &chunk.title {
+            document.add_text(fields.title, title);
+        }
+        document.add_text(fields.section, chunk.section_path.join(" > "));
+        document.add_text(fields.body, strip_markdown(&chunk.text));
+        if let Some(site) = chunk.site() {
+            let site = site.to_lowercase();
+            document.add_facet(fields.domain, domain_facet(&site));
+            document.add_facet(fields.facets, Facet::from_path(["host", site.as_str()]));
+            document.add_text(fields.site, site);
+        }
+        if let Some(published) = chunk.published {
+            document.add_date(
+                fields.published,
+                tantivy::DateTime::from_timestamp_secs(published.timestamp()),
+            );
+        }
+        for topic in &chunk.topics {
+            document.add_text(fields.topics, topic);
+            document.add_facet(fields.facets, Facet::from_path(["topic", topic.as_str()]));
+            document.add_facet(
+                fields.filters,
+                Facet::from_path(["topic", topic.trim().to_lowercase().as_str()]),
+            );
+        }
+        for category in &chunk.categories {
+            let name = category.replace('_', " ");
+            document.add_facet(fields.facets, Facet::from_path(["category", name.as_str()]));
+            document.add_facet(
+                fields.filters,
+                Facet::from_path(["category", category_key(category).as_str()]),
+            );
+        }
+        document
+    }
+}
+
+/// The labels of a host from the top down, eg /uk/co/bbc/www. A facet matches the ones below it, so /uk/co/bbc matches every BBC host.
+fn domain_facet(host: &str) -> Facet {
+    Facet::from_path(host.split('.').rev())
+}
+
+fn facet_query(field: Field, facet: &Facet) -> Box<dyn Query> {
+    Box::new(TermQuery::new(
+        Term::from_facet(field, facet),
+        IndexRecordOption::Basic,
+    ))
+}
+
+/// Published within the range, or not dated at all.
+fn date_query(date_range: DateRange) -> Box<dyn Query> {
+    let bound = |date: Option<DateTime<Utc>>| match date {
+        Some(date) => Bound::Included(tantivy::DateTime::from_timestamp_secs(date.timestamp())),
+        None => Bound::Unbounded,
+    };
+    let in_range = RangeQuery::new_date_bounds(
+        "published".to_string(),
+        bound(date_range.start),
+        bound(date_range.end),
+    );
+    let undated = BooleanQuery::new(vec![
+        (Occur::Must, Box::new(AllQuery) as Box<dyn Query>),
+        (
+            Occur::MustNot,
+            Box::new(ExistsQuery::new_exists_query("published".to_string())),
+        ),
+    ]);
+    Box::new(BooleanQuery::new(vec![
+        (Occur::Should, Box::new(in_range) as Box<dyn Query>),
+        (Occur::Should, Box::new(undated)),
+    ]))
+}
+
+/// The fragment with the highlighted ranges wrapped in `**`.
+//? Ranges with only spaces between them are joined, so a phrase is highlighted as one.
+fn highlight(fragment: &str, highlighted: &[std::ops::Range<usize>]) -> String {
+    let mut ranges: Vec<std::ops::Range<usize>> = Vec::with_capacity(highlighted.len());
+    for range in highlighted {
+        match ranges.last_mut() {
+            Some(last)
+                if last.end <= range.start && fragment[last.end..range.start].trim().is_empty() =>
+            {
+                last.end = range.end
+            }
+            _ => ranges.push(range.clone()),
+        }
+    }
+    let mut text = String::with_capacity(fragment.len() + ranges.len() * 4);
+    let mut position = 0;
+    for range in &ranges {
+        text.push_str(&fragment[position..range.start]);
+        text.push_str("**");
+        text.push_str(&fragment[range.clone()]);
+        text.push_str("**");
+        position = range.end;
+    }
+    text.push_str(&fragment[position..]);
+    text
+}
+
+fn index_error(error: impl std::fmt::Display) -> crate::Error {
+    SearchError::Index {
+        reason: error.to_string(),
+    }
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::content_hash;
+
+    fn chunk(url: &str, text: &str) -> Chunk {
+        Chunk {
+            id: format!("{url}#0"),
+            url: url.to_string(),
+            title: Some("Flood defences".to_string()),
+            section_path: Vec::new(),
+            index: 0,
+            text: text.to_string(),
+            token_count: text.split_whitespace().count(),
+            content_hash: content_hash(text),
+            published: None,
+            categories: Vec::new(),
+            topics: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn an_index_can_be_searched_while_it_is_open_for_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = "https://www.bbc.co.uk/news/articles/c0flood0001o";
+        let writer = TextIndex::open(dir.path()).unwrap();
+        writer
+            .put_chunks(
+                url,
+                &[chunk(url, "A scheme to protect homes from flooding")],
+            )
+            .unwrap();
+        writer.commit().unwrap();
+
+        let reader = TextIndex::open_read_only(dir.path()).unwrap();
+        let results = reader.search(&TextQuery::new("flooding")).unwrap();
+        assert_eq!(results.total, 1);
+        assert_eq!(results.hits[0].url, url);
+        assert!(reader.put_chunks(url, &[]).is_err());
+        assert!(reader.commit().is_err());
+    }
+
+    #[test]
+    fn a_missing_index_is_not_created_to_be_searched() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(TextIndex::open_read_only(dir.path().join("missing")).is_err());
+        assert!(!dir.path().join("missing").exists());
+    }
+}
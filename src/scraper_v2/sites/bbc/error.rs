@@ -24,6 +24,9 @@ pub enum BBCError {
     NoRelatedTopicsFound {
         url: String,
     },
+    NoPromosFound {
+        url: String,
+    },
 }
 
 // Error boilerplate.
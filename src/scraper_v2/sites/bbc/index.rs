@@ -0,0 +1,293 @@
+use super::error::BBCError;
+use super::model::PromoCard;
+use super::page::{element_text, select_text};
+use super::{BBCIndexUrl, BBCUrl};
+use crate::common::{
+    escape_markdown, extract_links, extract_page_links, FetchedDocument, LinkTo, Page,
+    ScrapableContent, Timestamp, UrlTrait,
+};
+use crate::Result;
+
+use itertools::Itertools;
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// The elements that wrap a single promo card, across the current and older front page markup.
+const CARD_SELECTOR: &str = "[data-testid$='-card'], [data-testid='card'], [data-testid='promo'], \
+    .gs-c-promo, [type='article']";
+/// The "Most read" list.
+const MOST_READ_SELECTOR: &str = "[data-testid='mostRead'], [data-testid='most-read'], \
+    [data-component='mostRead'], .nw-c-most-read";
+/// The section used for stories that are only linked from the "Most read" list.
+const MOST_READ_SECTION: &str = "Most read";
+
+/// The stories listed on a front page, section front or topic page.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct BBCIndexContent {
+    title: String,
+    /// The promo cards in page order.
+    promos: Vec<PromoCard>,
+    /// Other fronts and topic pages linked from this one.
+    index_links: HashSet<Page<LinkTo, BBCIndexUrl>>,
+}
+
+impl ScrapableContent for BBCIndexContent {
+    type Url = BBCIndexUrl;
+    fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self> {
+        tracing::debug!("Scraping index: {:?}", url);
+
+        let main_selector = Selector::parse("main, #main-content").unwrap();
+        let main = document
+            .select(&main_selector)
+            .next()
+            .unwrap_or_else(|| document.root_element());
+
+        let mut promos = Self::extract_promos(&main, document);
+        //? Topic pages and older fronts don't always mark up their cards, so fall back to every story link.
+        if promos.is_empty() {
+            promos = Self::extract_story_links(&main, document);
+        }
+
+        let most_read = Self::extract_most_read(document);
+        for promo in promos.iter_mut() {
+            promo.most_read_rank = most_read
+                .iter()
+                .position(|(url, _)| *url == promo.url)
+                .map(|index| index + 1);
+        }
+        let unpromoted = most_read
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (url, _))| !promos.iter().any(|promo| promo.url == *url))
+            .map(|(index, (url, headline))| PromoCard {
+                headline,
+                summary: None,
+                url,
+                position: 0,
+                section: Some(MOST_READ_SECTION.to_string()),
+                most_read_rank: Some(index + 1),
+                published: Timestamp::Missing,
+            })
+            .collect::<Vec<PromoCard>>();
+        promos.extend(unpromoted);
+
+        if promos.is_empty() {
+            return Err(BBCError::NoPromosFound {
+                url: url.full_url(),
+            }
+            .into());
+        }
+        for (index, promo) in promos.iter_mut().enumerate() {
+            promo.position = index + 1;
+        }
+
+        let title = select_text(&main, "h1")
+            .or_else(|| {
+                document
+                    .structured_data()
+                    .open_graph
+                    .get("og:title")
+                    .cloned()
+            })
+            .or_else(|| select_text(&document.root_element(), "title"))
+            .unwrap_or_else(|| url.to_string());
+
+        Ok(BBCIndexContent {
+            title,
+            promos,
+            index_links: extract_page_links::<BBCIndexUrl>(&document.root_element(), document),
+        })
+    }
+
+    /// Only other index pages are followed, the stories are handed to an article crawl with `seed_pages`.
+    fn get_related_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
+        self.index_links.clone()
+    }
+
+    fn body_text(&self) -> String {
+        self.promos
+            .iter()
+            .map(|promo| promo.headline.as_str())
+            .join("\n")
+    }
+
+    /// The stories as a list of links, grouped under the heading of the part of the page they are in.
+    fn to_markdown(&self) -> String {
+        let mut markdown = vec![format!("# {}", escape_markdown(&self.title))];
+        for (section, promos) in &self
+            .promos
+            .iter()
+            .chunk_by(|promo| promo.section.as_deref())
+        {
+            if let Some(section) = section {
+                markdown.push(format!("## {}", escape_markdown(section)));
+            }
+            markdown.push(
+                promos
+                    .map(|promo| {
+                        let link = format!(
+                            "- [{}]({})",
+                            escape_markdown(&promo.headline),
+                            promo.url.full_url()
+                        );
+                        match &promo.summary {
+                            Some(summary) => format!("{link}: {}", escape_markdown(summary)),
+                            None => link,
+                        }
+                    })
+                    .join("\n"),
+            );
+        }
+        markdown.join("\n\n")
+    }
+}
+
+impl BBCIndexContent {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    /// The promo cards in page order.
+    pub fn promos(&self) -> &[PromoCard] {
+        &self.promos
+    }
+    /// The stories in the "Most read" list, in rank order.
+    pub fn most_read(&self) -> Vec<&PromoCard> {
+        self.promos
+            .iter()
+            .filter(|promo| promo.most_read_rank.is_some())
+            .sorted_by_key(|promo| promo.most_read_rank)
+            .collect()
+    }
+
+    /// The stories on the page as LinkTo pages, to seed an article crawl with `PageHandler::add_seed_pages`.
+    pub fn seed_pages(&self) -> Vec<Page<LinkTo, BBCUrl>> {
+        self.promos
+            .iter()
+            .map(|promo| {
+                Page::<LinkTo, BBCUrl>::new(promo.url.clone(), promo.headline.clone())
+                    .with_published(promo.published.datetime())
+            })
+            .collect()
+    }
+
+    /// The first link in `element` that is a story, with the link text.
+    fn first_story_link(
+        element: &ElementRef,
+        document: &FetchedDocument,
+    ) -> Option<(BBCUrl, String)> {
+        extract_links(element, document)
+            .into_iter()
+            .find_map(|link| {
+                let url = BBCUrl::try_from(link.href).ok()?;
+                Some((url, link.text.trim().to_string()))
+            })
+    }
+
+    fn extract_promos(main: &ElementRef, document: &FetchedDocument) -> Vec<PromoCard> {
+        let card_selector = Selector::parse(CARD_SELECTOR).unwrap();
+        let most_read_selector = Selector::parse(MOST_READ_SELECTOR).unwrap();
+        let time_selector = Selector::parse("time[datetime]").unwrap();
+
+        main.select(&card_selector)
+            .filter(|card| !Self::is_within(card, &most_read_selector))
+            .filter_map(|card| {
+                let (url, link_text) = Self::first_story_link(&card, document)?;
+                let headline = select_text(
+                    &card,
+                    "[data-testid='card-headline'], .gs-c-promo-heading__title, h2, h3",
+                )
+                .unwrap_or(link_text);
+                if headline.is_empty() {
+                    return None;
+                }
+                Some(PromoCard {
+                    headline,
+                    summary: select_text(
+                        &card,
+                        "[data-testid='card-description'], .gs-c-promo-summary, p",
+                    ),
+                    url,
+                    position: 0,
+                    section: Self::extract_section(&card, &card_selector),
+                    most_read_rank: None,
+                    published: Timestamp::parse(
+                        card.select(&time_selector)
+                            .next()
+                            .and_then(|time| time.value().attr("datetime")),
+                    ),
+                })
+            })
+            //? A story can be promoted more than once, only its first (highest) card is kept.
+            .unique_by(|promo| promo.url.clone())
+            .collect()
+    }
+
+    /// Every story link on the page as a bare card, for pages whose cards we don't recognise.
+    fn extract_story_links(main: &ElementRef, document: &FetchedDocument) -> Vec<PromoCard> {
+        extract_links(main, document)
+            .into_iter()
+            .filter_map(|link| {
+                let headline = link.text.trim().to_string();
+                let url = BBCUrl::try_from(link.href).ok()?;
+                (!headline.is_empty()).then_some(PromoCard {
+                    headline,
+                    summary: None,
+                    url,
+                    position: 0,
+                    section: None,
+                    most_read_rank: None,
+                    published: Timestamp::Missing,
+                })
+            })
+            .unique_by(|promo| promo.url.clone())
+            .collect()
+    }
+
+    /// The stories in the "Most read" list in rank order, with their headlines.
+    /// The list can be outside of `<main>` so the whole document is searched.
+    fn extract_most_read(document: &FetchedDocument) -> Vec<(BBCUrl, String)> {
+        let most_read_selector = Selector::parse(MOST_READ_SELECTOR).unwrap();
+        let Some(most_read) = document.select(&most_read_selector).next() else {
+            return Vec::new();
+        };
+        let item_selector = Selector::parse("li").unwrap();
+        let items = most_read
+            .select(&item_selector)
+            .collect::<Vec<ElementRef>>();
+        let items = if items.is_empty() {
+            vec![most_read]
+        } else {
+            items
+        };
+        items
+            .iter()
+            .filter_map(|item| Self::first_story_link(item, document))
+            .unique_by(|(url, _)| url.clone())
+            .collect()
+    }
+
+    /// The heading of the closest `<section>` around a card, ignoring the headlines of the cards in it.
+    fn extract_section(card: &ElementRef, card_selector: &Selector) -> Option<String> {
+        let heading_selector = Selector::parse("h2, [data-testid$='section-title']").unwrap();
+        card.ancestors()
+            .filter_map(ElementRef::wrap)
+            .filter(|ancestor| ancestor.value().name() == "section")
+            .find_map(|section| {
+                section
+                    .select(&heading_selector)
+                    .find(|heading| !Self::is_within(heading, card_selector))
+                    .map(|heading| element_text(&heading))
+                    .filter(|text| !text.is_empty())
+            })
+    }
+
+    /// Whether an element is inside (or is) an element matching `selector`.
+    fn is_within(element: &ElementRef, selector: &Selector) -> bool {
+        selector.matches(element)
+            || element
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| selector.matches(&ancestor))
+    }
+}
@@ -1,7 +1,35 @@
 pub mod error;
+mod index;
+pub mod model;
 mod page;
 mod scraper;
 mod url;
 
+pub use index::BBCIndexContent;
 pub use page::BBCContent;
-pub use url::BBCUrl;
+pub use url::{BBCIndexUrl, BBCPageKind, BBCUrl};
+
+use crate::common::{SeedSource, UrlTrait};
+
+/// The BBC News top stories RSS feed.
+pub const BBC_NEWS_FEED: &str = "https://feeds.bbci.co.uk/news/rss.xml";
+/// The BBC News sitemap of recently published articles.
+pub const BBC_NEWS_SITEMAP: &str = "https://www.bbc.co.uk/sitemaps/https-index-uk-news.xml";
+
+/// The BBC News front page.
+pub const BBC_NEWS_FRONT: &str = "https://www.bbc.co.uk/news";
+/// The BBC Sport front page.
+pub const BBC_SPORT_FRONT: &str = "https://www.bbc.co.uk/sport";
+
+/// The default seed sources for a BBC News crawl.
+pub fn news_seeds() -> Vec<SeedSource> {
+    vec![
+        SeedSource::Feed(BBC_NEWS_FEED.to_string()),
+        SeedSource::Sitemap(BBC_NEWS_SITEMAP.to_string()),
+    ]
+}
+
+/// The default front pages to scrape with `BBCIndexContent` for stories to seed a crawl with.
+pub fn front_pages() -> Vec<BBCIndexUrl> {
+    BBCIndexUrl::from_collection([BBC_NEWS_FRONT, BBC_SPORT_FRONT])
+}
@@ -0,0 +1,100 @@
+use super::BBCUrl;
+use crate::common::Timestamp;
+
+use serde::{Deserialize, Serialize};
+
+/// A person credited on an article, eg "Chris Mason" - "Political editor".
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Contributor {
+    pub name: String,
+    pub role: Option<String>,
+}
+
+/// An image in the body of an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Image {
+    pub src: String,
+    pub alt: Option<String>,
+    pub caption: Option<String>,
+    /// The copyright holder, eg "Getty Images".
+    pub credit: Option<String>,
+}
+
+/// A quote pulled out of the body of an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Quote {
+    pub text: String,
+    /// Who is being quoted, if the quote has a citation.
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    Video,
+    Audio,
+}
+
+/// A reference to a video or audio clip embedded in an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct EmbeddedMedia {
+    pub kind: MediaKind,
+    /// The media url or identifier, if one is present in the markup.
+    pub src: Option<String>,
+    pub caption: Option<String>,
+    /// The duration as shown on the page, eg "2:31".
+    pub duration: Option<String>,
+}
+
+/// A block of an article's body, kept in page order so the structure of the article is preserved.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum ContentBlock {
+    Paragraph(String),
+    Subheading(String),
+    Quote(Quote),
+    Image(Image),
+    Media(EmbeddedMedia),
+}
+
+/// A post in a live blog.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct LiveEntry {
+    /// When the post was made. Live pages often only show the time of day, which is kept as unparseable.
+    pub published: Timestamp,
+    pub title: Option<String>,
+    pub content: Vec<ContentBlock>,
+}
+
+/// The video on a video page.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct VideoDetails {
+    pub description: Option<String>,
+    /// The duration as shown on the page, eg "2:31".
+    pub duration: Option<String>,
+    /// The media url or identifier, if one is present in the markup.
+    pub src: Option<String>,
+}
+
+/// The parts of a page that depend on its kind.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum PageDetails {
+    /// Articles (news and sport) only have the shared fields.
+    Article,
+    Live(Vec<LiveEntry>),
+    Video(VideoDetails),
+}
+
+/// A promo card on a front or topic page, linking to a story.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct PromoCard {
+    pub headline: String,
+    pub summary: Option<String>,
+    pub url: BBCUrl,
+    /// Where the card is on the page, starting at 1 for the top story.
+    pub position: usize,
+    /// The heading of the part of the page the card is in, eg "Only from the BBC".
+    pub section: Option<String>,
+    /// The story's place in the "Most read" list, if it is in it.
+    pub most_read_rank: Option<usize>,
+    /// When the story was published, if the card shows it.
+    pub published: Timestamp,
+}
@@ -1,123 +1,847 @@
 use super::error::BBCError;
-use super::BBCUrl;
-use crate::common::{LinkTo, Page, ScrapableContent, UrlTrait};
+use super::model::{
+    ContentBlock, Contributor, EmbeddedMedia, Image, LiveEntry, MediaKind, PageDetails, Quote,
+    VideoDetails,
+};
+use super::{BBCPageKind, BBCUrl};
+use crate::common::{
+    escape_markdown, extract_page_links, normalize_text, ArticleMetadata, FetchedDocument, LinkTo,
+    Page, Renderer, ScrapableContent, StructuredData, Timestamp, UrlTrait,
+};
 use crate::Result;
 
+use chrono::{DateTime, Utc};
 use futures::stream::{self, StreamExt};
 use itertools::Itertools;
 use scraper::{ElementRef, Html};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::{collections::HashSet, rc::Rc};
 
+/// The `data-component`s of the blocks that make up the body of a page.
+const CONTENT_BLOCKS: &[&str] = &[
+    "text-block",
+    "subheadline-block",
+    "crosshead-block",
+    "quote-block",
+    "image-block",
+    "video-block",
+    "media-block",
+    "audio-block",
+];
+
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct BBCContent {
+    kind: BBCPageKind,
     title: String,
-    content: Vec<String>,
+    /// The standfirst / lead paragraph of the article.
+    summary: Option<String>,
+    byline: Vec<Contributor>,
+    /// The section of the site the article is in, eg "UK Politics".
+    section: Option<String>,
+    content: Vec<ContentBlock>,
+    /// The content rendered to Markdown from the page, so the links, emphasis and lists in the text are kept.
+    markdown: String,
+    /// The lead image of the article, as advertised to social media and search engines.
+    thumbnail: Option<String>,
+    details: PageDetails,
     metadata: Metadata,
 }
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 struct Metadata {
     related_topics: Vec<String>,
-    timestamp: String,
+    published: Timestamp,
+    /// When the article was last updated, eg an edit to a developing story.
+    updated: Timestamp,
     page_links: HashSet<Page<LinkTo, BBCUrl>>,
 }
 impl ScrapableContent for BBCContent {
     type Url = BBCUrl;
-    fn from_scraped_page(url: &Self::Url, document: &Html) -> Result<Self> {
+    fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self> {
         // TODO Break this function into smaller functions to be able to run async
         println!("Scraping article: {:?}", url);
 
-        let article = Self::extract_article(&document).ok_or(BBCError::NoArticleFound {
+        let kind = url.kind();
+        let article = match kind {
+            BBCPageKind::Article | BBCPageKind::Sport => Self::extract_article(document),
+            //? Live and video pages don't always wrap their content in an article.
+            BBCPageKind::Live | BBCPageKind::Video => {
+                Self::extract_article(document).or_else(|| Self::extract_main(document))
+            }
+        }
+        .ok_or(BBCError::NoArticleFound {
             url: url.full_url(),
         })?;
 
-        let title = Self::extract_title(&article).ok_or(BBCError::NoTitleFound {
-            url: url.full_url(),
-        })?;
+        //? The JSON-LD / Open Graph metadata is more stable than the page markup, so it is used first and the selectors are the fallback.
+        let structured_data = document.structured_data();
+        let structured = structured_data.article();
+
+        let title = structured
+            .headline
+            .clone()
+            .or_else(|| Self::extract_title(&article))
+            .ok_or(BBCError::NoTitleFound {
+                url: url.full_url(),
+            })?;
 
-        let content = Self::extract_content(&article).ok_or(BBCError::NoContentFound {
+        let (content, details) = match kind {
+            BBCPageKind::Article => (Self::extract_content(&article), PageDetails::Article),
+            BBCPageKind::Sport => (Self::extract_sport_content(&article), PageDetails::Article),
+            BBCPageKind::Live => {
+                let entries = Self::extract_live_entries(&article, &structured_data);
+                let content = (!entries.is_empty()).then(Vec::new);
+                (content, PageDetails::Live(entries))
+            }
+            BBCPageKind::Video => {
+                let video = Self::extract_video(&article, &structured_data, &structured);
+                let content = (video.description.is_some() || video.src.is_some())
+                    .then(|| Self::extract_blocks(&article));
+                (content, PageDetails::Video(video))
+            }
+        };
+        let content = content.ok_or(BBCError::NoContentFound {
             url: url.full_url(),
         })?;
 
-        // Image Selector; to Vec<Image(Url, Caption)>
+        //? Only articles are expected to have a topic list.
+        let related_topics = match kind {
+            BBCPageKind::Article => {
+                Self::extract_related_topics(&article).ok_or(BBCError::NoRelatedTopicsFound {
+                    url: url.full_url(),
+                })?
+            }
+            _ => Self::extract_related_topics(&article).unwrap_or_default(),
+        };
 
-        let related_topics =
-            Self::extract_related_topics(&article).ok_or(BBCError::NoRelatedTopicsFound {
-                url: url.full_url(),
-            })?;
+        let (page_published, page_updated) = Self::extract_timestamps(&article);
+        let published = structured.date_published.clone().or_else(|| page_published);
+        let updated = structured.date_modified.clone().or_else(|| page_updated);
 
-        let timestamp = Self::extract_timestamp(&article);
+        let page_links = extract_page_links::<BBCUrl>(&article, document);
+        //? Live pages are rendered from their entries, which aren't blocks of the article.
+        let markdown = match kind {
+            BBCPageKind::Live => String::new(),
+            _ => Self::render_content(&article, document),
+        };
 
-        let page_links = Self::extract_related_links(&article)
-            .into_iter()
-            .filter_map(|(url, title)| {
-                let url = BBCUrl::try_from(url).ok()?;
-                let title = title.clone();
-                Some(Page::<LinkTo, BBCUrl>::new(url, title))
-            })
-            .collect::<HashSet<Page<LinkTo, BBCUrl>>>();
-
-        Ok(BBCContent::new(
+        Ok(BBCContent {
+            kind,
             title,
+            summary: structured
+                .description
+                .clone()
+                .or_else(|| Self::extract_summary(document, &article)),
+            byline: Self::merge_byline(&structured, Self::extract_byline(&article)),
+            section: structured
+                .section
+                .clone()
+                .or_else(|| Self::extract_section(document)),
             content,
-            related_topics,
-            timestamp,
-            page_links,
-        ))
+            markdown,
+            thumbnail: structured.thumbnail,
+            details,
+            metadata: Metadata {
+                related_topics,
+                published,
+                updated,
+                page_links,
+            },
+        })
     }
 
     fn get_related_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
         self.metadata.page_links.clone()
+    }
+
+    fn body_text(&self) -> String {
+        let details_text = match &self.details {
+            PageDetails::Article => Vec::new(),
+            PageDetails::Live(entries) => entries
+                .iter()
+                .flat_map(|entry| {
+                    entry
+                        .title
+                        .as_deref()
+                        .into_iter()
+                        .chain(blocks_text(&entry.content))
+                })
+                .collect(),
+            PageDetails::Video(video) => video.description.as_deref().into_iter().collect(),
+        };
+        blocks_text(&self.content)
+            .chain(details_text)
+            .collect::<Vec<&str>>()
+            .join("\n\n")
+    }
 
+    fn to_markdown(&self) -> String {
+        let mut markdown = vec![format!("# {}", escape_markdown(&self.title))];
+        markdown.extend(self.summary.as_deref().map(escape_markdown));
+        if !self.markdown.is_empty() {
+            markdown.push(self.markdown.clone());
+        }
+        match &self.details {
+            PageDetails::Article => {}
+            PageDetails::Live(entries) => markdown.extend(entries.iter().map(|entry| {
+                entry
+                    .title
+                    .as_deref()
+                    .map(|title| format!("## {}", escape_markdown(title)))
+                    .into_iter()
+                    .chain(blocks_markdown(&entry.content))
+                    .collect::<Vec<String>>()
+                    .join("\n\n")
+            })),
+            //? The description is often the only text of a video page, so it isn't in the rendered blocks.
+            PageDetails::Video(video) if self.markdown.is_empty() => {
+                markdown.extend(video.description.as_deref().map(escape_markdown))
+            }
+            PageDetails::Video(_) => {}
+        }
+        markdown
+            .into_iter()
+            .filter(|block| !block.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+
+    fn published(&self) -> Option<DateTime<Utc>> {
+        self.metadata.published.datetime()
     }
+
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        self.metadata.updated.datetime()
+    }
+
+    fn topics(&self) -> Vec<String> {
+        self.metadata.related_topics.clone()
+    }
+
+    fn page_title(&self) -> Option<String> {
+        Some(self.title.clone()).filter(|title| !title.is_empty())
+    }
+}
+
+/// The text of the blocks that hold text.
+fn blocks_text(blocks: &[ContentBlock]) -> impl Iterator<Item = &str> {
+    blocks.iter().filter_map(|block| match block {
+        ContentBlock::Paragraph(text) | ContentBlock::Subheading(text) => Some(text.as_str()),
+        ContentBlock::Quote(quote) => Some(quote.text.as_str()),
+        _ => None,
+    })
+}
+
+/// Blocks that were only kept as text (eg the posts of a live page) as Markdown.
+fn blocks_markdown(blocks: &[ContentBlock]) -> impl Iterator<Item = String> + '_ {
+    blocks.iter().filter_map(|block| match block {
+        ContentBlock::Paragraph(text) => Some(escape_markdown(text)),
+        ContentBlock::Subheading(text) => Some(format!("### {}", escape_markdown(text))),
+        ContentBlock::Quote(quote) => Some(match &quote.source {
+            Some(source) => format!(
+                "> {}\n>\n> — {}",
+                escape_markdown(&quote.text),
+                escape_markdown(source)
+            ),
+            None => format!("> {}", escape_markdown(&quote.text)),
+        }),
+        ContentBlock::Image(image) => Some(image_markdown(image)),
+        ContentBlock::Media(_) => None,
+    })
+}
+
+fn image_markdown(image: &Image) -> String {
+    let mut markdown = format!(
+        "![{}]({})",
+        escape_markdown(image.alt.as_deref().unwrap_or_default()),
+        image.src
+    );
+    if let Some(caption) = &image.caption {
+        markdown.push_str(&format!("\n\n*{}*", escape_markdown(caption)));
+    }
+    markdown
+}
+
+/// Turn an ISO 8601 duration (eg "PT2M31S", as used by JSON-LD) into the clock form shown on the page (eg "2:31").
+fn iso_duration_to_clock(duration: &str) -> Option<String> {
+    let time = duration.strip_prefix("PT")?;
+    let (mut hours, mut minutes, mut seconds) = (0, 0, 0);
+    let mut number = String::new();
+    for c in time.chars() {
+        match c {
+            // Fractions of a second are parsed but not shown.
+            '0'..='9' | '.' => number.push(c),
+            'H' | 'M' | 'S' => {
+                let value = number.parse::<f64>().ok()? as u64;
+                match c {
+                    'H' => hours = value,
+                    'M' => minutes = value,
+                    _ => seconds = value,
+                }
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    Some(if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    })
+}
+
+/// The text of a list item, leaving out the items of a list nested in it, which are items of their own.
+fn list_item_text(item: &ElementRef) -> String {
+    let text = item
+        .descendants()
+        .filter(|node| {
+            node.ancestors()
+                .find(|ancestor| {
+                    ancestor
+                        .value()
+                        .as_element()
+                        .is_some_and(|element| element.name() == "li")
+                })
+                .is_some_and(|ancestor| ancestor.id() == item.id())
+        })
+        .filter_map(|node| node.value().as_text().map(|text| &**text))
+        .collect::<String>();
+    normalize_text(&text)
+}
+
+/// The text of an element with its whitespace collapsed and unicode normalized.
+pub(super) fn element_text(element: &ElementRef) -> String {
+    normalize_text(&element.text().collect::<String>())
+}
+
+/// The trimmed text of the first element matching `selector` within `element`, if it isn't empty.
+pub(super) fn select_text(element: &ElementRef, selector: &str) -> Option<String> {
+    let selector = scraper::Selector::parse(selector).unwrap();
+    element
+        .select(&selector)
+        .map(|found| element_text(&found))
+        .find(|text| !text.is_empty())
 }
 
 impl BBCContent {
-    fn new(
-        title: String,
-        content: Vec<String>,
-        related_topics: Vec<String>,
-        timestamp: String,
-        page_links: HashSet<Page<LinkTo, BBCUrl>>,
-    ) -> Self {
-        BBCContent {
-            title,
-            content,
-            metadata: Metadata {
-                related_topics,
-                timestamp,
-                page_links,
-            },
+    pub fn kind(&self) -> BBCPageKind {
+        self.kind
+    }
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+    pub fn byline(&self) -> &[Contributor] {
+        &self.byline
+    }
+    pub fn section(&self) -> Option<&str> {
+        self.section.as_deref()
+    }
+    /// The body of the article in page order.
+    pub fn content(&self) -> &[ContentBlock] {
+        &self.content
+    }
+    pub fn paragraphs(&self) -> impl Iterator<Item = &String> {
+        self.content.iter().filter_map(|block| match block {
+            ContentBlock::Paragraph(text) => Some(text),
+            _ => None,
+        })
+    }
+    pub fn subheadings(&self) -> impl Iterator<Item = &String> {
+        self.content.iter().filter_map(|block| match block {
+            ContentBlock::Subheading(text) => Some(text),
+            _ => None,
+        })
+    }
+    pub fn images(&self) -> impl Iterator<Item = &Image> {
+        self.content.iter().filter_map(|block| match block {
+            ContentBlock::Image(image) => Some(image),
+            _ => None,
+        })
+    }
+    pub fn quotes(&self) -> impl Iterator<Item = &Quote> {
+        self.content.iter().filter_map(|block| match block {
+            ContentBlock::Quote(quote) => Some(quote),
+            _ => None,
+        })
+    }
+    pub fn media(&self) -> impl Iterator<Item = &EmbeddedMedia> {
+        self.content.iter().filter_map(|block| match block {
+            ContentBlock::Media(media) => Some(media),
+            _ => None,
+        })
+    }
+    /// The posts of a live page, empty for other kinds of page.
+    pub fn live_entries(&self) -> &[LiveEntry] {
+        match &self.details {
+            PageDetails::Live(entries) => entries,
+            _ => &[],
+        }
+    }
+    pub fn video(&self) -> Option<&VideoDetails> {
+        match &self.details {
+            PageDetails::Video(video) => Some(video),
+            _ => None,
         }
     }
+    pub fn thumbnail(&self) -> Option<&str> {
+        self.thumbnail.as_deref()
+    }
+    pub fn related_topics(&self) -> &[String] {
+        &self.metadata.related_topics
+    }
+    pub fn published_timestamp(&self) -> &Timestamp {
+        &self.metadata.published
+    }
+    pub fn updated_timestamp(&self) -> &Timestamp {
+        &self.metadata.updated
+    }
 
     fn extract_article<'a>(document: &'a Html) -> Option<ElementRef<'a>> {
         let article_selector = scraper::Selector::parse("article").unwrap();
         document.select(&article_selector).next()
     }
 
-    fn extract_title<'a>(article: &'a ElementRef) -> Option<String> {
+    fn extract_main<'a>(document: &'a Html) -> Option<ElementRef<'a>> {
+        let main_selector = scraper::Selector::parse("main, #main-content").unwrap();
+        document.select(&main_selector).next()
+    }
+
+    fn extract_title(article: &ElementRef) -> Option<String> {
         let title_selector = scraper::Selector::parse("h1").unwrap();
         article
             .select(&title_selector)
             .next()
             .map(|title| title.text().collect::<String>())
     }
-    fn extract_content<'a>(article: &'a ElementRef) -> Option<Vec<String>> {
-        let content_selector =
-            scraper::Selector::parse("div[data-component='text-block']").unwrap();
-        let content = article
-            .select(&content_selector)
-            .map(|element| element.text().collect::<String>())
-            .collect::<Vec<String>>();
 
-        if content.is_empty() {
+    /// The article's blocks, as long as there is at least one paragraph.
+    fn extract_content(article: &ElementRef) -> Option<Vec<ContentBlock>> {
+        let content = Self::extract_blocks(article);
+        if content
+            .iter()
+            .any(|block| matches!(block, ContentBlock::Paragraph(_)))
+        {
+            Some(content)
+        } else {
             None
+        }
+    }
+
+    /// Sport articles use the same blocks as news articles, but older ones are plain paragraphs.
+    fn extract_sport_content(article: &ElementRef) -> Option<Vec<ContentBlock>> {
+        Self::extract_content(article).or_else(|| {
+            let paragraphs = Self::extract_paragraphs(article);
+            (!paragraphs.is_empty()).then_some(paragraphs)
+        })
+    }
+
+    /// Every non-empty paragraph in the element, for markup that doesn't use blocks.
+    fn extract_paragraphs(element: &ElementRef) -> Vec<ContentBlock> {
+        let paragraph_selector = scraper::Selector::parse("p").unwrap();
+        element
+            .select(&paragraph_selector)
+            .map(|paragraph| element_text(&paragraph))
+            .filter(|text| !text.is_empty())
+            .map(ContentBlock::Paragraph)
+            .collect()
+    }
+
+    /// The posts of a live page, falling back to the `liveBlogUpdate`s in the JSON-LD.
+    fn extract_live_entries(
+        article: &ElementRef,
+        structured_data: &StructuredData,
+    ) -> Vec<LiveEntry> {
+        let post_selector = scraper::Selector::parse(
+            "[data-testid='content-post'], article.lx-stream-post, .lx-stream-post",
+        )
+        .unwrap();
+        let time_selector = scraper::Selector::parse("time[datetime]").unwrap();
+        let entries = article
+            .select(&post_selector)
+            .map(|post| {
+                let published = post
+                    .select(&time_selector)
+                    .next()
+                    .and_then(|time| time.value().attr("datetime"))
+                    .map(|datetime| Timestamp::parse(Some(datetime)))
+                    .unwrap_or_else(|| {
+                        Timestamp::parse(
+                            select_text(&post, "[data-testid='timestamp'], .qa-post-auto-meta")
+                                .as_deref(),
+                        )
+                    });
+                let content = Self::extract_blocks(&post);
+                LiveEntry {
+                    published,
+                    title: select_text(&post, "h3, h2, .lx-stream-post__header-text"),
+                    content: if content.is_empty() {
+                        Self::extract_paragraphs(&post)
+                    } else {
+                        content
+                    },
+                }
+            })
+            .filter(|entry| entry.title.is_some() || !entry.content.is_empty())
+            .collect::<Vec<LiveEntry>>();
+        if !entries.is_empty() {
+            return entries;
+        }
+
+        let updates = structured_data
+            .find_json_ld(&["LiveBlogPosting"])
+            .and_then(|live| live.get("liveBlogUpdate"))
+            .and_then(Value::as_array);
+        updates
+            .into_iter()
+            .flatten()
+            .map(|update| {
+                let text = |key: &str| {
+                    update
+                        .get(key)
+                        .and_then(Value::as_str)
+                        .map(|text| text.trim().to_string())
+                        .filter(|text| !text.is_empty())
+                };
+                LiveEntry {
+                    published: Timestamp::parse(text("datePublished").as_deref()),
+                    title: text("headline"),
+                    content: text("articleBody")
+                        .map(ContentBlock::Paragraph)
+                        .into_iter()
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// The video of a video page, from the video block with the `VideoObject` JSON-LD as a fallback.
+    fn extract_video(
+        article: &ElementRef,
+        structured_data: &StructuredData,
+        structured: &ArticleMetadata,
+    ) -> VideoDetails {
+        let video_object = structured_data.find_json_ld(&["VideoObject"]);
+        let video_text = |key: &str| {
+            video_object
+                .and_then(|video| video.get(key))
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let video_block_selector = scraper::Selector::parse(
+            "[data-component='video-block'], [data-testid='video-page-video-section']",
+        )
+        .unwrap();
+        let media = article
+            .select(&video_block_selector)
+            .next()
+            .and_then(|block| Self::extract_media(&block));
+
+        VideoDetails {
+            description: select_text(
+                article,
+                "[data-testid='video-page-description'], .story-body__introduction",
+            )
+            .or_else(|| video_text("description"))
+            .or_else(|| structured.description.clone()),
+            duration: media
+                .as_ref()
+                .and_then(|media| media.duration.clone())
+                .or_else(|| {
+                    video_text("duration").and_then(|duration| iso_duration_to_clock(&duration))
+                }),
+            src: media
+                .and_then(|media| media.src)
+                .or_else(|| video_text("contentUrl"))
+                .or_else(|| video_text("embedUrl")),
+        }
+    }
+
+    /// The blocks (`data-component` elements) of an element in page order.
+    //? Blocks can be nested, eg a text block holding a quote block, so only the outermost are walked and each block extracts what is inside it.
+    fn top_level_blocks<'a>(element: &ElementRef<'a>) -> Vec<(&'a str, ElementRef<'a>)> {
+        let block_selector = scraper::Selector::parse("[data-component]").unwrap();
+        let mut matched = HashSet::new();
+        let mut blocks = Vec::new();
+        for block in element.select(&block_selector) {
+            let kind = block.value().attr("data-component").unwrap_or_default();
+            if !CONTENT_BLOCKS.contains(&kind)
+                || block.ancestors().any(|node| matched.contains(&node.id()))
+            {
+                continue;
+            }
+            matched.insert(block.id());
+            blocks.push((kind, block));
+        }
+        blocks
+    }
+
+    /// Walk the blocks of an element in page order.
+    fn extract_blocks(article: &ElementRef) -> Vec<ContentBlock> {
+        let mut content = Vec::new();
+        for (kind, block) in Self::top_level_blocks(article) {
+            match kind {
+                "text-block" => content.extend(Self::extract_text_block(&block)),
+                "subheadline-block" | "crosshead-block" => {
+                    let text = element_text(&block);
+                    if !text.is_empty() {
+                        content.push(ContentBlock::Subheading(text));
+                    }
+                }
+                "quote-block" => {
+                    content.extend(Self::extract_quote(&block).map(ContentBlock::Quote))
+                }
+                "image-block" => {
+                    content.extend(Self::extract_image(&block).map(ContentBlock::Image))
+                }
+                "video-block" | "media-block" | "audio-block" => {
+                    content.extend(Self::extract_media(&block).map(ContentBlock::Media))
+                }
+                _ => {}
+            }
+        }
+        content
+    }
+
+    /// Render the text, subheading, quote and image blocks of an element to Markdown, in page order.
+    /// Older sport articles don't use blocks, so their paragraphs are rendered instead.
+    fn render_content(article: &ElementRef, document: &FetchedDocument) -> String {
+        let renderer = Renderer::markdown().with_base_url(&document.base_url());
+        let mut markdown = Vec::new();
+        for (kind, block) in Self::top_level_blocks(article) {
+            match kind {
+                "text-block" | "quote-block" => markdown.push(renderer.render(&block)),
+                "subheadline-block" | "crosshead-block" => {
+                    let text = element_text(&block);
+                    if !text.is_empty() {
+                        markdown.push(format!("## {}", escape_markdown(&text)));
+                    }
+                }
+                "image-block" => {
+                    markdown.extend(Self::extract_image(&block).map(|image| image_markdown(&image)))
+                }
+                _ => {}
+            }
+        }
+        if markdown.is_empty() {
+            let paragraph_selector = scraper::Selector::parse("p").unwrap();
+            markdown.push(renderer.render_all(article.select(&paragraph_selector)));
+        }
+        markdown
+            .into_iter()
+            .filter(|block| !block.is_empty())
+            .collect::<Vec<String>>()
+            .join("\n\n")
+    }
+
+    /// A text block is usually a single paragraph but can hold several, a list, or a quote between paragraphs.
+    fn extract_text_block(block: &ElementRef) -> Vec<ContentBlock> {
+        let text_selector = scraper::Selector::parse("blockquote, p, li").unwrap();
+        let mut content = Vec::new();
+        for element in block.select(&text_selector) {
+            //? The text of a quote, or of a paragraph in a list item, is taken with the quote or the list item.
+            let inside = |names: &[&str]| {
+                element
+                    .ancestors()
+                    .take_while(|node| node.id() != block.id())
+                    .filter_map(|node| node.value().as_element())
+                    .any(|ancestor| names.contains(&ancestor.name()))
+            };
+            if inside(&["blockquote"]) {
+                continue;
+            }
+            match element.value().name() {
+                "blockquote" => content.push(ContentBlock::Quote(Self::quote_of(&element))),
+                "p" if inside(&["li"]) => {}
+                name => {
+                    let text = if name == "li" {
+                        list_item_text(&element)
+                    } else {
+                        element_text(&element)
+                    };
+                    if !text.is_empty() {
+                        content.push(ContentBlock::Paragraph(text));
+                    }
+                }
+            }
+        }
+        if !content.is_empty() {
+            return content;
+        }
+        let text = element_text(block);
+        if text.is_empty() {
+            Vec::new()
         } else {
-            Some(content)
+            vec![ContentBlock::Paragraph(text)]
         }
     }
-    fn extract_related_topics<'a>(article: &'a ElementRef) -> Option<Vec<String>> {
+
+    fn extract_quote(block: &ElementRef) -> Option<Quote> {
+        let quote_selector = scraper::Selector::parse("blockquote").unwrap();
+        block
+            .select(&quote_selector)
+            .next()
+            .map(|quote| Self::quote_of(&quote))
+    }
+
+    fn quote_of(quote: &ElementRef) -> Quote {
+        let source = select_text(quote, "cite, footer");
+        let mut text = element_text(quote);
+        if let Some(source) = &source {
+            text = text.trim_end_matches(source.as_str()).trim().to_string();
+        }
+        Quote { text, source }
+    }
+
+    fn extract_image(block: &ElementRef) -> Option<Image> {
+        let image_selector = scraper::Selector::parse("img").unwrap();
+        let image = block.select(&image_selector).next()?;
+        // Lazy loaded images keep the real url in data-src
+        let src = image
+            .value()
+            .attr("src")
+            .or_else(|| image.value().attr("data-src"))?
+            .to_string();
+        Some(Image {
+            src,
+            alt: image
+                .value()
+                .attr("alt")
+                .map(str::trim)
+                .filter(|alt| !alt.is_empty())
+                .map(str::to_string),
+            caption: select_text(block, "figcaption"),
+            credit: select_text(
+                block,
+                "[data-testid='image-copyright'], [class*='Copyright'], [class*='copyright']",
+            ),
+        })
+    }
+
+    fn extract_media(block: &ElementRef) -> Option<EmbeddedMedia> {
+        let audio_selector = scraper::Selector::parse("audio").unwrap();
+        let kind = if block.value().attr("data-component") == Some("audio-block")
+            || block.select(&audio_selector).next().is_some()
+        {
+            MediaKind::Audio
+        } else {
+            MediaKind::Video
+        };
+        let source_selector =
+            scraper::Selector::parse("video[src], audio[src], source[src], iframe[src]").unwrap();
+        let src = block
+            .select(&source_selector)
+            .next()
+            .and_then(|source| source.value().attr("src"))
+            .or_else(|| block.value().attr("data-media-id"))
+            .map(str::to_string);
+        let caption = select_text(block, "figcaption, [data-testid='caption']");
+        let duration = select_text(block, "time, [data-testid='duration']");
+        if src.is_none() && caption.is_none() {
+            return None;
+        }
+        Some(EmbeddedMedia {
+            kind,
+            src,
+            caption,
+            duration,
+        })
+    }
+
+    /// The lead paragraph is bold in the first text block, otherwise fall back to the meta description.
+    fn extract_summary(document: &Html, article: &ElementRef) -> Option<String> {
+        let first_block_selector =
+            scraper::Selector::parse("div[data-component='text-block']").unwrap();
+        let lead = article
+            .select(&first_block_selector)
+            .next()
+            .and_then(|block| select_text(&block, "p > b, p > strong"));
+        lead.or_else(|| {
+            let description_selector =
+                scraper::Selector::parse("meta[name='description']").unwrap();
+            document
+                .select(&description_selector)
+                .next()
+                .and_then(|meta| meta.value().attr("content"))
+                .map(|content| content.trim().to_string())
+                .filter(|content| !content.is_empty())
+        })
+    }
+
+    /// Contributors are listed in the byline block as a name followed by an optional role.
+    fn extract_byline(article: &ElementRef) -> Vec<Contributor> {
+        let byline_selector = scraper::Selector::parse(
+            "[data-component='byline-block'], [data-testid='byline-new-contributors']",
+        )
+        .unwrap();
+        let Some(byline) = article.select(&byline_selector).next() else {
+            return Vec::new();
+        };
+        let contributor_selector = scraper::Selector::parse("li").unwrap();
+        let contributors = byline.select(&contributor_selector).collect::<Vec<_>>();
+        let contributors = if contributors.is_empty() {
+            vec![byline]
+        } else {
+            contributors
+        };
+
+        contributors
+            .iter()
+            .filter_map(|contributor| {
+                let mut lines = contributor
+                    .text()
+                    .map(str::trim)
+                    .filter(|text| !text.is_empty())
+                    .map(|text| text.trim_start_matches("By ").to_string())
+                    .unique();
+                let name = lines.next()?;
+                Some(Contributor {
+                    name,
+                    role: lines.next(),
+                })
+            })
+            .collect()
+    }
+
+    /// The structured data authors are the reliable list of names, the byline block adds their roles.
+    fn merge_byline(structured: &ArticleMetadata, byline: Vec<Contributor>) -> Vec<Contributor> {
+        if structured.authors.is_empty() {
+            return byline;
+        }
+        // Articles without a named author credit the organisation, which isn't a contributor.
+        let contributors = structured
+            .authors
+            .iter()
+            .filter(|name| !name.starts_with("BBC"))
+            .map(|name| Contributor {
+                name: name.clone(),
+                role: byline
+                    .iter()
+                    .find(|contributor| contributor.name.eq_ignore_ascii_case(name))
+                    .and_then(|contributor| contributor.role.clone()),
+            })
+            .collect::<Vec<Contributor>>();
+        if contributors.is_empty() {
+            byline
+        } else {
+            contributors
+        }
+    }
+
+    fn extract_section(document: &Html) -> Option<String> {
+        let section_selector =
+            scraper::Selector::parse("meta[property='article:section']").unwrap();
+        document
+            .select(&section_selector)
+            .next()
+            .and_then(|meta| meta.value().attr("content"))
+            .map(|section| section.trim().to_string())
+            .filter(|section| !section.is_empty())
+    }
+
+    fn extract_related_topics(article: &ElementRef) -> Option<Vec<String>> {
         let related_topics_selector =
             scraper::Selector::parse("div[data-component='topic-list']").unwrap();
         let related_topics = article.select(&related_topics_selector).next()?;
@@ -130,26 +854,39 @@ impl BBCContent {
                 .collect::<Vec<String>>(),
         )
     }
-    fn extract_related_links(article: &ElementRef) -> Vec<(String, String)> {
-        let related_links_selector = scraper::Selector::parse("a").unwrap();
-        article
-            .select(&related_links_selector)
-            .filter_map(|element| {
-                let url = element.value().attr("href")?.to_string();
-                let text = element.text().collect::<String>();
-                Some((url, text))
-            })
-            .collect::<Vec<(String, String)>>()
-    }
-    fn extract_timestamp(article: &ElementRef) -> String {
-        // <time data-testid="timestamp" datetime="2024-06-10T06:58:21.378Z">10 June 2024, 07:58 BST</time>
-        // I want to extract out the datetime attribute
+    /// The published and updated times shown on the page.
+    // <time data-testid="timestamp" datetime="2024-06-10T06:58:21.378Z">10 June 2024, 07:58 BST</time>
+    // Edited articles show a second time after an "Updated" label.
+    fn extract_timestamps(article: &ElementRef) -> (Timestamp, Timestamp) {
         let timestamp_selector = scraper::Selector::parse("time").unwrap();
-        article
+        let (updated, published): (Vec<ElementRef>, Vec<ElementRef>) = article
             .select(&timestamp_selector)
-            .next()
-            .and_then(|element| element.value().attr("datetime"))
-            .unwrap_or_default()
-            .to_string()
+            .partition(Self::is_updated_marker);
+        let timestamp = |times: &[ElementRef]| {
+            times
+                .first()
+                .map(|time| {
+                    // Fall back to the displayed text if there is no datetime attribute.
+                    Timestamp::parse(Some(
+                        &time
+                            .value()
+                            .attr("datetime")
+                            .map(str::to_string)
+                            .unwrap_or_else(|| element_text(time)),
+                    ))
+                })
+                .unwrap_or_default()
+        };
+        (timestamp(&published), timestamp(&updated))
+    }
+
+    /// Whether a time element is labelled "Updated", either in its own text or the element wrapping it.
+    fn is_updated_marker(time: &ElementRef) -> bool {
+        let is_updated = |text: String| text.trim().to_lowercase().starts_with("updated");
+        is_updated(element_text(time))
+            || time
+                .parent()
+                .and_then(ElementRef::wrap)
+                .is_some_and(|parent| is_updated(element_text(&parent)))
     }
 }
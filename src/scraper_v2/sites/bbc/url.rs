@@ -1,4 +1,4 @@
-use crate::common::UrlTrait;
+use crate::common::{RevisitPolicy, UrlTrait};
 use crate::{Error, Result};
 
 use super::error::BBCError;
@@ -12,6 +12,55 @@ use std::hash::{Hash, Hasher};
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct BBCUrl(String);
 
+/// The kinds of BBC page we know how to scrape. Each has its own content extractor.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BBCPageKind {
+    /// A news article, eg `/news/articles/c0000000000o` or `/news/uk-politics-12345678`.
+    Article,
+    /// A live blog, eg `/news/live/world-12345678` or `/sport/football/live/c0000000000t`.
+    Live,
+    /// A video page, eg `/news/av/uk-12345678` or `/news/videos/c0000000000o`.
+    Video,
+    /// A sport article, eg `/sport/football/articles/c0000000000o` or `/sport/tennis/12345678`.
+    Sport,
+}
+
+impl BBCPageKind {
+    /// Work out the kind of page from the path of a url. Returns None for pages that aren't content (section fronts, topic pages etc).
+    pub fn classify(path: &str) -> Option<Self> {
+        let path = path.split(['?', '#']).next().unwrap_or(path);
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<&str>>();
+        let ends_with_id = segments.last().is_some_and(|last_segment| {
+            last_segment
+                .split('-')
+                .next_back()
+                .is_some_and(|last_word| last_word.len() == 8 && last_word.parse::<u32>().is_ok())
+        });
+        let has_article_id = segments
+            .windows(2)
+            .any(|pair| pair[0] == "articles" || pair[0] == "videos")
+            || ends_with_id;
+
+        match segments.as_slice() {
+            [_, .., "live", _] => Some(BBCPageKind::Live),
+            ["news", "av" | "videos", _, ..] if has_article_id => Some(BBCPageKind::Video),
+            ["news", ..] if has_article_id => Some(BBCPageKind::Article),
+            ["sport", _, ..] if has_article_id => Some(BBCPageKind::Sport),
+            _ => None,
+        }
+    }
+}
+
+impl BBCUrl {
+    pub fn kind(&self) -> BBCPageKind {
+        //? Urls are only constructed once they have been classified.
+        BBCPageKind::classify(&self.0).unwrap_or(BBCPageKind::Article)
+    }
+}
+
 impl AsRef<String> for BBCUrl {
     fn as_ref(&self) -> &String {
         &self.0
@@ -20,7 +69,7 @@ impl AsRef<String> for BBCUrl {
 impl TryFrom<String> for BBCUrl {
     type Error = Error;
     fn try_from(url: String) -> Result<Self> {
-        match Self::parse_url(&url) {
+        match Self::parse_canonical(&url) {
             Ok(url) => Ok(BBCUrl(url)),
             Err(e) => Err(e),
         }
@@ -43,19 +92,38 @@ impl UrlTrait for BBCUrl {
     fn base_url() -> &'static str {
         "https://www.bbc.co.uk"
     }
+    fn host_aliases() -> &'static [&'static str] {
+        //? The international edition serves the same pages as the UK one, so they are crawled as one site.
+        &[
+            "bbc.co.uk",
+            "m.bbc.co.uk",
+            "bbc.com",
+            "www.bbc.com",
+            "m.bbc.com",
+        ]
+    }
+    /// News articles change a lot in the first day or two (corrections, live updates) and then settle.
+    fn revisit_policy() -> RevisitPolicy {
+        RevisitPolicy {
+            min_revisit_interval: chrono::Duration::hours(3),
+            settled_after: Some(chrono::Duration::days(2)),
+            max_age: chrono::Duration::days(7),
+        }
+    }
     fn to_string(&self) -> String {
         self.0.clone()
     }
     fn parse_url(url: &str) -> Result<String> {
         let stripped_url = url.trim().strip_prefix(Self::base_url()).unwrap_or(url);
 
-        if !stripped_url.starts_with("/news") || regex_is_match!(r"/(Special:|File:)", stripped_url)
+        if !(stripped_url.starts_with("/news") || stripped_url.starts_with("/sport"))
+            || regex_is_match!(r"/(Special:|File:)", stripped_url)
         {
             let reason = {
-                if !stripped_url.starts_with("/news") {
-                    "Does not start with /news"
-                } else {
+                if regex_is_match!(r"/(Special:|File:)", stripped_url) {
                     "Contains Special: or File:"
+                } else {
+                    "Does not start with /news or /sport"
                 }
                 .to_string()
             };
@@ -67,31 +135,183 @@ impl UrlTrait for BBCUrl {
             .into());
         };
 
-        let segments: Vec<&str> = stripped_url.split("/").collect();
-        let ends_with_number = segments.last().map_or(false, |last_segment| {
-            last_segment.split('-').last().map_or(false, |last_word| {
-                last_word.len() == 8 && last_word.parse::<u32>().is_ok()
-            })
-        });
-        let has_article_or_number = segments
-            .get(2)
-            .map_or(false, |&segment| segment == "articles" || ends_with_number);
-        let contains_live_segment = segments.iter().any(|&segment| segment == "live");
-
-        if !has_article_or_number || contains_live_segment {
-            let reason = if !has_article_or_number {
-                "Does not contain article or number"
-            } else {
-                "Contains live segment"
-            }
-            .to_string();
-            Err(BBCError::InvalidUrl {
+        match BBCPageKind::classify(stripped_url) {
+            Some(_) => Ok(stripped_url.to_string()),
+            None => Err(BBCError::InvalidUrl {
                 url: url.to_string(),
-                reason,
+                reason: "Not an article, live, video or sport page".to_string(),
             }
-            .into())
+            .into()),
+        }
+    }
+}
+
+/// A BBC front page, section front or topic page, eg `/news`, `/news/uk`, `/sport/football` or `/news/topics/c0000000000t`.
+/// These are the pages that list articles rather than being one.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BBCIndexUrl(String);
+
+impl BBCIndexUrl {
+    /// Whether this is a topic page (`/news/topics/...`), rather than a front page.
+    pub fn is_topic(&self) -> bool {
+        self.0.contains("/topics/")
+    }
+}
+
+impl AsRef<String> for BBCIndexUrl {
+    fn as_ref(&self) -> &String {
+        &self.0
+    }
+}
+impl TryFrom<String> for BBCIndexUrl {
+    type Error = Error;
+    fn try_from(url: String) -> Result<Self> {
+        match Self::parse_canonical(&url) {
+            Ok(url) => Ok(BBCIndexUrl(url)),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl From<BBCIndexUrl> for String {
+    fn from(url: BBCIndexUrl) -> String {
+        url.0
+    }
+}
+
+impl Hash for BBCIndexUrl {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_ref().hash(state);
+    }
+}
+
+impl UrlTrait for BBCIndexUrl {
+    fn base_url() -> &'static str {
+        BBCUrl::base_url()
+    }
+    fn host_aliases() -> &'static [&'static str] {
+        BBCUrl::host_aliases()
+    }
+    /// Fronts are rebuilt all day, so they are always worth refetching after a short while.
+    fn revisit_policy() -> RevisitPolicy {
+        RevisitPolicy {
+            min_revisit_interval: chrono::Duration::minutes(15),
+            settled_after: None,
+            max_age: chrono::Duration::hours(1),
+        }
+    }
+    fn to_string(&self) -> String {
+        self.0.clone()
+    }
+    fn parse_url(url: &str) -> Result<String> {
+        let stripped_url = url.trim().strip_prefix(Self::base_url()).unwrap_or(url);
+        let path = stripped_url
+            .split(['?', '#'])
+            .next()
+            .unwrap_or(stripped_url);
+
+        let reason = if !(path == "/news"
+            || path == "/sport"
+            || path.starts_with("/news/")
+            || path.starts_with("/sport/"))
+        {
+            Some("Does not start with /news or /sport")
+        } else if BBCPageKind::classify(path).is_some() {
+            Some("Is an article, live, video or sport page")
+        } else if path
+            .rsplit('/')
+            .next()
+            .is_some_and(|last| last.contains('.'))
+        {
+            //? eg /news/rss.xml or /news/10628494.stm
+            Some("Is a file rather than a page")
         } else {
-            Ok(stripped_url.to_string())
+            None
+        };
+
+        match reason {
+            Some(reason) => Err(BBCError::InvalidUrl {
+                url: url.to_string(),
+                reason: reason.to_string(),
+            }
+            .into()),
+            None => Ok(stripped_url.to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_are_classified_by_their_path() {
+        let cases = [
+            ("/news/articles/c0000000000o", Some(BBCPageKind::Article)),
+            ("/news/uk-politics-12345678", Some(BBCPageKind::Article)),
+            (
+                "/news/articles/c0000000000o?at_medium=RSS#comments",
+                Some(BBCPageKind::Article),
+            ),
+            ("/news/live/world-12345678", Some(BBCPageKind::Live)),
+            ("/news/live/c0000000000t", Some(BBCPageKind::Live)),
+            ("/sport/football/live/c0000000000t", Some(BBCPageKind::Live)),
+            ("/news/av/uk-12345678", Some(BBCPageKind::Video)),
+            ("/news/videos/c0000000000o", Some(BBCPageKind::Video)),
+            (
+                "/sport/football/articles/c0000000000o",
+                Some(BBCPageKind::Sport),
+            ),
+            ("/sport/tennis/12345678", Some(BBCPageKind::Sport)),
+            ("/news/topics/c0000000000t", None),
+            ("/news/uk", None),
+            ("/news", None),
+            ("/sport/football", None),
+            ("/weather/2643743", None),
+        ];
+        for (path, kind) in cases {
+            assert_eq!(BBCPageKind::classify(path), kind, "{path}");
+        }
+    }
+
+    #[test]
+    fn bbc_com_urls_are_the_same_pages_as_bbc_co_uk() {
+        let cases = [
+            "https://www.bbc.co.uk/news/articles/c0000000000o",
+            "https://bbc.co.uk/news/articles/c0000000000o",
+            "https://www.bbc.com/news/articles/c0000000000o",
+            "https://bbc.com/news/articles/c0000000000o",
+            "https://m.bbc.com/news/articles/c0000000000o",
+            "/news/articles/c0000000000o",
+        ];
+        for url in cases {
+            assert_eq!(
+                BBCUrl::parse(url).unwrap().full_url(),
+                "https://www.bbc.co.uk/news/articles/c0000000000o",
+                "{url}"
+            );
+        }
+    }
+
+    #[test]
+    fn pages_that_are_not_content_are_rejected() {
+        let cases = [
+            "https://www.bbc.co.uk/news/topics/c0000000000t",
+            "https://www.bbc.com/news/uk",
+            "https://www.bbc.co.uk/weather/2643743",
+            "https://www.bbc.co.uk/iplayer/episode/p0000000",
+            "https://www.example.com/news/articles/c0000000000o",
+        ];
+        for url in cases {
+            assert!(BBCUrl::parse(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn kind_follows_the_path() {
+        let live = BBCUrl::parse("https://www.bbc.com/news/live/world-12345678").unwrap();
+        assert_eq!(live.kind(), BBCPageKind::Live);
+        let video = BBCUrl::parse("https://www.bbc.co.uk/news/av/uk-12345678").unwrap();
+        assert_eq!(video.kind(), BBCPageKind::Video);
+    }
+}
@@ -1,4 +1,5 @@
 pub use super::bbc::error::*;
+pub use super::generic::error::*;
 pub use super::wikipedia::error::*;
 
 #[derive(Debug, derive_more::From)]
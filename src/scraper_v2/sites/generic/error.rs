@@ -0,0 +1,25 @@
+#[derive(Debug, derive_more::From)]
+pub enum GenericError {
+    #[from]
+    Custom(String),
+    InvalidUrl {
+        url: String,
+        reason: String,
+    },
+    NoTitleFound {
+        url: String,
+    },
+    /// Nothing on the page scored as the main content and it has no links to follow, eg a page that is rendered by scripts.
+    NoContentFound {
+        url: String,
+    },
+}
+
+// Error boilerplate.
+impl core::fmt::Display for GenericError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{self:?}")
+    }
+}
+
+impl std::error::Error for GenericError {}
@@ -0,0 +1,8 @@
+pub mod error;
+pub mod model;
+mod page;
+mod readability;
+mod url;
+
+pub use page::GenericArticleContent;
+pub use url::GenericUrl;
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// A block of the main content of a page, kept in page order.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum ArticleBlock {
+    /// A heading within the content, with its level (2 for an `h2`).
+    Heading {
+        level: u8,
+        text: String,
+    },
+    Paragraph(String),
+    ListItem(String),
+    Quote(String),
+    /// Preformatted text, eg a code sample. Whitespace is kept as it is.
+    Preformatted(String),
+    Image {
+        src: String,
+        alt: Option<String>,
+    },
+}
+
+impl ArticleBlock {
+    /// The text of the block, images have none.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            ArticleBlock::Heading { text, .. }
+            | ArticleBlock::Paragraph(text)
+            | ArticleBlock::ListItem(text)
+            | ArticleBlock::Quote(text)
+            | ArticleBlock::Preformatted(text) => Some(text),
+            ArticleBlock::Image { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,473 @@
+use super::error::GenericError;
+use super::model::ArticleBlock;
+use super::readability::{is_inline_only, is_skipped, link_density, main_content, visible_text};
+use super::GenericUrl;
+use crate::common::{
+    escape_markdown, extract_page_links, ArticleMetadata, FetchedDocument, LinkTo, Page, Renderer,
+    ScrapableContent, Timestamp, UrlTrait,
+};
+use crate::Result;
+
+use chrono::{DateTime, Utc};
+use lazy_regex::regex_is_match;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// What sites put between the title of the page and their name in `<title>`, eg "Title | Site".
+const TITLE_SEPARATORS: &[&str] = &[" | ", " - ", " – ", " — ", " :: ", " · ", " » "];
+
+/// Blocks with more of their text in links than this are link lists (related stories, tags), not content.
+const MAX_BLOCK_LINK_DENSITY: f64 = 0.5;
+
+/// The main content of a page on a site without its own extractor, found by scoring the page rather than by selectors.
+///
+/// The title, byline, dates and lead image come from the page's structured data (JSON-LD, Open Graph etc) when it has any, and from the markup otherwise.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct GenericArticleContent {
+    title: String,
+    byline: Vec<String>,
+    /// The summary the page gives of itself, eg its meta description.
+    description: Option<String>,
+    /// The name of the site, eg "The Guardian".
+    site_name: Option<String>,
+    /// The language of the page from `<html lang>`, eg "en-GB".
+    language: Option<String>,
+    lead_image: Option<String>,
+    content: Vec<ArticleBlock>,
+    /// The content rendered to Markdown, keeping the links and emphasis of the text.
+    markdown: String,
+    published: Timestamp,
+    updated: Timestamp,
+    page_links: HashSet<Page<LinkTo, GenericUrl>>,
+}
+
+impl ScrapableContent for GenericArticleContent {
+    type Url = GenericUrl;
+    fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self> {
+        let content_elements = main_content(document);
+        let base = url::Url::parse(&document.base_url()).ok();
+        let renderer = Renderer::markdown()
+            .with_base_url(&document.base_url())
+            .with_skip(is_skipped);
+        let mut blocks = Blocks::default();
+        for element in &content_elements {
+            Self::push_blocks(element, base.as_ref(), &renderer, &mut blocks);
+        }
+        let Blocks {
+            blocks: mut content,
+            mut markdown,
+        } = blocks;
+
+        //? Links are taken from the whole page, so a crawl can get from index pages to articles, but only those on the same site are followed.
+        let body_selector = Selector::parse("body").unwrap();
+        let page_links: HashSet<Page<LinkTo, GenericUrl>> = document
+            .select(&body_selector)
+            .next()
+            .map(|body| extract_page_links::<GenericUrl>(&body, document))
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|link| link.as_ref().same_host(url))
+            .collect();
+        //? A page without an article (a front page, a section or tag index) is still scraped for its links.
+        let has_body = content.iter().any(|block| block.text().is_some());
+        if !has_body && page_links.is_empty() {
+            return Err(GenericError::NoContentFound {
+                url: url.full_url(),
+            }
+            .into());
+        }
+
+        let structured = document.structured_data().article();
+        let title = match structured
+            .headline
+            .clone()
+            .or_else(|| Self::extract_heading(document, &content_elements))
+            .or_else(|| Self::extract_title(document))
+        {
+            Some(title) => title,
+            None if !has_body => String::new(),
+            None => {
+                return Err(GenericError::NoTitleFound {
+                    url: url.full_url(),
+                }
+                .into())
+            }
+        };
+        //? The title is usually repeated as the first heading of the content.
+        if let Some(index) = content
+            .iter()
+            .position(|block| matches!(block, ArticleBlock::Heading { text, .. } if *text == title))
+        {
+            content.remove(index);
+            markdown.remove(index);
+        }
+
+        let lead_image = structured
+            .thumbnail
+            .clone()
+            .or_else(|| {
+                Self::meta(
+                    document,
+                    "meta[property='og:image'], meta[name='twitter:image']",
+                )
+            })
+            .or_else(|| {
+                content.iter().find_map(|block| match block {
+                    ArticleBlock::Image { src, .. } => Some(src.clone()),
+                    _ => None,
+                })
+            });
+
+        let published = structured
+            .date_published
+            .clone()
+            .or_else(|| Self::extract_published(document, &content_elements));
+
+        Ok(GenericArticleContent {
+            title,
+            byline: Self::extract_byline(document, &structured),
+            description: structured
+                .description
+                .clone()
+                .or_else(|| Self::meta(document, "meta[name='description']")),
+            site_name: Self::meta(document, "meta[property='og:site_name']"),
+            language: Self::extract_language(document),
+            lead_image,
+            content,
+            markdown: markdown.join("\n\n"),
+            published,
+            updated: structured.date_modified,
+            page_links,
+        })
+    }
+
+    fn get_related_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
+        self.page_links.clone()
+    }
+
+    fn body_text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(ArticleBlock::text)
+            .collect::<Vec<&str>>()
+            .join("\n\n")
+    }
+
+    fn to_markdown(&self) -> String {
+        if self.title.is_empty() {
+            return self.markdown.clone();
+        }
+        format!("# {}\n\n{}", escape_markdown(&self.title), self.markdown)
+    }
+
+    fn page_title(&self) -> Option<String> {
+        Some(self.title.clone()).filter(|title| !title.is_empty())
+    }
+
+    fn published(&self) -> Option<DateTime<Utc>> {
+        self.published.datetime()
+    }
+
+    fn updated(&self) -> Option<DateTime<Utc>> {
+        self.updated.datetime()
+    }
+}
+
+impl GenericArticleContent {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    pub fn byline(&self) -> &[String] {
+        &self.byline
+    }
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+    pub fn site_name(&self) -> Option<&str> {
+        self.site_name.as_deref()
+    }
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+    pub fn lead_image(&self) -> Option<&str> {
+        self.lead_image.as_deref()
+    }
+    pub fn content(&self) -> &[ArticleBlock] {
+        &self.content
+    }
+    pub fn published_timestamp(&self) -> &Timestamp {
+        &self.published
+    }
+    pub fn updated_timestamp(&self) -> &Timestamp {
+        &self.updated
+    }
+
+    /// Add the blocks of a content element, in page order. Skipped elements and link lists are left out.
+    fn push_blocks(
+        element: &ElementRef,
+        base: Option<&url::Url>,
+        renderer: &Renderer,
+        blocks: &mut Blocks,
+    ) {
+        //? The byline and dates are kept in their own fields.
+        if is_skipped(element)
+            || element.value().name() == "time"
+            || element
+                .value()
+                .attr("class")
+                .is_some_and(|class| regex_is_match!(r"(?i)byline|author|dateline", class))
+        {
+            return;
+        }
+        let name = element.value().name();
+        let is_link_list = || link_density(element) > MAX_BLOCK_LINK_DENSITY;
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let text = visible_text(element);
+                if !text.is_empty() {
+                    blocks.push(
+                        ArticleBlock::Heading {
+                            level: name[1..].parse().unwrap_or(2),
+                            text,
+                        },
+                        renderer.render(element),
+                    );
+                }
+            }
+            "pre" => {
+                let text = element.text().collect::<String>();
+                if !text.trim().is_empty() {
+                    blocks.push(
+                        ArticleBlock::Preformatted(text.trim_matches('\n').to_string()),
+                        renderer.render(element),
+                    );
+                }
+            }
+            "blockquote" => {
+                let text = visible_text(element);
+                if !text.is_empty() {
+                    blocks.push(ArticleBlock::Quote(text), renderer.render(element));
+                }
+            }
+            "li" => {
+                let text = visible_text(element);
+                if !text.is_empty() && !is_link_list() {
+                    blocks.push(
+                        ArticleBlock::ListItem(text),
+                        format!("- {}", renderer.render(element)),
+                    );
+                }
+            }
+            "img" => blocks.push_image(Self::extract_image(element, base)),
+            "figure" => {
+                let image_selector = Selector::parse("img").unwrap();
+                let caption_selector = Selector::parse("figcaption").unwrap();
+                let caption = element
+                    .select(&caption_selector)
+                    .next()
+                    .map(|caption| visible_text(&caption))
+                    .filter(|caption| !caption.is_empty());
+                blocks.push_image(
+                    element
+                        .select(&image_selector)
+                        .next()
+                        .and_then(|image| Self::extract_image(&image, base))
+                        .map(|image| match image {
+                            ArticleBlock::Image { src, alt } => ArticleBlock::Image {
+                                src,
+                                alt: alt.or(caption),
+                            },
+                            block => block,
+                        }),
+                );
+            }
+            //? Data tables are only noise without the grid, layout tables are walked like any other container.
+            "table"
+                if element
+                    .select(&Selector::parse("th").unwrap())
+                    .next()
+                    .is_some() => {}
+            "p" => {
+                let text = visible_text(element);
+                if !text.is_empty() && !is_link_list() {
+                    blocks.push(ArticleBlock::Paragraph(text), renderer.render(element));
+                }
+            }
+            _ if is_inline_only(element) => {
+                let text = visible_text(element);
+                if text.is_empty() {
+                    let image_selector = Selector::parse("img").unwrap();
+                    blocks.push_image(
+                        element
+                            .select(&image_selector)
+                            .next()
+                            .and_then(|image| Self::extract_image(&image, base)),
+                    );
+                } else if !is_link_list() {
+                    blocks.push(ArticleBlock::Paragraph(text), renderer.render(element));
+                }
+            }
+            _ => {
+                for child in element.child_elements() {
+                    Self::push_blocks(&child, base, renderer, blocks);
+                }
+            }
+        }
+    }
+
+    /// An image with its url resolved against the page. Lazy loaded images keep the real url in `data-src`, placeholders (`data:` urls) are skipped.
+    fn extract_image(image: &ElementRef, base: Option<&url::Url>) -> Option<ArticleBlock> {
+        let value = image.value();
+        let src = [value.attr("data-src"), value.attr("src")]
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .find(|src| !src.is_empty() && !src.starts_with("data:"))?;
+        let src = match base {
+            Some(base) => base.join(src).ok()?.to_string(),
+            None => src.to_string(),
+        };
+        Some(ArticleBlock::Image {
+            src,
+            alt: value
+                .attr("alt")
+                .map(str::trim)
+                .filter(|alt| !alt.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    /// The `h1` of the content, or of the page if it only has one.
+    fn extract_heading(document: &Html, content_elements: &[ElementRef]) -> Option<String> {
+        let heading_selector = Selector::parse("h1").unwrap();
+        let in_content = content_elements
+            .iter()
+            .find_map(|element| element.select(&heading_selector).next());
+        let mut headings = document.select(&heading_selector);
+        let heading = match in_content {
+            Some(heading) => heading,
+            None => match (headings.next(), headings.next()) {
+                (Some(heading), None) => heading,
+                _ => return None,
+            },
+        };
+        Some(visible_text(&heading)).filter(|heading| !heading.is_empty())
+    }
+
+    /// The `<title>` without the site name, eg "Title | Site" is "Title". The longest part is taken as the title.
+    fn extract_title(document: &Html) -> Option<String> {
+        let title_selector = Selector::parse("title").unwrap();
+        let title = document
+            .select(&title_selector)
+            .next()?
+            .text()
+            .collect::<String>();
+        let title = title.split_whitespace().collect::<Vec<&str>>().join(" ");
+        let mut parts = vec![title.as_str()];
+        for separator in TITLE_SEPARATORS {
+            parts = parts
+                .into_iter()
+                .flat_map(|part| part.split(separator))
+                .collect();
+        }
+        parts
+            .into_iter()
+            .map(str::trim)
+            .max_by_key(|part| part.chars().count())
+            .filter(|title| !title.is_empty())
+            .map(str::to_string)
+    }
+
+    /// The authors from the structured data, the author meta tag or the byline in the markup, eg "By Jane Smith".
+    fn extract_byline(document: &Html, structured: &ArticleMetadata) -> Vec<String> {
+        if !structured.authors.is_empty() {
+            return structured.authors.clone();
+        }
+        if let Some(author) = Self::meta(document, "meta[name='author']") {
+            return vec![author];
+        }
+        let byline_selector = Selector::parse(
+            "[rel~='author'], [itemprop='author'], .byline, .author, [class*='byline'], [class*='author']",
+        )
+        .unwrap();
+        document
+            .select(&byline_selector)
+            .map(|byline| visible_text(&byline))
+            .map(|byline| {
+                let trimmed = byline.trim();
+                trimmed
+                    .strip_prefix("By ")
+                    .or_else(|| trimmed.strip_prefix("by "))
+                    .unwrap_or(trimmed)
+                    .trim()
+                    .to_string()
+            })
+            //? Long "bylines" are usually author bio boxes.
+            .find(|byline| (2..100).contains(&byline.chars().count()))
+            .into_iter()
+            .collect()
+    }
+
+    /// The published date from meta tags or the first `<time datetime>`, preferring one in the content.
+    fn extract_published(document: &Html, content_elements: &[ElementRef]) -> Timestamp {
+        let time_selector = Selector::parse("time[datetime]").unwrap();
+        let meta = Self::meta(
+            document,
+            "meta[name='date'], meta[name='pubdate'], meta[name='publish-date'], meta[itemprop='datePublished']",
+        );
+        let time = content_elements
+            .iter()
+            .find_map(|element| element.select(&time_selector).next())
+            .or_else(|| document.select(&time_selector).next())
+            .and_then(|time| time.value().attr("datetime"))
+            .map(str::to_string);
+        Timestamp::parse(meta.or(time).as_deref())
+    }
+
+    fn extract_language(document: &Html) -> Option<String> {
+        let html_selector = Selector::parse("html[lang]").unwrap();
+        document
+            .select(&html_selector)
+            .next()
+            .and_then(|html| html.value().attr("lang"))
+            .map(str::trim)
+            .filter(|lang| !lang.is_empty())
+            .map(str::to_string)
+    }
+
+    /// The content of the first meta tag matching `selector`.
+    fn meta(document: &Html, selector: &str) -> Option<String> {
+        let selector = Selector::parse(selector).unwrap();
+        document
+            .select(&selector)
+            .filter_map(|meta| meta.value().attr("content"))
+            .map(str::trim)
+            .find(|content| !content.is_empty())
+            .map(str::to_string)
+    }
+}
+
+/// The blocks of the content, each with its Markdown rendering.
+#[derive(Debug, Default)]
+struct Blocks {
+    blocks: Vec<ArticleBlock>,
+    markdown: Vec<String>,
+}
+
+impl Blocks {
+    fn push(&mut self, block: ArticleBlock, markdown: String) {
+        self.blocks.push(block);
+        self.markdown.push(markdown);
+    }
+
+    fn push_image(&mut self, image: Option<ArticleBlock>) {
+        if let Some(ArticleBlock::Image { src, alt }) = image {
+            let markdown = format!(
+                "![{}]({src})",
+                escape_markdown(alt.as_deref().unwrap_or_default())
+            );
+            self.push(ArticleBlock::Image { src, alt }, markdown);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::common::normalize_text;
+
+use lazy_regex::regex_is_match;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::HashMap;
+
+/// Elements whose contents are never part of the main content.
+const SKIPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "nav", "aside", "footer", "header", "form",
+    "iframe", "svg", "button", "select", "dialog",
+];
+
+/// ARIA roles of page furniture rather than content.
+const SKIPPED_ROLES: &[&str] = &[
+    "navigation",
+    "menu",
+    "menubar",
+    "complementary",
+    "banner",
+    "contentinfo",
+    "search",
+    "alert",
+    "alertdialog",
+    "dialog",
+];
+
+/// Elements that start a new block, so an element containing one of these isn't a paragraph itself.
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "div",
+    "section",
+    "article",
+    "main",
+    "table",
+    "ul",
+    "ol",
+    "dl",
+    "pre",
+    "blockquote",
+    "figure",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "li",
+    "dt",
+    "dd",
+    "tr",
+    "td",
+    "th",
+    "thead",
+    "tbody",
+    "tfoot",
+    "figcaption",
+    "header",
+    "footer",
+    "nav",
+    "aside",
+    "form",
+];
+
+/// Paragraphs shorter than this (in characters) don't count towards the score of their containers.
+const MIN_PARAGRAPH_LENGTH: usize = 25;
+
+/// The main content of a page: the best scoring container, along with the siblings that look like they belong to it, in page order.
+///
+/// This follows the approach of Readability. Every paragraph scores its parent and (less) its grandparent and great-grandparent by its length and number of commas,
+/// containers start with a weight from their tag and their class and id names (`article`, `content` up, `comment`, `sidebar` down),
+/// and a container's score is scaled down by how much of its text is links, so navigation and link lists lose to prose.
+pub(super) fn main_content(document: &Html) -> Vec<ElementRef<'_>> {
+    let paragraph_selector = Selector::parse("p, pre, td, blockquote, div").unwrap();
+    let mut scores = HashMap::new();
+    let mut candidates = Vec::new();
+
+    for paragraph in document.select(&paragraph_selector) {
+        //? A div only counts as a paragraph when it holds text directly rather than other blocks.
+        if paragraph.value().name() == "div" && !is_inline_only(&paragraph) {
+            continue;
+        }
+        if is_excluded(&paragraph) {
+            continue;
+        }
+        let text = visible_text(&paragraph);
+        let length = text.chars().count();
+        if length < MIN_PARAGRAPH_LENGTH {
+            continue;
+        }
+        let score =
+            1.0 + text.matches([',', '，', '、']).count() as f64 + (length / 100).min(3) as f64;
+
+        for (level, ancestor) in paragraph
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .take_while(|ancestor| ancestor.value().name() != "html")
+            .take(3)
+            .enumerate()
+        {
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                level => level as f64 * 3.0,
+            };
+            *scores.entry(ancestor.id()).or_insert_with(|| {
+                candidates.push(ancestor);
+                tag_weight(ancestor.value().name()) + class_weight(&ancestor)
+            }) += score / divider;
+        }
+    }
+
+    let scores = candidates
+        .iter()
+        .map(|candidate| {
+            let score = scores.get(&candidate.id()).copied().unwrap_or_default();
+            (candidate.id(), score * (1.0 - link_density(candidate)))
+        })
+        .collect::<HashMap<_, f64>>();
+    let Some((top, top_score)) = candidates
+        .iter()
+        .map(|candidate| (*candidate, scores[&candidate.id()]))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+    else {
+        return Vec::new();
+    };
+
+    let Some(parent) = top.parent().and_then(ElementRef::wrap) else {
+        return vec![top];
+    };
+    //? Articles are often split over several containers (eg around an ad slot), so siblings that score well or are paragraphs of prose are kept too.
+    let threshold = (top_score * 0.2).max(10.0);
+    let top_class = top.value().attr("class").filter(|class| !class.is_empty());
+    parent
+        .child_elements()
+        .filter(|sibling| {
+            if sibling.id() == top.id() {
+                return true;
+            }
+            if is_skipped(sibling) {
+                return false;
+            }
+            let bonus = match top_class {
+                Some(class) if sibling.value().attr("class") == Some(class) => top_score * 0.2,
+                _ => 0.0,
+            };
+            if scores
+                .get(&sibling.id())
+                .is_some_and(|score| score + bonus >= threshold)
+            {
+                return true;
+            }
+            if sibling.value().name() == "p" {
+                let text = visible_text(sibling);
+                let length = text.chars().count();
+                let density = link_density(sibling);
+                return (length > 80 && density < 0.25)
+                    || (length > 0 && density == 0.0 && regex_is_match!(r"\.( |$)", &text));
+            }
+            false
+        })
+        .collect()
+}
+
+/// Whether an element is page furniture (navigation, comments, share buttons etc) by its tag, role or class and id names.
+pub(super) fn is_skipped(element: &ElementRef) -> bool {
+    let value = element.value();
+    let name = value.name();
+    if SKIPPED_TAGS.contains(&name)
+        || value
+            .attr("role")
+            .is_some_and(|role| SKIPPED_ROLES.contains(&role))
+        || value.attr("hidden").is_some()
+        || value.attr("aria-hidden") == Some("true")
+    {
+        return true;
+    }
+    if matches!(name, "body" | "article" | "main") {
+        return false;
+    }
+    let names = class_and_id(element);
+    regex_is_match!(
+        r"(?i)-ad-|(?:^|\s)ads?(?:[-_\s]|$)|advert|ai2html|banner|breadcrumbs|combx|comment|community|cover-wrap|disqus|extra|footer|gdpr|header|legends|menu|related|remark|replies|rss|shoutbox|sidebar|skyscraper|social|sponsor|supplemental|ad-break|agegate|pagination|pager|popup|cookie|newsletter|subscribe",
+        &names
+    ) && !regex_is_match!(r"(?i)and|article|body|column|content|main|shadow", &names)
+}
+
+/// Whether the element or one of its ancestors is skipped.
+pub(super) fn is_excluded(element: &ElementRef) -> bool {
+    is_skipped(element)
+        || element
+            .ancestors()
+            .filter_map(ElementRef::wrap)
+            .any(|ancestor| is_skipped(&ancestor))
+}
+
+/// Whether the element only has text and inline elements in it.
+pub(super) fn is_inline_only(element: &ElementRef) -> bool {
+    element
+        .child_elements()
+        .all(|child| !BLOCK_TAGS.contains(&child.value().name()))
+}
+
+/// The text of an element on a single line, without the text of scripts, styles and other skipped elements.
+pub(super) fn visible_text(element: &ElementRef) -> String {
+    let mut text = String::new();
+    push_visible_text(element, &mut text);
+    normalize_text(&text)
+}
+
+fn push_visible_text(element: &ElementRef, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(fragment) => text.push_str(fragment),
+            Node::Element(child_element) if child_element.name() == "br" => text.push(' '),
+            Node::Element(_) => {
+                let child = ElementRef::wrap(child).unwrap();
+                if !is_skipped(&child) {
+                    push_visible_text(&child, text);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// How much of the text of an element is link text, from 0 to 1.
+pub(super) fn link_density(element: &ElementRef) -> f64 {
+    let length = visible_text(element).chars().count();
+    if length == 0 {
+        return 0.0;
+    }
+    let link_selector = Selector::parse("a").unwrap();
+    let link_length = element
+        .select(&link_selector)
+        .map(|link| visible_text(&link).chars().count())
+        .sum::<usize>();
+    (link_length as f64 / length as f64).min(1.0)
+}
+
+fn tag_weight(name: &str) -> f64 {
+    match name {
+        "article" | "main" => 10.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    }
+}
+
+/// Up for class and id names that content containers tend to have, down for the names of comments, sidebars, ads etc.
+fn class_weight(element: &ElementRef) -> f64 {
+    let names = class_and_id(element);
+    let mut weight = 0.0;
+    if regex_is_match!(
+        r"(?i)-ad-|hidden|banner|combx|comment|com-|contact|footer|gdpr|masthead|media|meta|outbrain|promo|related|scroll|share|shoutbox|sidebar|skyscraper|sponsor|shopping|tags|widget",
+        &names
+    ) {
+        weight -= 25.0;
+    }
+    if regex_is_match!(
+        r"(?i)article|body|content|entry|hentry|h-entry|main|page|post|text|blog|story",
+        &names
+    ) {
+        weight += 25.0;
+    }
+    weight
+}
+
+fn class_and_id(element: &ElementRef) -> String {
+    let value = element.value();
+    format!(
+        "{} {}",
+        value.attr("class").unwrap_or_default(),
+        value.id().unwrap_or_default()
+    )
+}
@@ -0,0 +1,113 @@
+use crate::common::UrlTrait;
+use crate::{Error, Result};
+
+use super::error::GenericError;
+
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+/// The extensions of files that aren't web pages, so links to them aren't crawled.
+const SKIPPED_EXTENSIONS: &[&str] = &[
+    "pdf", "jpg", "jpeg", "png", "gif", "webp", "svg", "ico", "bmp", "tiff", "css", "js", "json",
+    "xml", "rss", "atom", "txt", "csv", "zip", "gz", "tar", "rar", "7z", "mp3", "mp4", "m4a",
+    "mov", "avi", "webm", "wav", "ogg", "woff", "woff2", "ttf", "eot", "doc", "docx", "xls",
+    "xlsx", "ppt", "pptx", "exe", "dmg", "apk",
+];
+
+/// A url on any site, for crawling sites that don't have their own url type.
+/// The whole canonical url is kept, as there is no base url to take the host from.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct GenericUrl {
+    url: String,
+    host: String,
+}
+
+impl GenericUrl {
+    /// The host of the site the page is on, eg "www.example.com".
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Whether both urls are on the same host.
+    pub fn same_host(&self, other: &GenericUrl) -> bool {
+        self.host == other.host
+    }
+}
+
+impl AsRef<String> for GenericUrl {
+    fn as_ref(&self) -> &String {
+        &self.url
+    }
+}
+
+impl TryFrom<String> for GenericUrl {
+    type Error = Error;
+    fn try_from(url: String) -> Result<Self> {
+        let parsed = Self::parse_canonical(&url)?;
+        let host = url::Url::parse(&parsed)
+            .ok()
+            .and_then(|parsed| parsed.host_str().map(str::to_string))
+            .ok_or_else(|| GenericError::InvalidUrl {
+                url,
+                reason: "Has no host".to_string(),
+            })?;
+        Ok(GenericUrl { url: parsed, host })
+    }
+}
+
+impl From<GenericUrl> for String {
+    fn from(url: GenericUrl) -> String {
+        url.url
+    }
+}
+
+impl Hash for GenericUrl {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.url.hash(state);
+    }
+}
+
+impl UrlTrait for GenericUrl {
+    //? There is no one site, so no base url: hosts are kept as they are and relative urls can only be resolved against the page they are on (`UrlTrait::resolve`).
+    fn base_url() -> &'static str {
+        ""
+    }
+    /// Urls have to be absolute, there is no site to resolve them against.
+    fn canonicalize(url: &str) -> Result<String> {
+        url::Url::parse(url.trim()).map_err(|_| GenericError::InvalidUrl {
+            url: url.to_string(),
+            reason: "Not an absolute url".to_string(),
+        })?;
+        Self::canonicalizer().canonicalize_relative_to(url, url.trim())
+    }
+    fn to_string(&self) -> String {
+        self.url.clone()
+    }
+    fn full_url(&self) -> String {
+        self.url.clone()
+    }
+    /// Any web page is accepted, links to images, documents, feeds and other files are not.
+    fn parse_url(url: &str) -> Result<String> {
+        let invalid = |reason: &str| -> Error {
+            GenericError::InvalidUrl {
+                url: url.to_string(),
+                reason: reason.to_string(),
+            }
+            .into()
+        };
+
+        let parsed = url::Url::parse(url).map_err(|_| invalid("Not an absolute url"))?;
+        if parsed.host_str().is_none_or(str::is_empty) {
+            return Err(invalid("Has no host"));
+        }
+        let extension = parsed
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|segment| segment.rsplit_once('.'))
+            .map(|(_, extension)| extension.to_ascii_lowercase());
+        if extension.is_some_and(|extension| SKIPPED_EXTENSIONS.contains(&extension.as_str())) {
+            return Err(invalid("Links to a file, not a page"));
+        }
+        Ok(parsed.to_string())
+    }
+}
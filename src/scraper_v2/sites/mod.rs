@@ -1,5 +1,6 @@
 pub mod bbc;
 pub mod error;
+pub mod generic;
 pub mod wikipedia;
 
 use crate::common::UrlTrait;
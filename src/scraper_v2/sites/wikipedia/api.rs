@@ -0,0 +1,440 @@
+use super::error::WikipediaError;
+use super::url::encode_title;
+use super::WikipediaUrl;
+use crate::common::{
+    decode_body, fetch_bytes_as, FetchedDocument, Fetcher, UrlTrait, Validators, WebFetcher,
+};
+use crate::Result;
+
+use async_trait::async_trait;
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The Action API takes at most 50 titles per query.
+const MAX_TITLES_PER_QUERY: usize = 50;
+
+/// The properties every page query asks for. Hidden (maintenance) categories are left out.
+//? The links of a page are in its Parsoid HTML, so they aren't asked for.
+const QUERY_PARAMS: &[(&str, &str)] = &[
+    ("action", "query"),
+    ("format", "json"),
+    ("formatversion", "2"),
+    ("redirects", "1"),
+    ("prop", "categories|langlinks|pageprops"),
+    ("cllimit", "max"),
+    ("clshow", "!hidden"),
+    ("lllimit", "max"),
+    ("llprop", "url|langname|autonym"),
+    ("ppprop", "disambiguation|wikibase-shortdesc"),
+];
+
+/// A link to the same page in another language edition, as listed by the API.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct ApiLanguageLink {
+    pub language: String,
+    pub title: String,
+    pub url: Option<String>,
+    /// The name of the language in English, eg "French".
+    pub language_name: Option<String>,
+    /// The name of the language in that language, eg "Français".
+    pub autonym: Option<String>,
+}
+
+/// What the Action API knows about a page. Titles are full titles, with the namespace prefix.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ApiPage {
+    pub title: String,
+    pub page_id: Option<u64>,
+    /// The page doesn't exist.
+    pub missing: bool,
+    /// The title that was asked for, when it was a redirect to this page.
+    pub redirected_from: Option<String>,
+    /// The section the redirect points at, if any.
+    pub redirect_section: Option<String>,
+    pub short_description: Option<String>,
+    pub disambiguation: bool,
+    /// The visible categories, eg "Category:Cities in England".
+    pub categories: Vec<String>,
+    pub language_links: Vec<ApiLanguageLink>,
+}
+
+impl ApiPage {
+    /// The url of the page in the given language edition.
+    pub fn url(&self, language: &str) -> Result<WikipediaUrl> {
+        WikipediaUrl::from_title(language, &self.title)
+    }
+
+    /// The title that was asked for: the redirect the page was reached through, otherwise its own title.
+    pub fn requested_title(&self) -> &str {
+        self.redirected_from.as_deref().unwrap_or(&self.title)
+    }
+
+    /// Read the pages out of the responses of a query and its continuations, merging the parts of each page that were split across responses.
+    /// A page that was reached through redirects is returned on its own and once more for each redirect, with `redirected_from` set, as several titles can redirect to the same page.
+    pub fn from_query_responses(responses: &[Value]) -> Result<Vec<ApiPage>> {
+        let mut pages = Vec::<ApiPage>::new();
+        //? Keyed by the redirect rather than its target, as several redirects can have the same target.
+        let mut redirects = HashMap::<String, (String, Option<String>)>::new();
+
+        for response in responses {
+            if let Some(error) = response.get("error") {
+                return Err(WikipediaError::ApiError {
+                    url: "api.php".to_string(),
+                    reason: error
+                        .get("info")
+                        .and_then(Value::as_str)
+                        .unwrap_or("Unknown error")
+                        .to_string(),
+                }
+                .into());
+            }
+            let Some(query) = response.get("query") else {
+                continue;
+            };
+            for redirect in array(query, "redirects") {
+                if let (Some(from), Some(to)) =
+                    (str_field(redirect, "from"), str_field(redirect, "to"))
+                {
+                    redirects.insert(from, (to, str_field(redirect, "tofragment")));
+                }
+            }
+            for page_json in array(query, "pages") {
+                let Some(title) = str_field(page_json, "title") else {
+                    continue;
+                };
+                let index = match pages.iter().position(|page| page.title == title) {
+                    Some(index) => index,
+                    None => {
+                        pages.push(ApiPage {
+                            title: title.clone(),
+                            ..Default::default()
+                        });
+                        pages.len() - 1
+                    }
+                };
+                Self::merge(&mut pages[index], page_json);
+            }
+        }
+
+        let mut redirects = redirects.into_iter().collect::<Vec<_>>();
+        redirects.sort();
+        let redirected = redirects
+            .into_iter()
+            .filter_map(|(from, (to, section))| {
+                let page = pages.iter().find(|page| page.title == to)?;
+                Some(ApiPage {
+                    redirected_from: Some(from),
+                    redirect_section: section,
+                    ..page.clone()
+                })
+            })
+            .collect::<Vec<ApiPage>>();
+        pages.extend(redirected);
+        Ok(pages)
+    }
+
+    /// Add one response's part of a page to what we have of it so far.
+    fn merge(page: &mut ApiPage, json: &Value) {
+        page.page_id = page
+            .page_id
+            .or_else(|| json.get("pageid").and_then(Value::as_u64));
+        page.missing |= json
+            .get("missing")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        if let Some(pageprops) = json.get("pageprops") {
+            page.disambiguation |= pageprops.get("disambiguation").is_some();
+            page.short_description = page
+                .short_description
+                .take()
+                .or_else(|| str_field(pageprops, "wikibase-shortdesc"));
+        }
+        page.categories
+            .extend(array(json, "categories").filter_map(|category| str_field(category, "title")));
+        page.language_links
+            .extend(array(json, "langlinks").filter_map(|link| {
+                Some(ApiLanguageLink {
+                    language: str_field(link, "lang")?,
+                    title: str_field(link, "title")?,
+                    url: str_field(link, "url"),
+                    language_name: str_field(link, "langname"),
+                    autonym: str_field(link, "autonym"),
+                })
+            }));
+    }
+}
+
+pub(super) fn array<'a>(json: &'a Value, key: &str) -> impl Iterator<Item = &'a Value> {
+    json.get(key)
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+}
+
+pub(super) fn str_field(json: &Value, key: &str) -> Option<String> {
+    json.get(key)
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .filter(|value| !value.is_empty())
+}
+
+/// The Action API endpoint of a language edition.
+pub fn api_url(language: &str) -> String {
+    format!("https://{language}.wikipedia.org/w/api.php")
+}
+
+/// The REST endpoint for the Parsoid HTML of a page.
+pub fn page_html_url(language: &str, full_title: &str) -> String {
+    //? Slashes are part of titles (eg "AC/DC"), so they have to be escaped in the REST path.
+    format!(
+        "https://{language}.wikipedia.org/api/rest_v1/page/html/{}",
+        encode_title(full_title).replace('/', "%2F")
+    )
+}
+
+/// Query the Action API for the categories, language links and page properties of `titles`, following continuations until everything has been returned.
+/// Titles are sent in batches of 50, redirects are resolved (see `ApiPage::from_query_responses`). Titles that don't exist are returned with `missing` set.
+/// Wikimedia asks API clients to send a `user_agent` that says who they are and how to contact them (see `WikipediaApiFetcher::new`).
+pub async fn query_pages(
+    language: &str,
+    titles: &[String],
+    user_agent: &str,
+) -> Result<Vec<ApiPage>> {
+    let mut pages = Vec::new();
+    for batch in titles.chunks(MAX_TITLES_PER_QUERY) {
+        let mut responses = Vec::new();
+        let mut continuation = Vec::<(String, String)>::new();
+        loop {
+            let mut url = url::Url::parse(&api_url(language)).map_err(crate::Error::custom)?;
+            url.query_pairs_mut()
+                .extend_pairs(QUERY_PARAMS)
+                .append_pair("titles", &batch.join("|"))
+                .extend_pairs(&continuation);
+            let body = decode_body(fetch_bytes_as(url.as_str(), user_agent).await?)?;
+            let response =
+                serde_json::from_str::<Value>(&body).map_err(|e| WikipediaError::ApiError {
+                    url: url.to_string(),
+                    reason: e.to_string(),
+                })?;
+            continuation = response
+                .get("continue")
+                .and_then(Value::as_object)
+                .map(|fields| {
+                    fields
+                        .iter()
+                        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            responses.push(response);
+            if continuation.is_empty() {
+                break;
+            }
+        }
+        pages.extend(ApiPage::from_query_responses(&responses)?);
+    }
+    Ok(pages)
+}
+
+/// Fetches articles through the MediaWiki APIs instead of the rendered pages: the Action API for the metadata (redirects, categories, language links, disambiguation) and the REST API for the Parsoid HTML of the article.
+/// The two are put together into a document shaped like the rendered page (see `document_from_responses`), so `WikipediaContent` is scraped the same way from either source.
+///
+/// Category listings are generated by the rendered page and aren't in the Parsoid HTML, so pages outside the article namespace are fetched from the site.
+/// The APIs are always asked for the current version, validators from previous fetches aren't sent.
+///
+/// In a crawl the metadata of every page of an iteration is queried up front (see `Fetcher::prefetch`), 50 titles per request, so each page only needs a request for its HTML.
+#[derive(Debug)]
+pub struct WikipediaApiFetcher {
+    user_agent: String,
+    /// The pages queried ahead of being fetched, by language and the title that was asked for.
+    prefetched: Mutex<HashMap<(String, String), ApiPage>>,
+}
+
+impl WikipediaApiFetcher {
+    /// Wikimedia asks API clients to identify themselves, so requests are sent with a user agent naming this crate and `contact`, eg an email address or the url of the project's page.
+    pub fn new(contact: &str) -> Self {
+        Self {
+            user_agent: format!(
+                "{}/{} ({contact})",
+                env!("CARGO_PKG_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ),
+            prefetched: Mutex::default(),
+        }
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    fn take_prefetched(&self, language: &str, title: &str) -> Result<Option<ApiPage>> {
+        Ok(self
+            .prefetched
+            .lock()
+            .map_err(crate::Error::custom)?
+            .remove(&(language.to_string(), title.to_string())))
+    }
+}
+
+#[async_trait]
+impl Fetcher<WikipediaUrl> for WikipediaApiFetcher {
+    async fn fetch(
+        &self,
+        url: &WikipediaUrl,
+        validators: &Validators,
+    ) -> Result<Option<FetchedDocument>> {
+        if !url.is_article() {
+            return WebFetcher.fetch(url, validators).await;
+        }
+        let title = url.full_title();
+        let page = match self.take_prefetched(url.language(), &title)? {
+            Some(page) => Some(page),
+            None => query_pages(
+                url.language(),
+                std::slice::from_ref(&title),
+                &self.user_agent,
+            )
+            .await?
+            .into_iter()
+            .find(|page| page.requested_title() == title),
+        }
+        .filter(|page| !page.missing)
+        .ok_or(WikipediaError::PageNotFound {
+            url: url.full_url(),
+        })?;
+        let html = decode_body(
+            fetch_bytes_as(
+                &page_html_url(url.language(), &page.title),
+                &self.user_agent,
+            )
+            .await?,
+        )?;
+        Ok(Some(document_from_responses(url.language(), &page, &html)))
+    }
+
+    async fn prefetch(&self, urls: &[Arc<WikipediaUrl>]) -> Result<()> {
+        let mut titles = HashMap::<&str, Vec<String>>::new();
+        for url in urls.iter().filter(|url| url.is_article()) {
+            titles
+                .entry(url.language())
+                .or_default()
+                .push(url.full_title());
+        }
+        for (language, titles) in titles {
+            let pages = query_pages(language, &titles, &self.user_agent).await?;
+            let mut prefetched = self.prefetched.lock().map_err(crate::Error::custom)?;
+            for page in pages {
+                prefetched.insert(
+                    (language.to_string(), page.requested_title().to_string()),
+                    page,
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Put the Action API metadata of a page and its Parsoid HTML together into a document shaped like the rendered page, with the title heading, redirect notice, category links and language links where `WikipediaContent` expects them.
+/// Useful on its own to scrape recorded API responses.
+pub fn document_from_responses(
+    language: &str,
+    page: &ApiPage,
+    parsoid_html: &str,
+) -> FetchedDocument {
+    let base = format!("https://{language}.wikipedia.org/wiki/");
+    let page_url = format!("{base}{}", encode_title(&page.title));
+    let parsoid = Html::parse_document(parsoid_html);
+    let body_selector = Selector::parse("body").unwrap();
+    let shortdescription_selector = Selector::parse(".shortdescription").unwrap();
+
+    let mut content = String::new();
+    if let Some(body) = parsoid.select(&body_selector).next() {
+        if body.select(&shortdescription_selector).next().is_none() {
+            if let Some(short_description) = &page.short_description {
+                content.push_str(&format!(
+                    "<div class=\"shortdescription\">{}</div>",
+                    escape_html(short_description)
+                ));
+            }
+        }
+        push_unsectioned(&body, &mut content);
+    }
+    if page.disambiguation {
+        content.push_str("<meta property=\"mw:PageProp/disambiguation\">");
+    }
+
+    let redirect_notice = page
+        .redirected_from
+        .as_ref()
+        .map(|from| {
+            format!(
+                "<span class=\"mw-redirectedfrom\">(Redirected from <a href=\"/wiki/{}\">{}</a>)</span>",
+                encode_title(from),
+                escape_html(from)
+            )
+        })
+        .unwrap_or_default();
+    let categories = page
+        .categories
+        .iter()
+        .map(|category| {
+            let name = category
+                .split_once(':')
+                .map_or(category.as_str(), |(_, name)| name);
+            format!(
+                "<li><a href=\"/wiki/{}\">{}</a></li>",
+                encode_title(category),
+                escape_html(name)
+            )
+        })
+        .collect::<String>();
+    let language_links = page
+        .language_links
+        .iter()
+        .filter_map(|link| {
+            Some(format!(
+                "<li class=\"interlanguage-link\"><a href=\"{}\" title=\"{} – {}\" hreflang=\"{}\">{}</a></li>",
+                escape_html(link.url.as_ref()?),
+                escape_html(&link.title),
+                escape_html(link.language_name.as_deref().unwrap_or(&link.language)),
+                escape_html(&link.language),
+                escape_html(link.autonym.as_deref().unwrap_or(&link.language)),
+            ))
+        })
+        .collect::<String>();
+
+    let html = format!(
+        "<!DOCTYPE html><html><head><base href=\"{base}\"><link rel=\"canonical\" href=\"{page_url}\"></head><body>\
+        <h1 id=\"firstHeading\">{title}</h1><div id=\"contentSub\">{redirect_notice}</div>\
+        <div id=\"mw-content-text\"><div class=\"mw-parser-output\">{content}</div></div>\
+        <div id=\"catlinks\"><div id=\"mw-normal-catlinks\"><ul>{categories}</ul></div></div>\
+        <div id=\"p-lang\"><ul>{language_links}</ul></div></body></html>",
+        title = escape_html(&page.title),
+    );
+    FetchedDocument::parse(&html, page_url)
+}
+
+/// Parsoid wraps each section in a `<section>`, the rendered page doesn't. Write out the children of `element` with the sections unwrapped, so headings and paragraphs are siblings like on the rendered page.
+fn push_unsectioned(element: &ElementRef, html: &mut String) {
+    for child in element.children() {
+        match ElementRef::wrap(child) {
+            Some(child) if child.value().name() == "section" => push_unsectioned(&child, html),
+            Some(child) => html.push_str(&child.html()),
+            None => {
+                if let Some(text) = child.value().as_text() {
+                    html.push_str(&escape_html(text));
+                }
+            }
+        }
+    }
+}
+
+pub(super) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
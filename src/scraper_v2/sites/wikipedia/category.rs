@@ -0,0 +1,270 @@
+use super::error::WikipediaError;
+use super::model::{CategoryLink, CategoryMember};
+use super::text::clean_text;
+use super::{Namespace, WikipediaUrl};
+use crate::common::{
+    escape_markdown, extract_links, FetchedDocument, LinkRel, LinkTo, Page, ScrapableContent,
+    UrlTrait,
+};
+use crate::Result;
+
+use itertools::Itertools;
+use scraper::{ElementRef, Selector};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// A category page, eg `/wiki/Category:Cities_in_England`. Lists the subcategories and pages in the category, up to 200 of each per page.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct WikipediaCategoryContent {
+    /// The name of the category without the namespace prefix.
+    title: String,
+    language: String,
+    /// The paragraphs describing the category above the listing, if any.
+    description: Vec<String>,
+    subcategories: Vec<CategoryMember>,
+    /// The pages in the category. Mostly articles, but templates, portals etc can be categorised too.
+    members: Vec<CategoryMember>,
+    /// The categories this category is in.
+    parent_categories: Vec<CategoryLink>,
+    /// The next pages of the listing, when there are more members or subcategories than fit on one page.
+    next_pages: HashSet<Page<LinkTo, WikipediaUrl>>,
+}
+
+impl ScrapableContent for WikipediaCategoryContent {
+    type Url = WikipediaUrl;
+    fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self> {
+        tracing::debug!("Scraping category: {:?}", url);
+
+        if url.namespace() != Namespace::Category {
+            return Err(WikipediaError::WrongNamespace {
+                url: url.full_url(),
+                expected: Namespace::Category,
+                found: url.namespace(),
+            }
+            .into());
+        }
+        let content_selector = Selector::parse("#mw-content-text").unwrap();
+        let content =
+            document
+                .select(&content_selector)
+                .next()
+                .ok_or(WikipediaError::NoContentFound {
+                    url: url.full_url(),
+                })?;
+
+        Ok(WikipediaCategoryContent {
+            title: url.title().to_string(),
+            language: url.language().to_string(),
+            description: Self::extract_description(&content),
+            subcategories: Self::extract_listing(&content, "#mw-subcategories", url, document)
+                .into_iter()
+                .filter(|member| member.url.namespace() == Namespace::Category)
+                .collect(),
+            members: Self::extract_listing(&content, "#mw-pages", url, document),
+            parent_categories: extract_categories(document),
+            next_pages: Self::extract_next_pages(&content, url, document),
+        })
+    }
+
+    /// The subcategories and the next pages of the listing, so a crawl walks the whole category tree.
+    /// The members are handed to an article crawl with `seed_pages`.
+    fn get_related_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
+        self.subcategories
+            .iter()
+            .map(|subcategory| {
+                Page::<LinkTo, WikipediaUrl>::new(
+                    subcategory.url.clone(),
+                    subcategory.title.clone(),
+                )
+            })
+            .chain(self.next_pages.iter().cloned())
+            .collect()
+    }
+
+    fn body_text(&self) -> String {
+        self.description.join("\n\n")
+    }
+
+    /// The description followed by the subcategories and pages as lists of links.
+    fn to_markdown(&self) -> String {
+        let list = |members: &[CategoryMember]| {
+            members
+                .iter()
+                .map(|member| {
+                    format!(
+                        "- [{}]({})",
+                        escape_markdown(&member.title),
+                        member.url.full_url()
+                    )
+                })
+                .join("\n")
+        };
+        let mut markdown = vec![format!("# Category:{}", escape_markdown(&self.title))];
+        markdown.extend(
+            self.description
+                .iter()
+                .map(|paragraph| escape_markdown(paragraph)),
+        );
+        if !self.subcategories.is_empty() {
+            markdown.push("## Subcategories".to_string());
+            markdown.push(list(&self.subcategories));
+        }
+        if !self.members.is_empty() {
+            markdown.push("## Pages".to_string());
+            markdown.push(list(&self.members));
+        }
+        markdown.join("\n\n")
+    }
+}
+
+impl WikipediaCategoryContent {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn description(&self) -> &[String] {
+        &self.description
+    }
+    pub fn subcategories(&self) -> &[CategoryMember] {
+        &self.subcategories
+    }
+    /// The pages listed on this page of the category.
+    pub fn members(&self) -> &[CategoryMember] {
+        &self.members
+    }
+    pub fn parent_categories(&self) -> &[CategoryLink] {
+        &self.parent_categories
+    }
+    /// Whether the listing continues on another page.
+    pub fn has_next_page(&self) -> bool {
+        !self.next_pages.is_empty()
+    }
+
+    /// The articles in the category as LinkTo pages, to seed an article crawl with `PageHandler::add_seed_pages`.
+    pub fn seed_pages(&self) -> Vec<Page<LinkTo, WikipediaUrl>> {
+        self.members
+            .iter()
+            .filter(|member| member.url.is_article())
+            .map(|member| {
+                Page::<LinkTo, WikipediaUrl>::new(member.url.clone(), member.title.clone())
+            })
+            .collect()
+    }
+
+    /// The text before the listing, which some categories use to describe what belongs in them.
+    fn extract_description(content: &ElementRef) -> Vec<String> {
+        let paragraph_selector = Selector::parse(".mw-parser-output > p").unwrap();
+        content
+            .select(&paragraph_selector)
+            .map(|paragraph| clean_text(&paragraph))
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect()
+    }
+
+    /// The entries of a listing (`#mw-subcategories` or `#mw-pages`), grouped under letter headings.
+    // <div class="mw-category-group"><h3>A</h3><ul><li><a href="/wiki/Ashford" title="Ashford">Ashford</a></li></ul></div>
+    fn extract_listing(
+        content: &ElementRef,
+        listing: &str,
+        url: &WikipediaUrl,
+        document: &FetchedDocument,
+    ) -> Vec<CategoryMember> {
+        let listing_selector = Selector::parse(listing).unwrap();
+        let group_selector = Selector::parse(".mw-category-group").unwrap();
+        let heading_selector = Selector::parse("h3").unwrap();
+        let item_selector = Selector::parse("li").unwrap();
+        let Some(listing) = content.select(&listing_selector).next() else {
+            return Vec::new();
+        };
+
+        let groups = listing.select(&group_selector).collect::<Vec<ElementRef>>();
+        //? Listings with only a few entries aren't always split into groups.
+        let groups = if groups.is_empty() {
+            vec![listing]
+        } else {
+            groups
+        };
+        groups
+            .iter()
+            .flat_map(|group| {
+                let heading = group
+                    .select(&heading_selector)
+                    .next()
+                    .map(|heading| clean_text(&heading))
+                    .filter(|heading| !heading.is_empty());
+                group
+                    .select(&item_selector)
+                    .filter_map(|item| {
+                        //? Subcategory entries also have a tree toggle linking to the page itself, so the first link elsewhere is the entry.
+                        let (member_url, text) = extract_links(&item, document)
+                            .into_iter()
+                            .find_map(|link| {
+                                let member_url = WikipediaUrl::try_from(link.href).ok()?;
+                                (member_url.full_title() != url.full_title())
+                                    .then_some((member_url, link.text))
+                            })?;
+                        let title = text.trim().to_string();
+                        Some(CategoryMember {
+                            title: if title.is_empty() {
+                                member_url.title().to_string()
+                            } else {
+                                title
+                            },
+                            url: member_url,
+                            group: heading.clone(),
+                        })
+                    })
+                    .collect::<Vec<CategoryMember>>()
+            })
+            .unique_by(|member| member.url.clone())
+            .collect()
+    }
+
+    /// The "next page" links of the listings. They are the only links that page forwards (`pagefrom=` or `subcatfrom=`), so the link text, which is translated, isn't needed.
+    fn extract_next_pages(
+        content: &ElementRef,
+        url: &WikipediaUrl,
+        document: &FetchedDocument,
+    ) -> HashSet<Page<LinkTo, WikipediaUrl>> {
+        let paging_selector =
+            Selector::parse("#mw-subcategories > a[href], #mw-pages > a[href]").unwrap();
+        content
+            .select(&paging_selector)
+            .filter_map(|link| {
+                let next =
+                    WikipediaUrl::resolve(&document.base_url(), link.value().attr("href")?).ok()?;
+                let query = next.as_ref().split_once('?')?.1;
+                let pages_forward = query
+                    .split('&')
+                    .any(|pair| pair.starts_with("pagefrom=") || pair.starts_with("subcatfrom="));
+                (pages_forward && next.full_title() == url.full_title()).then(|| {
+                    Page::<LinkTo, WikipediaUrl>::new_with_rel(
+                        next,
+                        url.full_title(),
+                        vec![LinkRel::Next],
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// The visible categories at the bottom of a page. Hidden (maintenance) categories are skipped.
+// <div id="mw-normal-catlinks" class="mw-normal-catlinks"><a href="/wiki/Help:Category">Categories</a>: <ul><li><a href="/wiki/Category:Cities_in_England">Cities in England</a></li></ul></div>
+pub(super) fn extract_categories(document: &FetchedDocument) -> Vec<CategoryLink> {
+    let category_selector = Selector::parse("#mw-normal-catlinks li a[href]").unwrap();
+    document
+        .select(&category_selector)
+        .filter_map(|link| {
+            let url =
+                WikipediaUrl::resolve(&document.base_url(), link.value().attr("href")?).ok()?;
+            (url.namespace() == Namespace::Category).then(|| CategoryLink {
+                title: url.title().to_string(),
+                url,
+            })
+        })
+        .unique_by(|category| category.url.clone())
+        .collect()
+}
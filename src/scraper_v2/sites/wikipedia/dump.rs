@@ -0,0 +1,470 @@
+use super::api::{array, document_from_responses, escape_html, str_field, ApiPage};
+use super::error::WikipediaError;
+use super::url::encode_title;
+use super::wikitext::render_wikitext;
+use super::{WikipediaContent, WikipediaUrl};
+use crate::common::{FetchedDocument, LinkTo, Page, PageHandler, WasScraped};
+use crate::Result;
+
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use lazy_regex::regex_captures;
+use quick_xml::events::{BytesStart, Event};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Lines};
+use std::path::Path;
+
+/// An article (or a redirect to one) read from a dump, ready to be scraped as if it had been fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpPage {
+    language: String,
+    /// What the dump says about the page, in the shape the API gives it.
+    page: ApiPage,
+    /// The title (and section) a redirect page points at, eg "United Kingdom#History".
+    redirect_to: Option<String>,
+    /// The body of the page.
+    html: String,
+}
+
+impl DumpPage {
+    fn redirect(language: String, title: String, target: String) -> Self {
+        Self {
+            language,
+            page: ApiPage {
+                title,
+                ..Default::default()
+            },
+            redirect_to: Some(target),
+            html: String::new(),
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        &self.page.title
+    }
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    pub fn is_redirect(&self) -> bool {
+        self.redirect_to.is_some()
+    }
+
+    pub fn url(&self) -> Result<WikipediaUrl> {
+        self.page.url(&self.language)
+    }
+
+    /// A document shaped like the rendered page (see `api::document_from_responses`), so the page is scraped the same way as a fetched one.
+    /// Redirect pages get the notice Wikipedia shows when a redirect isn't followed.
+    pub fn document(&self) -> FetchedDocument {
+        let html = match &self.redirect_to {
+            Some(target) => redirect_html(target),
+            None => self.html.clone(),
+        };
+        document_from_responses(&self.language, &self.page, &html)
+    }
+
+    /// Scrape the page. Its `get_all_page_links` are its edges in the link graph.
+    pub fn scrape(&self) -> Result<Page<WasScraped<WikipediaContent>, WikipediaUrl>> {
+        Page::<LinkTo, WikipediaUrl>::new(self.url()?, self.page.title.clone())
+            .scrape_document(&self.document())
+    }
+}
+
+// <div class="redirectMsg"><p>Redirect to:</p><ul class="redirectText"><li><a href="/wiki/United_Kingdom#History">United Kingdom#History</a></li></ul></div>
+fn redirect_html(target: &str) -> String {
+    let href = match target.split_once('#') {
+        Some((page, section)) => format!(
+            "/wiki/{}#{}",
+            encode_title(page.trim()),
+            encode_title(section.trim())
+        ),
+        None => format!("/wiki/{}", encode_title(target.trim())),
+    };
+    format!(
+        "<div class=\"redirectMsg\"><p>Redirect to:</p><ul class=\"redirectText\"><li><a href=\"{href}\">{}</a></li></ul></div>",
+        escape_html(target)
+    )
+}
+
+fn dump_error(error: impl ToString) -> WikipediaError {
+    WikipediaError::DumpError {
+        reason: error.to_string(),
+    }
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .is_some_and(|path_extension| path_extension == extension)
+}
+
+/// The element of an XML dump whose text is being read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum XmlField {
+    /// The url of the main page in the `<siteinfo>`, which says which language edition the dump is of.
+    Base,
+    Title,
+    Namespace,
+    Text,
+}
+
+/// The parts of a `<page>` we keep while it is read.
+#[derive(Debug, Default)]
+struct XmlPage {
+    title: String,
+    namespace: Option<i64>,
+    redirect: Option<String>,
+    /// The wikitext of the last revision.
+    text: String,
+}
+
+/// Reads the articles out of a pages-articles XML dump (eg `enwiki-latest-pages-articles-multistream.xml.bz2`) one page at a time, so a whole dump never has to fit in memory.
+/// Redirect pages are read as redirects, pages outside the article namespace are skipped.
+///
+/// A dump has wikitext rather than HTML, which is rendered with a small subset of what MediaWiki does (see `render_wikitext`):
+/// the abstract, links, categories and disambiguation lists are kept, but infoboxes (which are templates), references and language links are not.
+/// Use `HtmlDumpReader` when those are needed.
+#[derive(Debug)]
+pub struct XmlDumpReader<R: BufRead> {
+    reader: quick_xml::Reader<R>,
+    /// The language code of the edition, taken from the `<siteinfo>` if it wasn't given.
+    language: Option<String>,
+    page: Option<XmlPage>,
+    field: Option<XmlField>,
+    text: String,
+    finished: bool,
+}
+
+impl XmlDumpReader<Box<dyn BufRead + Send>> {
+    /// Open a dump file, decompressing it as it is read if it ends in `.bz2`. Multistream dumps are several bz2 streams one after another, they are all read.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let reader: Box<dyn BufRead + Send> = if has_extension(path, "bz2") {
+            Box::new(BufReader::new(MultiBzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+        Ok(Self::new(reader))
+    }
+}
+
+impl<R: BufRead> XmlDumpReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: quick_xml::Reader::from_reader(reader),
+            language: None,
+            page: None,
+            field: None,
+            text: String::new(),
+            finished: false,
+        }
+    }
+
+    /// Set the language of the edition, for dumps whose `<siteinfo>` is missing or doesn't say.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    fn next_page(&mut self) -> Result<Option<DumpPage>> {
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match self.reader.read_event_into(&mut buf).map_err(dump_error)? {
+                Event::Start(element) => self.start_element(&element)?,
+                Event::Empty(element) => {
+                    self.start_element(&element)?;
+                    self.field = None;
+                }
+                Event::Text(text) if self.field.is_some() => {
+                    self.text.push_str(&text.unescape().map_err(dump_error)?);
+                }
+                Event::CData(data) if self.field.is_some() => {
+                    self.text.push_str(&String::from_utf8_lossy(&data));
+                }
+                Event::End(element) => {
+                    if element.local_name().as_ref() == b"page" {
+                        if let Some(page) = self.page.take() {
+                            if let Some(page) = self.finish_page(page)? {
+                                return Ok(Some(page));
+                            }
+                        }
+                    } else {
+                        self.end_field();
+                    }
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    fn start_element(&mut self, element: &BytesStart) -> Result<()> {
+        self.field = match element.local_name().as_ref() {
+            b"page" => {
+                self.page = Some(XmlPage::default());
+                None
+            }
+            b"base" => Some(XmlField::Base),
+            b"title" => Some(XmlField::Title),
+            b"ns" => Some(XmlField::Namespace),
+            b"text" => Some(XmlField::Text),
+            // <redirect title="United Kingdom" />
+            b"redirect" => {
+                let title = element
+                    .try_get_attribute("title")
+                    .map_err(dump_error)?
+                    .map(|title| title.unescape_value().map(|title| title.to_string()))
+                    .transpose()
+                    .map_err(dump_error)?;
+                if let Some(page) = &mut self.page {
+                    page.redirect = title;
+                }
+                None
+            }
+            _ => None,
+        };
+        self.text.clear();
+        Ok(())
+    }
+
+    fn end_field(&mut self) {
+        let Some(field) = self.field.take() else {
+            return;
+        };
+        let text = std::mem::take(&mut self.text);
+        if field == XmlField::Base {
+            if self.language.is_none() {
+                self.language =
+                    regex_captures!(r"^https?://([a-z][a-z0-9-]*)\.wikipedia\.org", &text)
+                        .map(|(_, language)| language.to_string());
+            }
+            return;
+        }
+        let Some(page) = &mut self.page else {
+            return;
+        };
+        match field {
+            XmlField::Title => page.title = text,
+            XmlField::Namespace => page.namespace = text.trim().parse().ok(),
+            XmlField::Text => page.text = text,
+            XmlField::Base => {}
+        }
+    }
+
+    /// Turn a page that has been read into a DumpPage, None if it isn't an article or a redirect to one.
+    fn finish_page(&self, page: XmlPage) -> Result<Option<DumpPage>> {
+        if page.namespace != Some(0) {
+            return Ok(None);
+        }
+        let language = self.language.clone().ok_or_else(|| {
+            dump_error(
+                "The dump doesn't say which language edition it is, set it with `with_language`",
+            )
+        })?;
+
+        if let Some(target) = page.redirect {
+            //? The redirect element only has the title, the section it points at is in the wikitext: #REDIRECT [[United Kingdom#History]]
+            let section = regex_captures!(r"(?i)^\s*#[^\[]*\[\[([^\]|]+)", &page.text)
+                .and_then(|(_, link)| link.split_once('#').map(|(_, section)| section.trim()))
+                .filter(|section| !section.is_empty());
+            let target = match section {
+                Some(section) => format!("{target}#{section}"),
+                None => target,
+            };
+            return Ok(Some(DumpPage::redirect(language, page.title, target)));
+        }
+
+        let rendered = render_wikitext(&language, &page.text);
+        Ok(Some(DumpPage {
+            page: ApiPage {
+                title: page.title,
+                short_description: rendered.short_description,
+                disambiguation: rendered.disambiguation,
+                categories: rendered.categories,
+                ..Default::default()
+            },
+            language,
+            redirect_to: None,
+            html: rendered.html,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for XmlDumpReader<R> {
+    type Item = Result<DumpPage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let page = self.next_page().transpose();
+        //? The reader can't pick up again after a malformed part of the dump.
+        self.finished = !matches!(page, Some(Ok(_)));
+        page
+    }
+}
+
+/// Reads the articles out of an HTML dump: the newline-delimited JSON of the Wikimedia Enterprise snapshots (eg `enwiki-NS0-20240601-ENTERPRISE-HTML.json.tar.gz`, unpacked), optionally gzipped.
+/// Each line is an article with its Parsoid HTML, categories and the redirects to it. The redirects are read as redirect pages after the article.
+#[derive(Debug)]
+pub struct HtmlDumpReader<R: BufRead> {
+    lines: Lines<R>,
+    /// The language code of the edition, for lines that don't say.
+    language: Option<String>,
+    /// The redirects to the last article read.
+    pending: VecDeque<DumpPage>,
+}
+
+impl HtmlDumpReader<Box<dyn BufRead + Send>> {
+    /// Open a dump file, decompressing it as it is read if it ends in `.gz`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let reader: Box<dyn BufRead + Send> = if has_extension(path, "gz") {
+            Box::new(BufReader::new(MultiGzDecoder::new(file)))
+        } else {
+            Box::new(BufReader::new(file))
+        };
+        Ok(Self::new(reader))
+    }
+}
+
+impl<R: BufRead> HtmlDumpReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            lines: reader.lines(),
+            language: None,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Set the language of the edition, for dumps whose lines don't say.
+    pub fn with_language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Read an article out of a line, None if it isn't in the article namespace.
+    // {"name":"London","namespace":{"identifier":0},"in_language":{"identifier":"en"},"description":"Capital city of England and the United Kingdom","article_body":{"html":"<!DOCTYPE html>..."},"categories":[{"name":"Category:Capitals in Europe"}],"redirects":[{"name":"London, England"}]}
+    fn read_line(&mut self, line: &str) -> Result<Option<DumpPage>> {
+        let json = serde_json::from_str::<Value>(line).map_err(dump_error)?;
+        let namespace = json
+            .get("namespace")
+            .and_then(|namespace| namespace.get("identifier"))
+            .and_then(Value::as_i64)
+            .unwrap_or(0);
+        if namespace != 0 {
+            return Ok(None);
+        }
+        let title = str_field(&json, "name").ok_or_else(|| dump_error("An article has no name"))?;
+        let language = json
+            .get("in_language")
+            .and_then(|language| str_field(language, "identifier"))
+            .or_else(|| self.language.clone())
+            .ok_or_else(|| {
+                dump_error("The dump doesn't say which language edition it is, set it with `with_language`")
+            })?;
+        let html = json
+            .get("article_body")
+            .and_then(|body| str_field(body, "html"))
+            .ok_or_else(|| dump_error(format!("{title} has no HTML")))?;
+
+        self.pending.extend(
+            array(&json, "redirects")
+                .filter_map(|redirect| str_field(redirect, "name"))
+                .map(|redirect| DumpPage::redirect(language.clone(), redirect, title.clone())),
+        );
+        Ok(Some(DumpPage {
+            page: ApiPage {
+                page_id: json.get("identifier").and_then(Value::as_u64),
+                short_description: str_field(&json, "description"),
+                categories: array(&json, "categories")
+                    .filter_map(|category| str_field(category, "name"))
+                    .collect(),
+                title,
+                ..Default::default()
+            },
+            language,
+            redirect_to: None,
+            html,
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for HtmlDumpReader<R> {
+    type Item = Result<DumpPage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(redirect) = self.pending.pop_front() {
+            return Some(Ok(redirect));
+        }
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e.into())),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.read_line(&line) {
+                Ok(Some(page)) => return Some(Ok(page)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// How many pages of a dump are read between flushing the handler's aliases to its store.
+const ALIAS_FLUSH_INTERVAL: usize = 10_000;
+
+/// How many pages of a dump were kept, skipped and failed to scrape.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IngestSummary {
+    pub read: usize,
+    /// Pages stored under their own url and handed to `on_page`.
+    pub stored: usize,
+    /// Redirects and other aliases, recorded by the handler but not handed on.
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+/// Scrape every page of a dump and feed it through the handler the way a crawl would (see `PageHandler::ingest_page`), so redirects are recorded as aliases and records are put in the handler's store.
+/// Chunks added to the handler's text index are committed once the whole dump is in.
+/// The aliases found are flushed to the handler's store every few thousand pages (see `PageHandler::flush_aliases`), so memory doesn't grow with the size of the dump.
+/// `on_page` is given each page that was kept, its `get_all_page_links` are its edges in the link graph.
+/// Pages that fail to scrape are counted and skipped, an error reading the dump stops the ingest.
+pub async fn ingest_dump<I>(
+    pages: I,
+    handler: &PageHandler<WikipediaUrl>,
+    mut on_page: impl FnMut(Page<WasScraped<WikipediaContent>, WikipediaUrl>),
+) -> Result<IngestSummary>
+where
+    I: IntoIterator<Item = Result<DumpPage>>,
+{
+    let mut summary = IngestSummary::default();
+    for page in pages {
+        let page = page?;
+        summary.read += 1;
+        if summary.read % ALIAS_FLUSH_INTERVAL == 0 {
+            handler.flush_aliases().await?;
+        }
+        let Ok(scraped) = page.scrape() else {
+            summary.failed += 1;
+            continue;
+        };
+        match handler.ingest_page(&scraped).await {
+            Some(_) => {
+                summary.stored += 1;
+                on_page(scraped);
+            }
+            None => summary.skipped += 1,
+        }
+    }
+    handler.flush_aliases().await?;
+    handler.commit_text_index()?;
+    Ok(summary)
+}
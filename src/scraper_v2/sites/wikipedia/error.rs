@@ -1,3 +1,5 @@
+use super::Namespace;
+
 #[derive(Debug, derive_more::From)]
 pub enum WikipediaError {
     #[from]
@@ -13,6 +15,29 @@ pub enum WikipediaError {
         url: String,
         reason: String,
     },
+    NoTitleFound {
+        url: String,
+    },
+    NoContentFound {
+        url: String,
+    },
+    /// The MediaWiki API answered with an error or something we couldn't read.
+    ApiError {
+        url: String,
+        reason: String,
+    },
+    PageNotFound {
+        url: String,
+    },
+    WrongNamespace {
+        url: String,
+        expected: Namespace,
+        found: Namespace,
+    },
+    /// A dump couldn't be read.
+    DumpError {
+        reason: String,
+    },
 }
 
 // Error boilerplate.
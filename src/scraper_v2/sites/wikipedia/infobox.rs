@@ -0,0 +1,262 @@
+use super::model::{
+    Coordinates, EntityLink, Infobox, InfoboxEntry, InfoboxImage, InfoboxSection, InfoboxValue,
+};
+use super::text::{cited_text, clean_lines, clean_text};
+use super::WikipediaUrl;
+use crate::common::{extract_links, FetchedDocument};
+
+use scraper::{ElementRef, Selector};
+
+/// Every infobox in `scope`, in page order. Infoboxes nested in another (sub-boxes) are merged into their parent.
+pub(super) fn extract_infoboxes(
+    scope: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> Vec<Infobox> {
+    let infobox_selector = Selector::parse("table.infobox").unwrap();
+    scope
+        .select(&infobox_selector)
+        .filter(|table| {
+            !table
+                .ancestors()
+                .filter_map(ElementRef::wrap)
+                .any(|ancestor| infobox_selector.matches(&ancestor))
+        })
+        .map(|table| extract_infobox(&table, page_url, document))
+        .filter(|infobox| infobox.title.is_some() || !infobox.sections.is_empty())
+        .collect()
+}
+
+fn extract_infobox(
+    table: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> Infobox {
+    let caption_selector = Selector::parse("caption").unwrap();
+    let mut infobox = Infobox {
+        //? Older infoboxes put the title in a caption rather than an "above" row.
+        title: table
+            .select(&caption_selector)
+            .next()
+            .map(|caption| clean_text(&caption))
+            .filter(|title| !title.is_empty()),
+        subheaders: Vec::new(),
+        images: Vec::new(),
+        sections: Vec::new(),
+        coordinates: extract_coordinates(table),
+    };
+    push_rows(&mut infobox, table, page_url, document);
+    infobox
+        .sections
+        .retain(|section| !section.entries.is_empty());
+    infobox
+}
+
+/// Add the rows of `table` (but not of tables nested in it) to the infobox.
+fn push_rows(
+    infobox: &mut Infobox,
+    table: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) {
+    let row_selector = Selector::parse("tr").unwrap();
+    let nested_table_selector = Selector::parse("table").unwrap();
+    let image_selector = Selector::parse("img").unwrap();
+
+    for row in table
+        .select(&row_selector)
+        .filter(|row| closest_table(row).is_some_and(|closest| closest.id() == table.id()))
+    {
+        let cells = row
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+            .collect::<Vec<ElementRef>>();
+
+        match cells.as_slice() {
+            [cell] if has_class(cell, "infobox-above") => {
+                infobox.title = Some(clean_text(cell)).filter(|title| !title.is_empty());
+            }
+            [cell] if has_class(cell, "infobox-subheader") => {
+                infobox.subheaders.extend(clean_lines(cell));
+            }
+            [cell] if has_class(cell, "infobox-below") => {}
+            //? Infoboxes without the infobox-* classes start with the title in a header row.
+            [cell]
+                if cell.value().name() == "th"
+                    && infobox.title.is_none()
+                    && infobox.sections.is_empty()
+                    && infobox.images.is_empty() =>
+            {
+                infobox.title = Some(clean_text(cell)).filter(|title| !title.is_empty());
+            }
+            [cell] if has_class(cell, "infobox-header") || cell.value().name() == "th" => {
+                let heading = clean_text(cell);
+                if !heading.is_empty() {
+                    infobox.sections.push(InfoboxSection {
+                        heading: Some(heading),
+                        entries: Vec::new(),
+                    });
+                }
+            }
+            [cell] if cell.select(&nested_table_selector).next().is_some() => {
+                for nested in cell.select(&nested_table_selector).filter(|nested| {
+                    //? Only the outermost tables in the cell, their own nested tables are handled when they are.
+                    nested
+                        .ancestors()
+                        .filter_map(ElementRef::wrap)
+                        .take_while(|ancestor| ancestor.id() != cell.id())
+                        .all(|ancestor| ancestor.value().name() != "table")
+                }) {
+                    push_rows(infobox, &nested, page_url, document);
+                }
+            }
+            [cell]
+                if has_class(cell, "infobox-image")
+                    || cell.select(&image_selector).next().is_some() =>
+            {
+                infobox.images.extend(extract_images(cell, document));
+            }
+            [cell] => push_entry(infobox, None, cell, page_url, document),
+            [label, data] => {
+                let label = clean_text(label);
+                push_entry(
+                    infobox,
+                    Some(label).filter(|label| !label.is_empty()),
+                    data,
+                    page_url,
+                    document,
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+fn push_entry(
+    infobox: &mut Infobox,
+    label: Option<String>,
+    cell: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) {
+    let value = extract_value(cell, page_url, document);
+    if value.text.is_empty() {
+        return;
+    }
+    if infobox.sections.is_empty() {
+        infobox.sections.push(InfoboxSection {
+            heading: None,
+            entries: Vec::new(),
+        });
+    }
+    if let Some(section) = infobox.sections.last_mut() {
+        section.entries.push(InfoboxEntry { label, value });
+    }
+}
+
+fn extract_value(
+    cell: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> InfoboxValue {
+    let lines = clean_lines(cell);
+    InfoboxValue {
+        text: clean_text(cell),
+        items: if lines.len() > 1 { lines } else { Vec::new() },
+        links: extract_entity_links(cell, page_url, document),
+        citations: cited_text(cell).citations,
+    }
+}
+
+/// The links in `element` to other articles. Links to other namespaces and links back to the page itself (eg footnote markers) are skipped.
+pub(super) fn extract_entity_links(
+    element: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> Vec<EntityLink> {
+    let mut links = Vec::<EntityLink>::new();
+    for link in extract_links(element, document) {
+        let Ok(url) = WikipediaUrl::try_from(link.href) else {
+            continue;
+        };
+        if !url.is_article() || url == *page_url || links.iter().any(|existing| existing.url == url)
+        {
+            continue;
+        }
+        links.push(EntityLink {
+            text: link.text.trim().to_string(),
+            url,
+        });
+    }
+    links
+}
+
+fn extract_images(cell: &ElementRef, document: &FetchedDocument) -> Vec<InfoboxImage> {
+    let image_selector = Selector::parse("img").unwrap();
+    let caption_selector = Selector::parse(".infobox-caption").unwrap();
+    let images = cell.select(&image_selector).collect::<Vec<ElementRef>>();
+    //? A cell with several images (eg a flag and a coat of arms) has a caption under each, which can't be told apart reliably.
+    let caption = (images.len() == 1)
+        .then(|| cell.select(&caption_selector).next())
+        .flatten()
+        .map(|caption| clean_text(&caption))
+        .filter(|caption| !caption.is_empty());
+
+    images
+        .iter()
+        .filter_map(|image| {
+            let src = image.value().attr("src")?;
+            Some(InfoboxImage {
+                src: resolve_src(src, document),
+                alt: image
+                    .value()
+                    .attr("alt")
+                    .map(str::trim)
+                    .filter(|alt| !alt.is_empty())
+                    .map(str::to_string),
+                caption: caption.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Image sources are usually protocol relative (`//upload.wikimedia.org/...`).
+pub(super) fn resolve_src(src: &str, document: &FetchedDocument) -> String {
+    url::Url::parse(document.final_url())
+        .and_then(|base| base.join(src.trim()))
+        .map(|src| src.to_string())
+        .unwrap_or_else(|_| src.to_string())
+}
+
+/// The decimal coordinates in the machine readable `.geo` span, eg "51.507; -0.128".
+//? `f64::from_str` also accepts "NaN" and "inf", which aren't coordinates (and would break `Eq`), so only values on the globe are kept.
+fn extract_coordinates(table: &ElementRef) -> Option<Coordinates> {
+    let geo_selector = Selector::parse("span.geo").unwrap();
+    table.select(&geo_selector).find_map(|geo| {
+        let text = geo.text().collect::<String>();
+        let (latitude, longitude) = text.split_once(';')?;
+        let degrees = |text: &str, limit: f64| {
+            text.trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|degrees| degrees.is_finite() && degrees.abs() <= limit)
+        };
+        Some(Coordinates {
+            latitude: degrees(latitude, 90.0)?,
+            longitude: degrees(longitude, 180.0)?,
+        })
+    })
+}
+
+fn has_class(element: &ElementRef, class: &str) -> bool {
+    element.value().classes().any(|name| name == class)
+}
+
+/// The closest table around an element, not counting the element itself.
+pub(super) fn closest_table<'a>(element: &ElementRef<'a>) -> Option<ElementRef<'a>> {
+    element
+        .ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|ancestor| ancestor.value().name() == "table")
+}
@@ -1,7 +1,30 @@
+pub mod api;
+mod category;
+pub mod dump;
 pub mod error;
+mod infobox;
+pub mod model;
 mod page;
+mod references;
 mod scraper;
+mod table;
+mod text;
 mod url;
+mod wikitext;
 
+pub use api::WikipediaApiFetcher;
+pub use category::WikipediaCategoryContent;
+pub use dump::{ingest_dump, DumpPage, HtmlDumpReader, XmlDumpReader};
 pub use page::WikipediaContent;
-pub use url::WikipediaUrl;
+pub use url::{normalize_title, Namespace, WikipediaUrl};
+
+/// A filter for `PageHandler::with_alternates` that follows the versions of articles in the given languages, eg `&["fr", "de"]`.
+pub fn language_filter(
+    languages: &[&str],
+) -> impl Fn(&WikipediaUrl) -> bool + Send + Sync + 'static {
+    let languages = languages
+        .iter()
+        .map(|language| language.to_string())
+        .collect::<Vec<String>>();
+    move |url: &WikipediaUrl| languages.iter().any(|language| language == url.language())
+}
@@ -0,0 +1,377 @@
+use super::WikipediaUrl;
+use crate::common::Timestamp;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// A link to another article, eg a linked person or place in an infobox value.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct EntityLink {
+    pub text: String,
+    pub url: WikipediaUrl,
+}
+
+/// A link to the same article in another language edition.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct LanguageLink {
+    /// The language code, eg "fr".
+    pub language: String,
+    /// The name of the language in that language, eg "Français".
+    pub language_name: String,
+    /// The title of the article in that language, eg "Londres".
+    pub title: String,
+    pub url: WikipediaUrl,
+}
+
+/// A category a page is in, from the list at the bottom of the page.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct CategoryLink {
+    /// The name of the category without the namespace prefix, eg "Cities in England".
+    pub title: String,
+    pub url: WikipediaUrl,
+}
+
+/// A page listed on a category page.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct CategoryMember {
+    /// The title as listed, eg "London" or "Cities in Kent".
+    pub title: String,
+    pub url: WikipediaUrl,
+    /// The letter heading the member is listed under, which comes from its sort key rather than its title.
+    pub group: Option<String>,
+}
+
+/// Where a redirect page points.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct RedirectTarget {
+    pub url: WikipediaUrl,
+    /// The section of the target the redirect points at, eg "History" for `United_Kingdom#History`.
+    pub section: Option<String>,
+}
+
+/// One of the articles a disambiguation page lists.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct DisambiguationEntry {
+    pub title: String,
+    pub url: WikipediaUrl,
+    /// The rest of the line after the link, eg "a city in Ontario, Canada".
+    pub description: Option<String>,
+    /// The heading the entry is listed under, eg "Places".
+    pub section: Option<String>,
+}
+
+/// The parts of a page that depend on what kind of page it is.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub enum PageDetails {
+    /// Articles only have the shared fields.
+    Article,
+    /// A page that only points at another article. These are usually followed by the server, so this is only seen when they aren't (eg `redirect=no`).
+    Redirect(RedirectTarget),
+    /// A page listing the articles a title could refer to.
+    Disambiguation(Vec<DisambiguationEntry>),
+}
+
+/// Latitude and longitude in decimal degrees.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+pub struct Coordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+//? Coordinates are only parsed from text, and values that aren't finite are rejected, so they are never NaN.
+impl Eq for Coordinates {}
+
+/// A footnote marker in the text, eg the "[1]" after a sentence.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Citation {
+    /// The id of the reference the marker links to, eg "cite_note-Smith2020-3".
+    pub reference_id: String,
+    /// The marker as shown on the page without brackets, eg "1" or "note 2".
+    pub label: String,
+    /// The byte offset in the text the marker comes after.
+    pub offset: usize,
+}
+
+/// A paragraph of text with its footnote markers taken out and kept as citations.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct Paragraph {
+    pub text: String,
+    pub citations: Vec<Citation>,
+}
+
+/// An entry in the references list at the bottom of an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Reference {
+    /// The id the citations link to, eg "cite_note-Smith2020-3".
+    pub id: String,
+    /// The full text of the reference as shown on the page.
+    pub text: String,
+    pub title: Option<String>,
+    /// The cited source, if it is online.
+    pub url: Option<String>,
+    /// The publisher, website or journal.
+    pub publisher: Option<String>,
+    /// When the source was published. Often a date like "12 March 2020", which is kept as unparseable.
+    pub date: Timestamp,
+    /// A copy of the source on an archive site, eg the Wayback Machine.
+    pub archive_url: Option<String>,
+    pub doi: Option<String>,
+    pub isbn: Option<String>,
+}
+
+/// A link in the "External links" section of an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct ExternalLink {
+    pub text: String,
+    pub url: String,
+}
+
+/// The value of an infobox row.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InfoboxValue {
+    /// The whole value as a single line of text, with footnote markers removed.
+    pub text: String,
+    /// The separate items of values that are lists (eg several spouses or a list of founders), otherwise empty.
+    pub items: Vec<String>,
+    pub links: Vec<EntityLink>,
+    /// The footnote markers in the value.
+    pub citations: Vec<Citation>,
+}
+
+/// A row of an infobox. Rows that span the whole infobox have no label.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InfoboxEntry {
+    pub label: Option<String>,
+    pub value: InfoboxValue,
+}
+
+/// The rows under a header of an infobox (eg "Personal details"). Rows before the first header have no heading.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InfoboxSection {
+    pub heading: Option<String>,
+    pub entries: Vec<InfoboxEntry>,
+}
+
+/// An image in an infobox, eg a portrait, flag or logo.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InfoboxImage {
+    pub src: String,
+    pub alt: Option<String>,
+    pub caption: Option<String>,
+}
+
+/// The summary table (`table.infobox`) at the top of an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Infobox {
+    /// The title above the infobox, usually the name of the subject.
+    pub title: Option<String>,
+    /// The lines under the title, eg the office a person held.
+    pub subheaders: Vec<String>,
+    pub images: Vec<InfoboxImage>,
+    /// The rows of the infobox in order, grouped by header.
+    pub sections: Vec<InfoboxSection>,
+    pub coordinates: Option<Coordinates>,
+}
+
+impl Infobox {
+    /// Every row of the infobox in order.
+    pub fn entries(&self) -> impl Iterator<Item = &InfoboxEntry> {
+        self.sections
+            .iter()
+            .flat_map(|section| section.entries.iter())
+    }
+
+    /// The value of the first row with the given label, ignoring case.
+    pub fn get(&self, label: &str) -> Option<&InfoboxValue> {
+        self.entries()
+            .find(|entry| {
+                entry
+                    .label
+                    .as_deref()
+                    .is_some_and(|entry_label| entry_label.eq_ignore_ascii_case(label))
+            })
+            .map(|entry| &entry.value)
+    }
+
+    /// Every article linked from the infobox.
+    pub fn links(&self) -> impl Iterator<Item = &EntityLink> {
+        self.entries().flat_map(|entry| entry.value.links.iter())
+    }
+}
+
+/// What a table cell holds, parsed from its text (or its sort key) when it is a single number, amount or date.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub enum CellValue {
+    /// The cell is empty or only a dash.
+    Empty,
+    /// Anything that isn't one of the other variants, the cell's text is all there is.
+    Text,
+    Number(f64),
+    /// A percentage as shown, eg 12.5 for "12.5%".
+    Percent(f64),
+    /// An amount of money, eg "£1,200" is 1200 in "£".
+    Money {
+        amount: f64,
+        currency: String,
+    },
+    Date(NaiveDate),
+}
+
+//? Cell values are only ever parsed from text, so they are never NaN.
+impl Eq for CellValue {}
+
+impl CellValue {
+    /// The value as JSON: numbers for numbers, ISO dates for dates, null for empty cells and the text for everything else.
+    pub fn to_json(&self, text: &str) -> Value {
+        match self {
+            CellValue::Empty => Value::Null,
+            CellValue::Text => Value::from(text),
+            CellValue::Number(number) | CellValue::Percent(number) => Value::from(*number),
+            CellValue::Money { amount, .. } => Value::from(*amount),
+            CellValue::Date(date) => Value::from(date.to_string()),
+        }
+    }
+}
+
+/// A cell of a table. Cells spanning several rows or columns are repeated in each position they cover.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct TableCell {
+    /// The text of the cell on a single line, with footnote markers removed.
+    pub text: String,
+    pub value: CellValue,
+    pub links: Vec<EntityLink>,
+    pub citations: Vec<Citation>,
+    /// Whether the cell is a header cell (`th`), eg the row headers in the first column.
+    pub is_header: bool,
+    /// Whether this is a repeat of a cell that spans from the row above or the column before.
+    pub continued: bool,
+}
+
+/// A data table (`table.wikitable`) in an article.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Table {
+    pub caption: Option<String>,
+    /// The heading of the section the table is in, None for tables before the first heading.
+    pub section: Option<String>,
+    /// The header rows from top to bottom. Tables with grouped columns have several, with the group headers repeated over each column in the group.
+    pub header_rows: Vec<Vec<String>>,
+    /// The body rows. Every row has a cell for each column.
+    pub rows: Vec<Vec<TableCell>>,
+    /// The notes at the foot of the table.
+    pub footnotes: Vec<String>,
+}
+
+impl Table {
+    /// The number of columns.
+    pub fn width(&self) -> usize {
+        self.header_rows
+            .iter()
+            .map(Vec::len)
+            .chain(self.rows.iter().map(Vec::len))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The name of each column, made of its headers from the top level down, eg "Population / 2011".
+    /// Levels repeating the one above (a header spanning rows) are only named once, columns without headers are named by position ("Column 3").
+    pub fn column_names(&self) -> Vec<String> {
+        (0..self.width())
+            .map(|column| {
+                let mut levels = Vec::<&str>::new();
+                for row in &self.header_rows {
+                    let Some(header) = row.get(column).filter(|header| !header.is_empty()) else {
+                        continue;
+                    };
+                    if levels.last() != Some(&header.as_str()) {
+                        levels.push(header);
+                    }
+                }
+                if levels.is_empty() {
+                    format!("Column {}", column + 1)
+                } else {
+                    levels.join(" / ")
+                }
+            })
+            .collect()
+    }
+
+    /// The cells of the column with the given name (see `column_names`), ignoring case.
+    pub fn column(&self, name: &str) -> Option<Vec<&TableCell>> {
+        let index = self
+            .column_names()
+            .iter()
+            .position(|column_name| column_name.eq_ignore_ascii_case(name))?;
+        Some(self.rows.iter().filter_map(|row| row.get(index)).collect())
+    }
+
+    /// The header cell each row starts with, if it has one.
+    pub fn row_headers(&self) -> Vec<Option<&str>> {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.first()
+                    .filter(|cell| cell.is_header)
+                    .map(|cell| cell.text.as_str())
+            })
+            .collect()
+    }
+
+    /// The table as CSV, with a line of column names and then the text of each row.
+    pub fn to_csv(&self) -> String {
+        let line = |fields: Vec<&str>| {
+            fields
+                .into_iter()
+                .map(|field| {
+                    if field.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", field.replace('"', "\"\""))
+                    } else {
+                        field.to_string()
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        let column_names = self.column_names();
+        let mut csv = line(column_names.iter().map(String::as_str).collect());
+        csv.push_str("\r\n");
+        for row in &self.rows {
+            csv.push_str(&line(row.iter().map(|cell| cell.text.as_str()).collect()));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    /// The rows as JSON objects keyed by column name, with the parsed values (see `CellValue::to_json`).
+    /// Columns with the same name get a number after the first, eg "Votes (2)".
+    pub fn to_json(&self) -> Value {
+        let mut seen = HashMap::<String, usize>::new();
+        let keys = self
+            .column_names()
+            .into_iter()
+            .map(|name| {
+                let count = seen.entry(name.clone()).or_default();
+                *count += 1;
+                if *count == 1 {
+                    name
+                } else {
+                    format!("{name} ({count})")
+                }
+            })
+            .collect::<Vec<String>>();
+        Value::Array(
+            self.rows
+                .iter()
+                .map(|row| {
+                    Value::Object(
+                        keys.iter()
+                            .zip(row)
+                            .map(|(key, cell)| (key.clone(), cell.value.to_json(&cell.text)))
+                            .collect::<Map<String, Value>>(),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
@@ -1,30 +1,492 @@
+use super::category::extract_categories;
 use super::error::WikipediaError;
-use super::WikipediaUrl;
-use crate::common::{LinkTo, Page, ScrapableContent, UrlTrait};
+use super::infobox::extract_infoboxes;
+use super::model::{
+    CategoryLink, DisambiguationEntry, ExternalLink, Infobox, LanguageLink, PageDetails, Paragraph,
+    RedirectTarget, Reference, Table,
+};
+use super::references::{extract_external_links, extract_references};
+use super::table::extract_tables;
+use super::text::{cited_text, clean_lines, clean_text};
+use super::{normalize_title, Namespace, WikipediaUrl};
+use crate::common::{
+    escape_markdown, extract_links, extract_page_links, FetchedDocument, LinkRel, LinkTo, Page,
+    Renderer, ScrapableContent, UrlTrait,
+};
 use crate::Result;
 
-use scraper::{ElementRef, Html};
+use lazy_regex::regex_captures;
+use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, rc::Rc};
+use std::collections::HashSet;
+
+/// The banners that mark a disambiguation page, across the template markup of the larger language editions.
+const DISAMBIGUATION_SELECTOR: &str = "#disambigbox, .dmbox-disambig, #disambig, .homonymie, \
+    meta[property='mw:PageProp/disambiguation']";
+
+/// The parts of the body that aren't prose: footnote markers, edit links, infoboxes, navboxes, banners, images, the table of contents and the reference lists.
+const MARKDOWN_SKIP_SELECTOR: &str = "sup.reference, .mw-editsection, .infobox, .navbox, \
+    .vertical-navbox, .sidebar, .hatnote, .ambox, .mbox-small, .metadata, .noprint, \
+    .shortdescription, .toc, #toc, .mw-references-wrap, ol.references, .reflist, .mw-empty-elt, \
+    figure, .thumb, style, link";
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct WikipediaContent {
     title: String,
-    short_description: String,
-    //table: Table,
-    abstract_text: Vec<String>,
+    /// The url of the page that was served, which is the target when the requested url was a redirect.
+    url: WikipediaUrl,
+    /// The redirect the page was reached through, eg `/wiki/UK` for United Kingdom.
+    redirected_from: Option<WikipediaUrl>,
+    language: String,
+    details: PageDetails,
+    short_description: Option<String>,
+    infoboxes: Vec<Infobox>,
+    /// The paragraphs before the first heading.
+    abstract_text: Vec<Paragraph>,
+    /// The data tables in the article.
+    tables: Vec<Table>,
+    /// The body of the article rendered to Markdown, with headings, lists and tables but without the infoboxes, navboxes and references.
+    markdown: String,
+    /// The body of the article as plain text, which the content hash and near-duplicate fingerprint are taken from.
+    #[serde(default)]
+    text: String,
+    /// The references the citations in the text point at.
+    references: Vec<Reference>,
+    external_links: Vec<ExternalLink>,
+    /// The same article in other language editions.
+    language_links: Vec<LanguageLink>,
     //content: SectionContentType,
-    //categories: Vec<Link>,
-    page_links: HashSet<WikipediaUrl>,
+    categories: Vec<CategoryLink>,
+    page_links: HashSet<Page<LinkTo, WikipediaUrl>>,
 }
 impl ScrapableContent for WikipediaContent {
     type Url = WikipediaUrl;
-    fn from_scraped_page(url: &Self::Url, document: &Html) -> Result<Self> {
-        // Parse the HTML to create a BBCPage
-        todo!()
+    fn from_scraped_page(url: &Self::Url, document: &FetchedDocument) -> Result<Self> {
+        if !url.is_article() {
+            return Err(WikipediaError::WrongNamespace {
+                url: url.full_url(),
+                expected: Namespace::Article,
+                found: url.namespace(),
+            }
+            .into());
+        }
+
+        let title = Self::extract_title(document).ok_or(WikipediaError::NoTitleFound {
+            url: url.full_url(),
+        })?;
+        let body = Self::extract_body(document).ok_or(WikipediaError::NoContentFound {
+            url: url.full_url(),
+        })?;
+
+        //? The page that was served differs from the requested one when a redirect was followed.
+        let served_url =
+            Self::extract_served_url(document, url.language()).unwrap_or_else(|| url.clone());
+        let redirected_from = Self::extract_redirected_from(document)
+            .or_else(|| (served_url != *url).then(|| url.clone()));
+
+        let details = if let Some(target) = Self::extract_redirect_target(&body, document) {
+            PageDetails::Redirect(target)
+        } else if Self::has_disambiguation_banner(document) {
+            PageDetails::Disambiguation(Self::extract_disambiguation_entries(
+                &body,
+                &served_url,
+                document,
+            ))
+        } else {
+            PageDetails::Article
+        };
+
+        let infoboxes = extract_infoboxes(&body, &served_url, document);
+        let tables = extract_tables(&body, &served_url, document);
+
+        Ok(WikipediaContent {
+            title,
+            url: served_url,
+            redirected_from,
+            language: url.language().to_string(),
+            details,
+            short_description: Self::extract_short_description(&body),
+            infoboxes,
+            abstract_text: Self::extract_abstract(&body),
+            tables,
+            markdown: Renderer::markdown()
+                .with_base_url(&document.base_url())
+                .with_skip_selector(MARKDOWN_SKIP_SELECTOR)
+                .render(&body),
+            text: Renderer::plain_text()
+                .with_skip_selector(MARKDOWN_SKIP_SELECTOR)
+                .render(&body),
+            references: extract_references(&body, document),
+            external_links: extract_external_links(&body, document),
+            language_links: Self::extract_language_links(document),
+            categories: extract_categories(document),
+            //? Links to talk pages, templates, categories etc aren't articles, so they aren't followed.
+            page_links: extract_page_links::<WikipediaUrl>(&body, document)
+                .into_iter()
+                .filter(|page| page.as_ref().is_article())
+                .collect(),
+        })
     }
 
+    /// A redirect page only leads to its target.
     fn get_related_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
-        todo!()
+        match &self.details {
+            PageDetails::Redirect(target) => HashSet::from([Page::<LinkTo, WikipediaUrl>::new(
+                target.url.clone(),
+                target.url.title(),
+            )]),
+            _ => self.page_links.clone(),
+        }
+    }
+
+    fn canonical_url(&self) -> Option<Self::Url> {
+        match &self.details {
+            PageDetails::Redirect(_) => None,
+            _ => Some(self.url.clone()),
+        }
+    }
+
+    fn redirect_target(&self) -> Option<Self::Url> {
+        match &self.details {
+            PageDetails::Redirect(target) => Some(target.url.clone()),
+            _ => None,
+        }
+    }
+
+    fn get_alternate_pages(&self) -> HashSet<Page<LinkTo, Self::Url>> {
+        self.language_links
+            .iter()
+            .map(|link| {
+                Page::<LinkTo, WikipediaUrl>::new_with_rel(
+                    link.url.clone(),
+                    link.title.clone(),
+                    vec![LinkRel::Alternate],
+                )
+            })
+            .collect()
+    }
+
+    fn body_text(&self) -> String {
+        self.text.clone()
+    }
+
+    fn to_markdown(&self) -> String {
+        format!("# {}\n\n{}", escape_markdown(&self.title), self.markdown)
+            .trim_end()
+            .to_string()
+    }
+
+    fn page_title(&self) -> Option<String> {
+        Some(self.title.clone())
+    }
+
+    fn categories(&self) -> Vec<String> {
+        self.categories
+            .iter()
+            .map(|category| category.title.clone())
+            .collect()
+    }
+
+    fn citations(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        self.references
+            .iter()
+            .filter_map(|reference| {
+                reference
+                    .url
+                    .clone()
+                    .or_else(|| reference.archive_url.clone())
+                    .or_else(|| {
+                        let doi = reference.doi.as_ref()?;
+                        Some(format!("https://doi.org/{doi}"))
+                    })
+            })
+            .filter(|url| seen.insert(url.clone()))
+            .collect()
+    }
+
+    //? Only articles are the page of an entity, redirects and disambiguation pages point at others.
+    fn entity_names(&self) -> Vec<String> {
+        if !self.is_article() {
+            return Vec::new();
+        }
+        let mut names = vec![self.title.clone()];
+        if let Some(redirect) = self
+            .redirected_from
+            .as_ref()
+            .filter(|redirect| redirect.is_article())
+        {
+            names.push(redirect.title().to_string());
+        }
+        names
+    }
+}
+
+impl WikipediaContent {
+    /// The body of the article as Markdown, without the title.
+    pub fn markdown(&self) -> &str {
+        &self.markdown
+    }
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+    /// The url of the page that was served, which is the target when the requested url was a redirect.
+    pub fn url(&self) -> &WikipediaUrl {
+        &self.url
+    }
+    /// The redirect the page was reached through, if any.
+    pub fn redirected_from(&self) -> Option<&WikipediaUrl> {
+        self.redirected_from.as_ref()
+    }
+    pub fn details(&self) -> &PageDetails {
+        &self.details
+    }
+    pub fn is_article(&self) -> bool {
+        matches!(self.details, PageDetails::Article)
+    }
+    pub fn is_redirect(&self) -> bool {
+        matches!(self.details, PageDetails::Redirect(_))
+    }
+    pub fn is_disambiguation(&self) -> bool {
+        matches!(self.details, PageDetails::Disambiguation(_))
+    }
+    /// The articles a disambiguation page lists, empty for other pages.
+    pub fn disambiguation_entries(&self) -> &[DisambiguationEntry] {
+        match &self.details {
+            PageDetails::Disambiguation(entries) => entries,
+            _ => &[],
+        }
     }
+    pub fn short_description(&self) -> Option<&str> {
+        self.short_description.as_deref()
+    }
+    pub fn abstract_text(&self) -> &[Paragraph] {
+        &self.abstract_text
+    }
+    /// The data tables (`wikitable`s) in the article, in page order.
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+    pub fn references(&self) -> &[Reference] {
+        &self.references
+    }
+    /// The reference a citation points at.
+    pub fn reference(&self, id: &str) -> Option<&Reference> {
+        self.references.iter().find(|reference| reference.id == id)
+    }
+    /// The language code of the edition the article is in, eg "en".
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+    /// The same article in other language editions.
+    pub fn language_links(&self) -> &[LanguageLink] {
+        &self.language_links
+    }
+    /// The same article in another language, if it has been written.
+    pub fn in_language(&self, language: &str) -> Option<&LanguageLink> {
+        self.language_links
+            .iter()
+            .find(|link| link.language == language)
+    }
+    /// The categories the article is in, without hidden maintenance categories.
+    pub fn categories(&self) -> &[CategoryLink] {
+        &self.categories
+    }
+    /// The links in the "External links" section.
+    pub fn external_links(&self) -> &[ExternalLink] {
+        &self.external_links
+    }
+    /// The infoboxes on the page, most articles have at most one.
+    pub fn infoboxes(&self) -> &[Infobox] {
+        &self.infoboxes
+    }
+    pub fn infobox(&self) -> Option<&Infobox> {
+        self.infoboxes.first()
+    }
+
+    /// The interlanguage links in the sidebar (or the language menu in the newer skin).
+    // <li class="interlanguage-link interwiki-fr"><a href="https://fr.wikipedia.org/wiki/Londres" title="Londres – French" lang="fr" hreflang="fr" class="interlanguage-link-target"><span>Français</span></a></li>
+    fn extract_language_links(document: &FetchedDocument) -> Vec<LanguageLink> {
+        let link_selector = Selector::parse("li.interlanguage-link a[href]").unwrap();
+        document
+            .select(&link_selector)
+            .filter_map(|link| {
+                let url =
+                    WikipediaUrl::resolve(&document.base_url(), link.value().attr("href")?).ok()?;
+                let title = link
+                    .value()
+                    .attr("title")
+                    .and_then(|title| title.rsplit_once(" – "))
+                    .map(|(title, _)| title.trim().to_string())
+                    .unwrap_or_else(|| url.full_title());
+                Some(LanguageLink {
+                    language: link
+                        .value()
+                        .attr("hreflang")
+                        .or_else(|| link.value().attr("lang"))
+                        .map(str::to_string)
+                        .unwrap_or_else(|| url.language().to_string()),
+                    language_name: clean_text(&link),
+                    title,
+                    url,
+                })
+            })
+            .collect()
+    }
+
+    /// The url of the page that was served, from `wgPageName` or else the canonical link.
+    //? Not from the heading: `DISPLAYTITLE` and italic titles make it differ from the title of the page.
+    // <script>RLCONF={"wgCanonicalNamespace":"","wgNamespaceNumber":0,"wgPageName":"Ada_Lovelace","wgTitle":"Ada Lovelace",...};</script>
+    fn extract_served_url(document: &FetchedDocument, language: &str) -> Option<WikipediaUrl> {
+        let script_selector = Selector::parse("script").unwrap();
+        let page_name = document.select(&script_selector).find_map(|script| {
+            let text = script.text().collect::<String>();
+            let (_, name) = regex_captures!(r#""wgPageName"\s*:\s*("(?:[^"\\]|\\.)*")"#, &text)?;
+            serde_json::from_str::<String>(name).ok()
+        });
+        page_name
+            .and_then(|name| WikipediaUrl::from_title(language, &name).ok())
+            .or_else(|| WikipediaUrl::parse(document.canonical_link()?).ok())
+            .filter(|served_url| served_url.is_article())
+    }
+
+    /// The "(Redirected from ...)" notice under the title of a page that was reached through a redirect.
+    // <span class="mw-redirectedfrom">(Redirected from <a href="/w/index.php?title=UK&amp;redirect=no" class="mw-redirect" title="UK">UK</a>)</span>
+    fn extract_redirected_from(document: &FetchedDocument) -> Option<WikipediaUrl> {
+        let notice_selector = Selector::parse(".mw-redirectedfrom a[href]").unwrap();
+        let link = document.select(&notice_selector).next()?;
+        WikipediaUrl::resolve(&document.base_url(), link.value().attr("href")?).ok()
+    }
+
+    /// The target of a redirect page that wasn't followed.
+    // <div class="redirectMsg"><p>Redirect to:</p><ul class="redirectText"><li><a href="/wiki/United_Kingdom#History">United Kingdom#History</a></li></ul></div>
+    fn extract_redirect_target(
+        body: &ElementRef,
+        document: &FetchedDocument,
+    ) -> Option<RedirectTarget> {
+        let target_selector = Selector::parse(".redirectMsg .redirectText a[href]").unwrap();
+        let href = body.select(&target_selector).next()?.value().attr("href")?;
+        let url = WikipediaUrl::resolve(&document.base_url(), href).ok()?;
+        let section = href
+            .split_once('#')
+            .map(|(_, section)| normalize_title(url.language(), section).1)
+            .filter(|section| !section.is_empty());
+        Some(RedirectTarget { url, section })
+    }
+
+    fn has_disambiguation_banner(document: &FetchedDocument) -> bool {
+        let disambiguation_selector = Selector::parse(DISAMBIGUATION_SELECTOR).unwrap();
+        document.select(&disambiguation_selector).next().is_some()
+    }
+
+    /// The list items of a disambiguation page that link to an article, with the heading they are under.
+    // <li><a href="/wiki/London,_Ontario" title="London, Ontario">London, Ontario</a>, a city in Canada</li>
+    fn extract_disambiguation_entries(
+        body: &ElementRef,
+        page_url: &WikipediaUrl,
+        document: &FetchedDocument,
+    ) -> Vec<DisambiguationEntry> {
+        let item_selector = Selector::parse("li").unwrap();
+        let banner_selector = Selector::parse(DISAMBIGUATION_SELECTOR).unwrap();
+        let mut section = None;
+        let mut entries = Vec::<DisambiguationEntry>::new();
+
+        for element in body.child_elements() {
+            if is_heading(&element) {
+                section = Some(clean_text(&element)).filter(|heading| !heading.is_empty());
+                continue;
+            }
+            //? Lists are either bare or wrapped in a column div, navboxes and the banner are tables or divs with classes.
+            let is_list = matches!(element.value().name(), "ul" | "ol")
+                || element.value().classes().any(|class| class == "div-col");
+            if !is_list || banner_selector.matches(&element) {
+                continue;
+            }
+            for item in element.select(&item_selector) {
+                let Some((url, text)) =
+                    extract_links(&item, document).into_iter().find_map(|link| {
+                        let url = WikipediaUrl::try_from(link.href).ok()?;
+                        (url.is_article() && url != *page_url).then_some((url, link.text))
+                    })
+                else {
+                    continue;
+                };
+                if entries.iter().any(|entry| entry.url == url) {
+                    continue;
+                }
+                let title = text.trim().to_string();
+                //? Only the first line, nested lists are entries of their own.
+                let description = clean_lines(&item)
+                    .into_iter()
+                    .next()
+                    .and_then(|line| {
+                        line.split_once(title.as_str())
+                            .map(|(_, rest)| rest.to_string())
+                    })
+                    .map(|rest| {
+                        rest.trim_start_matches([',', ' ', '-', '–', '—', ':'])
+                            .trim()
+                            .to_string()
+                    })
+                    .filter(|description| !description.is_empty());
+                entries.push(DisambiguationEntry {
+                    title: if title.is_empty() {
+                        url.title().to_string()
+                    } else {
+                        title
+                    },
+                    url,
+                    description,
+                    section: section.clone(),
+                });
+            }
+        }
+        entries
+    }
+
+    fn extract_title(document: &Html) -> Option<String> {
+        let title_selector = Selector::parse("h1#firstHeading").unwrap();
+        document
+            .select(&title_selector)
+            .next()
+            .map(|title| clean_text(&title))
+            .filter(|title| !title.is_empty())
+    }
+
+    /// The parsed wikitext of the article, without the page chrome around it.
+    fn extract_body(document: &Html) -> Option<ElementRef<'_>> {
+        //? Selector lists match in document order, so the fallback has to be a separate select.
+        ["#mw-content-text .mw-parser-output", "#mw-content-text"]
+            .iter()
+            .find_map(|selector| {
+                let body_selector = Selector::parse(selector).unwrap();
+                document.select(&body_selector).next()
+            })
+    }
+
+    fn extract_short_description(body: &ElementRef) -> Option<String> {
+        let short_description_selector = Selector::parse("div.shortdescription").unwrap();
+        body.select(&short_description_selector)
+            .next()
+            .map(|description| description.text().collect::<String>().trim().to_string())
+            .filter(|description| !description.is_empty())
+    }
+
+    /// The paragraphs up to the first section heading.
+    fn extract_abstract(body: &ElementRef) -> Vec<Paragraph> {
+        body.child_elements()
+            .take_while(|element| !is_heading(element))
+            .filter(|element| element.value().name() == "p")
+            .map(|paragraph| cited_text(&paragraph))
+            .filter(|paragraph| !paragraph.text.is_empty())
+            .collect()
+    }
+}
+
+/// Whether an element is a section heading. Headings are either bare (`<h2>`) or wrapped in a `div.mw-heading`.
+pub(super) fn is_heading(element: &ElementRef) -> bool {
+    matches!(
+        element.value().name(),
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
+    ) || element.value().classes().any(|class| class == "mw-heading")
 }
@@ -0,0 +1,188 @@
+use super::model::{ExternalLink, Reference};
+use super::page::is_heading;
+use super::text::clean_text;
+use crate::common::{extract_links, FetchedDocument, Timestamp};
+
+use scraper::{ElementRef, Selector};
+use std::collections::HashMap;
+
+/// Hosts of web archives, links to which are archived copies rather than the source itself.
+const ARCHIVE_HOSTS: &[&str] = &[
+    "web.archive.org",
+    "archive.org",
+    "archive.today",
+    "archive.ph",
+    "archive.is",
+    "webcitation.org",
+    "ghostarchive.org",
+];
+
+/// The id of the "External links" section heading.
+const EXTERNAL_LINKS_ID: &str = "External_links";
+
+/// Every entry of the references lists (`ol.references`) in `body`, in order.
+pub(super) fn extract_references(body: &ElementRef, document: &FetchedDocument) -> Vec<Reference> {
+    let reference_selector = Selector::parse("ol.references > li[id]").unwrap();
+    let text_selector = Selector::parse(".reference-text").unwrap();
+    body.select(&reference_selector)
+        .filter_map(|item| {
+            let id = item.value().attr("id")?.to_string();
+            let reference_text = item.select(&text_selector).next().unwrap_or(item);
+            Some(extract_reference(id, &reference_text, document))
+        })
+        .collect()
+}
+
+fn extract_reference(id: String, element: &ElementRef, document: &FetchedDocument) -> Reference {
+    //? Citation templates embed their fields as an OpenURL (COinS) in a hidden span, which is more reliable than picking apart the formatted text.
+    let coins = extract_coins(element);
+    let coins_field = |keys: &[&str]| keys.iter().find_map(|key| coins.get(key).cloned());
+
+    let links = extract_links(element, document);
+    let is_archive = |href: &str| {
+        url::Url::parse(href)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .is_some_and(|host| {
+                ARCHIVE_HOSTS
+                    .iter()
+                    .any(|archive| host == *archive || host.ends_with(&format!(".{archive}")))
+            })
+    };
+    let is_wikipedia = |href: &str| {
+        url::Url::parse(href)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .is_some_and(|host| host.ends_with("wikipedia.org"))
+    };
+    let source_link = links.iter().find(|link| {
+        !is_archive(&link.href) && !is_wikipedia(&link.href) && !is_identifier(&link.href)
+    });
+
+    let doi = coins
+        .get_all("rft_id")
+        .iter()
+        .find_map(|id| id.strip_prefix("info:doi/").map(str::to_string))
+        .or_else(|| {
+            links
+                .iter()
+                .find(|link| link.href.contains("doi.org/"))
+                .map(|link| link.text.trim().to_string())
+        });
+    let isbn = coins_field(&["rft.isbn"]).or_else(|| {
+        links
+            .iter()
+            .find(|link| link.href.contains("Special:BookSources"))
+            .map(|link| link.text.trim().to_string())
+    });
+
+    Reference {
+        id,
+        text: clean_text(element),
+        title: coins_field(&["rft.atitle", "rft.btitle", "rft.title"]).or_else(|| {
+            source_link
+                .map(|link| link.text.trim().trim_matches('"').to_string())
+                .filter(|title| !title.is_empty())
+        }),
+        url: coins
+            .get_all("rft_id")
+            .into_iter()
+            .find(|id| id.starts_with("http"))
+            .or_else(|| source_link.map(|link| link.href.clone())),
+        publisher: coins_field(&["rft.pub", "rft.jtitle"]).or_else(|| {
+            let publisher_selector = Selector::parse("cite i").unwrap();
+            element
+                .select(&publisher_selector)
+                .map(|publisher| clean_text(&publisher))
+                .find(|publisher| !publisher.is_empty())
+        }),
+        date: Timestamp::parse(coins_field(&["rft.date"]).as_deref()),
+        archive_url: links
+            .iter()
+            .find(|link| is_archive(&link.href))
+            .map(|link| link.href.clone()),
+        doi,
+        isbn,
+    }
+}
+
+/// Links to identifier resolvers (DOI, ISBN, PMID etc) rather than the source.
+fn is_identifier(href: &str) -> bool {
+    [
+        "doi.org/",
+        "Special:BookSources",
+        "pubmed.ncbi",
+        "jstor.org/stable",
+        "worldcat.org",
+    ]
+    .iter()
+    .any(|resolver| href.contains(resolver))
+}
+
+/// The fields of a COinS span (`<span class="Z3988" title="ctx_ver=Z39.88-2004&rft.atitle=...">`).
+struct Coins(HashMap<String, Vec<String>>);
+
+impl Coins {
+    fn get(&self, key: &str) -> Option<&String> {
+        self.0.get(key).and_then(|values| values.first())
+    }
+
+    fn get_all(&self, key: &str) -> Vec<String> {
+        self.0.get(key).cloned().unwrap_or_default()
+    }
+}
+
+fn extract_coins(element: &ElementRef) -> Coins {
+    let coins_selector = Selector::parse("span.Z3988[title]").unwrap();
+    let mut fields = HashMap::<String, Vec<String>>::new();
+    if let Some(title) = element
+        .select(&coins_selector)
+        .next()
+        .and_then(|coins| coins.value().attr("title"))
+    {
+        for (key, value) in url::form_urlencoded::parse(title.as_bytes()) {
+            let value = value.trim().to_string();
+            if !value.is_empty() {
+                fields.entry(key.into_owned()).or_default().push(value);
+            }
+        }
+    }
+    Coins(fields)
+}
+
+/// The links in the "External links" section of the article.
+pub(super) fn extract_external_links(
+    body: &ElementRef,
+    document: &FetchedDocument,
+) -> Vec<ExternalLink> {
+    let id_selector = Selector::parse(&format!("#{EXTERNAL_LINKS_ID}")).unwrap();
+    let external_selector = Selector::parse("a.external").unwrap();
+    //? Links are usually protocol relative, so they are resolved against the page.
+    let base = url::Url::parse(&document.base_url()).ok();
+    let is_section_heading = |element: &ElementRef| {
+        is_heading(element)
+            && (element.value().id() == Some(EXTERNAL_LINKS_ID)
+                || element.select(&id_selector).next().is_some())
+    };
+
+    body.child_elements()
+        .skip_while(|element| !is_section_heading(element))
+        .skip(1)
+        .take_while(|element| !is_heading(element))
+        .flat_map(|element| {
+            element
+                .select(&external_selector)
+                .filter_map(|link| {
+                    Some(ExternalLink {
+                        text: clean_text(&link),
+                        url: base
+                            .as_ref()?
+                            .join(link.value().attr("href")?.trim())
+                            .ok()?
+                            .to_string(),
+                    })
+                })
+                .collect::<Vec<ExternalLink>>()
+        })
+        .collect()
+}
@@ -0,0 +1,301 @@
+use super::infobox::{closest_table, extract_entity_links};
+use super::model::{CellValue, Table, TableCell};
+use super::page::is_heading;
+use super::text::{cited_text, clean_text};
+use super::WikipediaUrl;
+use crate::common::FetchedDocument;
+
+use chrono::NaiveDate;
+use lazy_regex::regex_captures;
+use scraper::{ElementRef, Selector};
+use std::collections::HashMap;
+
+/// Spans are clipped to this, so a stray `colspan="1000"` doesn't blow up the grid.
+const MAX_SPAN: usize = 100;
+
+/// The formats dates are written in in tables, eg "12 March 2020" or "March 12, 2020".
+const DATE_FORMATS: &[&str] = &["%d %B %Y", "%B %d, %Y", "%Y-%m-%d"];
+
+/// A position in the grid of a table, taken by a cell that starts there or spans over it.
+#[derive(Debug, Clone, Copy)]
+struct GridCell<'a> {
+    element: ElementRef<'a>,
+    continued: bool,
+}
+
+/// The data tables in the body, in page order, with the heading of the section each is in.
+/// Infoboxes, navboxes and layout tables don't have the `wikitable` class, so they aren't included.
+pub(super) fn extract_tables(
+    body: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> Vec<Table> {
+    let table_selector = Selector::parse("table.wikitable").unwrap();
+    let mut section = None;
+    let mut tables = Vec::new();
+
+    for element in body.child_elements() {
+        if is_heading(&element) {
+            section = Some(clean_text(&element)).filter(|heading| !heading.is_empty());
+            continue;
+        }
+        //? Tables are often wrapped in a div (eg to scroll on small screens), so look inside each element too.
+        let candidates = if table_selector.matches(&element) {
+            vec![element]
+        } else {
+            element.select(&table_selector).collect()
+        };
+        tables.extend(
+            candidates
+                .into_iter()
+                .filter(|table| {
+                    !table
+                        .ancestors()
+                        .filter_map(ElementRef::wrap)
+                        .any(|ancestor| table_selector.matches(&ancestor))
+                })
+                .filter_map(|table| extract_table(&table, section.clone(), page_url, document)),
+        );
+    }
+    tables
+}
+
+fn extract_table(
+    table: &ElementRef,
+    section: Option<String>,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> Option<Table> {
+    let caption = table
+        .child_elements()
+        .find(|child| child.value().name() == "caption")
+        .map(|caption| clean_text(&caption))
+        .filter(|caption| !caption.is_empty());
+
+    let row_selector = Selector::parse("tr").unwrap();
+    let rows = table
+        .select(&row_selector)
+        .filter(|row| closest_table(row).is_some_and(|closest| closest.id() == table.id()))
+        .collect::<Vec<ElementRef>>();
+    let mut grid = build_grid(&rows);
+    let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 {
+        return None;
+    }
+
+    //? The header is every row at the top that only has header cells, plus any rows in a thead.
+    let header_count = rows
+        .iter()
+        .zip(&grid)
+        .take_while(|(row, cells)| {
+            in_section(row, "thead")
+                || (!cells.is_empty()
+                    && cells
+                        .iter()
+                        .flatten()
+                        .all(|cell| cell.element.value().name() == "th"))
+        })
+        .count();
+    //? Notes are put in a tfoot or in rows at the bottom with a single cell across the whole table.
+    let mut footer_count = 0;
+    for (row, cells) in rows.iter().zip(&grid).skip(header_count).rev() {
+        let spans_table = match cells.first().copied().flatten() {
+            Some(first) if width > 1 && cells.len() == width && !first.continued => cells
+                .iter()
+                .all(|cell| cell.is_some_and(|cell| cell.element.id() == first.element.id())),
+            _ => false,
+        };
+        if !(in_section(row, "tfoot") || spans_table) {
+            break;
+        }
+        footer_count += 1;
+    }
+    let footer_rows = grid.split_off(grid.len() - footer_count);
+    let body_rows = grid.split_off(header_count);
+
+    let header_rows = grid
+        .iter()
+        .map(|cells| {
+            (0..width)
+                .map(|column| {
+                    cells
+                        .get(column)
+                        .copied()
+                        .flatten()
+                        .map(|cell| clean_text(&cell.element))
+                        .unwrap_or_default()
+                })
+                .collect::<Vec<String>>()
+        })
+        .collect::<Vec<Vec<String>>>();
+
+    //? A cell spanning several positions is only read once.
+    let mut read_cells = HashMap::new();
+    let rows = body_rows
+        .iter()
+        .map(|cells| {
+            (0..width)
+                .map(|column| match cells.get(column).copied().flatten() {
+                    Some(cell) => TableCell {
+                        continued: cell.continued,
+                        ..read_cells
+                            .entry(cell.element.id())
+                            .or_insert_with(|| extract_cell(&cell.element, page_url, document))
+                            .clone()
+                    },
+                    None => empty_cell(),
+                })
+                .collect::<Vec<TableCell>>()
+        })
+        .filter(|row| row.iter().any(|cell| cell.value != CellValue::Empty))
+        .collect::<Vec<Vec<TableCell>>>();
+
+    let footnotes = footer_rows
+        .iter()
+        .flat_map(|cells| {
+            let mut seen = Vec::new();
+            cells
+                .iter()
+                .flatten()
+                .filter(|cell| {
+                    let first = !seen.contains(&cell.element.id());
+                    seen.push(cell.element.id());
+                    first
+                })
+                .map(|cell| clean_text(&cell.element))
+                .collect::<Vec<String>>()
+        })
+        .filter(|footnote| !footnote.is_empty())
+        .collect();
+
+    Some(Table {
+        caption,
+        section,
+        header_rows,
+        rows,
+        footnotes,
+    })
+    .filter(|table| !table.rows.is_empty() || !table.header_rows.is_empty())
+}
+
+/// Lay the cells of the rows out in a grid, so each row has a position for every column and cells spanning several rows or columns take each position they cover.
+fn build_grid<'a>(rows: &[ElementRef<'a>]) -> Vec<Vec<Option<GridCell<'a>>>> {
+    let mut grid = vec![Vec::<Option<GridCell>>::new(); rows.len()];
+    for (row_index, row) in rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in row
+            .child_elements()
+            .filter(|cell| matches!(cell.value().name(), "th" | "td"))
+        {
+            while grid[row_index].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+            let rowspan = span(&cell, "rowspan");
+            let colspan = span(&cell, "colspan");
+            for (offset, spanned_row) in grid.iter_mut().skip(row_index).take(rowspan).enumerate() {
+                if spanned_row.len() < column + colspan {
+                    spanned_row.resize(column + colspan, None);
+                }
+                for spanned_column in 0..colspan {
+                    spanned_row[column + spanned_column] = Some(GridCell {
+                        element: cell,
+                        continued: offset > 0 || spanned_column > 0,
+                    });
+                }
+            }
+            column += colspan;
+        }
+    }
+    grid
+}
+
+fn span(cell: &ElementRef, attribute: &str) -> usize {
+    cell.value()
+        .attr(attribute)
+        .and_then(|span| span.trim().parse::<usize>().ok())
+        .unwrap_or(1)
+        .clamp(1, MAX_SPAN)
+}
+
+/// Whether a row is in the given section (`thead`, `tbody` or `tfoot`) of its table.
+fn in_section(row: &ElementRef, section: &str) -> bool {
+    row.parent()
+        .and_then(ElementRef::wrap)
+        .is_some_and(|parent| parent.value().name() == section)
+}
+
+fn extract_cell(
+    cell: &ElementRef,
+    page_url: &WikipediaUrl,
+    document: &FetchedDocument,
+) -> TableCell {
+    let text = clean_text(cell);
+    TableCell {
+        value: parse_cell_value(&text, cell.value().attr("data-sort-value")),
+        links: extract_entity_links(cell, page_url, document),
+        citations: cited_text(cell).citations,
+        is_header: cell.value().name() == "th",
+        continued: false,
+        text,
+    }
+}
+
+fn empty_cell() -> TableCell {
+    TableCell {
+        text: String::new(),
+        value: CellValue::Empty,
+        links: Vec::new(),
+        citations: Vec::new(),
+        is_header: false,
+        continued: false,
+    }
+}
+
+/// Parse the text of a cell. Sortable tables give some cells a sort key, which is used for dates written in a way we can't read (eg "c. March 2020").
+fn parse_cell_value(text: &str, sort_key: Option<&str>) -> CellValue {
+    let text = text.trim();
+    if matches!(text, "" | "-" | "–" | "—" | "N/A" | "n/a") {
+        return CellValue::Empty;
+    }
+    parse_number(text)
+        .or_else(|| parse_cell_date(text))
+        .or_else(|| {
+            let (_, date) = regex_captures!(r"(\d{4}-\d{2}-\d{2})", sort_key?)?;
+            parse_cell_date(date)
+        })
+        .unwrap_or(CellValue::Text)
+}
+
+/// Numbers, percentages and amounts of money, eg "1,234", "−5.2", "12.5%", "£1,200" or "$1.2 billion".
+fn parse_number(text: &str) -> Option<CellValue> {
+    let (_, sign, currency, digits, scale, percent) = regex_captures!(
+        r"^([-+−]?)\s*(US\$|A\$|C\$|[$£€¥₹])?\s*(\d{1,3}(?:[, ]\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?)\s*(thousand|million|billion|trillion)?\s*(%)?$",
+        text
+    )?;
+    let mut number = digits.replace([',', ' '], "").parse::<f64>().ok()?;
+    number *= match scale {
+        "thousand" => 1e3,
+        "million" => 1e6,
+        "billion" => 1e9,
+        "trillion" => 1e12,
+        _ => 1.0,
+    };
+    if matches!(sign, "-" | "−") {
+        number = -number;
+    }
+    Some(match (currency, percent) {
+        ("", "%") => CellValue::Percent(number),
+        ("", _) => CellValue::Number(number),
+        (currency, _) => CellValue::Money {
+            amount: number,
+            currency: currency.to_string(),
+        },
+    })
+}
+
+fn parse_cell_date(text: &str) -> Option<CellValue> {
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .map(CellValue::Date)
+}
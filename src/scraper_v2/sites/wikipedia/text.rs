@@ -0,0 +1,141 @@
+use super::model::{Citation, Paragraph};
+
+use scraper::{ElementRef, Node, Selector};
+
+/// Elements whose text isn't part of the content: footnote markers, edit links, hidden metadata and styles.
+const SKIP_SELECTOR: &str = "sup.reference, style, script, link, .noprint, .mw-editsection, \
+    [style*='display:none'], [style*='display: none']";
+
+/// The text of an element on a single line, without footnote markers or hidden text.
+pub(super) fn clean_text(element: &ElementRef) -> String {
+    collapse_whitespace(&raw_text(element))
+}
+
+/// The lines of text of an element, split on `<br>` and list items, without footnote markers or hidden text.
+pub(super) fn clean_lines(element: &ElementRef) -> Vec<String> {
+    raw_text(element)
+        .lines()
+        .map(collapse_whitespace)
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Stands in for a footnote marker while the text is collapsed, so the marker's position can be found afterwards.
+//? A private use character, so it can't clash with the page text.
+const CITATION_PLACEHOLDER: char = '\u{E000}';
+
+/// The text of an element on a single line, with the footnote markers taken out of the text and kept as citations.
+pub(super) fn cited_text(element: &ElementRef) -> Paragraph {
+    let skip_selector = Selector::parse(SKIP_SELECTOR).unwrap();
+    let mut citations = Vec::new();
+    let mut raw = String::new();
+    push_cited_text(element, &skip_selector, &mut raw, &mut citations);
+
+    let mut text = String::new();
+    let mut citations = citations.into_iter();
+    let mut positioned = Vec::new();
+    for c in collapse_whitespace(&raw).chars() {
+        if c == CITATION_PLACEHOLDER {
+            positioned.extend(citations.next().map(|citation| Citation {
+                offset: text.trim_end().len(),
+                ..citation
+            }));
+        } else if c == ' ' && (text.is_empty() || text.ends_with(' ')) {
+            //? A marker between two spaces would otherwise leave a double space behind.
+            continue;
+        } else {
+            text.push(c);
+        }
+    }
+    Paragraph {
+        text: text.trim_end().to_string(),
+        citations: positioned,
+    }
+}
+
+fn push_cited_text(
+    element: &ElementRef,
+    skip_selector: &Selector,
+    text: &mut String,
+    citations: &mut Vec<Citation>,
+) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(child_text) => text.push_str(child_text),
+            Node::Element(child_element) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                if child_element.name() == "sup"
+                    && child_element.classes().any(|class| class == "reference")
+                {
+                    if let Some(citation) = citation_marker(&child) {
+                        text.push(CITATION_PLACEHOLDER);
+                        citations.push(citation);
+                    }
+                } else if child_element.name() == "br" {
+                    text.push(' ');
+                } else if !skip_selector.matches(&child) {
+                    push_cited_text(&child, skip_selector, text, citations);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The reference a footnote marker (`<sup class="reference"><a href="#cite_note-1">[1]</a></sup>`) points at.
+pub(super) fn citation_marker(marker: &ElementRef) -> Option<Citation> {
+    let link_selector = Selector::parse("a[href]").unwrap();
+    let link = marker.select(&link_selector).next()?;
+    let reference_id = link.value().attr("href")?.split_once('#')?.1.to_string();
+    Some(Citation {
+        reference_id,
+        label: link
+            .text()
+            .collect::<String>()
+            .trim()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string(),
+        offset: 0,
+    })
+}
+
+/// Collapse runs of whitespace (including non-breaking spaces) into single spaces.
+pub(super) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn raw_text(element: &ElementRef) -> String {
+    let skip_selector = Selector::parse(SKIP_SELECTOR).unwrap();
+    let mut text = String::new();
+    push_text(element, &skip_selector, &mut text);
+    text
+}
+
+fn push_text(element: &ElementRef, skip_selector: &Selector, text: &mut String) {
+    for child in element.children() {
+        match child.value() {
+            Node::Text(child_text) => text.push_str(child_text),
+            Node::Element(child_element) => {
+                let Some(child) = ElementRef::wrap(child) else {
+                    continue;
+                };
+                if skip_selector.matches(&child) {
+                    continue;
+                }
+                match child_element.name() {
+                    "br" => text.push('\n'),
+                    "li" | "p" | "div" | "dd" | "dt" => {
+                        text.push('\n');
+                        push_text(&child, skip_selector, text);
+                        text.push('\n');
+                    }
+                    _ => push_text(&child, skip_selector, text),
+                }
+            }
+            _ => {}
+        }
+    }
+}
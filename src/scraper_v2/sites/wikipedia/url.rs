@@ -1,41 +1,364 @@
-use crate::common::UrlTrait;
+use crate::common::{normalize_percent_encoding, UrlTrait};
 use crate::{Error, Result};
 
 use super::error::WikipediaError;
 use super::WikipediaContent;
 
-use lazy_regex::regex_is_match;
+use lazy_regex::regex_captures;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
 use serde::{Deserialize, Serialize};
 use std::hash::{Hash, Hasher};
 
+/// The characters that are percent-encoded in the title part of a path, on top of every non-ascii character.
+const TITLE_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b']')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// The query parameters that change what a page shows. These are the paging parameters of category listings, everything else is dropped.
+const KEPT_QUERY_PARAMS: &[&str] = &[
+    "pagefrom",
+    "pageuntil",
+    "subcatfrom",
+    "subcatuntil",
+    "filefrom",
+    "fileuntil",
+];
+
+/// The query parameters of urls that aren't a plain view of the current page, eg an edit form or an old revision.
+const NON_VIEW_QUERY_PARAMS: &[&str] = &["action", "diff", "oldid", "curid", "veaction", "search"];
+
+/// The namespace of a Wikipedia page, given by the prefix of its title (`Category:Cities in England`). Titles without a known prefix are articles.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    Article,
+    Talk,
+    User,
+    UserTalk,
+    /// Pages about Wikipedia itself (`Wikipedia:` or `WP:`), eg policies.
+    Project,
+    ProjectTalk,
+    File,
+    FileTalk,
+    MediaWiki,
+    MediaWikiTalk,
+    Template,
+    TemplateTalk,
+    Help,
+    HelpTalk,
+    Category,
+    CategoryTalk,
+    Portal,
+    PortalTalk,
+    Draft,
+    DraftTalk,
+    Module,
+    ModuleTalk,
+    /// Generated pages, eg search or recent changes.
+    Special,
+    /// Direct links to media files.
+    Media,
+}
+
+/// The canonical names of the namespaces, which every language edition accepts.
+const CANONICAL_NAMESPACES: &[(&str, Namespace)] = &[
+    ("Talk", Namespace::Talk),
+    ("User", Namespace::User),
+    ("User talk", Namespace::UserTalk),
+    ("Wikipedia", Namespace::Project),
+    ("Wikipedia talk", Namespace::ProjectTalk),
+    ("File", Namespace::File),
+    ("File talk", Namespace::FileTalk),
+    ("MediaWiki", Namespace::MediaWiki),
+    ("MediaWiki talk", Namespace::MediaWikiTalk),
+    ("Template", Namespace::Template),
+    ("Template talk", Namespace::TemplateTalk),
+    ("Help", Namespace::Help),
+    ("Help talk", Namespace::HelpTalk),
+    ("Category", Namespace::Category),
+    ("Category talk", Namespace::CategoryTalk),
+    ("Portal", Namespace::Portal),
+    ("Portal talk", Namespace::PortalTalk),
+    ("Draft", Namespace::Draft),
+    ("Draft talk", Namespace::DraftTalk),
+    ("Module", Namespace::Module),
+    ("Module talk", Namespace::ModuleTalk),
+    ("Special", Namespace::Special),
+    ("Media", Namespace::Media),
+];
+
+/// Other names every language edition accepts.
+const NAMESPACE_ALIASES: &[(&str, Namespace)] = &[
+    ("WP", Namespace::Project),
+    ("WT", Namespace::ProjectTalk),
+    ("Project", Namespace::Project),
+    ("Project talk", Namespace::ProjectTalk),
+    ("Image", Namespace::File),
+    ("Image talk", Namespace::FileTalk),
+];
+
+/// The local names of the namespaces that links on the larger language editions use.
+//? Not every namespace of every edition, only the ones a crawl is likely to run into.
+const LOCAL_NAMESPACES: &[(&str, &[(&str, Namespace)])] = &[
+    (
+        "fr",
+        &[
+            ("Discussion", Namespace::Talk),
+            ("Utilisateur", Namespace::User),
+            ("Wikipédia", Namespace::Project),
+            ("Fichier", Namespace::File),
+            ("Modèle", Namespace::Template),
+            ("Aide", Namespace::Help),
+            ("Catégorie", Namespace::Category),
+            ("Portail", Namespace::Portal),
+            ("Spécial", Namespace::Special),
+        ],
+    ),
+    (
+        "de",
+        &[
+            ("Diskussion", Namespace::Talk),
+            ("Benutzer", Namespace::User),
+            ("Datei", Namespace::File),
+            ("Vorlage", Namespace::Template),
+            ("Hilfe", Namespace::Help),
+            ("Kategorie", Namespace::Category),
+            ("Spezial", Namespace::Special),
+        ],
+    ),
+    (
+        "es",
+        &[
+            ("Discusión", Namespace::Talk),
+            ("Usuario", Namespace::User),
+            ("Archivo", Namespace::File),
+            ("Plantilla", Namespace::Template),
+            ("Ayuda", Namespace::Help),
+            ("Categoría", Namespace::Category),
+            ("Especial", Namespace::Special),
+        ],
+    ),
+    (
+        "it",
+        &[
+            ("Discussione", Namespace::Talk),
+            ("Utente", Namespace::User),
+            ("Aiuto", Namespace::Help),
+            ("Categoria", Namespace::Category),
+            ("Portale", Namespace::Portal),
+            ("Speciale", Namespace::Special),
+        ],
+    ),
+    (
+        "pt",
+        &[
+            ("Discussão", Namespace::Talk),
+            ("Usuário(a)", Namespace::User),
+            ("Ficheiro", Namespace::File),
+            ("Arquivo", Namespace::File),
+            ("Predefinição", Namespace::Template),
+            ("Ajuda", Namespace::Help),
+            ("Categoria", Namespace::Category),
+            ("Especial", Namespace::Special),
+        ],
+    ),
+    (
+        "nl",
+        &[
+            ("Overleg", Namespace::Talk),
+            ("Gebruiker", Namespace::User),
+            ("Bestand", Namespace::File),
+            ("Sjabloon", Namespace::Template),
+            ("Categorie", Namespace::Category),
+            ("Portaal", Namespace::Portal),
+            ("Speciaal", Namespace::Special),
+        ],
+    ),
+];
+
+impl Namespace {
+    /// The canonical name of the namespace, which is used as the prefix in normalized titles. Articles have no prefix.
+    pub fn name(&self) -> Option<&'static str> {
+        CANONICAL_NAMESPACES
+            .iter()
+            .find(|(_, namespace)| namespace == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// The namespace a title prefix names in the given language edition, ignoring case and treating underscores as spaces.
+    pub fn from_prefix(language: &str, prefix: &str) -> Option<Self> {
+        let prefix = prefix.replace('_', " ");
+        let prefix = prefix.trim();
+        let local = LOCAL_NAMESPACES
+            .iter()
+            .filter(|(local_language, _)| *local_language == language)
+            .flat_map(|(_, names)| names.iter());
+        CANONICAL_NAMESPACES
+            .iter()
+            .chain(NAMESPACE_ALIASES)
+            .chain(local)
+            .find(|(name, _)| name.to_lowercase() == prefix.to_lowercase())
+            .map(|(_, namespace)| *namespace)
+    }
+
+    pub fn is_article(&self) -> bool {
+        *self == Namespace::Article
+    }
+
+    /// Whether this is a talk (discussion) namespace.
+    pub fn is_talk(&self) -> bool {
+        self.name()
+            .is_some_and(|name| name.ends_with("talk") || name == "Talk")
+    }
+
+    /// Whether pages in the namespace can be fetched and scraped. Special and media pages aren't stored pages, and file pages are mostly the file itself.
+    pub fn is_scrapable(&self) -> bool {
+        !matches!(
+            self,
+            Namespace::Special | Namespace::Media | Namespace::File
+        )
+    }
+}
+
+/// Split a title into its namespace and the title within it, normalized the way Wikipedia does:
+/// percent-decoded, underscores as spaces, whitespace collapsed and the first letter upper-cased (`category:cities_in_england` is `(Category, "Cities in England")`).
+pub fn normalize_title(language: &str, title: &str) -> (Namespace, String) {
+    let decoded = percent_decode_str(title)
+        .decode_utf8_lossy()
+        .replace('_', " ");
+    let decoded = decoded.split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    let (namespace, title) = match decoded.split_once(':') {
+        Some((prefix, rest)) => match Namespace::from_prefix(language, prefix) {
+            Some(namespace) => (namespace, rest.trim()),
+            None => (Namespace::Article, decoded.as_str()),
+        },
+        None => (Namespace::Article, decoded.as_str()),
+    };
+
+    let mut chars = title.chars();
+    let title = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+        None => String::new(),
+    };
+    (namespace, title)
+}
+
+/// A title as it appears in a path, with underscores for spaces and percent-encoding.
+pub(super) fn encode_title(full_title: &str) -> String {
+    utf8_percent_encode(&full_title.replace(' ', "_"), TITLE_ENCODE_SET).to_string()
+}
+
+/// A Wikipedia page url. Each language edition is its own host (`fr.wikipedia.org`), so the language is kept alongside the path.
+/// The path is always `/wiki/{Namespace:Title}` with the title normalized, so the different ways of linking to a page compare equal.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
-pub struct WikipediaUrl(String);
+pub struct WikipediaUrl {
+    /// The language code from the host, eg "en", "fr" or "zh-yue".
+    language: String,
+    namespace: Namespace,
+    /// The title without the namespace prefix, eg "Cities in England".
+    title: String,
+    path: String,
+}
+
+impl WikipediaUrl {
+    pub fn language(&self) -> &str {
+        &self.language
+    }
+
+    pub fn namespace(&self) -> Namespace {
+        self.namespace
+    }
+
+    /// The title of the page without the namespace prefix, eg "Cities in England".
+    pub fn title(&self) -> &str {
+        &self.title
+    }
 
+    /// The title of the page with the namespace prefix, eg "Category:Cities in England".
+    pub fn full_title(&self) -> String {
+        match self.namespace.name() {
+            Some(name) => format!("{name}:{}", self.title),
+            None => self.title.clone(),
+        }
+    }
+
+    /// Whether the url is an article, rather than a category, talk page etc.
+    pub fn is_article(&self) -> bool {
+        self.namespace.is_article()
+    }
+
+    /// The url of a page from its title (with the namespace prefix) in the given language edition, eg `("en", "United Kingdom")`.
+    pub fn from_title(language: &str, full_title: &str) -> Result<Self> {
+        Self::try_from(format!(
+            "https://{language}.wikipedia.org/wiki/{}",
+            encode_title(full_title)
+        ))
+    }
+
+    /// The host of the language edition the page is in.
+    pub fn host(&self) -> String {
+        format!("{}.wikipedia.org", self.language)
+    }
+
+    /// Split a parsed url (see `parse_url`) into its language, namespace and path.
+    fn from_parsed(parsed: &str) -> Option<Self> {
+        let (_, language, path, title) = regex_captures!(
+            r"^https://([a-z][a-z0-9-]*)\.wikipedia\.org(/wiki/([^?]*).*)$",
+            parsed
+        )?;
+        let (namespace, title) = normalize_title(language, title);
+        Some(WikipediaUrl {
+            language: language.to_string(),
+            namespace,
+            title,
+            path: path.to_string(),
+        })
+    }
+}
+
+/// The path of the url, the language is only part of `full_url`.
 impl AsRef<String> for WikipediaUrl {
     fn as_ref(&self) -> &String {
-        &self.0
+        &self.path
     }
 }
 impl TryFrom<String> for WikipediaUrl {
     type Error = Error;
     fn try_from(url: String) -> Result<Self> {
-        match Self::parse_url(&url) {
-            Ok(url) => Ok(WikipediaUrl(url)),
-
-            Err(e) => Err(e),
-        }
+        let parsed = Self::parse_canonical(&url)?;
+        Self::from_parsed(&parsed).ok_or_else(|| {
+            WikipediaError::InvalidUrl {
+                url,
+                reason: "Unable to split language and path".to_string(),
+            }
+            .into()
+        })
     }
 }
 
 impl From<WikipediaUrl> for String {
     fn from(url: WikipediaUrl) -> String {
-        url.0
+        url.full_url()
     }
 }
 
 impl Hash for WikipediaUrl {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.as_ref().hash(state);
+        self.language.hash(state);
+        self.path.hash(state);
     }
 }
 
@@ -43,30 +366,109 @@ impl UrlTrait for WikipediaUrl {
     fn base_url() -> &'static str {
         "https://en.wikipedia.org"
     }
+    fn host_aliases() -> &'static [&'static str] {
+        &["en.m.wikipedia.org", "www.en.wikipedia.org"]
+    }
     fn to_string(&self) -> String {
-        self.0.clone()
+        self.path.clone()
+    }
+    fn full_url(&self) -> String {
+        format!("https://{}{}", self.host(), self.path)
     }
+    /// Returns the full url, as the language is part of the host. Mobile (`fr.m.wikipedia.org`) and `www.` hosts are mapped to the language's host.
+    /// `/w/index.php?title=...` urls are rewritten to `/wiki/...`, and the title is normalized (see `normalize_title`).
     fn parse_url(url: &str) -> Result<String> {
-        let stripped_url = url.strip_prefix(Self::base_url()).unwrap_or(url);
-
-        if !stripped_url.starts_with("/wiki") || regex_is_match!(r"/(Special:|File:)", stripped_url)
-        {
-            let reason = {
-                if !stripped_url.starts_with("/wiki") {
-                    "Does not start with /wiki"
-                } else {
-                    "Contains Special: or File:"
-                }
-            }
-            .to_string();
-
-            return Err(WikipediaError::InvalidUrl {
+        let invalid = |reason: &str| -> Error {
+            WikipediaError::InvalidUrl {
                 url: url.to_string(),
-                reason,
+                reason: reason.to_string(),
             }
-            .into());
+            .into()
+        };
+
+        let parsed = url::Url::parse(url).map_err(|_| invalid("Not an absolute url"))?;
+        let host = parsed.host_str().unwrap_or_default();
+        let (_, language) = regex_captures!(
+            r"^(?:www\.)?([a-z][a-z0-9-]*)(?:\.m)?\.wikipedia\.org$",
+            host
+        )
+        .ok_or_else(|| invalid("Not a Wikipedia language edition"))?;
+        if language == "www" {
+            return Err(invalid("Is the Wikipedia portal, not a language edition"));
+        }
+
+        let query = parsed
+            .query_pairs()
+            .map(|(key, value)| (key.into_owned(), value.into_owned()))
+            .collect::<Vec<(String, String)>>();
+        let is_view = query.iter().all(|(key, value)| {
+            !NON_VIEW_QUERY_PARAMS.contains(&key.as_str()) || (key == "action" && value == "view")
+        });
+        if !is_view {
+            return Err(invalid("Is not a view of the current page"));
+        }
+
+        let title = if let Some(title) = parsed.path().strip_prefix("/wiki/") {
+            title.to_string()
+        } else if parsed.path() == "/w/index.php" {
+            query
+                .iter()
+                .find(|(key, _)| key == "title")
+                .map(|(_, title)| title.clone())
+                .ok_or_else(|| invalid("index.php without a title"))?
+        } else {
+            return Err(invalid("Does not start with /wiki"));
+        };
+
+        let (namespace, title) = normalize_title(language, &title);
+        if title.is_empty() {
+            return Err(invalid("Has no title"));
         }
+        if !namespace.is_scrapable() {
+            return Err(invalid(&format!("Is in the {namespace:?} namespace")));
+        }
+
+        let full_title = match namespace.name() {
+            Some(name) => format!("{name}:{title}"),
+            None => title,
+        };
+        let mut path = format!("/wiki/{}", encode_title(&full_title));
+        //? Kept params are left as written, as `Canonicalizer` does, so `%20` isn't re-encoded as `+`.
+        let kept = parsed
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .filter(|param| {
+                let key = param.split('=').next().unwrap_or_default();
+                KEPT_QUERY_PARAMS.contains(&percent_decode_str(key).decode_utf8_lossy().as_ref())
+            })
+            .map(normalize_percent_encoding)
+            .collect::<Vec<String>>();
+        if !kept.is_empty() {
+            path = format!("{path}?{}", kept.join("&"));
+        }
+
+        Ok(format!("https://{language}.wikipedia.org{path}"))
+    }
+}
 
-        Ok(stripped_url.to_string())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paging_params_keep_their_encoded_spaces() {
+        let urls = [
+            "https://en.wikipedia.org/wiki/Category:Cities_in_England?pagefrom=Kingston%20upon%20Hull",
+            "https://en.m.wikipedia.org/wiki/Category:Cities_in_England?utm_source=x&pagefrom=Kingston%20upon%20Hull",
+            "https://en.wikipedia.org/w/index.php?title=Category:Cities_in_England&pagefrom=Kingston%20upon%20Hull",
+        ];
+        for url in urls {
+            assert_eq!(
+                WikipediaUrl::parse(url).unwrap().full_url(),
+                "https://en.wikipedia.org/wiki/Category:Cities_in_England?pagefrom=Kingston%20upon%20Hull",
+                "{url}"
+            );
+        }
     }
 }
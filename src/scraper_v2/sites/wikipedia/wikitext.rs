@@ -0,0 +1,327 @@
+use super::api::escape_html;
+use super::url::encode_title;
+use super::{normalize_title, Namespace};
+
+use lazy_regex::{regex_captures, regex_replace_all};
+
+/// The templates that mark a disambiguation page, lower-cased, across the larger language editions.
+const DISAMBIGUATION_TEMPLATES: &[&str] = &[
+    "disambiguation",
+    "disambig",
+    "disamb",
+    "dab",
+    "hndis",
+    "geodis",
+    "human name disambiguation",
+    "place name disambiguation",
+    "homonymie",
+    "begriffsklärung",
+    "desambiguación",
+    "disambigua",
+    "desambiguação",
+    "dp",
+];
+
+/// Stand in for the `<` and `>` of the markup the renderer makes, so it can be told apart from the text, which is escaped.
+const TAG_OPEN: char = '\u{1}';
+const TAG_CLOSE: char = '\u{2}';
+
+/// A page's wikitext rendered to HTML, along with what the wikitext says about the page besides its body.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct RenderedWikitext {
+    pub html: String,
+    /// The categories the page is put in, eg "Category:Cities in England".
+    pub categories: Vec<String>,
+    /// From the `{{Short description}}` template.
+    pub short_description: Option<String>,
+    pub disambiguation: bool,
+}
+
+/// Render wikitext to HTML shaped like the body of a rendered page: headings, paragraphs, lists and links.
+///
+/// This is a small subset of what MediaWiki does, enough to get the abstract, the links between articles, categories and disambiguation lists out of a dump.
+/// Templates (so infoboxes and navboxes), tables, references, files and interwiki links are dropped.
+pub(super) fn render_wikitext(language: &str, wikitext: &str) -> RenderedWikitext {
+    let mut rendered = RenderedWikitext::default();
+
+    let wikitext = wikitext.replace([TAG_OPEN, TAG_CLOSE], "");
+    let text = regex_replace_all!(r"(?s)<!--.*?-->", &wikitext, "");
+    let text = regex_replace_all!(r"(?is)<ref[^>]*/>", &text, "");
+    let text = regex_replace_all!(r"(?is)<ref[^>]*>.*?</ref>", &text, "");
+    let text = regex_replace_all!(
+        r"(?is)<(?:gallery|math|chem|score|timeline|imagemap|syntaxhighlight|templatedata|mapframe)\b[^>]*>.*?</(?:gallery|math|chem|score|timeline|imagemap|syntaxhighlight|templatedata|mapframe)>",
+        &text,
+        ""
+    );
+    let text = replace_balanced(&text, "{{", "}}", |template| {
+        let mut parts = template.split('|');
+        let name = parts
+            .next()
+            .unwrap_or_default()
+            .replace('_', " ")
+            .trim()
+            .to_lowercase();
+        if DISAMBIGUATION_TEMPLATES.contains(&name.as_str()) {
+            rendered.disambiguation = true;
+        } else if name == "short description" {
+            rendered.short_description = parts
+                .next()
+                .map(|description| description.trim().to_string())
+                .filter(|description| !description.is_empty() && description != "none");
+        }
+        String::new()
+    });
+    if text.contains("__DISAMBIG__") {
+        rendered.disambiguation = true;
+    }
+    let text = regex_replace_all!(r"__[A-Z]+__", &strip_tables(&text), "").into_owned();
+    //? What is left of the inline HTML (`<small>`, `<span>`, `<br>` etc) is formatting, only its text is kept.
+    let text = regex_replace_all!(r"(?i)<br\s*/?>", &text, " ");
+    let text = regex_replace_all!(r"</?[a-zA-Z][a-zA-Z0-9]*(?:\s[^<>]*)?/?>", &text, "");
+    let text = escape_text(&text);
+
+    let text = replace_balanced(&text, "[[", "]]", |link| {
+        render_link(language, link, &mut rendered.categories)
+    });
+    let text = regex_replace_all!(
+        r"\[(https?://[^\s\]]+)(?:\s+([^\]]*))?\]",
+        &text,
+        |_, url: &str, label: &str| format!(
+            "{}{label}{}",
+            tag(&format!(
+                "<a class=\"external\" href=\"{}\">",
+                escape_html(&unescape_text(url))
+            )),
+            tag("</a>")
+        )
+    );
+    let text = regex_replace_all!(r"'{2,5}", &text, "");
+
+    rendered.html = render_blocks(&text)
+        .replace(TAG_OPEN, "<")
+        .replace(TAG_CLOSE, ">");
+    rendered
+}
+
+/// Escape text for HTML. Character references that are already in the wikitext (`&nbsp;`, `&#8212;`) are kept, as MediaWiki keeps them.
+fn escape_text(text: &str) -> String {
+    regex_replace_all!(
+        r"&(#[0-9]+;|#[xX][0-9a-fA-F]+;|[a-zA-Z][a-zA-Z0-9]*;)?",
+        text,
+        |whole: &str, reference: &str| if reference.is_empty() {
+            "&amp;".to_string()
+        } else {
+            whole.to_string()
+        }
+    )
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+}
+
+/// Undo `escape_text`, for the parts of the text that are urls and titles rather than text.
+fn unescape_text(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Markup made by the renderer, kept apart from the escaped text until the blocks are rendered.
+fn tag(html: &str) -> String {
+    html.replace('<', &TAG_OPEN.to_string())
+        .replace('>', &TAG_CLOSE.to_string())
+}
+
+/// Split text into headings, lists and paragraphs. The text is already escaped.
+fn render_blocks(text: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph = Vec::<&str>::new();
+    let mut list: Option<(&str, Vec<&str>)> = None;
+
+    let flush_paragraph = |paragraph: &mut Vec<&str>, html: &mut String| {
+        let text = paragraph.join(" ");
+        if !text.trim().is_empty() {
+            html.push_str(&format!("<p>{}</p>", text.trim()));
+        }
+        paragraph.clear();
+    };
+    let flush_list = |list: &mut Option<(&str, Vec<&str>)>, html: &mut String| {
+        if let Some((tag, items)) = list.take() {
+            let items = items
+                .iter()
+                .map(|item| format!("<li>{item}</li>"))
+                .collect::<String>();
+            html.push_str(&format!("<{tag}>{items}</{tag}>"));
+        }
+    };
+
+    for line in text.lines().map(str::trim_end) {
+        if let Some((_, level, heading)) = regex_captures!(r"^(={1,6})\s*(.+?)\s*=+$", line) {
+            flush_paragraph(&mut paragraph, &mut html);
+            flush_list(&mut list, &mut html);
+            let level = level.len().max(2);
+            html.push_str(&format!("<h{level}>{heading}</h{level}>"));
+        } else if let Some((_, marker, item)) = regex_captures!(r"^([*#]+)\s*(.*)$", line) {
+            flush_paragraph(&mut paragraph, &mut html);
+            //? Nested lists are flattened, each item is still its own entry.
+            let tag = if marker.ends_with('#') { "ol" } else { "ul" };
+            if list.as_ref().is_some_and(|(list_tag, _)| *list_tag != tag) {
+                flush_list(&mut list, &mut html);
+            }
+            if !item.trim().is_empty() {
+                list.get_or_insert((tag, Vec::new())).1.push(item.trim());
+            }
+        } else if line.trim().is_empty() {
+            flush_paragraph(&mut paragraph, &mut html);
+            flush_list(&mut list, &mut html);
+        } else {
+            flush_list(&mut list, &mut html);
+            //? Indented (`:`) and definition (`;`) lines are kept as plain text.
+            paragraph.push(line.trim_start_matches([':', ';']).trim());
+        }
+    }
+    flush_paragraph(&mut paragraph, &mut html);
+    flush_list(&mut list, &mut html);
+    html
+}
+
+/// Render the inside of a `[[...]]` link. Category links are collected rather than rendered, file and interwiki links are dropped.
+/// The link is escaped text, only the page it links to is unescaped.
+fn render_link(language: &str, link: &str, categories: &mut Vec<String>) -> String {
+    let (target, text) = match link.split_once('|') {
+        Some((target, text)) => (target, Some(text.trim()).filter(|text| !text.is_empty())),
+        None => (link, None),
+    };
+    //? A leading colon links to a category or file page instead of putting the page in it.
+    let visible = target.trim_start().starts_with(':');
+    let target = target.trim().trim_start_matches(':').trim();
+    let (page, section) = match target.split_once('#') {
+        Some((page, section)) => (page.trim(), Some(section.trim())),
+        None => (target, None),
+    };
+    if page.is_empty() {
+        return text.unwrap_or(target).to_string();
+    }
+    if let Some((prefix, _)) = page.split_once(':') {
+        let is_interwiki = Namespace::from_prefix(language, prefix).is_none()
+            && regex_captures!(r"^[a-z][a-z-]{1,11}$", prefix).is_some();
+        if is_interwiki {
+            return if visible {
+                text.unwrap_or(target).to_string()
+            } else {
+                String::new()
+            };
+        }
+    }
+
+    let (namespace, title) = normalize_title(language, &unescape_text(page));
+    let full_title = match namespace.name() {
+        Some(name) => format!("{name}:{title}"),
+        None => title,
+    };
+    match namespace {
+        Namespace::Category if !visible => {
+            categories.push(full_title);
+            return String::new();
+        }
+        Namespace::File | Namespace::Media if !visible => return String::new(),
+        _ => {}
+    }
+    let href = match section {
+        Some(section) => format!(
+            "/wiki/{}#{}",
+            encode_title(&full_title),
+            encode_title(&unescape_text(section))
+        ),
+        None => format!("/wiki/{}", encode_title(&full_title)),
+    };
+    format!(
+        "{}{}{}",
+        tag(&format!(
+            "<a href=\"{}\" title=\"{}\">",
+            escape_html(&href),
+            escape_html(&full_title)
+        )),
+        text.unwrap_or(target),
+        tag("</a>")
+    )
+}
+
+/// Replace every outermost `open ... close` span with what `replace` makes of its inside. Spans can be nested, unclosed spans are left as they are.
+fn replace_balanced(
+    text: &str,
+    open: &str,
+    close: &str,
+    mut replace: impl FnMut(&str) -> String,
+) -> String {
+    let mut replaced = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        replaced.push_str(&rest[..start]);
+        let inside = &rest[start + open.len()..];
+        let mut depth = 1;
+        let mut end = None;
+        let mut i = 0;
+        while i < inside.len() {
+            if inside[i..].starts_with(close) {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(i);
+                    break;
+                }
+                i += close.len();
+            } else if inside[i..].starts_with(open) {
+                depth += 1;
+                i += open.len();
+            } else {
+                i += inside[i..].chars().next().map_or(1, char::len_utf8);
+            }
+        }
+        match end {
+            Some(end) => {
+                replaced.push_str(&replace(&inside[..end]));
+                rest = &inside[end + close.len()..];
+            }
+            None => {
+                replaced.push_str(open);
+                rest = inside;
+            }
+        }
+    }
+    replaced.push_str(rest);
+    replaced
+}
+
+/// Drop `{| ... |}` tables, which can be nested.
+fn strip_tables(text: &str) -> String {
+    let mut depth = 0;
+    text.lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            if line.starts_with("{|") {
+                depth += 1;
+            } else if depth > 0 && line.starts_with("|}") {
+                depth -= 1;
+                return false;
+            }
+            depth == 0
+        })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_escaped_and_links_are_markup() {
+        let rendered = render_wikitext(
+            "en",
+            "[[AT&T]] & <small>Bell</small> say 5 < 6&nbsp;<script>alert(1)</script>\n\n* a > b",
+        );
+        assert_eq!(
+            rendered.html,
+            "<p><a href=\"/wiki/AT&amp;T\" title=\"AT&amp;T\">AT&amp;T</a> &amp; Bell say 5 &lt; 6&nbsp;alert(1)</p><ul><li>a &gt; b</li></ul>"
+        );
+    }
+}
@@ -0,0 +1,234 @@
+mod common;
+
+use common::scrape;
+use my_crate::scraper_v2::common::{FetchedDocument, ScrapableContent, UrlTrait};
+use my_crate::scraper_v2::graph::{EdgeKind, GraphStore, KnowledgeGraph, MemoryGraphStore};
+use my_crate::scraper_v2::sites::bbc::model::{ContentBlock, EmbeddedMedia, MediaKind, Quote};
+use my_crate::scraper_v2::sites::bbc::{BBCContent, BBCUrl};
+use std::sync::Arc;
+
+fn paragraphs(content: &BBCContent) -> Vec<&str> {
+    content.paragraphs().map(String::as_str).collect()
+}
+
+#[test]
+fn quotes_are_kept_once_between_their_paragraphs() {
+    let content: BBCContent = scrape(
+        "https://www.bbc.co.uk/news/articles/c0flood0001o",
+        include_str!("fixtures/bbc/article_quotes.html"),
+    );
+
+    assert_eq!(
+        content.content(),
+        &[
+            ContentBlock::Paragraph(
+                "A £12m scheme to protect more than 400 homes from flooding has been approved by councillors.".to_string()
+            ),
+            ContentBlock::Paragraph(
+                "The defences will include a new embankment and a flood gate on the high street."
+                    .to_string()
+            ),
+            ContentBlock::Quote(Quote {
+                text: "We have waited years for this and it will change lives.".to_string(),
+                source: Some("Resident Mary Jones".to_string()),
+            }),
+            ContentBlock::Paragraph("Work is expected to start in the summer.".to_string()),
+            ContentBlock::Subheading("Years of flooding".to_string()),
+            ContentBlock::Quote(Quote {
+                text: "The river has flooded the town three times in ten years.".to_string(),
+                source: Some("Environment Agency".to_string()),
+            }),
+            ContentBlock::Paragraph("The council said the scheme would be finished by 2026.".to_string()),
+        ]
+    );
+    assert_eq!(
+        content
+            .to_markdown()
+            .matches("We have waited years")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn list_items_are_kept_as_paragraphs() {
+    let content: BBCContent = scrape(
+        "https://www.bbc.co.uk/news/articles/c0budget002o",
+        include_str!("fixtures/bbc/article_lists.html"),
+    );
+
+    assert_eq!(
+        paragraphs(&content),
+        [
+            "The chancellor has set out the government's tax and spending plans.",
+            "The main announcements were:",
+            "A cut in National Insurance of 2p",
+            "A freeze on fuel duty",
+            "Changes to child benefit",
+            "The threshold rises to £60,000",
+            "More details are expected later this year.",
+            "First, the tax changes",
+            "Then, the spending plans",
+        ]
+    );
+    let markdown = content.to_markdown();
+    assert!(
+        markdown.contains("- A cut in National Insurance of 2p"),
+        "{markdown}"
+    );
+    assert!(markdown.contains("1. First, the tax changes"), "{markdown}");
+}
+
+#[test]
+fn embeds_are_kept_in_page_order() {
+    let content: BBCContent = scrape(
+        "https://www.bbc.co.uk/news/articles/c0birds003o",
+        include_str!("fixtures/bbc/article_embeds.html"),
+    );
+
+    let media = content.media().cloned().collect::<Vec<EmbeddedMedia>>();
+    assert_eq!(
+        media,
+        [
+            EmbeddedMedia {
+                kind: MediaKind::Video,
+                src: Some("https://www.bbc.co.uk/media/p0birdvid.mp4".to_string()),
+                caption: Some("The bird was filmed by a volunteer".to_string()),
+                duration: Some("1:42".to_string()),
+            },
+            EmbeddedMedia {
+                kind: MediaKind::Audio,
+                src: Some("https://www.bbc.co.uk/sounds/p0birdsong.mp3".to_string()),
+                caption: Some("Listen to the bird's song".to_string()),
+                duration: None,
+            },
+            EmbeddedMedia {
+                kind: MediaKind::Video,
+                src: Some("https://www.bbc.co.uk/embed/p0map".to_string()),
+                caption: Some("Where the bird was seen".to_string()),
+                duration: None,
+            },
+        ]
+    );
+    let image = content.images().next().unwrap();
+    assert_eq!(image.alt.as_deref(), Some("A small brown bird on a rock"));
+    assert_eq!(image.credit.as_deref(), Some("RSPB"));
+    assert!(matches!(content.content()[0], ContentBlock::Media(_)));
+    assert_eq!(paragraphs(&content).len(), 2);
+}
+
+#[tokio::test]
+async fn pages_added_before_an_entity_is_known_are_linked_to_it() {
+    let url = BBCUrl::parse("https://www.bbc.co.uk/news/articles/c0budget002o").unwrap();
+    let content: BBCContent = scrape(
+        &url.full_url(),
+        include_str!("fixtures/bbc/article_lists.html"),
+    );
+    let store = Arc::new(MemoryGraphStore::new());
+    let graph = KnowledgeGraph::open(Arc::clone(&store) as Arc<dyn GraphStore>)
+        .await
+        .unwrap();
+    graph.add_page(&url, &content).await.unwrap();
+
+    let entity = "https://en.wikipedia.org/wiki/National_Insurance";
+    graph
+        .add_entity("National Insurance", entity)
+        .await
+        .unwrap();
+    graph
+        .add_entity("Council tax", "https://en.wikipedia.org/wiki/Council_Tax")
+        .await
+        .unwrap();
+    let mentions = store
+        .edges_from(&url.full_url())
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|edge| edge.kind == EdgeKind::MentionsEntity)
+        .map(|edge| edge.to)
+        .collect::<Vec<_>>();
+    assert_eq!(mentions, [entity]);
+}
+
+#[test]
+fn live_posts_are_entries() {
+    let content: BBCContent = scrape(
+        "https://www.bbc.co.uk/news/live/uk-12345678",
+        include_str!("fixtures/bbc/live.html"),
+    );
+    let entries = content.live_entries();
+
+    assert_eq!(
+        content.title(),
+        "Storm Bert: Flood warnings as heavy rain hits"
+    );
+    assert_eq!(entries.len(), 3);
+    assert_eq!(
+        entries[0].title.as_deref(),
+        Some("Roads closed in the valleys")
+    );
+    assert!(entries[0].published.datetime().is_some());
+    assert_eq!(
+        entries[1].content,
+        [
+            ContentBlock::Paragraph(
+                "Rail services between the two cities have been suspended until the afternoon."
+                    .to_string()
+            ),
+            ContentBlock::Paragraph("Replacement buses are running.".to_string()),
+        ]
+    );
+    assert_eq!(entries[2].title, None);
+    assert_eq!(
+        entries[2].content,
+        [ContentBlock::Paragraph(
+            "The Met Office has issued an amber warning for rain.".to_string()
+        )]
+    );
+    assert!(content
+        .body_text()
+        .contains("Replacement buses are running."));
+    assert!(content.to_markdown().contains("## Trains cancelled"));
+}
+
+#[test]
+fn live_updates_fall_back_to_the_json_ld() {
+    let html = include_str!("fixtures/bbc/live.html").replace("content-post", "promo");
+    let content: BBCContent = scrape("https://www.bbc.co.uk/news/live/uk-12345678", &html);
+    let entries = content.live_entries();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries[0].title.as_deref(),
+        Some("Roads closed in the valleys")
+    );
+    assert_eq!(
+        entries[0].content,
+        [ContentBlock::Paragraph(
+            "Several roads have been closed after rivers burst their banks.".to_string()
+        )]
+    );
+}
+
+#[test]
+fn video_pages_take_the_description_and_fall_back_to_the_video_object() {
+    let content: BBCContent = scrape(
+        "https://www.bbc.co.uk/news/av/uk-england-norfolk-12345678",
+        include_str!("fixtures/bbc/video.html"),
+    );
+    let video = content.video().unwrap();
+
+    assert_eq!(content.title(), "Seal pup rescued from beach");
+    assert_eq!(
+        video.description.as_deref(),
+        Some("A seal pup has been rescued by volunteers after it was found stranded on a beach in Norfolk.")
+    );
+    assert_eq!(video.duration.as_deref(), Some("1:42"));
+    assert_eq!(
+        video.src.as_deref(),
+        Some("https://www.bbc.co.uk/embed/p0sealpup")
+    );
+    assert!(content
+        .body_text()
+        .contains("found stranded on a beach in Norfolk"));
+}
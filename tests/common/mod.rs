@@ -0,0 +1,8 @@
+use my_crate::scraper_v2::common::{FetchedDocument, ScrapableContent, UrlTrait};
+
+/// Scrape a fixture as if it had been fetched from `url`.
+pub fn scrape<C: ScrapableContent>(url: &str, html: &str) -> C {
+    let url = C::Url::parse(url).unwrap();
+    let document = FetchedDocument::parse(html, url.full_url());
+    C::from_scraped_page(&url, &document).unwrap()
+}
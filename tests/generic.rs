@@ -0,0 +1,44 @@
+mod common;
+
+use common::scrape;
+use my_crate::scraper_v2::common::{FetchedDocument, ScrapableContent, UrlTrait};
+use my_crate::scraper_v2::sites::generic::{GenericArticleContent, GenericUrl};
+use std::collections::HashSet;
+
+#[test]
+fn pages_without_an_article_are_scraped_for_their_links() {
+    let content: GenericArticleContent = scrape(
+        "https://www.example.com/science",
+        include_str!("fixtures/generic/section_page.html"),
+    );
+
+    assert!(content.body_text().is_empty());
+    let links = content
+        .get_related_pages()
+        .iter()
+        .map(|link| link.as_ref().full_url())
+        .collect::<HashSet<String>>();
+    assert!(links.contains("https://www.example.com/science/2024/05/01/comet-seen"));
+    assert!(links.contains("https://www.example.com/science/2024/05/02/new-species"));
+    assert!(links.contains("https://www.example.com/politics"));
+    assert!(!links.iter().any(|link| link.contains("other-site")));
+}
+
+#[test]
+fn the_article_json_ld_wins_over_the_page() {
+    let content: GenericArticleContent = scrape(
+        "https://www.example.com/science/2024/05/01/comet-seen",
+        include_str!("fixtures/generic/article_json_ld.html"),
+    );
+
+    assert_eq!(content.title(), "Comet seen over the coast");
+    assert_eq!(
+        content.description(),
+        Some("Astronomers say it will be visible until Friday.")
+    );
+    assert_eq!(content.byline(), ["Sam Jones"]);
+    assert_eq!(
+        content.published().map(|published| published.to_rfc3339()),
+        Some("2024-05-01T08:30:00+00:00".to_string())
+    );
+}
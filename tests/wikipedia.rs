@@ -0,0 +1,504 @@
+mod common;
+
+use common::scrape;
+use my_crate::scraper_v2::common::{
+    FetchedDocument, MemoryPageStore, Page, PageHandler, PageStore, ScrapableContent, UrlTrait,
+};
+use my_crate::scraper_v2::graph::{Edge, EdgeKind, GraphStore, KnowledgeGraph, MemoryGraphStore};
+use my_crate::scraper_v2::sites::wikipedia::api::{self, ApiPage};
+use my_crate::scraper_v2::sites::wikipedia::model::{CellValue, Coordinates, Infobox};
+use my_crate::scraper_v2::sites::wikipedia::{
+    ingest_dump, WikipediaContent, WikipediaUrl, XmlDumpReader,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+fn infobox(content: &WikipediaContent) -> &Infobox {
+    assert_eq!(content.infoboxes().len(), 1);
+    &content.infoboxes()[0]
+}
+
+fn text<'a>(infobox: &'a Infobox, label: &str) -> &'a str {
+    &infobox.get(label).unwrap().text
+}
+
+fn link_titles(infobox: &Infobox, label: &str) -> Vec<String> {
+    infobox
+        .get(label)
+        .unwrap()
+        .links
+        .iter()
+        .map(|link| link.url.title().to_string())
+        .collect()
+}
+
+#[test]
+fn person_infobox() {
+    let content: WikipediaContent = scrape(
+        "https://en.wikipedia.org/wiki/Ada_Lovelace",
+        include_str!("fixtures/wikipedia/person.html"),
+    );
+    let infobox = infobox(&content);
+
+    assert_eq!(infobox.title.as_deref(), Some("Ada Lovelace"));
+    assert_eq!(
+        infobox.images[0].caption.as_deref(),
+        Some("Portrait by Alfred Edward Chalon, 1840")
+    );
+    assert_eq!(
+        infobox.get("born").unwrap().items,
+        ["Augusta Ada Byron", "10 December 1815", "London, England"]
+    );
+    assert_eq!(link_titles(infobox, "Born"), ["London"]);
+    assert_eq!(text(infobox, "Known for"), "Mathematics Computing");
+    assert_eq!(
+        link_titles(infobox, "Parents"),
+        ["Lord Byron", "Anne Isabella Milbanke"]
+    );
+    let spouse = infobox.get("Spouse").unwrap();
+    assert_eq!(spouse.text, "William King-Noel, 1st Earl of Lovelace");
+    assert_eq!(spouse.citations[0].reference_id, "cite_note-spouse-1");
+    assert_eq!(
+        infobox
+            .sections
+            .iter()
+            .map(|section| section.heading.as_deref())
+            .collect::<Vec<_>>(),
+        [None, Some("Scientific career")]
+    );
+    assert_eq!(infobox.coordinates, None);
+}
+
+#[test]
+fn place_infobox() {
+    let content: WikipediaContent = scrape(
+        "https://en.wikipedia.org/wiki/London",
+        include_str!("fixtures/wikipedia/place.html"),
+    );
+    let infobox = infobox(&content);
+
+    assert_eq!(infobox.title.as_deref(), Some("London"));
+    assert_eq!(infobox.subheaders, ["Capital city"]);
+    assert_eq!(
+        infobox.coordinates,
+        Some(Coordinates {
+            latitude: 51.507,
+            longitude: -0.128,
+        })
+    );
+    assert_eq!(text(infobox, "Country"), "England");
+    assert_eq!(
+        link_titles(infobox, "• Mayor"),
+        ["Sadiq Khan", "Labour Party (UK)"]
+    );
+    let population = infobox.get("• Total").unwrap();
+    assert_eq!(population.text, "8,866,180");
+    assert_eq!(population.citations[0].reference_id, "cite_note-pop-3");
+}
+
+#[test]
+fn organisation_infobox() {
+    let content: WikipediaContent = scrape(
+        "https://en.wikipedia.org/wiki/BBC",
+        include_str!("fixtures/wikipedia/organisation.html"),
+    );
+    let infobox = infobox(&content);
+
+    assert_eq!(
+        infobox.title.as_deref(),
+        Some("British Broadcasting Corporation")
+    );
+    assert_eq!(infobox.images[0].alt.as_deref(), Some("BBC logo"));
+    assert_eq!(
+        text(infobox, "Company type"),
+        "Statutory corporation with a royal charter"
+    );
+    assert_eq!(text(infobox, "Founded"), "18 October 1922");
+    assert_eq!(
+        link_titles(infobox, "Headquarters"),
+        ["Broadcasting House", "London"]
+    );
+    assert_eq!(
+        infobox.get("Key people").unwrap().items,
+        ["Samir Shah (Chairman)", "Tim Davie (Director-General)"]
+    );
+    assert_eq!(text(infobox, "Website"), "bbc.co.uk");
+    assert_eq!(infobox.coordinates, None);
+}
+
+#[test]
+fn coordinates_that_are_not_on_the_globe_are_dropped() {
+    for geo in ["NaN; -0.128", "51.507; inf", "-inf; 0", "91; 0", "0; 180.5"] {
+        let html = include_str!("fixtures/wikipedia/place.html").replace("51.507; -0.128", geo);
+        let content: WikipediaContent = scrape("https://en.wikipedia.org/wiki/London", &html);
+        assert_eq!(infobox(&content).coordinates, None, "{geo}");
+    }
+}
+
+fn api_pages() -> Vec<ApiPage> {
+    let responses: Vec<Value> =
+        serde_json::from_str(include_str!("fixtures/wikipedia/api/query.json")).unwrap();
+    ApiPage::from_query_responses(&responses).unwrap()
+}
+
+fn scrape_api_page(page: &ApiPage) -> WikipediaContent {
+    let document = api::document_from_responses(
+        "en",
+        page,
+        include_str!("fixtures/wikipedia/api/ada_lovelace.html"),
+    );
+    let url = WikipediaUrl::from_title("en", page.requested_title()).unwrap();
+    WikipediaContent::from_scraped_page(&url, &document).unwrap()
+}
+
+#[test]
+fn query_responses_are_merged_and_every_redirect_is_kept() {
+    let pages = api_pages();
+    let requested = pages
+        .iter()
+        .map(|page| (page.requested_title(), page.title.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        requested,
+        [
+            ("Ada Lovelace", "Ada Lovelace"),
+            ("Charles Babbage's Daughter", "Charles Babbage's Daughter"),
+            ("Ada Byron", "Ada Lovelace"),
+            ("Lady Lovelace", "Ada Lovelace"),
+        ]
+    );
+    let ada = &pages[0];
+    assert_eq!(ada.page_id, Some(974));
+    assert_eq!(
+        ada.categories,
+        [
+            "Category:1815 births",
+            "Category:English women mathematicians"
+        ]
+    );
+    assert_eq!(
+        ada.language_links
+            .iter()
+            .map(|link| link.language.as_str())
+            .collect::<Vec<_>>(),
+        ["fr", "de"]
+    );
+    assert!(pages[1].missing);
+    assert_eq!(pages[2].redirect_section, None);
+    assert_eq!(pages[3].redirect_section.as_deref(), Some("Later life"));
+}
+
+#[test]
+fn api_and_rendered_pages_scrape_the_same() {
+    let rendered: WikipediaContent = scrape(
+        "https://en.wikipedia.org/wiki/Ada_Lovelace",
+        include_str!("fixtures/wikipedia/person.html"),
+    );
+    let api = scrape_api_page(&api_pages()[0]);
+
+    assert_eq!(api.title(), rendered.title());
+    assert_eq!(api.url(), rendered.url());
+    assert_eq!(api.short_description(), rendered.short_description());
+    assert_eq!(api.abstract_text(), rendered.abstract_text());
+    assert_eq!(api.infoboxes(), rendered.infoboxes());
+    assert_eq!(api.categories(), rendered.categories());
+    assert_eq!(api.references(), rendered.references());
+    //? Parsoid marks links with `rel="mw:WikiLink"`, so only where they lead is compared.
+    let link_urls = |content: &WikipediaContent| {
+        content
+            .get_related_pages()
+            .iter()
+            .map(|page| page.get_url_arc().full_url())
+            .collect::<HashSet<String>>()
+    };
+    assert_eq!(link_urls(&api), link_urls(&rendered));
+    assert_eq!(
+        api.language_links()
+            .iter()
+            .map(|link| link.url.full_url())
+            .collect::<Vec<_>>(),
+        [
+            "https://fr.wikipedia.org/wiki/Ada_Lovelace",
+            "https://de.wikipedia.org/wiki/Ada_Lovelace"
+        ]
+    );
+}
+
+#[test]
+fn api_pages_reached_through_a_redirect_are_the_article() {
+    for page in &api_pages()[2..] {
+        let content = scrape_api_page(page);
+        assert_eq!(content.title(), "Ada Lovelace");
+        assert_eq!(
+            content.canonical_url().unwrap().full_url(),
+            "https://en.wikipedia.org/wiki/Ada_Lovelace"
+        );
+        assert_eq!(
+            content.redirected_from().unwrap().title(),
+            page.requested_title()
+        );
+    }
+}
+
+fn links_to(edges: Vec<Edge>) -> Vec<String> {
+    edges
+        .into_iter()
+        .filter(|edge| edge.kind == EdgeKind::LinksTo)
+        .map(|edge| edge.to)
+        .collect()
+}
+
+#[tokio::test]
+async fn links_to_an_alias_are_links_to_its_page() {
+    let page = &api_pages()[0];
+    let url = WikipediaUrl::from_title("en", page.requested_title()).unwrap();
+    let content = scrape_api_page(page);
+    let store = Arc::new(MemoryGraphStore::new());
+    let graph = KnowledgeGraph::open(Arc::clone(&store) as Arc<dyn GraphStore>)
+        .await
+        .unwrap();
+    graph.add_page(&url, &content).await.unwrap();
+
+    let before = links_to(store.edges_from(&url.full_url()).await.unwrap());
+    let alias = before.first().expect("the article links to a page").clone();
+    let canonical = "https://en.wikipedia.org/wiki/Canonical_page";
+    graph.add_alias(&alias, canonical).await.unwrap();
+    let after = links_to(store.edges_from(&url.full_url()).await.unwrap());
+    assert!(after.iter().any(|to| to == canonical), "{after:?}");
+    assert!(!after.contains(&alias), "{after:?}");
+    assert!(store.edges_to(&alias).await.unwrap().is_empty());
+
+    //? Adding the page again follows its links through the alias.
+    graph.add_page(&url, &content).await.unwrap();
+    let again = links_to(store.edges_from(&url.full_url()).await.unwrap());
+    assert_eq!(again, after);
+}
+
+#[tokio::test]
+async fn aliases_from_a_dump_are_flushed_to_the_store() {
+    let store = Arc::new(MemoryPageStore::new());
+    let handler =
+        PageHandler::new().with_store(Arc::clone(&store) as Arc<dyn PageStore<WikipediaUrl>>);
+    let dump = XmlDumpReader::new(include_str!("fixtures/wikipedia/dump.xml").as_bytes());
+    let mut stored = Vec::new();
+    let summary = ingest_dump(dump, &handler, |page| stored.push(page))
+        .await
+        .unwrap();
+    assert_eq!((summary.read, summary.stored, summary.skipped), (2, 1, 1));
+
+    let markdown = stored[0].content().to_markdown();
+    assert!(markdown.contains("mathematician"), "{markdown}");
+    assert!(!markdown.contains("A note"), "{markdown}");
+
+    //? The redirect is in the store rather than held by the handler.
+    assert!(handler.aliases().await.is_empty());
+    let alias = WikipediaUrl::from_title("en", "Ada Byron").unwrap();
+    let canonical = handler.canonical_of(&alias).await.unwrap();
+    assert_eq!(
+        canonical.full_url(),
+        "https://en.wikipedia.org/wiki/Ada_Lovelace"
+    );
+}
+
+#[test]
+fn a_display_title_is_not_taken_for_a_redirect() {
+    let html = include_str!("fixtures/wikipedia/person.html").replace(
+        "<span class=\"mw-page-title-main\">Ada Lovelace</span>",
+        "<span class=\"mw-page-title-main\"><i>Ada</i> Lovelace, Countess of Lovelace</span>",
+    );
+    let content: WikipediaContent = scrape("https://en.wikipedia.org/wiki/Ada_Lovelace", &html);
+
+    assert_eq!(content.title(), "Ada Lovelace, Countess of Lovelace");
+    assert_eq!(
+        content.url().full_url(),
+        "https://en.wikipedia.org/wiki/Ada_Lovelace"
+    );
+    assert_eq!(content.redirected_from(), None);
+}
+
+#[test]
+fn the_body_text_covers_every_section() {
+    let html = include_str!("fixtures/wikipedia/person.html").replace(
+        "<div class=\"mw-heading mw-heading2\"><h2 id=\"References\">",
+        "<div class=\"mw-heading mw-heading2\"><h2 id=\"Legacy\">Legacy</h2></div>\n\
+         <p>The Ada programming language is named after her.</p>\n\
+         <div class=\"mw-heading mw-heading2\"><h2 id=\"References\">",
+    );
+    let content: WikipediaContent = scrape("https://en.wikipedia.org/wiki/Ada_Lovelace", &html);
+    let body_text = content.body_text();
+
+    assert!(body_text.contains("Analytical Engine"));
+    assert!(body_text.contains("The Ada programming language is named after her."));
+    assert!(!body_text.contains("[2]"));
+}
+
+#[test]
+fn an_edit_after_the_lead_changes_the_content_hash() {
+    let url = WikipediaUrl::parse("https://en.wikipedia.org/wiki/Ada_Lovelace").unwrap();
+    let record = |html: &str| {
+        let document = FetchedDocument::parse(html, url.full_url());
+        Page::new_to_scrape(url.clone())
+            .scrape_document::<WikipediaContent>(&document)
+            .unwrap()
+            .record()
+    };
+    let section = |text: &str| {
+        include_str!("fixtures/wikipedia/person.html").replace(
+            "<div class=\"mw-heading mw-heading2\"><h2 id=\"References\">",
+            &format!(
+                "<div class=\"mw-heading mw-heading2\"><h2 id=\"Legacy\">Legacy</h2></div>\n\
+                 <p>{text}</p>\n\
+                 <div class=\"mw-heading mw-heading2\"><h2 id=\"References\">"
+            ),
+        )
+    };
+
+    let before = record(&section("The Ada programming language is named after her."));
+    let after = record(&section(
+        "The Ada programming language was named after her in 1980.",
+    ));
+
+    assert!(before.content_hash.is_some());
+    assert_ne!(before.content_hash, after.content_hash);
+}
+
+#[test]
+fn tables_are_laid_out_with_their_spans_values_and_notes() {
+    let content: WikipediaContent = scrape(
+        "https://en.wikipedia.org/wiki/Exampleshire_County_Council_election",
+        include_str!("fixtures/wikipedia/table.html"),
+    );
+    let [table] = content.tables() else {
+        panic!("expected one table, got {:?}", content.tables());
+    };
+
+    assert_eq!(table.caption.as_deref(), Some("Results by party"));
+    assert_eq!(table.section.as_deref(), Some("Results"));
+    assert_eq!(
+        table.header_rows,
+        [
+            ["Party", "Votes", "Votes", "Spending", "Declared"],
+            ["Party", "Number", "Share", "Spending", "Declared"],
+        ]
+    );
+    assert_eq!(
+        table.column_names(),
+        [
+            "Party",
+            "Votes / Number",
+            "Votes / Share",
+            "Spending",
+            "Declared"
+        ]
+    );
+
+    let grid = table
+        .rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| (cell.text.as_str(), cell.continued))
+                .collect::<Vec<(&str, bool)>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        grid,
+        [
+            vec![
+                ("Labour", false),
+                ("1,234,567", false),
+                ("41.2%", false),
+                ("£1,200", false),
+                ("c. December 2019", false),
+            ],
+            vec![
+                ("Labour", true),
+                ("987,654", false),
+                ("33.0%", false),
+                ("£950", false),
+                ("12 December 2019", false),
+            ],
+            vec![
+                ("Conservative", false),
+                ("Not contested", false),
+                ("Not contested", true),
+                ("—", false),
+                ("—", false),
+            ],
+        ]
+    );
+    assert_eq!(
+        table.row_headers(),
+        [Some("Labour"), Some("Labour"), Some("Conservative")]
+    );
+    assert_eq!(
+        table.rows[0][0].links[0].url.full_url(),
+        "https://en.wikipedia.org/wiki/Labour_Party_(UK)"
+    );
+
+    let date = |text: &str| CellValue::Date(text.parse().unwrap());
+    let values = table
+        .rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|cell| cell.value.clone())
+                .collect::<Vec<CellValue>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        [
+            vec![
+                CellValue::Text,
+                CellValue::Number(1_234_567.0),
+                CellValue::Percent(41.2),
+                CellValue::Money {
+                    amount: 1200.0,
+                    currency: "£".to_string()
+                },
+                date("2019-12-13"),
+            ],
+            vec![
+                CellValue::Text,
+                CellValue::Number(987_654.0),
+                CellValue::Percent(33.0),
+                CellValue::Money {
+                    amount: 950.0,
+                    currency: "£".to_string()
+                },
+                date("2019-12-12"),
+            ],
+            vec![
+                CellValue::Text,
+                CellValue::Text,
+                CellValue::Text,
+                CellValue::Empty,
+                CellValue::Empty,
+            ],
+        ]
+    );
+
+    assert_eq!(
+        table.footnotes,
+        ["Source: Exampleshire Electoral Services."]
+    );
+    assert_eq!(
+        table.to_csv(),
+        "Party,Votes / Number,Votes / Share,Spending,Declared\r\n\
+         Labour,\"1,234,567\",41.2%,\"£1,200\",c. December 2019\r\n\
+         Labour,\"987,654\",33.0%,£950,12 December 2019\r\n\
+         Conservative,Not contested,Not contested,—,—\r\n"
+    );
+    assert_eq!(
+        table.to_json()[0],
+        serde_json::json!({
+            "Party": "Labour",
+            "Votes / Number": 1_234_567.0,
+            "Votes / Share": 41.2,
+            "Spending": 1200.0,
+            "Declared": "2019-12-13",
+        })
+    );
+}